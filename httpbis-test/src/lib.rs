@@ -13,11 +13,13 @@ mod client;
 mod misc;
 mod server_one_conn;
 mod server_test;
+mod stream_handler;
 mod task;
 mod tester;
 
 pub use self::server_one_conn::*;
 pub use self::server_test::*;
+pub use self::stream_handler::*;
 pub use self::tester::*;
 pub use client::*;
 pub use misc::*;
@@ -8,6 +8,7 @@ use httpbis;
 use httpbis::Headers;
 use httpbis::Server;
 use httpbis::ServerBuilder;
+use httpbis::ServerConf;
 use httpbis::ServerHandler;
 
 use futures::stream;
@@ -68,8 +69,13 @@ impl ServerHandler for Echo {
 
 impl ServerTest {
     pub fn new() -> ServerTest {
+        ServerTest::new_with_conf(ServerConf::new())
+    }
+
+    pub fn new_with_conf(conf: ServerConf) -> ServerTest {
         let mut server = ServerBuilder::new_plain();
         server.set_port(0);
+        server.conf = conf;
         server.service.set_service("/blocks", Arc::new(Blocks {}));
         server.service.set_service("/echo", Arc::new(Echo {}));
         let server = server.build().expect("server");
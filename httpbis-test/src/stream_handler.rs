@@ -0,0 +1,144 @@
+use std::sync::mpsc;
+
+use bytes::Bytes;
+
+use httpbis::Client;
+use httpbis::ClientIncreaseInWindow;
+use httpbis::ClientInterface;
+use httpbis::ClientRequest;
+use httpbis::ClientResponse;
+use httpbis::ClientResponseStreamHandler;
+use httpbis::ClientStreamCreatedHandler;
+use httpbis::ErrorCode;
+use httpbis::Headers;
+use httpbis::Response;
+use httpbis::StreamId;
+
+/// Response stream event forwarded by `ChannelStreamHandler`.
+pub enum StreamEvent {
+    Headers(Headers, bool),
+    InformationalHeaders(Headers),
+    Data(Bytes, bool),
+    Trailers(Headers),
+    Rst(ErrorCode),
+    Error(httpbis::Error),
+    PushPromise(StreamId, Headers, Response),
+}
+
+/// Stream handler which forwards all events into a channel.
+///
+/// Window is not increased until the receiver calls
+/// `ClientIncreaseInWindow::data_frame_processed`.
+pub struct ChannelStreamHandler(mpsc::Sender<StreamEvent>);
+
+impl ChannelStreamHandler {
+    fn send(&self, event: StreamEvent) -> httpbis::Result<()> {
+        // Receiver may be dropped when test no longer cares
+        drop(self.0.send(event));
+        Ok(())
+    }
+}
+
+impl ClientResponseStreamHandler for ChannelStreamHandler {
+    fn headers(&mut self, headers: Headers, end_stream: bool) -> httpbis::Result<()> {
+        self.send(StreamEvent::Headers(headers, end_stream))
+    }
+
+    fn informational_headers(&mut self, headers: Headers) -> httpbis::Result<()> {
+        self.send(StreamEvent::InformationalHeaders(headers))
+    }
+
+    fn data_frame(&mut self, data: Bytes, end_stream: bool) -> httpbis::Result<()> {
+        self.send(StreamEvent::Data(data, end_stream))
+    }
+
+    fn trailers(&mut self, trailers: Headers) -> httpbis::Result<()> {
+        self.send(StreamEvent::Trailers(trailers))
+    }
+
+    fn rst(&mut self, error_code: ErrorCode) -> httpbis::Result<()> {
+        self.send(StreamEvent::Rst(error_code))
+    }
+
+    fn error(&mut self, error: httpbis::Error) -> httpbis::Result<()> {
+        self.send(StreamEvent::Error(error))
+    }
+
+    fn push_promise(
+        &mut self,
+        promised_stream_id: StreamId,
+        request_headers: Headers,
+        response: ClientResponse,
+    ) -> httpbis::Result<()> {
+        let response = response.make_stream();
+        self.send(StreamEvent::PushPromise(
+            promised_stream_id,
+            request_headers,
+            response,
+        ))
+    }
+}
+
+struct Created {
+    events_tx: mpsc::Sender<StreamEvent>,
+    increase_in_window_tx: mpsc::Sender<ClientIncreaseInWindow>,
+}
+
+impl ClientStreamCreatedHandler for Created {
+    fn request_created(
+        self: Box<Self>,
+        _req: ClientRequest,
+        resp: ClientResponse,
+    ) -> httpbis::Result<()> {
+        let Created {
+            events_tx,
+            increase_in_window_tx,
+        } = *self;
+        resp.register_stream_handler(move |increase_in_window| {
+            drop(increase_in_window_tx.send(increase_in_window));
+            (ChannelStreamHandler(events_tx), ())
+        });
+        Ok(())
+    }
+
+    fn error(self: Box<Self>, error: httpbis::Error) {
+        panic!("request failed: {:?}", error);
+    }
+}
+
+/// Receiving side of a request started with `start_request_channel`.
+pub struct StreamEvents {
+    pub events: mpsc::Receiver<StreamEvent>,
+    pub increase_in_window: mpsc::Receiver<ClientIncreaseInWindow>,
+}
+
+/// Start a request with empty body and forward response stream events
+/// into a channel with `ChannelStreamHandler`.
+pub fn start_request_channel(client: &Client, headers: Headers) -> StreamEvents {
+    let (events_tx, events) = mpsc::channel();
+    let (increase_in_window_tx, increase_in_window) = mpsc::channel();
+    client
+        .start_request_low_level(
+            headers,
+            None,
+            None,
+            true,
+            Box::new(Created {
+                events_tx,
+                increase_in_window_tx,
+            }),
+        )
+        .unwrap();
+    StreamEvents {
+        events,
+        increase_in_window,
+    }
+}
+
+/// `GET` request headers for a plain text client.
+pub fn get_request_headers(path: &str) -> Headers {
+    let mut headers = Headers::new_get(path.to_owned());
+    headers.add(":authority", "localhost");
+    headers.add(":scheme", "http");
+    headers
+}
@@ -8,26 +8,33 @@ use std::mem;
 use std::net;
 use std::net::ToSocketAddrs;
 use std::str;
+use std::time::Duration;
 
 use bytes::Bytes;
 
 use httpbis::for_test;
 use httpbis::for_test::hpack;
+use httpbis::for_test::solicit::frame::pack_header;
 use httpbis::for_test::solicit::frame::ContinuationFlag;
 use httpbis::for_test::solicit::frame::ContinuationFrame;
 use httpbis::for_test::solicit::frame::DataFlag;
 use httpbis::for_test::solicit::frame::DataFrame;
+use httpbis::for_test::solicit::frame::Flags;
+use httpbis::for_test::solicit::frame::FrameHeader;
 use httpbis::for_test::solicit::frame::FrameIR;
 use httpbis::for_test::solicit::frame::GoawayFrame;
 use httpbis::for_test::solicit::frame::HeadersFlag;
 use httpbis::for_test::solicit::frame::HeadersFrame;
 use httpbis::for_test::solicit::frame::HttpFrame;
+use httpbis::for_test::solicit::frame::PushPromiseFlag;
+use httpbis::for_test::solicit::frame::PushPromiseFrame;
 use httpbis::for_test::solicit::frame::RawFrame;
 use httpbis::for_test::solicit::frame::RstStreamFrame;
 use httpbis::for_test::solicit::frame::SettingsFrame;
 use httpbis::for_test::solicit::frame::WindowUpdateFrame;
 use httpbis::for_test::solicit::header::*;
 use httpbis::Client;
+use httpbis::ClientConf;
 use httpbis::ErrorCode;
 use httpbis::SimpleHttpMessage;
 use httpbis::StreamId;
@@ -55,10 +62,13 @@ impl HttpServerTester {
     }
 
     pub fn new_with_client() -> (HttpServerTester, Client) {
+        HttpServerTester::new_with_client_conf(Default::default())
+    }
+
+    pub fn new_with_client_conf(conf: ClientConf) -> (HttpServerTester, Client) {
         let server = HttpServerTester::new();
 
-        let client =
-            Client::new_plain(BIND_HOST, server.port(), Default::default()).expect("client");
+        let client = Client::new_plain(BIND_HOST, server.port(), conf).expect("client");
 
         (server, client)
     }
@@ -139,6 +149,27 @@ impl HttpConnTester {
         assert_eq!(PREFACE, &preface[..]);
     }
 
+    /// Read HTTP/1 request head, e. g. h2c upgrade request.
+    pub fn recv_http_1_request_head(&mut self) -> String {
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+            self.tcp.read_exact(&mut byte).expect("read");
+            head.push(byte[0]);
+        }
+        String::from_utf8(head).expect("utf-8")
+    }
+
+    pub fn recv_raw(&mut self, len: usize) -> Vec<u8> {
+        let mut data = vec![0; len];
+        self.tcp.read_exact(&mut data).expect("recv_raw");
+        data
+    }
+
+    pub fn send_raw(&mut self, data: &[u8]) {
+        self.tcp.write_all(data).expect("send_raw");
+    }
+
     pub fn recv_eof(&mut self) {
         let r = self.tcp.read(&mut [0]);
         match r {
@@ -154,6 +185,12 @@ impl HttpConnTester {
         info!("EOF received");
     }
 
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.tcp
+            .set_read_timeout(timeout)
+            .expect("set_read_timeout");
+    }
+
     pub fn send_preface(&mut self) {
         self.tcp.write(PREFACE).expect("send");
     }
@@ -165,6 +202,14 @@ impl HttpConnTester {
             .expect("send_frame");
     }
 
+    /// Send a frame bypassing frame serialization, e. g. to send an invalid frame.
+    pub fn send_raw_frame(&mut self, header: FrameHeader, payload: &[u8]) {
+        info!("sending raw {:?}", header);
+        let mut buf = pack_header(&header).to_vec();
+        buf.extend_from_slice(payload);
+        self.tcp.write_all(&buf).expect("send_raw_frame");
+    }
+
     pub fn send_window_update_conn(&mut self, increment: u32) {
         self.in_window_size.try_increase(increment).unwrap();
         self.send_frame(WindowUpdateFrame::for_connection(increment));
@@ -213,6 +258,29 @@ impl HttpConnTester {
             .expect("decrease");
     }
 
+    /// Send several `DATA` frames with a single socket write.
+    pub fn send_data_frames_single_write(
+        &mut self,
+        stream_id: StreamId,
+        chunks: &[&[u8]],
+        end: bool,
+    ) {
+        let mut buf = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let mut data_frame = DataFrame::new(stream_id);
+            data_frame.data = Bytes::copy_from_slice(chunk);
+            if end && i == chunks.len() - 1 {
+                data_frame.set_flag(DataFlag::EndStream);
+            }
+            info!("sending {:?}", data_frame);
+            buf.extend_from_slice(&data_frame.serialize_into_vec());
+            self.out_window_size
+                .try_decrease_to_non_negative(chunk.len() as i32)
+                .expect("decrease");
+        }
+        self.tcp.write_all(&buf).expect("send_data_frames");
+    }
+
     pub fn send_rst(&mut self, stream_id: StreamId, error_code: ErrorCode) {
         self.send_frame(RstStreamFrame::new(stream_id, error_code));
     }
@@ -231,6 +299,10 @@ impl HttpConnTester {
 
     pub fn recv_special_frame_process_special(&mut self) -> Option<HttpFrame> {
         let frame = self.fn_recv_frame_no_check_ack();
+        self.process_special_frame(frame)
+    }
+
+    fn process_special_frame(&mut self, frame: HttpFrame) -> Option<HttpFrame> {
         if let HttpFrame::Settings(ref f) = frame {
             if self.our_settings_sent.is_some() && f.is_ack() {
                 self.process_peer_settings_ack(&f);
@@ -258,6 +330,27 @@ impl HttpConnTester {
         }
     }
 
+    /// Receive a frame like `recv_frame`, return `None` if nothing arrives in `timeout`.
+    pub fn recv_frame_timeout(&mut self, timeout: Duration) -> Option<HttpFrame> {
+        self.set_read_timeout(Some(timeout));
+        let frame = loop {
+            let raw_frame = match for_test::recv_raw_frame_sync(
+                &mut self.tcp,
+                self.our_settings_ack.max_frame_size,
+            ) {
+                Ok(raw_frame) => raw_frame,
+                Err(_) => break None,
+            };
+            let frame = HttpFrame::from_raw(&raw_frame).expect("parse frame");
+            debug!("received frame: {:?}", frame);
+            if let Some(frame) = self.process_special_frame(frame) {
+                break Some(frame);
+            }
+        };
+        self.set_read_timeout(None);
+        frame
+    }
+
     pub fn recv_frame_settings(&mut self) -> SettingsFrame {
         match self.fn_recv_frame_no_check_ack() {
             HttpFrame::Settings(settings) => settings,
@@ -452,37 +545,102 @@ impl HttpConnTester {
         data
     }
 
+    pub fn recv_frame_push_promise_check(&mut self, stream_id: StreamId) -> (StreamId, Headers) {
+        let frame = match self.recv_frame() {
+            HttpFrame::PushPromise(frame) => frame,
+            f => panic!("expecting PUSH_PROMISE, got: {:?}", f),
+        };
+        assert_eq!(stream_id, frame.stream_id);
+        assert!(frame.flags.is_set(PushPromiseFlag::EndHeaders));
+        let headers = self.decoder.decode(frame.header_fragment).expect("decode");
+        let headers = Headers::from_vec(
+            headers
+                .into_iter()
+                .map(|(n, v)| Header::new(n, v))
+                .collect(),
+        );
+        (frame.promised_stream_id, headers)
+    }
+
+    pub fn send_push_promise(
+        &mut self,
+        stream_id: StreamId,
+        promised_stream_id: StreamId,
+        headers: Headers,
+    ) {
+        let header_fragment = self
+            .encoder
+            .encode_for_test(headers.iter().map(|h| (h.name().as_bytes(), h.value())));
+        self.send_frame(PushPromiseFrame {
+            flags: Flags::new(PushPromiseFlag::EndHeaders as u8),
+            stream_id,
+            promised_stream_id,
+            header_fragment: Bytes::from(header_fragment),
+            padding_len: 0,
+        });
+    }
+
+    /// Receive `HEADERS` or `DATA` frame and add it to the message,
+    /// return `true` on end of stream.
+    fn recv_message_part(&mut self, frame: HttpFrame, message: &mut SimpleHttpMessage) -> bool {
+        match frame {
+            HttpFrame::Headers(headers_frame) => {
+                let end_of_stream = headers_frame.is_end_of_stream();
+                let headers = self
+                    .decoder
+                    .decode(headers_frame.header_fragment)
+                    .expect("decode");
+                let headers = Headers::from_vec(
+                    headers
+                        .into_iter()
+                        .map(|(n, v)| Header::new(n, v))
+                        .collect(),
+                );
+                if message.headers.is_empty() {
+                    message.headers = headers;
+                } else {
+                    message
+                        .trailers
+                        .get_or_insert_with(Headers::new)
+                        .extend(headers);
+                }
+                end_of_stream
+            }
+            HttpFrame::Data(data_frame) => {
+                let end_of_stream = data_frame.is_end_of_stream();
+                message.body.extend(data_frame.data);
+                end_of_stream
+            }
+            frame => panic!("expecting HEADERS or DATA, got: {:?}", frame),
+        }
+    }
+
     pub fn recv_message(&mut self, stream_id: StreamId) -> SimpleHttpMessage {
         let mut r = SimpleHttpMessage::default();
         loop {
             let frame = self.recv_frame();
             assert_eq!(stream_id, frame.get_stream_id());
-            let end_of_stream = match frame {
-                HttpFrame::Headers(headers_frame) => {
-                    let end_of_stream = headers_frame.is_end_of_stream();
-                    let headers = self
-                        .decoder
-                        .decode(headers_frame.header_fragment)
-                        .expect("decode");
-                    let headers = Headers::from_vec(
-                        headers
-                            .into_iter()
-                            .map(|(n, v)| Header::new(n, v))
-                            .collect(),
-                    );
-                    r.headers.extend(headers);
-                    end_of_stream
-                }
-                HttpFrame::Data(data_frame) => {
-                    let end_of_stream = data_frame.is_end_of_stream();
-                    r.body.extend(data_frame.data);
-                    end_of_stream
-                }
-                frame => panic!("expecting HEADERS or DATA, got: {:?}", frame),
-            };
-            if end_of_stream {
+            if self.recv_message_part(frame, &mut r) {
                 return r;
             }
         }
     }
+
+    /// Receive messages on several streams, frames of which may be interleaved.
+    pub fn recv_messages(&mut self, stream_ids: &[StreamId]) -> Vec<SimpleHttpMessage> {
+        let mut messages: Vec<_> = stream_ids.iter().map(|_| None).collect();
+        let mut done = 0;
+        while done != stream_ids.len() {
+            let frame = self.recv_frame();
+            let index = stream_ids
+                .iter()
+                .position(|&s| s == frame.get_stream_id())
+                .unwrap_or_else(|| panic!("unexpected frame: {:?}", frame));
+            let message = messages[index].get_or_insert_with(SimpleHttpMessage::default);
+            if self.recv_message_part(frame, message) {
+                done += 1;
+            }
+        }
+        messages.into_iter().map(Option::unwrap).collect()
+    }
 }
@@ -2,10 +2,15 @@
 
 use log::info;
 
+use std::cmp;
 use std::io;
+use std::pin::Pin;
 use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
 use std::thread;
 use std::time::Duration;
+use std::time::Instant;
 
 use httpbis_test::*;
 
@@ -17,11 +22,26 @@ use futures::stream::StreamExt;
 use futures::future;
 use futures::future::TryFutureExt;
 
+use httpbis::for_test::solicit::frame::ContinuationFrame;
+use httpbis::for_test::solicit::frame::GoawayFrame;
+use httpbis::for_test::solicit::frame::HeadersFlag;
+use httpbis::for_test::solicit::frame::HeadersFrame;
+use httpbis::for_test::solicit::frame::HttpFrame;
+use httpbis::for_test::solicit::frame::HttpSetting;
+use httpbis::for_test::solicit::frame::PingFrame;
+use httpbis::for_test::solicit::frame::RstStreamFrame;
+use httpbis::for_test::solicit::frame::SettingsFrame;
+use httpbis::for_test::solicit::frame::WindowUpdateFrame;
 use httpbis::for_test::solicit::DEFAULT_SETTINGS;
 use httpbis::for_test::*;
 use httpbis::ErrorCode;
 use httpbis::*;
+use std::task::Context;
 use std::task::Poll;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::DuplexStream;
+use tokio::io::ReadBuf;
 use tokio::runtime::Runtime;
 
 #[test]
@@ -58,125 +78,2082 @@ fn stream_count() {
     assert_eq!(0, state.streams.len(), "{:?}", state);
 }
 
+#[test]
+fn rst_unknown_error_code() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let req = client.start_get("/fgfg", "localhost").collect();
+
+    server_tester.recv_message(1);
+    server_tester.send_headers(1, Headers::ok_200(), false);
+    server_tester.send_frame(RstStreamFrame::with_raw_error_code(1, 0x1234));
+
+    let rt = Runtime::new().unwrap();
+
+    match rt.block_on(req) {
+        Ok(..) => panic!("expected error"),
+        Err(Error::RstStreamReceivedUnknown(0x1234)) => {}
+        Err(e) => panic!("wrong error: {:?}", e),
+    }
+}
+
 #[test]
 fn rst_is_error() {
     init_logger();
 
-    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let req = client.start_get("/fgfg", "localhost").collect();
+
+    let get = server_tester.recv_message(1);
+    assert_eq!("GET", get.headers.method());
+
+    server_tester.send_headers(1, Headers::ok_200(), false);
+    server_tester.send_rst(1, ErrorCode::InadequateSecurity);
+
+    let rt = Runtime::new().unwrap();
+
+    match rt.block_on(req) {
+        Ok(..) => panic!("expected error"),
+        Err(Error::RstStreamReceived(ErrorCode::InadequateSecurity)) => {}
+        Err(e) => panic!("wrong error: {:?}", e),
+    }
+
+    let state: ConnStateSnapshot = client.conn_state();
+    assert_eq!(0, state.streams.len(), "{:?}", state);
+}
+
+#[test]
+fn rst_drops_queued_data() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+    server_tester.send_recv_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::InitialWindowSize(10),
+    ]));
+
+    let rt = Runtime::new().unwrap();
+
+    let (mut sender, _response) = rt
+        .block_on(client.start_post_sink("/upload", "localhost"))
+        .expect("start_post_sink");
+    server_tester.recv_frame_headers_check(1, false);
+
+    sender.send_data(Bytes::from(vec![17; 100])).unwrap();
+    assert_eq!(10, server_tester.recv_frame_data_check(1, false).len());
+    while client.stream_state(1).queued_out_data_size != 90 {
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    server_tester.send_rst(1, ErrorCode::Cancel);
+    while !client.conn_state().streams.is_empty() {
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    // Data sent after reset is dropped too
+    drop(sender.send_data(Bytes::from(vec![17; 100])));
+    drop(sender.send_data_end_of_stream(Bytes::new()));
+
+    // Window would allow the dropped data
+    server_tester.send_window_update_stream(1, 1000);
+
+    // Next stream frames are the first written after reset
+    let req = client.start_get("/after", "localhost").collect();
+    server_tester.recv_frame_headers_check(3, true);
+    server_tester.send_headers(3, Headers::ok_200(), true);
+    assert_eq!(200, rt.block_on(req).expect("after").headers.status());
+
+    let state = client.conn_state();
+    assert_eq!(0, state.buffered_data_size);
+    assert_eq!(
+        state.out_window_size as isize, state.pump_out_window_size,
+        "{:?}",
+        state
+    );
+}
+
+#[test]
+fn handle_1xx_headers() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let req = client.start_get("/fgfg", "localhost").collect();
+
+    let get = server_tester.recv_message(1);
+    assert_eq!("GET", get.headers.method());
+
+    server_tester.send_headers(1, Headers::new_status(100), false);
+    server_tester.send_headers(1, Headers::new_status(100), false);
+
+    server_tester.send_headers(1, Headers::ok_200(), false);
+
+    server_tester.send_data(1, b"hello", true);
+
+    let rt = Runtime::new().unwrap();
+
+    rt.block_on(req).expect("Should be OK");
+
+    let state: ConnStateSnapshot = client.conn_state();
+    assert_eq!(0, state.streams.len(), "{:?}", state);
+}
+
+#[test]
+fn client_call_dropped() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    {
+        let req = client.start_get("/fgfg", "localhost");
+
+        server_tester.recv_message(1);
+
+        drop(req);
+
+        server_tester.send_headers(1, Headers::ok_200(), true);
+    }
+
+    let rt = Runtime::new().unwrap();
+
+    {
+        let req = client.start_get("/fgfg", "localhost").collect();
+        server_tester.recv_message(3);
+        server_tester.send_headers(3, Headers::ok_200(), true);
+        let resp = rt.block_on(req).expect("OK");
+        assert_eq!(200, resp.headers.status());
+    }
+
+    let state: ConnStateSnapshot = client.conn_state();
+    assert_eq!(0, state.streams.len(), "{:?}", state);
+}
+
+#[test]
+fn reconnect_on_disconnect() {
+    init_logger();
+
+    let (server, client) = HttpServerTester::new_with_client();
+
+    let mut server_tester = server.accept_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    {
+        let req = client.start_get("/111", "localhost").collect();
+        server_tester.recv_message(1);
+        server_tester.send_headers(1, Headers::ok_200(), true);
+        let resp = rt.block_on(req).expect("OK");
+        assert_eq!(200, resp.headers.status());
+    }
+
+    // drop server connection
+    drop(server_tester);
+
+    // waiting for client connection to die
+    while let Ok(_) = rt.block_on(client.dump_state()) {
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    {
+        let req = client.start_get("/222", "localhost").collect();
+
+        let mut server_tester = server.accept();
+        server_tester.recv_preface();
+        server_tester.settings_xchg_but_ack();
+
+        server_tester.recv_message(1);
+        server_tester.send_headers(1, Headers::ok_200(), true);
+        let resp = rt.block_on(req).expect("OK");
+        assert_eq!(200, resp.headers.status());
+    }
+}
+
+#[test]
+fn ping() {
+    init_logger();
+
+    let (server, client) = HttpServerTester::new_with_client();
+
+    let mut server_tester = server.accept_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let ping = client.ping(0x0102030405060708);
+
+    let opaque_data = match server_tester.recv_frame() {
+        HttpFrame::Ping(frame) => {
+            assert!(!frame.is_ack());
+            frame.opaque_data()
+        }
+        f => panic!("expecting PING, got: {:?}", f),
+    };
+    assert_eq!(0x0102030405060708, opaque_data);
+
+    match rt.block_on(client.ping(17)) {
+        Err(Error::PingInFlight) => {}
+        r => panic!("expecting PingInFlight, got: {:?}", r),
+    }
+
+    thread::sleep(Duration::from_millis(10));
+    server_tester.send_frame(PingFrame::new_ack(opaque_data));
+
+    let rtt = rt.block_on(ping).expect("ping");
+    assert!(rtt >= Duration::from_millis(10), "{:?}", rtt);
+
+    // next PING can be sent after ACK
+    let ping = client.ping(18);
+    match server_tester.recv_frame() {
+        HttpFrame::Ping(frame) => {
+            assert_eq!(18, frame.opaque_data());
+            server_tester.send_frame(PingFrame::new_ack(frame.opaque_data()));
+        }
+        f => panic!("expecting PING, got: {:?}", f),
+    }
+    rt.block_on(ping).expect("ping");
+}
+
+#[test]
+fn keepalive_timeout() {
+    init_logger();
+
+    let mut conf = ClientConf::new();
+    conf.common.keepalive_interval = Some(Duration::from_millis(100));
+    conf.common.keepalive_timeout = Some(Duration::from_millis(300));
+
+    let (server, _client) = HttpServerTester::new_with_client_conf(conf);
+    let mut server_tester = server.accept_xchg();
+
+    // Acknowledged PING keeps the connection alive
+    let ping = match server_tester.recv_frame() {
+        HttpFrame::Ping(ping) => ping,
+        f => panic!("expecting PING, got: {:?}", f),
+    };
+    assert!(!ping.is_ack());
+    server_tester.send_frame(PingFrame::new_ack(ping.opaque_data()));
+
+    match server_tester.recv_frame() {
+        HttpFrame::Ping(ping) => assert!(!ping.is_ack()),
+        f => panic!("expecting PING, got: {:?}", f),
+    }
+    let sent = Instant::now();
+
+    // Never acknowledged
+    server_tester.recv_eof();
+    assert!(sent.elapsed() >= Duration::from_millis(200));
+}
+
+#[test]
+fn keepalive_in_memory() {
+    init_logger();
+
+    let server = HttpServerTester::new();
+
+    let rt = Runtime::new().unwrap();
+
+    let (client_io, mut bridge_io) = tokio::io::duplex(0x10000);
+    let port = server.port();
+    rt.spawn(async move {
+        let mut tcp = tokio::net::TcpStream::connect((BIND_HOST, port))
+            .await
+            .expect("connect");
+        drop(tokio::io::copy_bidirectional(&mut bridge_io, &mut tcp).await);
+    });
+
+    let mut conf = ClientConf::new();
+    conf.common.keepalive_interval = Some(Duration::from_millis(50));
+    conf.common.keepalive_timeout = Some(Duration::from_millis(200));
+    let client = Client::over_tls_io(client_io, "h2", conf).expect("client");
+
+    let mut server_tester = server.accept();
+    server_tester.recv_preface();
+    server_tester.settings_xchg();
+
+    assert_eq!(None, rt.block_on(client.dump_state()).expect("state").rtt);
+
+    for _ in 0..3 {
+        let ping = match server_tester.recv_frame() {
+            HttpFrame::Ping(ping) => ping,
+            f => panic!("expecting PING, got: {:?}", f),
+        };
+        assert!(!ping.is_ack());
+        server_tester.send_frame(PingFrame::new_ack(ping.opaque_data()));
+    }
+
+    // Acknowledged keepalive PING is used to measure RTT
+    assert!(rt
+        .block_on(client.dump_state())
+        .expect("state")
+        .rtt
+        .is_some());
+    assert!(client.is_alive());
+
+    match server_tester.recv_frame() {
+        HttpFrame::Ping(ping) => assert!(!ping.is_ack()),
+        f => panic!("expecting PING, got: {:?}", f),
+    }
+
+    // Peer stopped acknowledging
+    assert!(rt.block_on(client.wait_for_conn_close()).is_err());
+    assert!(!client.is_alive());
+}
+
+#[test]
+fn is_alive() {
+    init_logger();
+
+    let (server, client) = HttpServerTester::new_with_client();
+
+    let server_tester = server.accept_xchg();
+
+    assert!(client.is_alive());
+
+    drop(server_tester);
+
+    while client.is_alive() {
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn wait_for_conn_close() {
+    init_logger();
+
+    let (server, client) = HttpServerTester::new_with_client();
+
+    let server_tester = server.accept_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let closed = client.wait_for_conn_close();
+
+    drop(server_tester);
+
+    match rt.block_on(closed) {
+        Err(Error::ConnDied(e)) => assert!(!matches!(*e, Error::DeathReasonUnknown)),
+        r => panic!("expecting ConnDied, got: {:?}", r),
+    }
+}
+
+#[test]
+fn enable_push_setting() {
+    init_logger();
+
+    for &enable_push in &[false, true] {
+        let server = HttpServerTester::new();
+
+        let mut conf = ClientConf::new();
+        conf.enable_push = enable_push;
+        let _client = Client::new_plain(BIND_HOST, server.port(), conf).expect("client");
+
+        let mut server_tester = server.accept();
+        server_tester.recv_preface();
+        let settings = server_tester.recv_frame_settings_set();
+        assert_eq!(
+            vec![
+                HttpSetting::EnablePush(enable_push),
+                HttpSetting::MaxHeaderListSize(64 << 10),
+            ],
+            settings.settings
+        );
+    }
+}
+
+#[test]
+fn max_outstanding_pings() {
+    init_logger();
+
+    let server = HttpServerTester::new();
+
+    let mut conf = ClientConf::new();
+    conf.common.max_outstanding_pings = Some(2);
+    let client = Client::new_plain(BIND_HOST, server.port(), conf).expect("client");
+
+    let mut server_tester = server.accept_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let ping1 = client.ping(1);
+    let ping2 = client.ping(2);
+    for expected in 1..=2 {
+        match server_tester.recv_frame() {
+            HttpFrame::Ping(frame) => assert_eq!(expected, frame.opaque_data()),
+            f => panic!("expecting PING, got: {:?}", f),
+        }
+    }
+
+    match rt.block_on(client.ping(3)) {
+        Err(Error::PingInFlight) => {}
+        r => panic!("expecting PingInFlight, got: {:?}", r),
+    }
+
+    // ACKs may arrive in any order
+    server_tester.send_frame(PingFrame::new_ack(2));
+    rt.block_on(ping2).expect("ping");
+
+    let ping4 = client.ping(4);
+    match server_tester.recv_frame() {
+        HttpFrame::Ping(frame) => assert_eq!(4, frame.opaque_data()),
+        f => panic!("expecting PING, got: {:?}", f),
+    }
+    server_tester.send_frame(PingFrame::new_ack(1));
+    server_tester.send_frame(PingFrame::new_ack(4));
+    rt.block_on(ping1).expect("ping");
+    rt.block_on(ping4).expect("ping");
+}
+
+#[test]
+fn send_goaway_with_debug() {
+    init_logger();
+
+    let (server, client) = HttpServerTester::new_with_client();
+
+    let mut server_tester = server.accept_xchg();
+
+    match client.send_goaway_with_debug(ErrorCode::NoError, Bytes::from(vec![0; 16377])) {
+        Err(Error::PayloadTooLarge(16377, 16376)) => {}
+        r => panic!("expecting PayloadTooLarge, got: {:?}", r),
+    }
+
+    client
+        .send_goaway_with_debug(ErrorCode::EnhanceYourCalm, Bytes::from_static(b"too fast"))
+        .expect("send_goaway_with_debug");
+
+    let goaway = server_tester.recv_goaway_frame();
+    assert_eq!(ErrorCode::EnhanceYourCalm, goaway.error_code());
+    assert_eq!(&b"too fast"[..], &goaway.debug_data()[..]);
+    server_tester.recv_eof();
+}
+
+#[test]
+fn handshake_settings_override() {
+    init_logger();
+
+    let server = HttpServerTester::new();
+
+    let mut handshake_settings = SettingsFrame::from_settings(vec![
+        HttpSetting::MaxConcurrentStreams(7),
+        HttpSetting::InitialWindowSize(100_000),
+        HttpSetting::MaxFrameSize(32768),
+    ]);
+    handshake_settings.unknown_settings.push((0xf0, 1));
+
+    let mut conf = ClientConf::new();
+    conf.handshake_settings_override = Some(handshake_settings.clone());
+    let client = Client::new_plain(BIND_HOST, server.port(), conf).expect("client");
+
+    let mut server_tester = server.accept();
+    server_tester.recv_preface();
+    server_tester.send_settings(SettingsFrame::new());
+    assert_eq!(handshake_settings, server_tester.recv_frame_settings_set());
+    server_tester.send_frame(SettingsFrame::new_ack());
+    server_tester.recv_frame_settings_ack();
+
+    // Client accepts frames as large as it advertised
+    let req = client.start_get("/large", "localhost").collect();
+    server_tester.recv_message(1);
+    server_tester.send_headers(1, Headers::ok_200(), false);
+    server_tester.send_data(1, &[17; 20000], true);
+
+    let rt = Runtime::new().unwrap();
+    let resp = rt.block_on(req).expect("response");
+    assert_eq!(20000, resp.body.len());
+}
+
+#[test]
+fn handshake_settings_override_invalid() {
+    init_logger();
+
+    let server = HttpServerTester::new();
+
+    let mut conf = ClientConf::new();
+    conf.handshake_settings_override = Some(SettingsFrame::from_settings(vec![
+        HttpSetting::MaxFrameSize(100),
+    ]));
+    assert!(Client::new_plain(BIND_HOST, server.port(), conf).is_err());
+}
+
+#[test]
+fn never_indexed_headers() {
+    init_logger();
+
+    let mut conf = ClientConf::new();
+    conf.common.never_indexed_headers = Some(vec!["Authorization".to_owned()]);
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
+
+    let mut server_tester = server.accept_xchg();
+
+    let mut responses = Vec::new();
+    for stream_id in [1, 3] {
+        let mut headers = Headers::new();
+        headers.add(":method", "GET");
+        headers.add(":path", "/");
+        headers.add(":authority", "localhost");
+        headers.add(":scheme", "http");
+        headers.add("authorization", "Bearer secret");
+        responses.push(client.start_request_end_stream(headers, None, None));
+
+        let headers = server_tester.recv_frame_headers_check(stream_id, true);
+        assert_eq!("Bearer secret", headers.get("authorization"));
+    }
+
+    assert_eq!(2, server_tester.decoder.stats().never_indexed);
+    assert!(server_tester
+        .decoder
+        .dynamic_table_entries()
+        .iter()
+        .all(|(n, _)| n != "authorization"));
+}
+
+#[test]
+fn hpack_huffman_disabled() {
+    init_logger();
+
+    let mut conf = ClientConf::new();
+    conf.common.hpack_huffman = Some(false);
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
+
+    let mut server_tester = server.accept_xchg();
+
+    let mut headers = Headers::new();
+    headers.add(":method", "GET");
+    headers.add(":path", "/");
+    headers.add(":authority", "localhost");
+    headers.add(":scheme", "http");
+    headers.add("x-token", "raw literal value");
+    let _resp = client.start_request_end_stream(headers, None, None);
+
+    let frame = match server_tester.recv_frame() {
+        HttpFrame::Headers(frame) => frame,
+        f => panic!("expecting HEADERS, got: {:?}", f),
+    };
+    let fragment = &frame.header_fragment[..];
+    assert!(fragment
+        .windows(b"raw literal value".len())
+        .any(|w| w == b"raw literal value"));
+}
+
+#[test]
+fn max_frame_size_invalid() {
+    init_logger();
+
+    let server = HttpServerTester::new();
+
+    for max_frame_size in [16383, 16777216] {
+        let mut conf = ClientConf::new();
+        conf.common.max_frame_size = Some(max_frame_size);
+        assert!(Client::new_plain(BIND_HOST, server.port(), conf).is_err());
+    }
+}
+
+#[test]
+fn initial_peer_settings() {
+    init_logger();
+
+    let (server, client) = HttpServerTester::new_with_client();
+
+    assert_eq!(None, client.initial_peer_settings());
+
+    let mut server_tester = server.accept();
+    server_tester.recv_preface();
+    let mut settings = SettingsFrame::from_settings(vec![HttpSetting::MaxConcurrentStreams(17)]);
+    settings.unknown_settings.push((0xf000, 3));
+    settings.unknown_settings.push((8, 1));
+    settings.unknown_settings.push((0xf000, 5));
+    server_tester.send_settings(settings);
+    server_tester.recv_frame_settings_set();
+    server_tester.send_frame(SettingsFrame::new_ack());
+    server_tester.recv_frame_settings_ack();
+
+    let settings = client.initial_peer_settings().expect("settings");
+    assert_eq!(17, settings.max_concurrent_streams);
+    assert_eq!(
+        vec![(8, 1), (0xf000, 5)],
+        client.initial_peer_unknown_settings()
+    );
+
+    // Later updates are not reflected
+    server_tester.send_recv_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::MaxConcurrentStreams(20),
+    ]));
+    let settings = client.initial_peer_settings().expect("settings");
+    assert_eq!(17, settings.max_concurrent_streams);
+}
+
+#[test]
+fn peer_initial_window_size_applies_to_new_streams() {
+    init_logger();
+
+    let rt = Runtime::new().unwrap();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let (_sender1, _response1) = rt
+        .block_on(client.start_post_sink("/before", "sink"))
+        .expect("start_post_sink");
+    server_tester.recv_frame_headers_check(1, false);
+    assert_eq!(65535, client.stream_state(1).out_window_size);
+
+    server_tester.send_recv_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::InitialWindowSize(1000),
+    ]));
+
+    let (_sender3, _response3) = rt
+        .block_on(client.start_post_sink("/after", "sink"))
+        .expect("start_post_sink");
+    server_tester.recv_frame_headers_check(3, false);
+    assert_eq!(1000, client.stream_state(3).out_window_size);
+    assert_eq!(1000, client.stream_state(3).pump_out_window_size);
+}
+
+#[test]
+fn peer_initial_window_size_adjusts_open_streams() {
+    init_logger();
+
+    let rt = Runtime::new().unwrap();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let (_sender, _response) = rt
+        .block_on(client.start_post_sink("/foo", "sink"))
+        .expect("start_post_sink");
+    server_tester.recv_frame_headers_check(1, false);
+    assert_eq!(65535, client.stream_state(1).out_window_size);
+
+    server_tester.send_recv_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::InitialWindowSize(1000),
+    ]));
+    assert_eq!(1000, client.stream_state(1).out_window_size);
+    assert_eq!(1000, client.stream_state(1).pump_out_window_size);
+
+    server_tester.send_recv_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::InitialWindowSize(3000),
+    ]));
+    assert_eq!(3000, client.stream_state(1).out_window_size);
+    assert_eq!(3000, client.stream_state(1).pump_out_window_size);
+}
+
+#[test]
+fn peer_initial_window_size_overflows_open_stream() {
+    init_logger();
+
+    let rt = Runtime::new().unwrap();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let (_sender, _response) = rt
+        .block_on(client.start_post_sink("/foo", "sink"))
+        .expect("start_post_sink");
+    server_tester.recv_frame_headers_check(1, false);
+
+    // Stream window is at maximum
+    server_tester.send_window_update_stream(1, 0x7fffffff - 65535);
+
+    server_tester.send_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::InitialWindowSize(65536),
+    ]));
+    server_tester.recv_goaway_frame_check(ErrorCode::FlowControlError);
+    server_tester.recv_eof();
+}
+
+#[test]
+fn window_update_overflows_stream_window() {
+    init_logger();
+
+    let rt = Runtime::new().unwrap();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let (_sender, _response) = rt
+        .block_on(client.start_post_sink("/foo", "sink"))
+        .expect("start_post_sink");
+    server_tester.recv_frame_headers_check(1, false);
+
+    server_tester.send_window_update_stream(1, 0x7fffffff - 65535);
+    server_tester.send_window_update_stream(1, 1);
+    server_tester.recv_rst_frame_check(1, ErrorCode::FlowControlError);
+
+    // Connection is still alive
+    assert_eq!(0, client.conn_state().streams.len());
+}
+
+#[test]
+fn window_update_overflows_conn_window() {
+    init_logger();
+
+    let (mut server_tester, _client) = HttpConnTester::new_server_with_client_xchg();
+
+    server_tester.send_window_update_conn(0x7fffffff - 65535);
+    // Bypass tester own window accounting
+    server_tester.send_frame(WindowUpdateFrame::for_connection(1));
+    server_tester.recv_goaway_frame_check(ErrorCode::FlowControlError);
+    server_tester.recv_eof();
+}
+
+#[test]
+fn goaway_last_stream_id_greater_than_opened() {
+    init_logger();
+
+    let (server, client) = HttpServerTester::new_with_client();
+
+    let mut server_tester = server.accept_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let req = client.start_get("/111", "localhost").collect();
+    server_tester.recv_message(1);
+
+    // Peer allows streams we have never opened
+    server_tester.send_goaway(0x7fff_ffff);
+
+    server_tester.send_headers(1, Headers::ok_200(), false);
+    server_tester.send_data(1, b"hello", true);
+
+    let resp = rt.block_on(req).expect("OK");
+    assert_eq!(200, resp.headers.status());
+    assert_eq!(&b"hello"[..], resp.body.get_bytes());
+
+    server_tester.recv_eof();
+}
+
+#[test]
+fn goaway_when_client_dropped() {
+    init_logger();
+
+    let rt = Runtime::new().unwrap();
+
+    let server = HttpServerTester::new();
+
+    let mut client = ClientBuilder::new_plain();
+    client
+        .set_addr((BIND_HOST, server.port()))
+        .expect("set_addr");
+    client.event_loop = Some(rt.handle().clone());
+    let client = client.build().expect("client");
+
+    let mut server_tester = server.accept_xchg();
+
+    let req = client.start_get("/111", "localhost").collect();
+    server_tester.recv_message(1);
+    server_tester.send_headers(1, Headers::ok_200(), true);
+    assert_eq!(200, rt.block_on(req).expect("OK").headers.status());
+
+    // Connection outlives the client on the shared event loop,
+    // so it must close itself
+    drop(client);
+
+    let goaway = server_tester.recv_goaway_frame();
+    assert_eq!(ErrorCode::NoError, goaway.error_code());
+    assert_eq!(0, goaway.last_stream_id());
+
+    server_tester.recv_eof();
+}
+
+#[test]
+fn start_request_split() {
+    init_logger();
+
+    let (server, client) = HttpServerTester::new_with_client();
+
+    let mut server_tester = server.accept_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let headers = Headers::from_vec(vec![
+        Header::new(":method", "GET"),
+        Header::new(":path", "/split"),
+        Header::new(":authority", "localhost"),
+        Header::new(":scheme", "http"),
+    ]);
+
+    let (headers, body) = client.start_request_split(headers, None, None);
+    server_tester.recv_message(1);
+
+    // Headers are available while server has not sent body yet
+    server_tester.send_headers(1, Headers::ok_200(), false);
+    assert_eq!(200, rt.block_on(headers).expect("headers").status());
+
+    server_tester.send_data(1, b"abc", false);
+    server_tester.send_data(1, b"de", true);
+    let body: Vec<Bytes> = rt
+        .block_on(body.collect::<Vec<_>>())
+        .into_iter()
+        .collect::<Result<_>>()
+        .expect("body");
+    assert_eq!(b"abcde".to_vec(), body.concat());
+}
+
+#[test]
+fn response_trailers() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let mut trailers = Headers::new();
+    trailers.add("grpc-status", "0");
+
+    let req = client.start_get("/collect", "localhost").collect();
+    server_tester.recv_message(1);
+    server_tester.send_headers(1, Headers::ok_200(), false);
+    server_tester.send_data(1, b"abc", false);
+    server_tester.send_headers(1, trailers.clone(), true);
+
+    let message = rt.block_on(req).expect("collect");
+    assert_eq!(200, message.headers.status());
+    assert_eq!(None, message.headers.get_opt("grpc-status"));
+    assert_eq!(&b"abc"[..], &message.body.get_bytes()[..]);
+    assert_eq!(Some(&trailers), message.trailers.as_ref());
+
+    // Trailers are distinct from initial headers when reading the stream
+    let resp = client.start_get("/stream", "localhost");
+    server_tester.recv_message(3);
+    server_tester.send_headers(3, Headers::ok_200(), false);
+    server_tester.send_data(3, b"de", false);
+    server_tester.send_headers(3, trailers.clone(), true);
+
+    let (headers, stream) = rt.block_on(resp).expect("headers");
+    assert_eq!(200, headers.status());
+    let parts = rt
+        .block_on(stream.collect::<Vec<_>>())
+        .into_iter()
+        .collect::<Result<Vec<_>>>()
+        .expect("stream");
+    match &parts[..] {
+        [DataOrTrailers::Data(data, _), DataOrTrailers::Trailers(t)] => {
+            assert_eq!(&b"de"[..], &data[..]);
+            assert_eq!(&trailers, t);
+        }
+        _ => panic!("expecting data and trailers"),
+    }
+}
+
+#[test]
+fn trailers_without_end_stream() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let mut trailers = Headers::new();
+    trailers.add("grpc-status", "0");
+
+    let req = client.start_get("/trailers", "localhost").collect();
+    server_tester.recv_message(1);
+    server_tester.send_headers(1, Headers::ok_200(), false);
+    server_tester.send_data(1, b"abc", false);
+    server_tester.send_headers(1, trailers, false);
+
+    server_tester.recv_rst_frame_check(1, ErrorCode::ProtocolError);
+    assert!(rt.block_on(req).is_err());
+}
+
+fn ok_200_content_length(content_length: u64) -> Headers {
+    let mut headers = Headers::ok_200();
+    headers.add("content-length", content_length.to_string());
+    headers
+}
+
+#[test]
+fn response_body_longer_than_content_length() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let req = client.start_get("/long", "localhost").collect();
+    server_tester.recv_message(1);
+    server_tester.send_headers(1, ok_200_content_length(3), false);
+    server_tester.send_data(1, b"abcde", true);
+
+    server_tester.recv_rst_frame_check(1, ErrorCode::ProtocolError);
+    assert!(rt.block_on(req).is_err());
+}
+
+#[test]
+fn response_body_shorter_than_content_length() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let req = client.start_get("/short", "localhost").collect();
+    server_tester.recv_message(1);
+    server_tester.send_headers(1, ok_200_content_length(10), false);
+    server_tester.send_data(1, b"abc", false);
+    server_tester.send_data(1, b"de", true);
+
+    server_tester.recv_rst_frame_check(1, ErrorCode::ProtocolError);
+    assert!(rt.block_on(req).is_err());
+
+    let req = client.start_get("/trailers", "localhost").collect();
+    server_tester.recv_message(3);
+    server_tester.send_headers(3, ok_200_content_length(10), false);
+    server_tester.send_data(3, b"abc", false);
+    let mut trailers = Headers::new();
+    trailers.add("grpc-status", "0");
+    server_tester.send_headers(3, trailers, true);
+
+    server_tester.recv_rst_frame_check(3, ErrorCode::ProtocolError);
+    assert!(rt.block_on(req).is_err());
+}
+
+#[test]
+fn response_headers_end_stream_with_content_length() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let req = client.start_get("/empty", "localhost").collect();
+    server_tester.recv_message(1);
+    server_tester.send_headers(1, ok_200_content_length(10), true);
+
+    server_tester.recv_rst_frame_check(1, ErrorCode::ProtocolError);
+    assert!(rt.block_on(req).is_err());
+
+    // 204 response may have `content-length` without body
+    let req = client.start_get("/no-content", "localhost").collect();
+    server_tester.recv_message(3);
+    let mut headers = Headers::new_status(204);
+    headers.add("content-length", "10");
+    server_tester.send_headers(3, headers, true);
+
+    let resp = rt.block_on(req).expect("response");
+    assert_eq!(204, resp.headers.status());
+
+    let req = client.start_get("/zero", "localhost").collect();
+    server_tester.recv_message(5);
+    server_tester.send_headers(5, ok_200_content_length(0), true);
+
+    let resp = rt.block_on(req).expect("response");
+    assert_eq!(200, resp.headers.status());
+}
+
+#[test]
+fn response_to_head_with_content_length() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let headers = Headers::from_vec(vec![
+        Header::new(":method", "HEAD"),
+        Header::new(":path", "/head"),
+        Header::new(":authority", "localhost"),
+        Header::new(":scheme", "http"),
+    ]);
+    let req = client
+        .start_request_end_stream(headers, None, None)
+        .collect();
+    server_tester.recv_message(1);
+    server_tester.send_headers(1, ok_200_content_length(10), false);
+    server_tester.send_data(1, b"", true);
+
+    let resp = rt.block_on(req).expect("response");
+    assert_eq!(200, resp.headers.status());
+    assert_eq!(0, resp.body.get_bytes().len());
+}
+
+#[test]
+fn response_pseudo_header_after_regular_header() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let req = client.start_get("/order", "localhost").collect();
+    server_tester.recv_message(1);
+    let fragment = server_tester.encoder.encode_for_test(
+        vec![
+            (&b"content-type"[..], &b"text/plain"[..]),
+            (&b":status"[..], &b"200"[..]),
+        ]
+        .into_iter(),
+    );
+    let mut headers_frame = HeadersFrame::new_conv(fragment, 1);
+    headers_frame.set_flag(HeadersFlag::EndHeaders);
+    headers_frame.set_flag(HeadersFlag::EndStream);
+    server_tester.send_frame(headers_frame);
+
+    server_tester.recv_rst_frame_check(1, ErrorCode::ProtocolError);
+    assert!(rt.block_on(req).is_err());
+}
+
+fn send_uppercase_content_type(server_tester: &mut HttpConnTester, stream_id: StreamId) {
+    let fragment = server_tester.encoder.encode_for_test(
+        vec![
+            (&b":status"[..], &b"200"[..]),
+            (&b"Content-Type"[..], &b"text/plain"[..]),
+        ]
+        .into_iter(),
+    );
+    let mut headers_frame = HeadersFrame::new_conv(fragment, stream_id);
+    headers_frame.set_flag(HeadersFlag::EndHeaders);
+    headers_frame.set_flag(HeadersFlag::EndStream);
+    server_tester.send_frame(headers_frame);
+}
+
+#[test]
+fn response_uppercase_header_name() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let req = client.start_get("/upper", "localhost").collect();
+    server_tester.recv_message(1);
+    send_uppercase_content_type(&mut server_tester, 1);
+
+    server_tester.recv_rst_frame_check(1, ErrorCode::ProtocolError);
+    assert!(rt.block_on(req).is_err());
+
+    let req = client.start_get("/lower", "localhost").collect();
+    server_tester.recv_message(3);
+    let mut headers = Headers::ok_200();
+    headers.add("content-type", "text/plain");
+    server_tester.send_headers(3, headers, true);
+
+    let resp = rt.block_on(req).expect("response");
+    assert_eq!("text/plain", resp.headers.get("content-type"));
+}
+
+#[test]
+fn response_uppercase_header_name_allowed() {
+    init_logger();
+
+    let mut conf = ClientConf::new();
+    conf.allow_uppercase_headers = true;
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
+    let mut server_tester = server.accept_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let req = client.start_get("/upper", "localhost").collect();
+    server_tester.recv_message(1);
+    send_uppercase_content_type(&mut server_tester, 1);
+
+    let resp = rt.block_on(req).expect("response");
+    assert_eq!("text/plain", resp.headers.get("content-type"));
+}
+
+#[test]
+fn start_request_parts() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let headers = Headers::from_vec(vec![
+        Header::new(":method", "GET"),
+        Header::new(":path", "/parts"),
+        Header::new(":authority", "localhost"),
+        Header::new(":scheme", "http"),
+    ]);
+
+    let mut trailers = Headers::new();
+    trailers.add("grpc-status", "0");
+
+    let parts = client.start_request_parts(headers, None, None);
+    server_tester.recv_message(1);
+    server_tester.send_headers(1, Headers::ok_200(), false);
+    server_tester.send_data(1, b"abc", false);
+    server_tester.send_data(1, b"de", false);
+    server_tester.send_headers(1, trailers.clone(), true);
+
+    let parts = rt
+        .block_on(parts.collect::<Vec<_>>())
+        .into_iter()
+        .collect::<Result<Vec<_>>>()
+        .expect("parts");
+    match &parts[..] {
+        [DataOrHeaders::Headers(h), DataOrHeaders::Data(d1), DataOrHeaders::Data(d2), DataOrHeaders::Headers(t)] =>
+        {
+            assert_eq!(200, h.status());
+            assert_eq!(&b"abc"[..], &d1[..]);
+            assert_eq!(&b"de"[..], &d2[..]);
+            assert_eq!(&trailers, t);
+        }
+        _ => panic!("unexpected parts: {:?}", parts),
+    }
+}
+
+#[test]
+fn request_timeout() {
+    init_logger();
+
+    let (server, client) = HttpServerTester::new_with_client();
+
+    let mut server_tester = server.accept_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let headers = Headers::from_vec(vec![
+        Header::new(":method", "GET"),
+        Header::new(":path", "/slow"),
+        Header::new(":authority", "localhost"),
+        Header::new(":scheme", "http"),
+    ]);
+
+    // completed in time
+    let fast = client.start_request_with_timeout(
+        headers.clone(),
+        None,
+        None,
+        true,
+        Duration::from_millis(300),
+    );
+    server_tester.recv_message(1);
+    server_tester.send_headers(1, Headers::ok_200(), true);
+    let (_req, resp) = rt.block_on(fast).expect("request");
+    assert_eq!(
+        200,
+        rt.block_on(resp.collect()).expect("OK").headers.status()
+    );
+
+    // server never responds
+    let slow =
+        client.start_request_with_timeout(headers, None, None, true, Duration::from_millis(300));
+    server_tester.recv_message(3);
+    let (_req, resp) = rt.block_on(slow).expect("request");
+    match rt.block_on(resp.collect()) {
+        Err(Error::RequestTimeout) => {}
+        r => panic!("expecting timeout: {:?}", r.map(|r| r.headers)),
+    }
+
+    server_tester.recv_rst_frame_check(3, ErrorCode::Cancel);
+
+    // connection is still usable
+    let req = client.start_get("/111", "localhost").collect();
+    server_tester.recv_message(5);
+    server_tester.send_headers(5, Headers::ok_200(), true);
+    assert_eq!(200, rt.block_on(req).expect("OK").headers.status());
+}
+
+#[test]
+fn peer_max_concurrent_streams() {
+    init_logger();
+
+    let (server, client) = HttpServerTester::new_with_client();
+
+    let mut server_tester = server.accept();
+    server_tester.recv_preface();
+    server_tester.send_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::MaxConcurrentStreams(2),
+    ]));
+    server_tester.recv_frame_settings_set();
+    server_tester.send_frame(SettingsFrame::new_ack());
+    server_tester.recv_frame_settings_ack();
+
+    let rt = Runtime::new().unwrap();
+
+    let req1 = client.start_get("/1", "localhost").collect();
+    let req2 = client.start_get("/2", "localhost").collect();
+    let req3 = client.start_get("/3", "localhost").collect();
+
+    assert_eq!("/1", server_tester.recv_message(1).headers.path());
+    assert_eq!("/2", server_tester.recv_message(3).headers.path());
+
+    // third request is queued until one of the streams is closed
+    assert_eq!(2, client.conn_state().streams.len());
+
+    server_tester.send_headers(1, Headers::ok_200(), true);
+    assert_eq!(200, rt.block_on(req1).expect("OK").headers.status());
+
+    assert_eq!("/3", server_tester.recv_message(5).headers.path());
+    assert_eq!(2, client.conn_state().streams.len());
+
+    server_tester.send_headers(3, Headers::ok_200(), true);
+    server_tester.send_headers(5, Headers::ok_200(), true);
+    assert_eq!(200, rt.block_on(req2).expect("OK").headers.status());
+    assert_eq!(200, rt.block_on(req3).expect("OK").headers.status());
+
+    // queued requests fail when connection dies
+    let req4 = client.start_get("/4", "localhost").collect();
+    let req5 = client.start_get("/5", "localhost").collect();
+    let req6 = client.start_get("/6", "localhost").collect();
+    server_tester.recv_message(7);
+    server_tester.recv_message(9);
+    drop(server_tester);
+
+    assert!(rt.block_on(req4).is_err());
+    assert!(rt.block_on(req5).is_err());
+    match rt.block_on(req6) {
+        Err(Error::ClientDied(_)) => {}
+        r => panic!("expecting client died: {:?}", r.map(|r| r.headers)),
+    }
+}
+
+#[test]
+fn start_request_streaming() {
+    init_logger();
+
+    let server = ServerTest::new();
+    let client = Client::new_plain(BIND_HOST, server.port, ClientConf::new()).expect("client");
+
+    let rt = Runtime::new().unwrap();
+
+    let headers = Headers::from_vec(vec![
+        Header::new(":method", "POST"),
+        Header::new(":path", "/echo"),
+        Header::new(":authority", "localhost"),
+        Header::new(":scheme", "http"),
+    ]);
+    let (mut req, resp) = rt
+        .block_on(client.start_request_streaming(headers))
+        .expect("start_request_streaming");
+
+    // Response is read concurrently, otherwise echo stalls on flow control
+    let resp = rt.spawn(resp.collect());
+
+    let chunks: Vec<Bytes> = (0..64u32)
+        .map(|i| Bytes::from(vec![(i % 0xff) as u8; 0x10000]))
+        .collect();
+
+    rt.block_on(async {
+        for chunk in &chunks {
+            req.ready().await.expect("ready");
+            req.send_data(chunk.clone()).expect("send_data");
+        }
+        req.close().expect("close");
+    });
+
+    let resp = rt.block_on(resp).unwrap().expect("response");
+    assert_eq!(200, resp.headers.status());
+    assert_eq!(chunks.concat(), resp.body.get_bytes());
+}
+
+#[test]
+fn over_tls_io() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let rt = Runtime::new().unwrap();
+
+    // In-memory connection bridged to the test server,
+    // pretending TLS with ALPN `h2` was done by the user
+    let (client_io, mut bridge_io) = tokio::io::duplex(0x10000);
+    let port = server.port;
+    rt.spawn(async move {
+        let mut tcp = tokio::net::TcpStream::connect((BIND_HOST, port))
+            .await
+            .expect("connect");
+        drop(tokio::io::copy_bidirectional(&mut bridge_io, &mut tcp).await);
+    });
+
+    let client = Client::over_tls_io(client_io, "h2", ClientConf::new()).expect("client");
+
+    let resp = rt
+        .block_on(
+            client
+                .start_post("/echo", "localhost", Bytes::from_static(b"abcd"))
+                .collect(),
+        )
+        .expect("post");
+    assert_eq!(200, resp.headers.status());
+    assert_eq!(&b"abcd"[..], resp.body.get_bytes());
+
+    let (io, _) = tokio::io::duplex(1);
+    match Client::over_tls_io(io, "http/1.1", ClientConf::new()) {
+        Err(Error::AlpnMismatch(alpn)) => assert_eq!("http/1.1", alpn),
+        r => panic!("expecting ALPN error: {:?}", r),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum IoEvent {
+    Write(Vec<u8>),
+    Flush,
+}
+
+/// In-memory IO which records writes and flushes.
+struct RecordIo {
+    io: DuplexStream,
+    events: Arc<Mutex<Vec<IoEvent>>>,
+}
+
+impl AsyncRead for RecordIo {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for RecordIo {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let r = Pin::new(&mut self.io).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = r {
+            let write = IoEvent::Write(buf[..n].to_vec());
+            self.events.lock().unwrap().push(write);
+        }
+        r
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let r = Pin::new(&mut self.io).poll_flush(cx);
+        if let Poll::Ready(Ok(())) = r {
+            self.events.lock().unwrap().push(IoEvent::Flush);
+        }
+        r
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+/// Client connected to `server` over `RecordIo`.
+fn record_io_client(server: &HttpServerTester, rt: &Runtime) -> (Client, Arc<Mutex<Vec<IoEvent>>>) {
+    let (client_io, mut bridge_io) = tokio::io::duplex(0x10000);
+    let port = server.port();
+    rt.spawn(async move {
+        let mut tcp = tokio::net::TcpStream::connect((BIND_HOST, port))
+            .await
+            .expect("connect");
+        drop(tokio::io::copy_bidirectional(&mut bridge_io, &mut tcp).await);
+    });
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let io = RecordIo {
+        io: client_io,
+        events: events.clone(),
+    };
+    let client = Client::over_tls_io(io, "h2", ClientConf::new()).expect("client");
+    (client, events)
+}
+
+/// Socket is flushed right after data sent with `send_data_flush` is written.
+fn assert_flushed_after_first(events: &[IoEvent]) {
+    let first = events
+        .iter()
+        .position(|e| matches!(e, IoEvent::Write(w) if w.ends_with(b"first")))
+        .expect("first");
+    let second = events
+        .iter()
+        .position(|e| matches!(e, IoEvent::Write(w) if w.ends_with(b"second")))
+        .expect("second");
+    assert_eq!(IoEvent::Flush, events[first + 1]);
+    assert!(first < second);
+}
+
+#[test]
+fn send_data_flush() {
+    init_logger();
+
+    let server = HttpServerTester::new();
+
+    let rt = Runtime::new().unwrap();
+
+    let (client, events) = record_io_client(&server, &rt);
+
+    let mut server_tester = server.accept();
+    server_tester.recv_preface();
+    server_tester.settings_xchg();
+    server_tester.send_recv_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::InitialWindowSize(0),
+    ]));
+
+    let (mut sender, _response) = rt
+        .block_on(client.start_post_sink("/foo", "sink"))
+        .expect("start_post_sink");
+    server_tester.recv_frame_headers_check(1, false);
+
+    sender
+        .send_data_flush(Bytes::from_static(b"first"))
+        .unwrap();
+    sender.send_data(Bytes::from_static(b"second")).unwrap();
+
+    // Both parts become writable at once
+    while client.stream_state(1).queued_out_data_size != 11 {
+        thread::sleep(Duration::from_millis(1));
+    }
+    server_tester.send_window_update_stream(1, 100);
+
+    assert_eq!(
+        &b"first"[..],
+        &server_tester.recv_frame_data_check(1, false)[..]
+    );
+    assert_eq!(
+        &b"second"[..],
+        &server_tester.recv_frame_data_check(1, false)[..]
+    );
+
+    assert_flushed_after_first(&events.lock().unwrap());
+}
+
+#[test]
+fn send_data_flush_open_window() {
+    init_logger();
+
+    let server = HttpServerTester::new();
+
+    let rt = Runtime::new().unwrap();
+
+    let (client, events) = record_io_client(&server, &rt);
+
+    let mut server_tester = server.accept();
+    server_tester.recv_preface();
+    server_tester.settings_xchg();
+
+    let (mut sender, _response) = rt
+        .block_on(client.start_post_sink("/foo", "sink"))
+        .expect("start_post_sink");
+    server_tester.recv_frame_headers_check(1, false);
+
+    // Data is written as soon as it is enqueued, flush request
+    // must be processed together with the data
+    for _ in 0..100 {
+        sender
+            .send_data_flush(Bytes::from_static(b"first"))
+            .unwrap();
+        sender.send_data(Bytes::from_static(b"second")).unwrap();
+    }
+
+    for _ in 0..100 {
+        assert_eq!(
+            &b"first"[..],
+            &server_tester.recv_frame_data_check(1, false)[..]
+        );
+        assert_eq!(
+            &b"second"[..],
+            &server_tester.recv_frame_data_check(1, false)[..]
+        );
+    }
+
+    let events = events.lock().unwrap();
+    for (i, e) in events.iter().enumerate() {
+        if let IoEvent::Write(w) = e {
+            if w.ends_with(b"first") {
+                assert_eq!(IoEvent::Flush, events[i + 1]);
+            }
+        }
+    }
+    assert_flushed_after_first(&events);
+}
+
+#[test]
+fn h2c_upgrade() {
+    init_logger();
+
+    let mut conf = ClientConf::new();
+    conf.enable_h2c_upgrade = true;
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
+    let mut server_tester = server.accept();
+
+    // The first request is the upgrade request
+    let first = client
+        .start_post("/first", "example.com", Bytes::from_static(b"hello"))
+        .collect();
+
+    let head = server_tester.recv_http_1_request_head();
+    assert!(head.starts_with("POST /first HTTP/1.1\r\n"), "{}", head);
+    assert!(head.contains("\r\nHost: example.com\r\n"), "{}", head);
+    assert!(head.contains("\r\nContent-Length: 5\r\n"), "{}", head);
+    assert!(
+        head.contains("\r\nConnection: Upgrade, HTTP2-Settings\r\n"),
+        "{}",
+        head
+    );
+    assert!(head.contains("\r\nUpgrade: h2c\r\n"), "{}", head);
+    // `SETTINGS_ENABLE_PUSH = 0`, `SETTINGS_MAX_HEADER_LIST_SIZE = 65536`
+    assert!(
+        head.contains("\r\nHTTP2-Settings: AAIAAAAAAAYAAQAA\r\n"),
+        "{}",
+        head
+    );
+    assert_eq!(&b"hello"[..], &server_tester.recv_raw(5)[..]);
+
+    server_tester.send_raw(
+        b"HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: h2c\r\n\r\n",
+    );
+    server_tester.recv_preface();
+    server_tester.settings_xchg();
+
+    // Response to the upgrade request
+    server_tester.send_headers(1, Headers::ok_200(), false);
+    server_tester.send_data(1, b"upgraded", true);
+
+    let rt = Runtime::new().unwrap();
+
+    let message = rt.block_on(first).expect("first");
+    assert_eq!(200, message.headers.status());
+    assert_eq!(&b"upgraded"[..], &message.body.get_bytes()[..]);
+
+    let req = client.start_get("/after-upgrade", "localhost").collect();
+    server_tester.recv_message(3);
+    server_tester.send_headers(3, Headers::ok_200(), false);
+    server_tester.send_data(3, b"abc", true);
+
+    let message = rt.block_on(req).expect("get");
+    assert_eq!(200, message.headers.status());
+    assert_eq!(&b"abc"[..], &message.body.get_bytes()[..]);
+
+    let state = rt.block_on(client.dump_state()).expect("dump_state");
+    assert_eq!(0, state.streams.len(), "{:?}", state);
+}
+
+#[test]
+fn h2c_upgrade_rejected() {
+    init_logger();
+
+    let mut conf = ClientConf::new();
+    conf.enable_h2c_upgrade = true;
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
+    let mut server_tester = server.accept();
+
+    let req = client.start_get("/", "localhost").collect();
+
+    server_tester.recv_http_1_request_head();
+    server_tester.send_raw(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+
+    let rt = Runtime::new().unwrap();
+
+    match rt.block_on(req) {
+        Err(Error::ConnDied(e)) => match &*e {
+            Error::H2cUpgradeFailed(status_line) => assert_eq!("HTTP/1.1 200 OK", status_line),
+            e => panic!("expecting h2c upgrade error, got: {:?}", e),
+        },
+        Err(e) => panic!("expecting h2c upgrade error, got: {:?}", e),
+        Ok(_) => panic!("expecting h2c upgrade error"),
+    }
+}
+
+#[test]
+fn goaway_when_client_dropped_response_in_progress() {
+    init_logger();
+
+    let rt = Runtime::new().unwrap();
+
+    let server = HttpServerTester::new();
+
+    let mut client = ClientBuilder::new_plain();
+    client
+        .set_addr((BIND_HOST, server.port()))
+        .expect("set_addr");
+    client.event_loop = Some(rt.handle().clone());
+    let client = client.build().expect("client");
+
+    let mut server_tester = server.accept_xchg();
+
+    let req = rt.spawn(client.start_get("/big", "localhost").collect());
+    server_tester.recv_message(1);
+    server_tester.send_headers(1, Headers::ok_200(), false);
+
+    drop(client);
+
+    let goaway = server_tester.recv_goaway_frame();
+    assert_eq!(ErrorCode::NoError, goaway.error_code());
+
+    // Response larger than initial windows completes only if
+    // client keeps sending `WINDOW_UPDATE` after `GOAWAY`
+    let body = vec![17; 1 << 20];
+    let mut stream_window = DEFAULT_SETTINGS.initial_window_size as usize;
+    for chunk in body.chunks(16384) {
+        while cmp::min(server_tester.out_window_size.size() as usize, stream_window) < chunk.len() {
+            match server_tester.fn_recv_frame_no_check_ack() {
+                HttpFrame::WindowUpdate(f) if f.stream_id == 0 => {
+                    server_tester
+                        .out_window_size
+                        .try_increase(f.increment)
+                        .unwrap();
+                }
+                HttpFrame::WindowUpdate(f) => {
+                    assert_eq!(1, f.stream_id);
+                    stream_window += f.increment as usize;
+                }
+                f => panic!("unexpected frame: {:?}", f),
+            }
+        }
+        server_tester.send_data(1, chunk, false);
+        stream_window -= chunk.len();
+    }
+    server_tester.send_data(1, &[], true);
+
+    let resp = rt.block_on(req).unwrap().expect("OK");
+    assert_eq!(200, resp.headers.status());
+    assert_eq!(&body[..], resp.body.get_bytes());
+
+    // Skip trailing `WINDOW_UPDATE` frames
+    if let Some(frame) = server_tester.recv_frame_timeout(Duration::from_secs(1)) {
+        panic!("unexpected frame: {:?}", frame);
+    }
+    server_tester.recv_eof();
+}
+
+fn pushed_request_headers(path: &str) -> Headers {
+    let mut headers = Headers::new();
+    headers.add(":method", "GET");
+    headers.add(":path", path);
+    headers.add(":scheme", "http");
+    headers
+}
+
+#[test]
+fn push_promise_when_push_disabled() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let _req = client.start_get("/main", "localhost").collect();
+    server_tester.recv_message(1);
+
+    server_tester.send_push_promise(1, 2, pushed_request_headers("/pushed"));
+
+    server_tester.recv_goaway_frame_check(ErrorCode::ProtocolError);
+}
+
+#[test]
+fn push_promise_refused_by_default() {
+    init_logger();
+
+    let mut conf = ClientConf::new();
+    conf.enable_push = true;
+
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
+
+    let mut server_tester = server.accept_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let req = client.start_get("/main", "localhost").collect();
+    server_tester.recv_message(1);
+
+    server_tester.send_push_promise(1, 2, pushed_request_headers("/pushed"));
+    server_tester.recv_rst_frame_check(2, ErrorCode::Cancel);
+
+    server_tester.send_headers(1, Headers::ok_200(), false);
+    server_tester.send_data(1, b"main", true);
+
+    let resp = rt.block_on(req).expect("OK");
+    assert_eq!(&b"main"[..], resp.body.get_bytes());
+}
+
+#[test]
+fn informational_headers() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let events = start_request_channel(&client, get_request_headers("/hints")).events;
+    server_tester.recv_message(1);
+
+    let mut early_hints = Headers::new_status(103);
+    early_hints.add("link", "</style.css>; rel=preload; as=style");
+    server_tester.send_headers(1, early_hints, false);
+    server_tester.send_headers(1, Headers::ok_200(), true);
+
+    match events.recv().unwrap() {
+        StreamEvent::InformationalHeaders(headers) => assert_eq!(103, headers.status()),
+        _ => panic!("expecting informational headers"),
+    }
+    match events.recv().unwrap() {
+        StreamEvent::Headers(headers, true) => assert_eq!(200, headers.status()),
+        _ => panic!("expecting final headers"),
+    }
+
+    // Default handler skips informational headers
+    let req = client.start_get("/continue", "localhost").collect();
+    server_tester.recv_message(3);
+    server_tester.send_headers(3, Headers::new_status(100), false);
+    server_tester.send_headers(3, Headers::ok_200(), true);
+    let rt = Runtime::new().unwrap();
+    assert_eq!(200, rt.block_on(req).expect("response").headers.status());
+}
+
+#[test]
+fn push_promise_accepted() {
+    init_logger();
+
+    let mut conf = ClientConf::new();
+    conf.enable_push = true;
+
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
+
+    let mut server_tester = server.accept_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let events = start_request_channel(&client, get_request_headers("/main")).events;
+    server_tester.recv_message(1);
+
+    server_tester.send_push_promise(1, 2, pushed_request_headers("/pushed"));
+
+    let (promised_stream_id, request_headers, pushed) =
+        match events.recv_timeout(Duration::from_secs(10)).unwrap() {
+            StreamEvent::PushPromise(promised_stream_id, request_headers, pushed) => {
+                (promised_stream_id, request_headers, pushed)
+            }
+            _ => panic!("expecting push promise"),
+        };
+    assert_eq!(2, promised_stream_id);
+    assert_eq!("/pushed", request_headers.path());
+
+    server_tester.send_headers(2, Headers::ok_200(), false);
+    server_tester.send_data(2, b"pushed", true);
+
+    let pushed = rt.block_on(pushed.collect()).expect("pushed");
+    assert_eq!(200, pushed.headers.status());
+    assert_eq!(&b"pushed"[..], pushed.body.get_bytes());
+}
+
+#[test]
+fn stream_handler_backpressure() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let events = start_request_channel(&client, get_request_headers("/slow-consumer"));
+    server_tester.recv_message(1);
+    server_tester.send_headers(1, Headers::ok_200(), false);
+
+    // Exhaust the stream window
+    let window = DEFAULT_SETTINGS.initial_window_size as usize;
+    let chunk = [17; 16384];
+    let mut sent = 0;
+    while sent != window {
+        let len = cmp::min(chunk.len(), window - sent);
+        server_tester.send_data(1, &chunk[..len], false);
+        sent += len;
+    }
+
+    // Stream window is not replenished while data is not consumed
+    server_tester.send_frame(PingFrame::with_data(17));
+    loop {
+        match server_tester.fn_recv_frame_no_check_ack() {
+            HttpFrame::WindowUpdate(f) => assert_eq!(0, f.stream_id, "{:?}", f),
+            HttpFrame::Ping(f) => {
+                assert!(f.is_ack());
+                break;
+            }
+            f => panic!("unexpected frame: {:?}", f),
+        }
+    }
+
+    // Window is not increased until data is consumed
+    let received: usize = events
+        .events
+        .try_iter()
+        .map(|e| match e {
+            StreamEvent::Headers(..) => 0,
+            StreamEvent::Data(data, _) => data.len(),
+            _ => panic!("unexpected event"),
+        })
+        .sum();
+    assert_eq!(window, received);
+
+    let mut increase_in_window = events.increase_in_window.recv().unwrap();
+    increase_in_window.data_frame_processed(received as u32);
+    assert_eq!(0, increase_in_window.in_window_size());
+
+    // Consuming the data opens the window
+    increase_in_window.increase_window(1000).unwrap();
+    loop {
+        match server_tester.fn_recv_frame_no_check_ack() {
+            // Connection window may be replenished after `PING` is acknowledged
+            HttpFrame::WindowUpdate(f) if f.stream_id == 0 => {}
+            HttpFrame::WindowUpdate(f) => {
+                assert_eq!(1, f.stream_id);
+                assert_eq!(1000, f.increment);
+                break;
+            }
+            f => panic!("unexpected frame: {:?}", f),
+        }
+    }
+}
+
+#[test]
+fn header_block_too_large() {
+    init_logger();
+
+    let mut conf = ClientConf::new();
+    conf.common.max_header_block_size = Some(100);
 
-    let req = client.start_get("/fgfg", "localhost").collect();
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
 
-    let get = server_tester.recv_message(1);
-    assert_eq!("GET", get.headers.method());
+    let mut server_tester = server.accept_xchg();
 
-    server_tester.send_headers(1, Headers::ok_200(), false);
-    server_tester.send_rst(1, ErrorCode::InadequateSecurity);
+    let _req = client.start_get("/111", "localhost").collect();
+    server_tester.recv_message(1);
+
+    // Header block is not decoded before it is complete
+    server_tester.send_frame(HeadersFrame::new_conv(vec![0; 60], 1));
+    server_tester.send_frame(ContinuationFrame::new(Bytes::from(vec![0; 60]), 1));
+
+    server_tester.recv_goaway_frame_check(ErrorCode::EnhanceYourCalm);
+    server_tester.recv_eof();
+}
+
+#[test]
+fn goaway_error_vs_rst_stream() {
+    init_logger();
+
+    let (server, client) = HttpServerTester::new_with_client();
+
+    let mut server_tester = server.accept_xchg();
 
     let rt = Runtime::new().unwrap();
 
-    match rt.block_on(req) {
-        Ok(..) => panic!("expected error"),
-        Err(Error::RstStreamReceived(ErrorCode::InadequateSecurity)) => {}
-        Err(e) => panic!("wrong error: {:?}", e),
-    }
+    let req1 = client.start_get("/1", "localhost").collect();
+    server_tester.recv_message(1);
+    let req3 = client.start_get("/3", "localhost").collect();
+    server_tester.recv_message(3);
 
-    let state: ConnStateSnapshot = client.conn_state();
-    assert_eq!(0, state.streams.len(), "{:?}", state);
+    server_tester.send_rst(1, ErrorCode::Cancel);
+    server_tester.send_frame(GoawayFrame::with_debug_data(
+        1,
+        ErrorCode::ProtocolError,
+        Bytes::from_static(b"bad frame"),
+    ));
+
+    match rt.block_on(req1) {
+        Err(Error::RstStreamReceived(ErrorCode::Cancel)) => {}
+        Err(e) => panic!("expecting RstStreamReceived, got: {:?}", e),
+        Ok(_) => panic!("expecting error"),
+    }
+    match rt.block_on(req3) {
+        Err(Error::ConnectionError(ErrorCode::ProtocolError, debug_data)) => {
+            assert_eq!(&b"bad frame"[..], &debug_data[..]);
+        }
+        Err(e) => panic!("expecting ConnectionError, got: {:?}", e),
+        Ok(_) => panic!("expecting error"),
+    }
 }
 
 #[test]
-fn handle_1xx_headers() {
+fn too_many_continuation_frames() {
     init_logger();
 
-    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+    let mut conf = ClientConf::new();
+    conf.common.max_continuation_frames = Some(3);
 
-    let req = client.start_get("/fgfg", "localhost").collect();
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
 
-    let get = server_tester.recv_message(1);
-    assert_eq!("GET", get.headers.method());
+    let mut server_tester = server.accept_xchg();
 
-    server_tester.send_headers(1, Headers::new_status(100), false);
-    server_tester.send_headers(1, Headers::new_status(100), false);
+    let _req = client.start_get("/111", "localhost").collect();
+    server_tester.recv_message(1);
 
-    server_tester.send_headers(1, Headers::ok_200(), false);
+    // Total size is far below header block size limit
+    server_tester.send_frame(HeadersFrame::new_conv(vec![0; 1], 1));
+    for _ in 0..4 {
+        server_tester.send_frame(ContinuationFrame::new(Bytes::from(vec![0; 1]), 1));
+    }
 
-    server_tester.send_data(1, b"hello", true);
+    server_tester.recv_goaway_frame_check(ErrorCode::EnhanceYourCalm);
+    server_tester.recv_eof();
+}
 
-    let rt = Runtime::new().unwrap();
+#[test]
+fn frame_larger_than_max_frame_size() {
+    init_logger();
 
-    rt.block_on(req).expect("Should be OK");
+    let mut conf = ClientConf::new();
+    conf.common.max_frame_size = Some(20000);
 
-    let state: ConnStateSnapshot = client.conn_state();
-    assert_eq!(0, state.streams.len(), "{:?}", state);
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
+
+    let mut server_tester = server.accept_xchg();
+    assert_eq!(20000, server_tester.peer_settings.max_frame_size);
+
+    let _req = client.start_get("/111", "localhost").collect();
+    server_tester.recv_message(1);
+
+    server_tester.send_headers(1, Headers::ok_200(), false);
+    // Frame of exactly advertised size is accepted
+    server_tester.send_data(1, &[0; 20000], false);
+    server_tester.send_data(1, &[0; 20001], false);
+
+    server_tester.recv_goaway_frame_check(ErrorCode::FrameSizeError);
+    server_tester.recv_eof();
 }
 
 #[test]
-fn client_call_dropped() {
+fn max_buffered_data_size() {
     init_logger();
 
-    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
-
-    {
-        let req = client.start_get("/fgfg", "localhost");
+    const MAX_BUFFERED: usize = 100_000;
 
-        server_tester.recv_message(1);
+    let mut conf = ClientConf::new();
+    conf.common.max_buffered_data_size = Some(MAX_BUFFERED as u32);
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
 
-        drop(req);
+    let mut server_tester = server.accept_xchg();
 
-        server_tester.send_headers(1, Headers::ok_200(), true);
+    // Responses are never read, so stream windows are never increased
+    let stream_ids: Vec<StreamId> = (0..8).map(|i| 1 + 2 * i).collect();
+    let _responses: Vec<_> = stream_ids
+        .iter()
+        .map(|_| client.start_get("/buffered", "localhost"))
+        .collect();
+    server_tester.recv_messages(&stream_ids);
+    for &stream_id in &stream_ids {
+        server_tester.send_headers(stream_id, Headers::ok_200(), false);
     }
 
-    let rt = Runtime::new().unwrap();
+    let chunk = [17; 1000];
+    let mut stream_sent = vec![0; stream_ids.len()];
+    let mut sent = 0;
+    loop {
+        let sent_before = sent;
+        // Send as much as connection window and stream windows allow
+        for (i, &stream_id) in stream_ids.iter().enumerate() {
+            let len = cmp::min(
+                server_tester.out_window_size.size() as usize,
+                DEFAULT_SETTINGS.initial_window_size as usize - stream_sent[i],
+            );
+            let len = cmp::min(len, chunk.len());
+            if len != 0 {
+                server_tester.send_data(stream_id, &chunk[..len], false);
+                stream_sent[i] += len;
+                sent += len;
+            }
+        }
 
-    {
-        let req = client.start_get("/fgfg", "localhost").collect();
-        server_tester.recv_message(3);
-        server_tester.send_headers(3, Headers::ok_200(), true);
-        let resp = rt.block_on(req).expect("OK");
-        assert_eq!(200, resp.headers.status());
+        if sent == sent_before {
+            // Stream windows are exhausted
+            break;
+        }
+        if server_tester.out_window_size.size() != 0 {
+            continue;
+        }
+
+        // Wait for connection window update
+        match server_tester.recv_frame_timeout(Duration::from_millis(300)) {
+            Some(frame) => panic!("unexpected frame: {:?}", frame),
+            None if server_tester.out_window_size.size() != 0 => {}
+            None => break,
+        }
     }
 
-    let state: ConnStateSnapshot = client.conn_state();
-    assert_eq!(0, state.streams.len(), "{:?}", state);
+    // Client granted more than initial window, but not more than the limit
+    assert!(sent > DEFAULT_SETTINGS.initial_window_size as usize);
+    assert!(sent <= MAX_BUFFERED, "{}", sent);
+
+    let state = client.conn_state();
+    assert_eq!(sent, state.buffered_data_size);
+    assert!(state.buffered_data_size <= MAX_BUFFERED);
 }
 
 #[test]
-fn reconnect_on_disconnect() {
+fn retry_on_goaway() {
     init_logger();
 
-    let (server, client) = HttpServerTester::new_with_client();
+    let mut conf = ClientConf::new();
+    conf.retry_count = 1;
+
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
+
+    let rt = Runtime::new().unwrap();
 
     let mut server_tester = server.accept_xchg();
 
+    // replay is driven by the response future, so poll it in background
+    let req = rt.spawn(client.start_get("/111", "localhost").collect());
+    server_tester.recv_message(1);
+
+    // stream 1 is not processed
+    server_tester.send_goaway(0);
+
+    server_tester.recv_eof();
+
+    let mut server_tester = server.accept();
+    server_tester.recv_preface();
+    server_tester.settings_xchg_but_ack();
+
+    server_tester.recv_message(1);
+    server_tester.send_headers(1, Headers::ok_200(), true);
+
+    let resp = rt.block_on(req).unwrap().expect("OK");
+    assert_eq!(200, resp.headers.status());
+}
+
+#[test]
+fn retry_keeps_deadline() {
+    init_logger();
+
+    let mut conf = ClientConf::new();
+    conf.retry_count = 1;
+
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
+
     let rt = Runtime::new().unwrap();
 
-    {
-        let req = client.start_get("/111", "localhost").collect();
-        server_tester.recv_message(1);
-        server_tester.send_headers(1, Headers::ok_200(), true);
-        let resp = rt.block_on(req).expect("OK");
-        assert_eq!(200, resp.headers.status());
+    let mut server_tester = server.accept_xchg();
+
+    let headers = Headers::from_vec(vec![
+        Header::new(":method", "GET"),
+        Header::new(":path", "/slow"),
+        Header::new(":authority", "localhost"),
+        Header::new(":scheme", "http"),
+    ]);
+    let start = Instant::now();
+    let req = rt.spawn(
+        client
+            .start_request_end_stream_with_timeout(headers, None, None, Duration::from_millis(500))
+            .collect(),
+    );
+    server_tester.recv_message(1);
+    thread::sleep(Duration::from_millis(300));
+    server_tester.send_goaway(0);
+    server_tester.recv_eof();
+
+    // replayed request is not answered
+    let mut server_tester = server.accept();
+    server_tester.recv_preface();
+    server_tester.settings_xchg_but_ack();
+    server_tester.recv_message(1);
+
+    match rt.block_on(req).unwrap() {
+        Err(Error::RequestTimeout) => {}
+        r => panic!("expecting timeout: {:?}", r.map(|r| r.headers)),
     }
+    // replay got only the time left
+    assert!(start.elapsed() < Duration::from_millis(800));
 
-    // drop server connection
-    drop(server_tester);
+    server_tester.recv_rst_frame_check(1, ErrorCode::Cancel);
+}
 
-    // waiting for client connection to die
-    while let Ok(_) = rt.block_on(client.dump_state()) {
-        thread::sleep(Duration::from_millis(1));
-    }
+#[test]
+fn no_retry_for_post() {
+    init_logger();
 
-    {
-        let req = client.start_get("/222", "localhost").collect();
+    let mut conf = ClientConf::new();
+    conf.retry_count = 1;
 
-        let mut server_tester = server.accept();
-        server_tester.recv_preface();
-        server_tester.settings_xchg_but_ack();
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
 
-        server_tester.recv_message(1);
-        server_tester.send_headers(1, Headers::ok_200(), true);
-        let resp = rt.block_on(req).expect("OK");
-        assert_eq!(200, resp.headers.status());
+    let rt = Runtime::new().unwrap();
+
+    let mut server_tester = server.accept_xchg();
+
+    let req = client
+        .start_post("/111", "localhost", Bytes::from_static(b"x"))
+        .collect();
+    server_tester.recv_message(1);
+    server_tester.send_goaway(0);
+
+    match rt.block_on(req) {
+        Err(Error::ConnectionError(ErrorCode::InadequateSecurity, _)) => {}
+        Err(e) => panic!("expecting ConnectionError, got: {:?}", e),
+        Ok(_) => panic!("expecting error"),
     }
 }
 
@@ -303,6 +2280,46 @@ fn external_event_loop() {
     t.join().expect("join");
 }
 
+#[test]
+fn reactor_gone() {
+    init_logger();
+
+    let server = HttpServerTester::new();
+
+    let client_rt = Runtime::new().unwrap();
+    let mut client = ClientBuilder::new_plain();
+    client
+        .set_addr((BIND_HOST, server.port()))
+        .expect("set_addr");
+    client.event_loop = Some(client_rt.handle().clone());
+    let client = client.build().expect("client");
+
+    let mut server_tester = server.accept_xchg();
+
+    let req = client.start_get("/never", "localhost").collect();
+    server_tester.recv_message(1);
+
+    drop(client_rt);
+
+    let rt = Runtime::new().unwrap();
+    let r = rt
+        .block_on(async { tokio::time::timeout(Duration::from_secs(5), req).await })
+        .expect("in-flight request hangs");
+    match r {
+        Err(Error::ConnDied(e)) if matches!(*e, Error::ReactorGone) => {}
+        r => panic!("unexpected: {:?}", r.map(|_| ())),
+    }
+
+    let req = client.start_get("/after", "localhost").collect();
+    let r = rt
+        .block_on(async { tokio::time::timeout(Duration::from_secs(5), req).await })
+        .expect("new request hangs");
+    match r {
+        Err(Error::ClientDied(e)) if matches!(*e, Error::ReactorGone) => {}
+        r => panic!("unexpected: {:?}", r.map(|_| ())),
+    }
+}
+
 #[test]
 pub fn sink_poll() {
     init_logger();
@@ -457,6 +2474,252 @@ fn sink_reset_by_peer() {
     assert_eq!(0, client.conn_state().pump_out_window_size);
 }
 
+#[test]
+fn write_timeout() {
+    init_logger();
+
+    let server = HttpServerTester::new();
+
+    let mut conf = ClientConf::new();
+    conf.common.write_timeout = Some(Duration::from_millis(300));
+    let client = Client::new_plain(BIND_HOST, server.port(), conf).expect("client");
+
+    let mut server_tester = server.accept();
+    server_tester.send_frame(SettingsFrame::from_settings(vec![
+        HttpSetting::InitialWindowSize(0x7fffffff),
+    ]));
+    server_tester.send_window_update_conn(0x7fffffff - DEFAULT_SETTINGS.initial_window_size);
+
+    // Server never reads, so the client eventually fills socket buffers
+    let body = Bytes::from(vec![17; 64 << 20]);
+
+    let rt = Runtime::new().unwrap();
+    let err = rt
+        .block_on(client.start_post("/upload", "localhost", body).collect())
+        .err()
+        .unwrap();
+    match err {
+        httpbis::Error::ConnDied(e) => match &*e {
+            httpbis::Error::WriteTimeout => {}
+            e => panic!("wrong error: {:?}", e),
+        },
+        e => panic!("wrong error: {:?}", e),
+    }
+
+    drop(server_tester);
+}
+
+#[test]
+fn max_response_header_list_size() {
+    init_logger();
+
+    let server = HttpServerTester::new();
+
+    let mut conf = ClientConf::new();
+    conf.max_response_header_list_size = Some(200);
+    let client = Client::new_plain(BIND_HOST, server.port(), conf).expect("client");
+
+    let mut server_tester = server.accept_xchg();
+    assert_eq!(200, server_tester.peer_settings.max_header_list_size);
+
+    let rt = Runtime::new().unwrap();
+
+    let req = client.start_get("/big", "localhost").collect();
+    server_tester.recv_message(1);
+    let mut headers = Headers::ok_200();
+    headers.add("x-big", "x".repeat(300));
+    server_tester.send_headers(1, headers, true);
+    server_tester.recv_rst_frame_check(1, ErrorCode::ProtocolError);
+    assert!(rt.block_on(req).is_err());
+
+    let req = client.start_get("/small", "localhost").collect();
+    server_tester.recv_message(3);
+    server_tester.send_headers(3, Headers::ok_200(), true);
+    let resp = rt.block_on(req).expect("OK");
+    assert_eq!(200, resp.headers.status());
+}
+
+#[test]
+fn max_response_header_count() {
+    init_logger();
+
+    let server = HttpServerTester::new();
+
+    let mut conf = ClientConf::new();
+    conf.max_response_header_count = Some(10);
+    let client = Client::new_plain(BIND_HOST, server.port(), conf).expect("client");
+
+    let mut server_tester = server.accept_xchg();
+    // Count limit is not advertised, list size limit is the default
+    assert_eq!(64 << 10, server_tester.peer_settings.max_header_list_size);
+
+    let rt = Runtime::new().unwrap();
+
+    let req = client.start_get("/many", "localhost").collect();
+    server_tester.recv_message(1);
+    let mut headers = Headers::ok_200();
+    for i in 0..10 {
+        headers.add(format!("x-{}", i), "x");
+    }
+    server_tester.send_headers(1, headers, true);
+    server_tester.recv_rst_frame_check(1, ErrorCode::ProtocolError);
+    assert!(rt.block_on(req).is_err());
+
+    let req = client.start_get("/few", "localhost").collect();
+    server_tester.recv_message(3);
+    let mut headers = Headers::ok_200();
+    for i in 0..9 {
+        headers.add(format!("x-{}", i), "x");
+    }
+    server_tester.send_headers(3, headers, true);
+    let resp = rt.block_on(req).expect("OK");
+    assert_eq!(200, resp.headers.status());
+}
+
+#[test]
+fn out_buffer_limit() {
+    init_logger();
+
+    let mut conf = ClientConf::new();
+    conf.common.out_buffer_limit = Some(0x10000);
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
+    let mut server_tester = server.accept_xchg();
+
+    // Flow control does not limit the client, only the socket does
+    server_tester.send_recv_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::InitialWindowSize(0x7fffffff),
+    ]));
+    server_tester.send_window_update_conn(0x7fffffff - 65535);
+
+    let rt = Runtime::new().unwrap();
+
+    let (mut sender, _response) = rt
+        .block_on(client.start_post_sink("/upload", "localhost"))
+        .expect("start_post_sink");
+    server_tester.recv_frame_headers_check(1, false);
+
+    // Server stops reading, so socket buffers are filled first
+    let chunk = Bytes::from(vec![17; 1000]);
+    let sent = Arc::new(Mutex::new(0));
+    let sent_copy = sent.clone();
+    rt.spawn(async move {
+        for _ in 0..0x10000 {
+            sender
+                .send_data_wait(chunk.clone())
+                .await
+                .expect("send_data_wait");
+            *sent_copy.lock().unwrap() += chunk.len();
+        }
+    });
+
+    let mut last_sent = 0;
+    loop {
+        thread::sleep(Duration::from_millis(100));
+        let sent = *sent.lock().unwrap();
+        if sent == last_sent {
+            break;
+        }
+        last_sent = sent;
+    }
+    assert!(last_sent < 0x10000 * 1000, "producer is not blocked");
+
+    // Sender may overshoot by a chunk, and frame headers are added
+    // when data is moved from stream queue to the write buffer
+    let state = client.conn_state();
+    let queued = state.out_buf_bytes + state.buffered_data_size;
+    let slack = 1000 + (0x10000 / 1000 + 1) * 9;
+    assert!(queued <= 0x10000 + slack, "queued: {}", queued);
+}
+
+#[test]
+fn write_buffer_high_watermark() {
+    init_logger();
+
+    let mut conf = ClientConf::new();
+    conf.common.write_buffer_high_watermark = Some(100);
+    let (server, client) = HttpServerTester::new_with_client_conf(conf);
+    let mut server_tester = server.accept_xchg();
+
+    server_tester.send_recv_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::InitialWindowSize(0x7fffffff),
+    ]));
+    server_tester.send_window_update_conn(0x7fffffff - 65535);
+
+    let rt = Runtime::new().unwrap();
+
+    let (mut sender, _response) = rt
+        .block_on(client.start_post_sink("/upload", "localhost"))
+        .expect("start_post_sink");
+    server_tester.recv_frame_headers_check(1, false);
+
+    // Server stops reading, and plain `send_data` enqueues
+    // more than socket buffers can hold
+    let chunk = Bytes::from(vec![17; 1000]);
+    for _ in 0..0x10000 {
+        sender.send_data(chunk.clone()).expect("send_data");
+    }
+
+    let mut last_buffered = 0;
+    loop {
+        thread::sleep(Duration::from_millis(100));
+        let buffered = client.conn_state().buffered_data_size;
+        if buffered == last_buffered {
+            break;
+        }
+        last_buffered = buffered;
+    }
+    let state = client.conn_state();
+    assert!(state.buffered_data_size > 0, "socket is not full");
+    // Write buffer holds at most one chunk past the watermark
+    assert!(
+        state.out_buf_bytes < 100 + 1000 + 9,
+        "out_buf_bytes: {}",
+        state.out_buf_bytes
+    );
+}
+
+#[test]
+fn flush() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    let rt = Runtime::new().unwrap();
+
+    let req = client.start_get("/flush", "localhost");
+    rt.block_on(client.flush()).expect("flush");
+
+    // HEADERS are already in the socket, do not wait
+    server_tester.set_read_timeout(Some(Duration::from_millis(1)));
+    let headers = server_tester.recv_frame_headers_check(1, true);
+    assert_eq!("/flush", headers.path());
+    server_tester.set_read_timeout(None);
+
+    server_tester.send_headers(1, Headers::ok_200(), true);
+    let resp = rt.block_on(req.collect()).expect("OK");
+    assert_eq!(200, resp.headers.status());
+}
+
+#[test]
+fn set_nodelay() {
+    init_logger();
+
+    let server = ServerTest::new();
+    let client = Client::new_plain(BIND_HOST, server.port, ClientConf::new()).expect("client");
+
+    let rt = Runtime::new().unwrap();
+
+    for &no_delay in &[false, true] {
+        rt.block_on(client.set_nodelay(no_delay))
+            .expect("set_nodelay");
+
+        let resp = rt
+            .block_on(client.start_get("/blocks/1/1", "localhost").collect())
+            .expect("get");
+        assert_eq!(200, resp.headers.status());
+    }
+}
+
 #[test]
 fn connection_refused() {
     init_logger();
@@ -8,27 +8,42 @@ use httpbis_test::*;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::Mutex;
 
 use bytes::Bytes;
 
 use std::io::Read as _Read;
 use std::io::Write as _Write;
 use std::thread;
+use std::time::Duration;
 
 use futures::stream;
 
 use futures::channel::oneshot;
+use futures::future;
 use futures::stream::Stream;
 use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
 
 use std::task::Poll;
 
+use httpbis::for_test::solicit::frame::ContinuationFlag;
+use httpbis::for_test::solicit::frame::ContinuationFrame;
+use httpbis::for_test::solicit::frame::Frame;
+use httpbis::for_test::solicit::frame::FrameHeader;
 use httpbis::for_test::solicit::frame::HeadersFlag;
+use httpbis::for_test::solicit::frame::HeadersFrame;
+use httpbis::for_test::solicit::frame::HttpFrame;
 use httpbis::for_test::solicit::frame::HttpSetting;
+use httpbis::for_test::solicit::frame::PingFrame;
 use httpbis::for_test::solicit::frame::SettingsFrame;
+use httpbis::for_test::solicit::frame::StreamDependency;
+use httpbis::for_test::solicit::frame::WindowUpdateFrame;
 use httpbis::for_test::solicit::DEFAULT_SETTINGS;
+use httpbis::for_test::InputEndReason;
 use httpbis::*;
 
+use std::cmp;
 use std::iter::FromIterator;
 use std::net::TcpStream;
 use std::sync::mpsc;
@@ -70,169 +85,165 @@ fn simple_new() {
 }
 
 #[test]
-fn custom_drop_callback() {
+fn dump_state_hpack_tables() {
     init_logger();
 
     let server = ServerOneConn::new_fn(0, |_, _req, mut resp| {
-        resp.set_drop_callback(|resp| Ok(resp.send_internal_error_500("test test")?));
-        Err(httpbis::Error::User("my".to_owned()))
+        let mut headers = Headers::ok_200();
+        headers.add("x-resp", "rr");
+        resp.send_headers_end_of_stream(headers)?;
+        Ok(())
     });
 
     let mut tester = HttpConnTester::connect(server.port());
     tester.send_preface();
     tester.settings_xchg();
 
-    let mut headers = Headers::new();
-    headers.add(":method", "GET");
-    headers.add(":path", "/aabb");
-    headers.add(":scheme", "http");
-    tester.send_headers(1, headers, false);
+    for &stream_id in &[1, 3] {
+        let mut headers = Headers::new();
+        headers.add(":method", "GET");
+        headers.add(":path", "/aabb");
+        headers.add(":scheme", "http");
+        headers.add("x-req", "qq");
+        tester.send_headers(stream_id, headers, true);
 
-    tester.send_data(1, b"abcd", true);
+        let message = tester.recv_message(stream_id);
+        assert_eq!("rr", message.headers.get("x-resp"));
+    }
 
-    let recv_headers = tester.recv_frame_headers_check(1, false);
-    assert_eq!("500", recv_headers.get(":status"));
+    let state = server.dump_state();
+    assert!(state
+        .hpack_decoder_table
+        .iter()
+        .any(|(n, v)| &n[..] == b"x-req" && &v[..] == b"qq"));
+    assert_eq!(
+        tester.encoder.dynamic_table_entries(),
+        state.hpack_decoder_table
+    );
+    assert_eq!(
+        tester.encoder.dynamic_table_size(),
+        state.hpack_decoder_table_size
+    );
+    assert!(state
+        .hpack_encoder_table
+        .iter()
+        .any(|(n, v)| &n[..] == b"x-resp" && &v[..] == b"rr"));
+    assert_eq!(
+        tester.decoder.dynamic_table_entries(),
+        state.hpack_encoder_table
+    );
+    assert_eq!(
+        tester.decoder.dynamic_table_size(),
+        state.hpack_encoder_table_size
+    );
 }
 
 #[test]
-fn panic_in_handler() {
+fn request_bytes_received() {
     init_logger();
 
-    let server = ServerOneConn::new_fn(0, |_, req, mut resp| {
-        if req.headers.path() == "/panic" {
-            panic!("requested");
-        } else {
-            resp.send_found_200_plain_text("hi there")?;
-            Ok(())
-        }
+    let (tx, rx) = std::sync::mpsc::channel();
+    let tx = std::sync::Mutex::new(tx);
+
+    let server = ServerOneConn::new_fn(0, move |_, req, mut resp| {
+        // Handler is invoked on `HEADERS`, before any `DATA`
+        tx.lock()
+            .unwrap()
+            .send((req.content_length_hint(), req.bytes_received()))
+            .unwrap();
+        resp.send_headers(Headers::ok_200())?;
+        resp.pull_from_stream(req.make_stream())?;
+        Ok(())
     });
 
     let mut tester = HttpConnTester::connect(server.port());
     tester.send_preface();
     tester.settings_xchg();
 
-    info!("test /hello");
-
-    {
-        let resp = tester.get(1, "/hello");
-        assert_eq!(200, resp.headers.status());
-        assert_eq!(&b"hi there"[..], resp.body.get_bytes());
-    }
-
-    info!("test /panic");
-
-    {
-        tester.send_get(3, "/panic");
-        tester.recv_rst_frame_check(3, ErrorCode::InternalError);
-    }
+    let mut headers = Headers::new();
+    headers.add(":method", "POST");
+    headers.add(":path", "/upload");
+    headers.add(":scheme", "http");
+    headers.add("content-length", "10");
+    tester.send_headers(1, headers, false);
 
-    info!("test /world");
+    tester.send_data(1, b"abcdef", false);
+    tester.send_data(1, b"ghij", true);
 
-    {
-        let resp = tester.get(5, "/world");
-        assert_eq!(200, resp.headers.status());
-        assert_eq!(&b"hi there"[..], resp.body.get_bytes());
-    }
+    let message = tester.recv_message(1);
+    assert_eq!(&b"abcdefghij"[..], &message.body.get_bytes()[..]);
 
-    assert_eq!(0, server.dump_state().streams.len());
+    let (content_length_hint, bytes_received) = rx.recv().unwrap();
+    assert_eq!(Some(10), content_length_hint);
+    assert_eq!(0, bytes_received);
 }
 
 #[test]
-fn panic_in_stream() {
+fn request_body_shorter_than_content_length() {
     init_logger();
 
-    let server = ServerOneConn::new_fn(0, |_, req, mut resp| {
-        if req.headers.path() == "/panic" {
-            let stream = HttpStreamAfterHeaders::new(
-                stream::iter((0..2).map(|_| {
-                    panic!("should reset stream");
-                }))
-                .map(Ok),
-            );
-            resp.send_headers(Headers::ok_200())?;
-            resp.pull_from_stream(stream)?;
-        } else {
-            resp.send_found_200_plain_text("hi there")?;
-        }
-        Ok(())
-    });
+    let server = ServerTest::new();
 
-    let mut tester = HttpConnTester::connect(server.port());
+    let mut tester = HttpConnTester::connect(server.port);
     tester.send_preface();
     tester.settings_xchg();
 
-    info!("test /hello");
-
-    {
-        let resp = tester.get(1, "/hello");
-        assert_eq!(200, resp.headers.status());
-        assert_eq!(&b"hi there"[..], resp.body.get_bytes());
-    }
-
-    info!("test /panic");
-
-    {
-        tester.send_get(3, "/panic");
-        tester.recv_frame_headers_check(3, false);
-        tester.recv_rst_frame();
-    }
+    let mut headers = Headers::new();
+    headers.add(":method", "POST");
+    headers.add(":path", "/echo");
+    headers.add(":scheme", "http");
+    headers.add("content-length", "10");
+    tester.send_headers(1, headers, false);
 
-    info!("test /world");
+    tester.send_data(1, b"abcdef", true);
 
-    {
-        let resp = tester.get(5, "/world");
-        assert_eq!(200, resp.headers.status());
-        assert_eq!(&b"hi there"[..], resp.body.get_bytes());
+    // Echo handler may send response headers before the stream is reset
+    loop {
+        match tester.recv_frame() {
+            HttpFrame::Headers(..) => {}
+            HttpFrame::RstStream(rst) => {
+                assert_eq!(1, rst.stream_id);
+                assert_eq!(ErrorCode::ProtocolError, rst.error_code());
+                break;
+            }
+            frame => panic!("unexpected frame: {:?}", frame),
+        }
     }
-
-    assert_eq!(0, server.dump_state().streams.len());
 }
 
 #[test]
-fn response_large() {
+fn pseudo_header_after_regular_header() {
     init_logger();
 
-    let rt = Runtime::new().unwrap();
-
-    let mut large_resp = Vec::new();
-    while large_resp.len() < 100_000 {
-        if large_resp.len() != 0 {
-            write!(&mut large_resp, ",").unwrap();
-        }
-        let len = large_resp.len();
-        write!(&mut large_resp, "{}", len).unwrap();
-    }
-
-    let large_resp_copy = large_resp.clone();
+    let server = ServerTest::new();
 
-    let server = ServerOneConn::new_fn(0, move |_, _req, mut resp| {
-        resp.send_message(SimpleHttpMessage {
-            headers: Headers::ok_200(),
-            body: BytesDeque::from(large_resp_copy.clone()),
-        })?;
-        Ok(())
-    });
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
 
-    // TODO: rewrite with TCP
-    let client = Client::new_plain(BIND_HOST, server.port(), Default::default()).expect("connect");
-    let resp = rt
-        .block_on(
-            client
-                .start_post("/foobar", "localhost", Bytes::from(&b""[..]))
-                .collect(),
-        )
-        .expect("wait");
-    assert_eq!(large_resp.len(), resp.body.len());
-    assert_eq!(
-        (large_resp.len(), &large_resp[..]),
-        (resp.body.len(), &resp.body.get_bytes()[..])
+    let fragment = tester.encoder.encode_for_test(
+        vec![
+            (&b":method"[..], &b"GET"[..]),
+            (&b":scheme"[..], &b"http"[..]),
+            (&b"content-type"[..], &b"text/plain"[..]),
+            (&b":path"[..], &b"/blocks/1/1"[..]),
+        ]
+        .into_iter(),
     );
+    let mut headers_frame = HeadersFrame::new_conv(fragment, 1);
+    headers_frame.set_flag(HeadersFlag::EndHeaders);
+    headers_frame.set_flag(HeadersFlag::EndStream);
+    tester.send_frame(headers_frame);
 
-    assert_eq!(0, server.dump_state().streams.len());
+    tester.recv_rst_frame_check(1, ErrorCode::ProtocolError);
+
+    // Connection is still usable
+    tester.send_get(3, "/blocks/1/1");
+    assert_eq!(200, tester.recv_frame_headers_check(3, false).status());
 }
 
 #[test]
-fn rst_stream_on_data_without_stream() {
+fn uppercase_header_name() {
     init_logger();
 
     let server = ServerTest::new();
@@ -241,16 +252,33 @@ fn rst_stream_on_data_without_stream() {
     tester.send_preface();
     tester.settings_xchg();
 
-    // DATA frame without open stream
-    tester.send_data(11, &[10, 20, 30], false);
+    let fragment = tester.encoder.encode_for_test(
+        vec![
+            (&b":method"[..], &b"GET"[..]),
+            (&b":scheme"[..], &b"http"[..]),
+            (&b":path"[..], &b"/blocks/1/1"[..]),
+            (&b"Content-Type"[..], &b"text/plain"[..]),
+        ]
+        .into_iter(),
+    );
+    let mut headers_frame = HeadersFrame::new_conv(fragment, 1);
+    headers_frame.set_flag(HeadersFlag::EndHeaders);
+    headers_frame.set_flag(HeadersFlag::EndStream);
+    tester.send_frame(headers_frame);
 
-    tester.recv_goaway_frame_check(ErrorCode::StreamClosed);
+    tester.recv_rst_frame_check(1, ErrorCode::ProtocolError);
 
-    tester.recv_eof();
+    let mut headers = Headers::new();
+    headers.add(":method", "GET");
+    headers.add(":path", "/blocks/1/1");
+    headers.add(":scheme", "http");
+    headers.add("content-type", "text/plain");
+    tester.send_headers(3, headers, true);
+    assert_eq!(200, tester.recv_frame_headers_check(3, false).status());
 }
 
 #[test]
-fn exceed_max_frame_size() {
+fn request_headers_end_stream_with_content_length() {
     init_logger();
 
     let server = ServerTest::new();
@@ -259,90 +287,1552 @@ fn exceed_max_frame_size() {
     tester.send_preface();
     tester.settings_xchg();
 
-    tester.send_data(1, &[0; 17_000], false);
+    let mut headers = Headers::new();
+    headers.add(":method", "POST");
+    headers.add(":path", "/echo");
+    headers.add(":scheme", "http");
+    headers.add("content-length", "10");
+    tester.send_headers(1, headers, true);
 
-    tester.recv_eof();
+    tester.recv_rst_frame_check(1, ErrorCode::ProtocolError);
 
-    let mut tester = HttpConnTester::connect(server.port);
-    tester.send_preface();
-    tester.settings_xchg();
+    let mut headers = Headers::new();
+    headers.add(":method", "POST");
+    headers.add(":path", "/echo");
+    headers.add(":scheme", "http");
+    headers.add("content-length", "0");
+    tester.send_headers(3, headers, true);
 
-    assert_eq!(200, tester.get(1, "/echo").headers.status());
+    assert_eq!(200, tester.recv_frame_headers_check(3, false).status());
 }
 
 #[test]
-fn increase_frame_size() {
+fn conn_window_update_batched() {
     init_logger();
 
-    let server = ServerTest::new();
+    let server = ServerOneConn::new_fn(0, |_, req, mut resp| {
+        resp.send_headers(Headers::ok_200())?;
+        resp.pull_from_stream(req.make_stream())?;
+        Ok(())
+    });
 
-    let mut tester = HttpConnTester::connect(server.port);
+    let mut tester = HttpConnTester::connect(server.port());
     tester.send_preface();
     tester.settings_xchg();
 
-    let mut frame = SettingsFrame::new();
-    frame.settings.push(HttpSetting::MaxFrameSize(20000));
-    tester.send_recv_settings(frame);
+    let mut headers = Headers::new();
+    headers.add(":method", "POST");
+    headers.add(":path", "/upload");
+    headers.add(":scheme", "http");
+    tester.send_headers(1, headers, false);
 
-    tester.send_get(1, "/blocks/30000/1");
-    assert_eq!(200, tester.recv_frame_headers_check(1, false).status());
-    assert_eq!(20000, tester.recv_frame_data_check(1, false).len());
-    assert_eq!(10000, tester.recv_frame_data_tail(1).len());
+    let chunk = [17; 9000];
+    tester.send_data_frames_single_write(1, &[&chunk, &chunk, &chunk, &chunk], true);
+
+    let mut conn_window_updates = Vec::new();
+    let mut body_len = 0;
+    let mut end_stream = false;
+    while !end_stream || conn_window_updates.is_empty() {
+        match tester.fn_recv_frame_no_check_ack() {
+            HttpFrame::WindowUpdate(f) if f.stream_id == 0 => conn_window_updates.push(f.increment),
+            HttpFrame::Data(f) => {
+                assert_eq!(1, f.stream_id);
+                body_len += f.data.len();
+                end_stream = f.is_end_of_stream();
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(4 * 9000, body_len);
+    assert_eq!(vec![4 * 9000], conn_window_updates);
+
+    // Make sure no more connection window updates are pending
+    tester.send_frame(PingFrame::with_data(17));
+    loop {
+        match tester.fn_recv_frame_no_check_ack() {
+            HttpFrame::WindowUpdate(f) => assert_ne!(0, f.stream_id),
+            HttpFrame::Ping(f) => {
+                assert!(f.is_ack());
+                break;
+            }
+            f => panic!("unexpected frame: {:?}", f),
+        }
+    }
 }
 
 #[test]
-fn exceed_window_size() {
+fn conn_window_update_independent_of_streams() {
     init_logger();
 
-    let server = ServerTest::new();
+    let server = ServerOneConn::new_fn(0, |_, req, mut resp| {
+        resp.send_headers(Headers::ok_200())?;
+        resp.pull_from_stream(req.make_stream())?;
+        Ok(())
+    });
 
-    let mut tester = HttpConnTester::connect(server.port);
+    let mut tester = HttpConnTester::connect(server.port());
     tester.send_preface();
     tester.settings_xchg();
+    // Server echoes request body, do not let it block on connection window
+    tester.send_frame(WindowUpdateFrame::for_connection(1 << 30));
+
+    // Each stream consumes more than half of both stream and connection
+    // windows, so each round triggers one update for stream and connection
+    let chunk = [17; 8500];
+    for stream_id in (1..8).step_by(2) {
+        let mut headers = Headers::new();
+        headers.add(":method", "POST");
+        headers.add(":path", "/upload");
+        headers.add(":scheme", "http");
+        tester.send_headers(stream_id, headers, false);
+
+        tester.send_data_frames_single_write(stream_id, &[&chunk, &chunk, &chunk, &chunk], false);
+
+        let mut conn_window_updates = Vec::new();
+        let mut stream_window_updates = Vec::new();
+        while conn_window_updates.is_empty() || stream_window_updates.is_empty() {
+            match tester.fn_recv_frame_no_check_ack() {
+                HttpFrame::WindowUpdate(f) if f.stream_id == 0 => {
+                    tester
+                        .out_window_size
+                        .try_increase(f.increment)
+                        .expect("increment");
+                    conn_window_updates.push(f.increment);
+                }
+                HttpFrame::WindowUpdate(f) => {
+                    assert_eq!(stream_id, f.stream_id);
+                    stream_window_updates.push(f.increment);
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(1, conn_window_updates.len());
+        assert!(conn_window_updates[0] >= 4 * 8500);
+        assert_eq!(1, stream_window_updates.len());
+    }
+}
 
-    let mut frame = SettingsFrame::new();
-    frame.settings.push(HttpSetting::MaxFrameSize(
-        tester.peer_settings.initial_window_size + 5,
-    ));
-    tester.send_recv_settings(frame);
-
-    let data = Vec::from_iter((0..tester.peer_settings.initial_window_size + 3).map(|_| 2));
+#[test]
+fn custom_drop_callback() {
+    init_logger();
 
-    // Deliberately set wrong out_windows_size so `send_data` wouldn't fail.
-    tester.out_window_size.try_add(10000000).unwrap();
-    tester.send_data(1, &data, false);
-    tester.recv_eof();
+    let server = ServerOneConn::new_fn(0, |_, _req, mut resp| {
+        resp.set_drop_callback(|resp| Ok(resp.send_internal_error_500("test test")?));
+        Err(httpbis::Error::User("my".to_owned()))
+    });
 
-    let mut tester = HttpConnTester::connect(server.port);
+    let mut tester = HttpConnTester::connect(server.port());
     tester.send_preface();
     tester.settings_xchg();
 
-    assert_eq!(200, tester.get(1, "/echo").headers.status());
+    let mut headers = Headers::new();
+    headers.add(":method", "GET");
+    headers.add(":path", "/aabb");
+    headers.add(":scheme", "http");
+    tester.send_headers(1, headers, false);
+
+    tester.send_data(1, b"abcd", true);
+
+    let recv_headers = tester.recv_frame_headers_check(1, false);
+    assert_eq!("500", recv_headers.get(":status"));
 }
 
 #[test]
-fn stream_window_gt_conn_window() {
+fn event_stream() {
     init_logger();
 
-    let rt = Runtime::new().unwrap();
-
-    let server = ServerTest::new();
+    let server = ServerOneConn::new_fn(0, |_, _req, resp| {
+        let mut events = resp.send_event_stream()?;
+        events.send_event("first")?;
+        events.send_event("second")?;
+        events.send_event("third")?;
+        events.finish()?;
+        Ok(())
+    });
 
-    let mut tester = HttpConnTester::connect(server.port);
+    let mut tester = HttpConnTester::connect(server.port());
     tester.send_preface();
     tester.settings_xchg();
 
-    let w = DEFAULT_SETTINGS.initial_window_size;
+    tester.send_get(1, "/events");
+    let headers = tester.recv_frame_headers_check(1, false);
+    assert_eq!(200, headers.status());
+    assert_eq!("text/event-stream", headers.get("content-type"));
 
-    // May need to be changed if server defaults are changed
-    assert_eq!(w as i32, tester.in_window_size.size());
+    assert_eq!(
+        &b"data: first\n\n"[..],
+        &tester.recv_frame_data_check(1, false)[..]
+    );
+    assert_eq!(
+        &b"data: second\n\n"[..],
+        &tester.recv_frame_data_check(1, false)[..]
+    );
+    assert_eq!(
+        &b"data: third\n\n"[..],
+        &tester.recv_frame_data_check(1, false)[..]
+    );
+    tester.recv_frame_data_check_empty_end(1);
+}
 
-    tester.send_recv_settings(SettingsFrame::from_settings(vec![
-        HttpSetting::InitialWindowSize(w * 2),
-        HttpSetting::MaxFrameSize(w * 10),
-    ]));
+#[test]
+fn status_with_reason_phrase_rejected() {
+    init_logger();
 
-    // Now new stream window is gt than conn window
+    let server = ServerOneConn::new_fn(0, |_, _req, mut resp| {
+        let mut headers = Headers::new();
+        headers.add(":status", "200 OK");
+        match resp.send_headers(headers) {
+            Err(SendError::IncorrectHeaders(HeaderError::IncorrectStatus)) => {}
+            r => panic!("expecting incorrect status error, got: {:?}", r),
+        }
+        assert_eq!(SenderState::ExpectingHeaders, resp.state());
+        resp.send_found_200_plain_text("rejected")?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let resp = tester.get(1, "/status");
+    assert_eq!(200, resp.headers.status());
+    assert_eq!(&b"rejected"[..], resp.body.get_bytes());
+}
+
+#[test]
+fn trailers_only() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn(0, |_, req, mut resp| {
+        if req.headers.path() == "/trailers-only" {
+            let mut headers = Headers::ok_200();
+            headers.add("grpc-status", "5");
+            headers.add("grpc-message", "not found");
+            resp.send_trailers_only(headers)?;
+        } else {
+            resp.send_found_200_plain_text("hi there")?;
+        }
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_get(1, "/trailers-only");
+    let headers = tester.recv_frame_headers_check(1, true);
+    assert_eq!(200, headers.status());
+    assert_eq!("5", headers.get("grpc-status"));
+    assert_eq!("not found", headers.get("grpc-message"));
+
+    // No more frames for stream 1
+    let resp = tester.get(3, "/hello");
+    assert_eq!(200, resp.headers.status());
+
+    assert_eq!(0, server.dump_state().streams.len());
+}
+
+fn pushed_request_headers(path: &str) -> Headers {
+    let mut headers = Headers::new();
+    headers.add(":method", "GET");
+    headers.add(":path", path);
+    headers.add(":scheme", "http");
+    headers
+}
+
+/// Server which responds with request gRPC messages joined with `|`.
+fn grpc_echo_server() -> ServerOneConn {
+    ServerOneConn::new_fn(0, |context, req, mut resp| {
+        let messages = req.grpc_messages();
+        context.loop_remote().spawn(async move {
+            let messages: Vec<Bytes> = messages.try_collect().await.expect("messages");
+            let messages: Vec<&str> = messages
+                .iter()
+                .map(|m| std::str::from_utf8(m).unwrap())
+                .collect();
+            resp.send_found_200_plain_text(&messages.join("|")).unwrap();
+        });
+        Ok(())
+    })
+}
+
+fn grpc_request_headers() -> Headers {
+    let mut headers = Headers::new_post("/grpc");
+    headers.add(":scheme", "http");
+    headers
+}
+
+#[test]
+fn grpc_message_spans_data_frames() {
+    init_logger();
+
+    let server = grpc_echo_server();
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_headers(1, grpc_request_headers(), false);
+    tester.send_data(1, b"\0\0\0\0\x05he", false);
+    tester.send_data(1, b"llo", true);
+
+    assert_eq!(&b"hello"[..], tester.recv_message(1).body.get_bytes());
+}
+
+#[test]
+fn grpc_messages_in_one_data_frame() {
+    init_logger();
+
+    let server = grpc_echo_server();
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_headers(1, grpc_request_headers(), false);
+    tester.send_data(1, b"\0\0\0\0\x02ab\0\0\0\0\x03cde", true);
+
+    assert_eq!(&b"ab|cde"[..], tester.recv_message(1).body.get_bytes());
+}
+
+fn collect_body_server() -> ServerOneConn {
+    ServerOneConn::new_fn(0, |context, req, mut resp| {
+        let body = req.collect_body(10);
+        context.loop_remote().spawn(async move {
+            match body.await {
+                Ok(body) => resp
+                    .send_found_200_plain_text(std::str::from_utf8(&body).unwrap())
+                    .unwrap(),
+                Err(Error::BodyTooLarge(10)) => resp
+                    .send_headers_end_of_stream(Headers::new_status(413))
+                    .unwrap(),
+                Err(e) => panic!("unexpected error: {:?}", e),
+            }
+        });
+        Ok(())
+    })
+}
+
+fn collect_body_request_headers() -> Headers {
+    let mut headers = Headers::new_post("/collect");
+    headers.add(":scheme", "http");
+    headers
+}
+
+#[test]
+fn collect_body() {
+    init_logger();
+
+    let server = collect_body_server();
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_headers(1, collect_body_request_headers(), false);
+    tester.send_data(1, b"abc", false);
+    tester.send_data(1, b"defg", true);
+
+    let message = tester.recv_message(1);
+    assert_eq!(200, message.headers.status());
+    assert_eq!(&b"abcdefg"[..], message.body.get_bytes());
+}
+
+#[test]
+fn collect_body_too_large() {
+    init_logger();
+
+    let server = collect_body_server();
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_headers(1, collect_body_request_headers(), false);
+    tester.send_data(1, b"abcdef", false);
+    tester.send_data(1, b"ghijkl", true);
+
+    let message = tester.recv_message(1);
+    assert_eq!(413, message.headers.status());
+}
+
+#[test]
+fn expect_content_type() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn(0, |_, req, mut resp| {
+        match req.expect_content_type(&["application/json"]) {
+            Ok(()) => resp.send_found_200_plain_text("ok")?,
+            Err(Error::UnsupportedMediaType(..)) => resp.send_unsupported_media_type()?,
+            Err(e) => panic!("unexpected error: {:?}", e),
+        }
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let content_types = [
+        (Some("application/json; charset=utf-8"), 200),
+        (Some("text/plain"), 415),
+        (None, 415),
+    ];
+    for (i, &(content_type, status)) in content_types.iter().enumerate() {
+        let stream_id = 1 + 2 * i as u32;
+        let mut headers = Headers::new_post("/upload");
+        headers.add(":scheme", "http");
+        if let Some(content_type) = content_type {
+            headers.add("content-type", content_type);
+        }
+        tester.send_headers(stream_id, headers, true);
+
+        let message = tester.recv_message(stream_id);
+        assert_eq!(status, message.headers.status(), "{:?}", content_type);
+    }
+}
+
+#[test]
+fn push_promise() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn(0, |context, _req, mut resp| {
+        let pushed = resp.push_promise(pushed_request_headers("/pushed"));
+        context.loop_remote().spawn(async move {
+            let mut pushed = pushed.await.expect("push_promise");
+            pushed.send_found_200_plain_text("pushed").unwrap();
+            resp.send_found_200_plain_text("main").unwrap();
+        });
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_get(1, "/main");
+
+    let (promised_stream_id, headers) = tester.recv_frame_push_promise_check(1);
+    assert_eq!(2, promised_stream_id);
+    assert_eq!("/pushed", headers.path());
+
+    let messages = tester.recv_messages(&[1, 2]);
+    assert_eq!(&b"main"[..], messages[0].body.get_bytes());
+    assert_eq!(&b"pushed"[..], messages[1].body.get_bytes());
+
+    assert_eq!(0, server.dump_state().streams.len());
+}
+
+#[test]
+fn push_promise_disabled() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn(0, |context, _req, mut resp| {
+        let pushed = resp.push_promise(pushed_request_headers("/pushed"));
+        context.loop_remote().spawn(async move {
+            match pushed.await {
+                Err(httpbis::Error::PushDisabled) => {}
+                Err(e) => panic!("expecting push disabled, got: {:?}", e),
+                Ok(_) => panic!("expecting push disabled"),
+            }
+            resp.send_found_200_plain_text("main").unwrap();
+        });
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_recv_settings(SettingsFrame::from_settings(vec![HttpSetting::EnablePush(
+        false,
+    )]));
+
+    let resp = tester.get(1, "/main");
+    assert_eq!(&b"main"[..], resp.body.get_bytes());
+}
+
+#[test]
+fn push_promise_from_client() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_push_promise(1, 2, pushed_request_headers("/pushed"));
+
+    tester.recv_goaway_frame_check(ErrorCode::ProtocolError);
+}
+
+fn send_headers_without_end_headers(tester: &mut HttpConnTester, stream_id: StreamId) {
+    let fragment = tester.encoder.encode_for_test(
+        pushed_request_headers("/continued")
+            .iter()
+            .map(|h| (h.name().as_bytes(), h.value())),
+    );
+    tester.send_frame(HeadersFrame::new_conv(fragment, stream_id));
+}
+
+#[test]
+fn header_value_with_crlf() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    // `Headers` cannot contain such value, so encode header block manually
+    let fragment = tester.encoder.encode_for_test(vec![
+        (&b":method"[..], &b"GET"[..]),
+        (&b":path"[..], &b"/echo"[..]),
+        (&b":scheme"[..], &b"http"[..]),
+        (&b"x-foo"[..], &b"bar\r\nx-injected: 1"[..]),
+    ]);
+    let mut headers_frame = HeadersFrame::new_conv(fragment, 1);
+    headers_frame.set_flag(HeadersFlag::EndHeaders);
+    headers_frame.set_flag(HeadersFlag::EndStream);
+    tester.send_frame(headers_frame);
+
+    // Malformed request is a stream error
+    tester.recv_rst_frame_check(1, ErrorCode::ProtocolError);
+
+    let resp = tester.get(3, "/echo");
+    assert_eq!(200, resp.headers.status());
+}
+
+#[test]
+fn pseudo_header_value_obs_text() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let fragment = tester.encoder.encode_for_test(vec![
+        (&b":method"[..], &b"GET"[..]),
+        (&b":path"[..], &b"/caf\xe9"[..]),
+        (&b":scheme"[..], &b"http"[..]),
+    ]);
+    let mut headers_frame = HeadersFrame::new_conv(fragment, 1);
+    headers_frame.set_flag(HeadersFlag::EndHeaders);
+    headers_frame.set_flag(HeadersFlag::EndStream);
+    tester.send_frame(headers_frame);
+
+    tester.recv_rst_frame_check(1, ErrorCode::ProtocolError);
+
+    let resp = tester.get(3, "/echo");
+    assert_eq!(200, resp.headers.status());
+}
+
+#[test]
+fn header_value_obs_text() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn(0, |_, req, mut resp| {
+        let value = req
+            .headers
+            .iter()
+            .find(|h| h.name() == "x-foo")
+            .unwrap()
+            .value()
+            .to_owned();
+        resp.send_headers(Headers::ok_200())?;
+        resp.send_data_end_of_stream(Bytes::from(value))?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    // UTF-8 and Latin-1 octets
+    let value = &b"caf\xc3\xa9 caf\xe9"[..];
+    let fragment = tester.encoder.encode_for_test(vec![
+        (&b":method"[..], &b"GET"[..]),
+        (&b":path"[..], &b"/obs-text"[..]),
+        (&b":scheme"[..], &b"http"[..]),
+        (&b"x-foo"[..], value),
+    ]);
+    let mut headers_frame = HeadersFrame::new_conv(fragment, 1);
+    headers_frame.set_flag(HeadersFlag::EndHeaders);
+    headers_frame.set_flag(HeadersFlag::EndStream);
+    tester.send_frame(headers_frame);
+
+    let resp = tester.recv_message(1);
+    assert_eq!(200, resp.headers.status());
+    assert_eq!(value, resp.body.get_bytes());
+}
+
+#[test]
+fn data_instead_of_continuation() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    send_headers_without_end_headers(&mut tester, 1);
+    tester.send_data(1, b"abc", true);
+
+    tester.recv_goaway_frame_check(ErrorCode::ProtocolError);
+    tester.recv_eof();
+}
+
+#[test]
+fn continuation_on_different_stream() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    send_headers_without_end_headers(&mut tester, 1);
+    let mut continuation = ContinuationFrame::new(Bytes::new(), 3);
+    continuation.set_flag(ContinuationFlag::EndHeaders);
+    tester.send_frame(continuation);
+
+    tester.recv_goaway_frame_check(ErrorCode::ProtocolError);
+    tester.recv_eof();
+}
+
+#[test]
+fn headers_for_new_stream_instead_of_continuation() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    send_headers_without_end_headers(&mut tester, 1);
+    tester.send_get(3, "/");
+
+    tester.recv_goaway_frame_check(ErrorCode::ProtocolError);
+    tester.recv_eof();
+}
+
+#[test]
+fn header_list_too_large() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.common.max_header_list_size = Some(1000);
+    let server = ServerTest::new_with_conf(conf);
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+    assert_eq!(1000, tester.peer_settings.max_header_list_size);
+
+    // :method GET, :path /, :scheme http
+    let mut fragment = vec![0x82, 0x84, 0x86];
+    // literal with incremental indexing, new name
+    fragment.extend_from_slice(&[0x40, 6]);
+    fragment.extend_from_slice(b"x-bomb");
+    fragment.push(100);
+    fragment.extend_from_slice(&[b'a'; 100]);
+    // repeat the header with a single byte index of the dynamic table entry
+    fragment.extend_from_slice(&[0xbe; 1000]);
+
+    let mut headers_frame = HeadersFrame::new_conv(fragment, 1);
+    headers_frame.set_flag(HeadersFlag::EndHeaders);
+    headers_frame.set_flag(HeadersFlag::EndStream);
+    tester.send_frame(headers_frame);
+
+    tester.recv_goaway_frame_check(ErrorCode::EnhanceYourCalm);
+    tester.recv_eof();
+}
+
+#[test]
+fn header_list_size_limited_by_default() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+    assert_eq!(64 << 10, tester.peer_settings.max_header_list_size);
+
+    // Same header bomb as above, decoded list size exceeds 64 KiB
+    let mut fragment = vec![0x82, 0x84, 0x86];
+    fragment.extend_from_slice(&[0x40, 6]);
+    fragment.extend_from_slice(b"x-bomb");
+    fragment.push(100);
+    fragment.extend_from_slice(&[b'a'; 100]);
+    fragment.extend_from_slice(&[0xbe; 1000]);
+
+    let mut headers_frame = HeadersFrame::new_conv(fragment, 1);
+    headers_frame.set_flag(HeadersFlag::EndHeaders);
+    headers_frame.set_flag(HeadersFlag::EndStream);
+    tester.send_frame(headers_frame);
+
+    tester.recv_goaway_frame_check(ErrorCode::EnhanceYourCalm);
+    tester.recv_eof();
+}
+
+#[test]
+fn headers_depend_on_idle_stream() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn(0, |_, _req, mut resp| {
+        resp.send_found_200_plain_text("hi there")?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let mut headers = Headers::new();
+    headers.add(":method", "GET");
+    headers.add(":path", "/dep");
+    headers.add(":scheme", "http");
+    let fragment = tester
+        .encoder
+        .encode_for_test(headers.iter().map(|h| (h.name().as_bytes(), h.value())));
+
+    // Stream 7 is not opened yet, but dependency on idle stream is legal
+    let mut frame =
+        HeadersFrame::with_dependency(fragment, 1, StreamDependency::new(7, 100, false));
+    frame.set_flag(HeadersFlag::EndHeaders);
+    frame.set_flag(HeadersFlag::EndStream);
+    tester.send_frame(frame);
+
+    let resp = tester.recv_message(1);
+    assert_eq!(200, resp.headers.status());
+    assert_eq!(&b"hi there"[..], resp.body.get_bytes());
+}
+
+#[test]
+fn ping_ack_echoes_opaque_data() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let opaque_data = [0xde, 0xad, 0xbe, 0xef, 0x01, 0x02, 0x03, 0x04];
+    tester.send_frame(PingFrame::with_data(u64::from_be_bytes(opaque_data)));
+
+    let raw_frame = tester.recv_raw_frame();
+    let header = raw_frame.header();
+    assert_eq!(0x6, header.frame_type);
+    assert_eq!(0x1, header.flags);
+    assert_eq!(0, header.stream_id);
+    assert_eq!(&opaque_data[..], &raw_frame.payload()[..]);
+}
+
+#[test]
+fn ping_on_non_zero_stream() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_raw_frame(FrameHeader::new(8, 0x6, 0, 1), &[0; 8]);
+
+    tester.recv_goaway_frame_check(ErrorCode::ProtocolError);
+    tester.recv_eof();
+}
+
+#[test]
+fn settings_ack_with_payload() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_raw_frame(FrameHeader::new(6, 0x4, 0x1, 0), &[0; 6]);
+
+    tester.recv_goaway_frame_check(ErrorCode::FrameSizeError);
+    tester.recv_eof();
+}
+
+#[test]
+fn settings_payload_not_multiple_of_six() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_raw_frame(FrameHeader::new(5, 0x4, 0, 0), &[0; 5]);
+
+    tester.recv_goaway_frame_check(ErrorCode::FrameSizeError);
+    tester.recv_eof();
+}
+
+#[test]
+fn peer_unknown_settings() {
+    init_logger();
+
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let server = ServerOneConn::new_fn(0, move |context, _req, mut resp| {
+        drop(
+            tx.lock()
+                .unwrap()
+                .send(context.peer_unknown_settings().to_vec()),
+        );
+        resp.send_found_200_plain_text("")?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    let mut settings = SettingsFrame::new();
+    settings.unknown_settings.push((0xf000, 3));
+    settings.unknown_settings.push((8, 1));
+    tester.send_settings(settings);
+    tester.recv_frame_settings_set();
+    tester.send_frame(SettingsFrame::new_ack());
+    tester.recv_frame_settings_ack();
+
+    tester.send_get(1, "/");
+    assert_eq!(200, tester.recv_frame_headers_check(1, false).status());
+
+    assert_eq!(vec![(8, 1), (0xf000, 3)], rx.recv().unwrap());
+}
+
+#[test]
+fn panic_in_handler() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn(0, |_, req, mut resp| {
+        if req.headers.path() == "/panic" {
+            panic!("requested");
+        } else {
+            resp.send_found_200_plain_text("hi there")?;
+            Ok(())
+        }
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    info!("test /hello");
+
+    {
+        let resp = tester.get(1, "/hello");
+        assert_eq!(200, resp.headers.status());
+        assert_eq!(&b"hi there"[..], resp.body.get_bytes());
+    }
+
+    info!("test /panic");
+
+    {
+        tester.send_get(3, "/panic");
+        tester.recv_rst_frame_check(3, ErrorCode::InternalError);
+    }
+
+    info!("test /world");
+
+    {
+        let resp = tester.get(5, "/world");
+        assert_eq!(200, resp.headers.status());
+        assert_eq!(&b"hi there"[..], resp.body.get_bytes());
+    }
+
+    assert_eq!(0, server.dump_state().streams.len());
+}
+
+#[test]
+fn panic_in_stream() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn(0, |_, req, mut resp| {
+        if req.headers.path() == "/panic" {
+            let stream = HttpStreamAfterHeaders::new(
+                stream::iter((0..2).map(|_| {
+                    panic!("should reset stream");
+                }))
+                .map(Ok),
+            );
+            resp.send_headers(Headers::ok_200())?;
+            resp.pull_from_stream(stream)?;
+        } else {
+            resp.send_found_200_plain_text("hi there")?;
+        }
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    info!("test /hello");
+
+    {
+        let resp = tester.get(1, "/hello");
+        assert_eq!(200, resp.headers.status());
+        assert_eq!(&b"hi there"[..], resp.body.get_bytes());
+    }
+
+    info!("test /panic");
+
+    {
+        tester.send_get(3, "/panic");
+        tester.recv_frame_headers_check(3, false);
+        tester.recv_rst_frame();
+    }
+
+    info!("test /world");
+
+    {
+        let resp = tester.get(5, "/world");
+        assert_eq!(200, resp.headers.status());
+        assert_eq!(&b"hi there"[..], resp.body.get_bytes());
+    }
+
+    assert_eq!(0, server.dump_state().streams.len());
+}
+
+#[test]
+fn response_large() {
+    init_logger();
+
+    let rt = Runtime::new().unwrap();
+
+    let mut large_resp = Vec::new();
+    while large_resp.len() < 100_000 {
+        if large_resp.len() != 0 {
+            write!(&mut large_resp, ",").unwrap();
+        }
+        let len = large_resp.len();
+        write!(&mut large_resp, "{}", len).unwrap();
+    }
+
+    let large_resp_copy = large_resp.clone();
+
+    let server = ServerOneConn::new_fn(0, move |_, _req, mut resp| {
+        resp.send_message(SimpleHttpMessage {
+            headers: Headers::ok_200(),
+            body: BytesDeque::from(large_resp_copy.clone()),
+            trailers: None,
+        })?;
+        Ok(())
+    });
+
+    // TODO: rewrite with TCP
+    let client = Client::new_plain(BIND_HOST, server.port(), Default::default()).expect("connect");
+    let resp = rt
+        .block_on(
+            client
+                .start_post("/foobar", "localhost", Bytes::from(&b""[..]))
+                .collect(),
+        )
+        .expect("wait");
+    assert_eq!(large_resp.len(), resp.body.len());
+    assert_eq!(
+        (large_resp.len(), &large_resp[..]),
+        (resp.body.len(), &resp.body.get_bytes()[..])
+    );
+
+    assert_eq!(0, server.dump_state().streams.len());
+}
+
+#[test]
+fn response_exact_multiple_of_max_frame_size() {
+    init_logger();
+
+    let max_frame_size = DEFAULT_SETTINGS.max_frame_size as usize;
+
+    let server = ServerOneConn::new_fn(0, move |_, _req, mut resp| {
+        resp.send_message(SimpleHttpMessage {
+            headers: Headers::ok_200(),
+            body: BytesDeque::from(vec![17; 2 * max_frame_size]),
+            trailers: None,
+        })?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_get(1, "/two-frames");
+    assert_eq!(200, tester.recv_frame_headers_check(1, false).status());
+    assert_eq!(max_frame_size, tester.recv_frame_data_check(1, false).len());
+    // Last full frame carries END_STREAM, no empty DATA frame follows
+    assert_eq!(max_frame_size, tester.recv_frame_data_check(1, true).len());
+
+    assert_eq!(0, server.dump_state().streams.len());
+}
+
+#[test]
+fn new_streams_rate_limit() {
+    init_logger();
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_port(0);
+    server.conf.new_streams_per_second = Some(3);
+    server.service.set_service_fn("/", |_, _, mut resp| {
+        resp.send_found_200_plain_text("aabb")?;
+        Ok(())
+    });
+    let server = server.build().expect("server");
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let count = 10;
+    for i in 0..count {
+        tester.send_get(1 + 2 * i, "/");
+    }
+
+    let mut completed = 0;
+    let mut refused = 0;
+    while completed + refused != count {
+        match tester.recv_frame() {
+            HttpFrame::RstStream(rst) => {
+                assert_eq!(ErrorCode::RefusedStream, rst.error_code());
+                refused += 1;
+            }
+            HttpFrame::Data(data) if data.is_end_of_stream() => completed += 1,
+            _ => {}
+        }
+    }
+
+    assert!(completed >= 3, "completed: {}", completed);
+    assert!(refused > 0, "refused: {}", refused);
+}
+
+#[test]
+fn max_concurrent_streams() {
+    init_logger();
+
+    let responses = Arc::new(Mutex::new(Vec::new()));
+    let responses_copy = responses.clone();
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_port(0);
+    server.conf.max_concurrent_streams = Some(2);
+    server
+        .service
+        .set_service_fn("/hold", move |_, _, mut resp| {
+            resp.send_headers(Headers::ok_200())?;
+            responses_copy.lock().unwrap().push(resp);
+            Ok(())
+        });
+    server.service.set_service_fn("/", |_, _, mut resp| {
+        resp.send_found_200_plain_text("aabb")?;
+        Ok(())
+    });
+    let server = server.build().expect("server");
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+    assert_eq!(2, tester.peer_settings.max_concurrent_streams);
+
+    tester.send_get(1, "/hold");
+    tester.send_get(3, "/hold");
+    assert_eq!(200, tester.recv_frame_headers_check(1, false).status());
+    assert_eq!(200, tester.recv_frame_headers_check(3, false).status());
+
+    tester.send_get(5, "/");
+    tester.recv_rst_frame_check(5, ErrorCode::RefusedStream);
+
+    // Completed stream frees a slot
+    let mut resp = responses.lock().unwrap().remove(0);
+    resp.send_data_end_of_stream(Bytes::from_static(b"done"))
+        .expect("send");
+    assert_eq!(&b"done"[..], &tester.recv_frame_data_check(1, true)[..]);
+
+    assert_eq!(200, tester.get(7, "/").headers.status());
+
+    tester.send_get(9, "/hold");
+    assert_eq!(200, tester.recv_frame_headers_check(9, false).status());
+    tester.send_get(11, "/");
+    tester.recv_rst_frame_check(11, ErrorCode::RefusedStream);
+
+    // Reset stream frees a slot too
+    tester.send_rst(3, ErrorCode::Cancel);
+    assert_eq!(200, tester.get(13, "/").headers.status());
+}
+
+#[test]
+fn max_frame_size_invalid() {
+    init_logger();
+
+    for max_frame_size in [16383, 16777216] {
+        let mut server = ServerBuilder::new_plain();
+        server.set_port(0);
+        server.conf.common.max_frame_size = Some(max_frame_size);
+        assert!(server.build().is_err());
+    }
+}
+
+#[test]
+fn auto_date() {
+    init_logger();
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_port(0);
+    server.service.set_service_fn("/", |_, _, mut resp| {
+        resp.send_found_200_plain_text("aabb")?;
+        Ok(())
+    });
+    server.service.set_service_fn("/date", |_, _, mut resp| {
+        let mut headers = Headers::ok_200();
+        headers.add("date", "Sun, 06 Nov 1994 08:49:37 GMT");
+        resp.send_headers_end_of_stream(headers)?;
+        Ok(())
+    });
+    let server = server.build().expect("server");
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_get(1, "/");
+    let date = tester.recv_message(1).headers.get("date").to_owned();
+    // e. g. `Sun, 06 Nov 1994 08:49:37 GMT`
+    let parts: Vec<&str> = date.split(' ').collect();
+    assert_eq!(6, parts.len(), "{}", date);
+    assert!(parts[0].len() == 4 && parts[0].ends_with(','), "{}", date);
+    assert_eq!(2, parts[1].len(), "{}", date);
+    assert_eq!(3, parts[2].len(), "{}", date);
+    assert!(parts[3].parse::<u32>().unwrap() >= 2020, "{}", date);
+    assert_eq!(8, parts[4].len(), "{}", date);
+    assert_eq!("GMT", parts[5]);
+
+    tester.send_get(3, "/date");
+    let headers = tester.recv_message(3).headers;
+    assert_eq!(
+        vec![&b"Sun, 06 Nov 1994 08:49:37 GMT"[..]],
+        headers
+            .iter()
+            .filter(|h| h.name() == "date")
+            .map(|h| h.value())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn auto_date_clock() {
+    init_logger();
+
+    #[derive(Debug)]
+    struct FixedClock;
+
+    impl Clock for FixedClock {
+        fn now(&self) -> std::time::SystemTime {
+            std::time::UNIX_EPOCH + Duration::from_secs(784111777)
+        }
+    }
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_port(0);
+    server.conf.clock = Some(Arc::new(FixedClock));
+    server.service.set_service_fn("/", |_, _, mut resp| {
+        resp.send_found_200_plain_text("aabb")?;
+        Ok(())
+    });
+    let server = server.build().expect("server");
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    for stream_id in [1, 3] {
+        tester.send_get(stream_id, "/");
+        assert_eq!(
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            tester.recv_message(stream_id).headers.get("date")
+        );
+    }
+}
+
+#[test]
+fn set_services_and_default() {
+    init_logger();
+
+    struct Reply(&'static str);
+
+    impl ServerHandler for Reply {
+        fn start_request(
+            &self,
+            _context: ServerHandlerContext,
+            _req: ServerRequest,
+            mut resp: ServerResponse,
+        ) -> httpbis::Result<()> {
+            resp.send_found_200_plain_text(self.0)?;
+            Ok(())
+        }
+    }
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_port(0);
+    server.set_services(vec![
+        (
+            "/a".to_owned(),
+            Arc::new(Reply("a")) as Arc<dyn ServerHandler>,
+        ),
+        (
+            "/b".to_owned(),
+            Arc::new(Reply("b")) as Arc<dyn ServerHandler>,
+        ),
+        (
+            "/b/c".to_owned(),
+            Arc::new(Reply("bc")) as Arc<dyn ServerHandler>,
+        ),
+    ]);
+    server.set_default_service(Arc::new(Reply("default")));
+    let server = server.build().expect("server");
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let paths = [
+        ("/a", "a"),
+        ("/b", "b"),
+        ("/b/c", "bc"),
+        ("/b/d", "b"),
+        ("/", "default"),
+        ("/zz", "default"),
+    ];
+    for (i, &(path, expected)) in paths.iter().enumerate() {
+        let resp = tester.get(1 + 2 * i as u32, path);
+        assert_eq!(200, resp.headers.status());
+        assert_eq!(expected.as_bytes(), &resp.body.get_bytes()[..], "{}", path);
+    }
+}
+
+#[test]
+fn rst_stream_on_data_without_stream() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    // DATA frame without open stream
+    tester.send_data(11, &[10, 20, 30], false);
+
+    tester.recv_goaway_frame_check(ErrorCode::StreamClosed);
+
+    tester.recv_eof();
+}
+
+#[test]
+fn exceed_max_frame_size() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_data(1, &[0; 17_000], false);
+
+    tester.recv_goaway_frame_check(ErrorCode::FrameSizeError);
+    tester.recv_eof();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    assert_eq!(200, tester.get(1, "/echo").headers.status());
+}
+
+#[test]
+fn increase_frame_size() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let mut frame = SettingsFrame::new();
+    frame.settings.push(HttpSetting::MaxFrameSize(20000));
+    tester.send_recv_settings(frame);
+
+    tester.send_get(1, "/blocks/30000/1");
+    assert_eq!(200, tester.recv_frame_headers_check(1, false).status());
+    assert_eq!(20000, tester.recv_frame_data_check(1, false).len());
+    assert_eq!(10000, tester.recv_frame_data_tail(1).len());
+}
+
+#[test]
+fn header_table_size_update() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let mut frame = SettingsFrame::new();
+    frame.settings.push(HttpSetting::HeaderTableSize(0));
+    tester.send_recv_settings(frame);
+
+    for (stream_id, size_update) in vec![(1, true), (3, false)] {
+        tester.send_get(stream_id, "/blocks/10/1");
+        match tester.recv_frame() {
+            HttpFrame::Headers(headers) => {
+                // `001xxxxx` with size 0
+                assert_eq!(size_update, headers.header_fragment[0] == 0x20);
+            }
+            f => panic!("expecting HEADERS, got: {:?}", f),
+        }
+        assert_eq!(10, tester.recv_frame_data_tail(stream_id).len());
+    }
+}
+
+#[test]
+fn header_table_size_clamped() {
+    init_logger();
+
+    for (max_encoder_table_size, size_update) in vec![(None, None), (Some(0), Some(0x20))] {
+        let mut conf = ServerConf::new();
+        conf.common.max_encoder_table_size = max_encoder_table_size;
+        let server = ServerTest::new_with_conf(conf);
+
+        let mut tester = HttpConnTester::connect(server.port);
+        tester.send_preface();
+        tester.settings_xchg();
+
+        let mut frame = SettingsFrame::new();
+        frame.settings.push(HttpSetting::HeaderTableSize(1 << 30));
+        tester.send_recv_settings(frame);
+
+        tester.send_get(1, "/blocks/10/1");
+        match tester.recv_frame() {
+            HttpFrame::Headers(headers) => {
+                // `001xxxxx` is dynamic table size update
+                let first = headers.header_fragment[0];
+                match size_update {
+                    Some(b) => assert_eq!(b, first),
+                    None => assert_ne!(0x20, first & 0xe0),
+                }
+            }
+            f => panic!("expecting HEADERS, got: {:?}", f),
+        }
+        assert_eq!(10, tester.recv_frame_data_tail(1).len());
+    }
+}
+
+#[test]
+fn in_window_auto_tuning() {
+    init_logger();
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_port(0);
+    server.conf.common.max_window_size = Some(4 << 20);
+    server
+        .service
+        .set_service_fn("/", |context, req, mut resp| {
+            let body = req.make_stream().filter_data();
+            context.loop_remote().spawn(async move {
+                let len = body
+                    .try_fold(0, |len, data| future::ok(len + data.len()))
+                    .await
+                    .expect("body");
+                resp.send_found_200_plain_text(&format!("{}", len)).unwrap();
+            });
+            Ok(())
+        });
+    let server = server.build().expect("server");
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let mut headers = Headers::new_post("/");
+    headers.add(":scheme", "http");
+    tester.send_headers(1, headers, false);
+    // as fast as the peer allows
+    let total = 8 << 20;
+    let chunk = [0; 16384];
+    let mut sent = 0;
+    let mut stream_window = DEFAULT_SETTINGS.initial_window_size as usize;
+    let mut max_stream_increment = 0;
+    while sent < total {
+        let len = cmp::min(
+            chunk.len(),
+            cmp::min(stream_window, tester.out_window_size.size() as usize),
+        );
+        if len > 0 {
+            tester.send_data(1, &chunk[..len], false);
+            stream_window -= len;
+            sent += len;
+            continue;
+        }
+
+        match tester.fn_recv_frame_no_check_ack() {
+            HttpFrame::WindowUpdate(f) if f.stream_id == 0 => {
+                tester.out_window_size.try_increase(f.increment).unwrap();
+            }
+            HttpFrame::WindowUpdate(f) => {
+                assert_eq!(1, f.stream_id);
+                stream_window += f.increment as usize;
+                max_stream_increment = cmp::max(max_stream_increment, f.increment);
+            }
+            f => panic!("unexpected frame: {:?}", f),
+        }
+    }
+    tester.send_data(1, b"", true);
+
+    assert!(
+        max_stream_increment > DEFAULT_SETTINGS.initial_window_size,
+        "{}",
+        max_stream_increment
+    );
+    assert_eq!(
+        format!("{}", sent).as_bytes(),
+        tester.recv_message(1).body.get_bytes()
+    );
+}
+
+#[test]
+fn input_end_reason() {
+    init_logger();
+
+    let responses = Arc::new(Mutex::new(Vec::new()));
+    let responses_copy = responses.clone();
+    let server = ServerOneConn::new_fn(0, move |_, _req, resp| {
+        // keep streams open until checked
+        responses_copy.lock().unwrap().push(resp);
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let mut headers = Headers::new_post("/");
+    headers.add(":scheme", "http");
+
+    tester.send_headers(1, headers.clone(), false);
+    tester.send_data(1, b"abc", true);
+
+    let mut trailers = Headers::new();
+    trailers.add("x-trailer", "1");
+    tester.send_headers(3, headers.clone(), false);
+    tester.send_data(3, b"abc", false);
+    tester.send_headers(3, trailers, true);
+
+    tester.send_get(5, "/");
+
+    // Make sure all frames are processed
+    tester.send_frame(PingFrame::with_data(17));
+    loop {
+        match tester.fn_recv_frame_no_check_ack() {
+            HttpFrame::WindowUpdate(..) => {}
+            HttpFrame::Ping(f) => {
+                assert!(f.is_ack());
+                break;
+            }
+            f => panic!("unexpected frame: {:?}", f),
+        }
+    }
+
+    let state = server.dump_state();
+    assert_eq!(
+        Some(InputEndReason::Data),
+        state.streams[&1].input_end_reason
+    );
+    assert_eq!(
+        Some(InputEndReason::Trailers),
+        state.streams[&3].input_end_reason
+    );
+    assert_eq!(
+        Some(InputEndReason::Headers),
+        state.streams[&5].input_end_reason
+    );
+
+    assert_eq!(3, responses.lock().unwrap().len());
+}
+
+#[test]
+fn exceed_window_size() {
+    init_logger();
+
+    // Allow frame larger than window
+    let mut conf = ServerConf::new();
+    conf.common.max_frame_size = Some(1 << 20);
+    let server = ServerTest::new_with_conf(conf);
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let mut frame = SettingsFrame::new();
+    frame.settings.push(HttpSetting::MaxFrameSize(
+        tester.peer_settings.initial_window_size + 5,
+    ));
+    tester.send_recv_settings(frame);
+
+    let data = Vec::from_iter((0..tester.peer_settings.initial_window_size + 3).map(|_| 2));
+
+    // Deliberately set wrong out_windows_size so `send_data` wouldn't fail.
+    tester.out_window_size.try_add(10000000).unwrap();
+    tester.send_data(1, &data, false);
+    tester.recv_eof();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    assert_eq!(200, tester.get(1, "/echo").headers.status());
+}
+
+#[test]
+fn stream_window_gt_conn_window() {
+    init_logger();
+
+    let rt = Runtime::new().unwrap();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let w = DEFAULT_SETTINGS.initial_window_size;
+
+    // May need to be changed if server defaults are changed
+    assert_eq!(w as i32, tester.in_window_size.size());
+
+    tester.send_recv_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::InitialWindowSize(w * 2),
+        HttpSetting::MaxFrameSize(w * 10),
+    ]));
+
+    // Now new stream window is gt than conn window
 
     let w = tester.peer_settings.initial_window_size;
     tester.send_get(1, &format!("/blocks/{}/{}", w, 2));
@@ -381,6 +1871,477 @@ fn stream_window_gt_conn_window() {
     assert_eq!(w as usize, tester.recv_frame_data_tail(1).len());
 }
 
+#[test]
+fn out_window_stalls() {
+    init_logger();
+
+    let rt = Runtime::new().unwrap();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_recv_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::InitialWindowSize(10),
+    ]));
+
+    tester.send_get(1, "/blocks/10/3");
+    assert_eq!(200, tester.recv_frame_headers_check(1, false).status());
+    assert_eq!(10, tester.recv_frame_data_check(1, false).len());
+
+    // pump parks asynchronously after window is exhausted
+    let stalls = || {
+        let server_sn = rt.block_on(server.server.dump_state()).expect("state");
+        server_sn
+            .single_conn()
+            .1
+            .single_stream()
+            .1
+            .out_window_stalls
+    };
+    let mut attempts = 0;
+    while stalls() == 0 {
+        attempts += 1;
+        assert!(attempts < 500, "stream never stalled");
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    // more than remaining body, so end of stream is not blocked
+    tester.send_window_update_stream(1, 30);
+    tester.recv_frames_data_check(1, 10, 20, false);
+    tester.recv_frame_data_check_empty_end(1);
+}
+
+#[test]
+fn out_data_frames_bytes() {
+    init_logger();
+
+    let rt = Runtime::new().unwrap();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_get(1, "/blocks/10/3");
+    assert_eq!(200, tester.recv_frame_headers_check(1, false).status());
+
+    let mut received = 0;
+    loop {
+        let frame = tester.recv_frame_data();
+        received += frame.serialized_len() as u64;
+        if frame.is_end_of_stream() {
+            break;
+        }
+    }
+
+    let server_sn = rt.block_on(server.server.dump_state()).expect("state");
+    assert_eq!(received, server_sn.single_conn().1.out_data_frames_bytes);
+}
+
+#[test]
+fn graceful_shutdown() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let mut headers = Headers::new();
+    headers.add(":method", "POST");
+    headers.add(":path", "/echo");
+    headers.add(":scheme", "http");
+    tester.send_headers(1, headers, false);
+    tester.send_data(1, b"abc", false);
+
+    assert_eq!(200, tester.recv_frame_headers_check(1, false).status());
+    assert_eq!(&b"abc"[..], &tester.recv_frame_data_check(1, false)[..]);
+
+    server.server.graceful_shutdown();
+
+    let goaway = tester.recv_goaway_frame();
+    assert_eq!(ErrorCode::NoError, goaway.error_code());
+    assert_eq!(0x7fffffff, goaway.last_stream_id());
+
+    let ping = match tester.recv_frame() {
+        HttpFrame::Ping(ping) => ping,
+        f => panic!("expecting PING, got: {:?}", f),
+    };
+    assert!(!ping.is_ack());
+
+    // request sent before PING ACK is still accepted
+    tester.send_get(3, "/blocks/1/1");
+    assert_eq!(200, tester.recv_frame_headers_check(3, false).status());
+    assert_eq!(1, tester.recv_frame_data_tail(3).len());
+
+    tester.send_frame(PingFrame::new_ack(ping.opaque_data()));
+
+    let goaway = tester.recv_goaway_frame();
+    assert_eq!(ErrorCode::NoError, goaway.error_code());
+    assert_eq!(3, goaway.last_stream_id());
+
+    // new requests are refused
+    tester.send_get(5, "/blocks/1/1");
+    tester.recv_rst_frame_check(5, ErrorCode::RefusedStream);
+
+    // in-flight request completes
+    tester.send_data(1, b"def", true);
+    assert_eq!(&b"def"[..], &tester.recv_frame_data_tail(1)[..]);
+
+    tester.recv_eof();
+}
+
+#[test]
+fn send_goaway_with_debug() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    // Make sure the connection is registered in the server
+    assert_eq!(200, tester.get(1, "/echo").headers.status());
+
+    match server
+        .server
+        .send_goaway_with_debug(ErrorCode::InternalError, Bytes::from(vec![0; 16377]))
+    {
+        Err(Error::PayloadTooLarge(16377, 16376)) => {}
+        r => panic!("expecting PayloadTooLarge, got: {:?}", r),
+    }
+
+    server
+        .server
+        .send_goaway_with_debug(ErrorCode::InternalError, Bytes::from_static(b"maintenance"))
+        .expect("send_goaway_with_debug");
+
+    let goaway = tester.recv_goaway_frame();
+    assert_eq!(ErrorCode::InternalError, goaway.error_code());
+    assert_eq!(1, goaway.last_stream_id());
+    assert_eq!(&b"maintenance"[..], &goaway.debug_data()[..]);
+    tester.recv_eof();
+}
+
+#[test]
+fn on_stream_state_change() {
+    init_logger();
+
+    let transitions = Arc::new(Mutex::new(Vec::new()));
+
+    let mut conf = ServerConf::new();
+    let transitions_copy = transitions.clone();
+    conf.common.on_stream_state_change = Some(Arc::new(move |stream_id, old, new| {
+        transitions_copy.lock().unwrap().push((stream_id, old, new));
+    }));
+    let server = ServerTest::new_with_conf(conf);
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    assert_eq!(200, tester.get(1, "/echo").headers.status());
+
+    assert_eq!(
+        vec![
+            (1, StreamState::Idle, StreamState::Open),
+            (1, StreamState::Open, StreamState::HalfClosedRemote),
+            (1, StreamState::HalfClosedRemote, StreamState::Closed),
+        ],
+        *transitions.lock().unwrap()
+    );
+}
+
+#[test]
+fn rst_stream_flood() {
+    init_logger();
+
+    // Handlers keep responses, so streams are open until reset
+    let responses = Arc::new(Mutex::new(Vec::new()));
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_port(0);
+    server.conf.max_rst_streams_per_window = Some(10);
+    let responses_copy = responses.clone();
+    server.service.set_service_fn("/hold", move |_, _, resp| {
+        responses_copy.lock().unwrap().push(resp);
+        Ok(())
+    });
+    let responses_copy = responses.clone();
+    server
+        .service
+        .set_service_fn("/respond", move |_, _, mut resp| {
+            resp.send_headers(Headers::ok_200())?;
+            responses_copy.lock().unwrap().push(resp);
+            Ok(())
+        });
+    let server = server.build().expect("server");
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let mut stream_id = 1;
+
+    // Resets of streams with response in progress are not counted
+    for _ in 0..20 {
+        tester.send_get(stream_id, "/respond");
+        assert_eq!(
+            200,
+            tester.recv_frame_headers_check(stream_id, false).status()
+        );
+        tester.send_rst(stream_id, ErrorCode::Cancel);
+        stream_id += 2;
+    }
+
+    // Connection is still alive
+    tester.send_frame(PingFrame::with_data(17));
+    match tester.fn_recv_frame_no_check_ack() {
+        HttpFrame::Ping(f) => assert!(f.is_ack()),
+        f => panic!("unexpected frame: {:?}", f),
+    }
+
+    for _ in 0..11 {
+        tester.send_get(stream_id, "/hold");
+        tester.send_rst(stream_id, ErrorCode::Cancel);
+        stream_id += 2;
+    }
+
+    tester.recv_goaway_frame_check(ErrorCode::EnhanceYourCalm);
+    tester.recv_eof();
+}
+
+#[test]
+fn send_trailers_after_body() {
+    init_logger();
+
+    let (results_tx, results_rx) = mpsc::channel();
+    let results_tx = Mutex::new(results_tx);
+
+    let server = ServerOneConn::new_fn(0, move |_, req, mut resp| {
+        let results_tx = results_tx.lock().unwrap();
+
+        let mut trailers = Headers::new();
+        trailers.add("grpc-status", "0");
+
+        if req.headers.path() == "/ended" {
+            resp.send_headers(Headers::ok_200())?;
+            resp.send_data_end_of_stream(Bytes::from_static(b"abc"))?;
+            results_tx
+                .send(("ended", resp.send_trailers(trailers)))
+                .unwrap();
+            return Ok(());
+        }
+
+        resp.send_headers(Headers::ok_200())?;
+        resp.send_data(Bytes::from_static(b"ab"))?;
+        resp.send_data(Bytes::from_static(b"cd"))?;
+        results_tx
+            .send(("pseudo", resp.send_trailers(Headers::ok_200())))
+            .unwrap();
+        results_tx
+            .send(("first", resp.send_trailers(trailers.clone())))
+            .unwrap();
+        results_tx
+            .send(("second", resp.send_trailers(trailers)))
+            .unwrap();
+        Ok(())
+    });
+
+    let rt = Runtime::new().unwrap();
+
+    let client = Client::new_plain(BIND_HOST, server.port(), Default::default()).expect("connect");
+    let resp = rt
+        .block_on(client.start_get("/trailers", "localhost").collect())
+        .expect("response");
+    assert_eq!(200, resp.headers.status());
+    assert_eq!(&b"abcd"[..], &resp.body.get_bytes()[..]);
+    assert_eq!(
+        "0",
+        resp.trailers.as_ref().expect("trailers").get("grpc-status")
+    );
+
+    match results_rx.recv().unwrap() {
+        ("pseudo", Err(SendError::IncorrectHeaders(..))) => {}
+        r => panic!("unexpected: {:?}", r),
+    }
+    match results_rx.recv().unwrap() {
+        ("first", Ok(())) => {}
+        r => panic!("unexpected: {:?}", r),
+    }
+    match results_rx.recv().unwrap() {
+        ("second", Err(SendError::IncorrectState(SenderState::Done))) => {}
+        r => panic!("unexpected: {:?}", r),
+    }
+
+    let resp = rt
+        .block_on(client.start_get("/ended", "localhost").collect())
+        .expect("response");
+    assert_eq!(&b"abc"[..], &resp.body.get_bytes()[..]);
+    assert!(resp.trailers.is_none());
+    match results_rx.recv().unwrap() {
+        ("ended", Err(SendError::IncorrectState(SenderState::Done))) => {}
+        r => panic!("unexpected: {:?}", r),
+    }
+}
+
+#[test]
+fn forward_trailers_from() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn(0, |_, req, mut resp| {
+        resp.send_headers(Headers::ok_200())?;
+        resp.forward_trailers_from(req)?;
+        Ok(())
+    });
+
+    let rt = Runtime::new().unwrap();
+
+    let client = Client::new_plain(BIND_HOST, server.port(), Default::default()).expect("connect");
+
+    let request_headers = || {
+        let mut headers = Headers::new_post("/proxy");
+        headers.add(":authority", "localhost");
+        headers.add(":scheme", "http");
+        headers
+    };
+
+    let mut trailers = Headers::new();
+    trailers.add("x-trace", "abc");
+    let resp = rt
+        .block_on(
+            client
+                .start_request_end_stream(
+                    request_headers(),
+                    Some(Bytes::from_static(b"body")),
+                    Some(trailers),
+                )
+                .collect(),
+        )
+        .expect("response");
+    assert_eq!(200, resp.headers.status());
+    assert_eq!(0, resp.body.get_bytes().len());
+    assert_eq!(
+        "abc",
+        resp.trailers.as_ref().expect("trailers").get("x-trace")
+    );
+
+    // Request without trailers ends the response without trailers
+    let resp = rt
+        .block_on(
+            client
+                .start_request_end_stream(
+                    request_headers(),
+                    Some(Bytes::from_static(b"body")),
+                    None,
+                )
+                .collect(),
+        )
+        .expect("response");
+    assert_eq!(200, resp.headers.status());
+    assert!(resp.trailers.is_none());
+}
+
+#[test]
+fn closed_stream_window_update_flood() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.common.max_closed_stream_window_updates = Some(10);
+    let server = ServerTest::new_with_conf(conf);
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let resp = tester.get(1, "/echo");
+    assert_eq!(200, resp.headers.status());
+
+    // Stale updates are ignored up to the limit
+    for _ in 0..10 {
+        tester.send_window_update_stream(1, 1);
+    }
+    tester.send_frame(PingFrame::with_data(17));
+    match tester.recv_frame() {
+        HttpFrame::Ping(f) => assert!(f.is_ack()),
+        f => panic!("unexpected frame: {:?}", f),
+    }
+
+    tester.send_window_update_stream(1, 1);
+    tester.recv_goaway_frame_check(ErrorCode::EnhanceYourCalm);
+    tester.recv_eof();
+}
+
+#[test]
+fn received_ping_flood() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.common.max_received_pings = Some(10);
+    let server = ServerTest::new_with_conf(conf);
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    for i in 0..10 {
+        tester.send_frame(PingFrame::with_data(i));
+        match tester.recv_frame() {
+            HttpFrame::Ping(f) => {
+                assert!(f.is_ack());
+                assert_eq!(i, f.opaque_data());
+            }
+            f => panic!("unexpected frame: {:?}", f),
+        }
+    }
+
+    tester.send_frame(PingFrame::with_data(10));
+    tester.recv_goaway_frame_check(ErrorCode::EnhanceYourCalm);
+    tester.recv_eof();
+}
+
+#[test]
+fn settings_ack_timeout() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.common.settings_ack_timeout = Some(Duration::from_millis(100));
+    let server = ServerTest::new_with_conf(conf);
+
+    // Server `SETTINGS` is never acknowledged
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.send_settings(SettingsFrame::new());
+    tester.recv_frame_settings_set();
+
+    tester.recv_goaway_frame_check(ErrorCode::SettingsTimeout);
+    tester.recv_eof();
+}
+
+#[test]
+fn settings_ack_timeout_acknowledged() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.common.settings_ack_timeout = Some(Duration::from_millis(100));
+    let server = ServerTest::new_with_conf(conf);
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    assert!(tester
+        .recv_frame_timeout(Duration::from_millis(300))
+        .is_none());
+    assert_eq!(200, tester.get(1, "/blocks/1/1").headers.status());
+}
+
 #[test]
 fn do_not_poll_when_not_enough_window() {
     init_logger();
@@ -453,6 +2414,8 @@ pub fn server_sends_continuation_frame() {
     init_logger();
 
     let mut headers = Headers::ok_200();
+    // so the server does not add its own
+    headers.add("date", "Sun, 06 Nov 1994 08:49:37 GMT");
     for i in 0..1000 {
         headers.add(
             format!("abcdefghijklmnop{}", i),
@@ -466,6 +2429,7 @@ pub fn server_sends_continuation_frame() {
         resp.send_message(SimpleHttpMessage {
             headers: headers_copy.clone(),
             body: BytesDeque::from("there"),
+            trailers: None,
         })?;
         Ok(())
     });
@@ -15,6 +15,8 @@ use tls_api::TlsConnector as tls_api_TlsConnector;
 use tls_api::TlsConnectorBuilder;
 use tls_api_native_tls::TlsAcceptor;
 use tls_api_native_tls::TlsConnector;
+use tls_api_openssl::TlsAcceptor as OpensslTlsAcceptor;
+use tls_api_openssl::TlsConnector as OpensslTlsConnector;
 use tokio::runtime::Runtime;
 
 fn test_tls_acceptor() -> TlsAcceptor {
@@ -38,6 +40,43 @@ fn test_tls_connector() -> TlsConnector {
     builder.build().unwrap()
 }
 
+fn test_alpn_h2_tls_acceptor() -> OpensslTlsAcceptor {
+    let server_keys = &test_cert_gen::keys().server;
+
+    let mut builder = OpensslTlsAcceptor::builder_from_pkcs12(
+        &server_keys.cert_and_key_pkcs12.pkcs12.0,
+        &server_keys.cert_and_key_pkcs12.password,
+    )
+    .unwrap();
+    builder.set_alpn_protocols(&[b"h2"]).unwrap();
+    builder.build().unwrap()
+}
+
+fn test_alpn_h2_tls_connector() -> OpensslTlsConnector {
+    let client_keys = &test_cert_gen::keys().client;
+
+    let mut builder = OpensslTlsConnector::builder().unwrap();
+    builder
+        .add_root_certificate(client_keys.ca.get_der())
+        .expect("add_root_certificate");
+    builder.set_alpn_protocols(&[b"h2"]).unwrap();
+    builder.build().unwrap()
+}
+
+struct HelloService {}
+
+impl ServerHandler for HelloService {
+    fn start_request(
+        &self,
+        _context: ServerHandlerContext,
+        _req: ServerRequest,
+        mut resp: ServerResponse,
+    ) -> httpbis::Result<()> {
+        resp.send_found_200_plain_text("hello")?;
+        Ok(())
+    }
+}
+
 #[test]
 fn tls() {
     init_logger();
@@ -82,3 +121,113 @@ fn tls() {
     assert_eq!(200, resp.headers.status());
     assert_eq!(&b"hello"[..], resp.body.get_bytes());
 }
+
+#[test]
+fn alpn_h2() {
+    init_logger();
+
+    let rt = Runtime::new().unwrap();
+
+    let mut server = ServerBuilder::new();
+    server.set_addr((BIND_HOST, 0)).expect("set_addr");
+    server.set_tls(test_alpn_h2_tls_acceptor());
+    server.conf.alpn = Some(ServerAlpn::Require);
+    server.service.set_service("/", Arc::new(HelloService {}));
+    let server = server.build().expect("server");
+
+    let socket_addr = match server.local_addr() {
+        &AnySocketAddr::Inet(ref sock_addr) => sock_addr,
+        _ => panic!("Assumed server was an inet server"),
+    };
+
+    let mut conf = ClientConf::new();
+    conf.require_alpn_h2 = true;
+    let client: Client = Client::new_expl(
+        socket_addr,
+        ClientTlsOption::Tls(
+            "localhost".to_owned(),
+            Arc::new(test_alpn_h2_tls_connector()),
+        ),
+        conf,
+    )
+    .expect("http client");
+
+    let resp: SimpleHttpMessage = rt
+        .block_on(client.start_get("/hi", "localhost").collect())
+        .unwrap();
+    assert_eq!(200, resp.headers.status());
+
+    assert_eq!(
+        Some(b"h2".to_vec()),
+        rt.block_on(client.negotiated_alpn()).unwrap()
+    );
+
+    let server_state = rt.block_on(server.dump_state()).unwrap();
+    let (_, conn_state) = server_state.single_conn();
+    assert_eq!(Some(b"h2".to_vec()), conn_state.negotiated_alpn);
+}
+
+#[test]
+fn alpn_h2_required_but_not_negotiated() {
+    init_logger();
+
+    let rt = Runtime::new().unwrap();
+
+    let mut server = ServerBuilder::new();
+    server.set_addr((BIND_HOST, 0)).expect("set_addr");
+    // `native-tls` acceptor does not support ALPN
+    server.set_tls(test_tls_acceptor());
+    server.service.set_service("/", Arc::new(HelloService {}));
+    let server = server.build().expect("server");
+
+    let socket_addr = match server.local_addr() {
+        &AnySocketAddr::Inet(ref sock_addr) => sock_addr,
+        _ => panic!("Assumed server was an inet server"),
+    };
+
+    let mut conf = ClientConf::new();
+    conf.require_alpn_h2 = true;
+    let client: Client = Client::new_expl(
+        socket_addr,
+        ClientTlsOption::Tls("localhost".to_owned(), Arc::new(test_tls_connector())),
+        conf,
+    )
+    .expect("http client");
+
+    match rt.block_on(client.start_get("/hi", "localhost").collect()) {
+        Err(Error::ConnDied(e)) => match &*e {
+            Error::AlpnMismatch(alpn) => assert_eq!("", alpn),
+            e => panic!("expecting ALPN error, got: {:?}", e),
+        },
+        Err(e) => panic!("expecting ALPN error, got: {:?}", e),
+        Ok(_) => panic!("expecting ALPN error"),
+    }
+}
+
+#[test]
+fn new_resolved_localhost() {
+    init_logger();
+
+    let rt = Runtime::new().unwrap();
+
+    let mut server = ServerBuilder::new();
+    server.set_addr((BIND_HOST, 0)).expect("set_addr");
+    server.set_tls(test_tls_acceptor());
+    server.service.set_service("/", Arc::new(HelloService {}));
+    let server = server.build().expect("server");
+
+    // `localhost` may also resolve to `::1` where nobody listens
+    let client: Client = Client::new_resolved(
+        "localhost",
+        server.local_addr().port().unwrap(),
+        ClientTlsOption::Tls("localhost".to_owned(), Arc::new(test_tls_connector())),
+        Default::default(),
+    )
+    .expect("http client");
+
+    let resp: SimpleHttpMessage = rt
+        .block_on(client.start_get("/hi", "localhost").collect())
+        .unwrap();
+    assert_eq!(200, resp.headers.status());
+    assert_eq!(&b"hello"[..], resp.body.get_bytes());
+}
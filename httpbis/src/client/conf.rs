@@ -1,4 +1,5 @@
 use crate::common::conf::CommonConf;
+use crate::solicit::frame::SettingsFrame;
 use std::time::Duration;
 
 /// Client configuration.
@@ -10,6 +11,42 @@ pub struct ClientConf {
     pub thread_name: Option<String>,
     /// Connect timeout.
     pub connect_timeout: Option<Duration>,
+    /// Advertise `SETTINGS_ENABLE_PUSH` to the server. Disabled by default.
+    pub enable_push: bool,
+    /// Advertised as `SETTINGS_MAX_HEADER_LIST_SIZE`; streams with larger
+    /// response headers are reset.
+    pub max_response_header_list_size: Option<u32>,
+    /// Maximum number of fields (including pseudo headers) in response
+    /// headers; streams with more fields are reset. Fields past the limit
+    /// are dropped while decoding. Not advertised to the server.
+    pub max_response_header_count: Option<usize>,
+    /// How many times a request is replayed on a fresh connection when it
+    /// fails before response headers with `REFUSED_STREAM`, `GOAWAY`
+    /// or connection death. Only requests started with complete body
+    /// are replayed. Disabled by default.
+    pub retry_count: u32,
+    /// Methods of requests which may be replayed.
+    /// `GET`, `HEAD`, `PUT` and `DELETE` by default.
+    pub retry_methods: Option<Vec<String>>,
+    /// `SETTINGS` frame sent in the handshake instead of the one built
+    /// from this configuration (for interop and conformance testing).
+    /// Client fails to build if the frame is invalid.
+    pub handshake_settings_override: Option<SettingsFrame>,
+    /// Fail the connection with `Error::AlpnMismatch` instead of
+    /// sending HTTP/2 preface if `h2` was not negotiated with ALPN
+    /// (including plain connections). Disabled by default.
+    pub require_alpn_h2: bool,
+    /// Start plain connections with HTTP/1.1 `Upgrade: h2c` request
+    /// instead of sending HTTP/2 preface immediately. The first request
+    /// made on the connection is the upgrade request, and its response
+    /// is received on stream 1, so the connection is established
+    /// on the first request. That request must not stream body
+    /// or send trailers. Disabled by default.
+    pub enable_h2c_upgrade: bool,
+    /// Accept response headers with upper case names by converting them
+    /// to lower case, instead of resetting the stream. Only for interop
+    /// with non-conformant servers. Disabled by default.
+    pub allow_uppercase_headers: bool,
 
     /// Common client/server conf.
     pub common: CommonConf,
@@ -20,4 +57,19 @@ impl ClientConf {
     pub fn new() -> ClientConf {
         Default::default()
     }
+
+    /// Number of replays allowed for request with given method.
+    pub(crate) fn retry_count_for_method(&self, method: &str) -> u32 {
+        let retry = match &self.retry_methods {
+            Some(methods) => methods.iter().any(|m| m == method),
+            None => DEFAULT_RETRY_METHODS.contains(&method),
+        };
+        if retry {
+            self.retry_count
+        } else {
+            0
+        }
+    }
 }
+
+const DEFAULT_RETRY_METHODS: &[&str] = &["GET", "HEAD", "PUT", "DELETE"];
@@ -1,16 +1,30 @@
 //! Single client connection
 
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
 use std::io;
 use std::result::Result as std_Result;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
 
 use crate::error;
 use crate::error::Error;
 use crate::result;
 use crate::AnySocketAddr;
 
+use crate::common::init_where::InitWhere;
+use crate::common::types::Types;
 use crate::solicit::end_stream::EndStream;
+use crate::solicit::frame::GoawayFrame;
+use crate::solicit::frame::HttpSetting;
+use crate::solicit::frame::HttpSettings;
+use crate::solicit::frame::PushPromiseDecodedFrame;
+use crate::solicit::frame::SettingsFrame;
 use crate::solicit::header::*;
+use crate::solicit::DEFAULT_SETTINGS;
 
 use tls_api::TlsConnector;
 
@@ -18,11 +32,15 @@ use tls_api;
 
 use crate::solicit_async::*;
 
+use crate::client::h2c::client_h2c_upgrade;
+use crate::client::h2c::h2c_upgrade_request;
 use crate::client::req::ClientRequest;
 use crate::client::stream_handler::ClientStreamCreatedHandler;
 use crate::client::types::ClientTypes;
 use crate::client::ClientInterface;
 use crate::client_died_error_holder::ConnDiedType;
+use crate::client_died_error_holder::SomethingDiedErrorHolder;
+use crate::common::conn::handshake_settings_frame;
 use crate::common::conn::Conn;
 use crate::common::conn::ConnStateSnapshot;
 use crate::common::conn::SideSpecific;
@@ -49,14 +67,29 @@ use crate::ClientTlsOption;
 use crate::ErrorCode;
 use bytes::Bytes;
 use futures::channel::oneshot;
+use futures::FutureExt;
 use futures::TryFutureExt;
 use std::pin::Pin;
 
 use crate::client::resp::ClientResponse;
 use std::future::Future;
 use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
 
-pub struct ClientStreamData {}
+pub struct ClientStreamData {
+    request_timer: Option<RequestTimer>,
+    /// Response to `HEAD` has `content-length` of the body which is not sent
+    head_request: bool,
+}
+
+/// Task firing request timeout, aborted when stream is dropped.
+struct RequestTimer(JoinHandle<()>);
+
+impl Drop for RequestTimer {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
 
 impl HttpStreamDataSpecific for ClientStreamData {}
 
@@ -66,14 +99,111 @@ impl HttpStreamData for ClientStream {
     type Types = ClientTypes;
 }
 
+/// Connection state shared with `Client`.
+pub(crate) struct ClientConnShared {
+    /// Connection loop is running and no `GOAWAY` sent or received
+    pub alive: AtomicBool,
+    /// Settings from the first `SETTINGS` frame received from the server
+    pub initial_peer_settings: Mutex<Option<HttpSettings>>,
+    /// Unknown settings from the same frame as `initial_peer_settings`
+    pub initial_peer_unknown_settings: Mutex<Vec<(u16, u32)>>,
+}
+
+impl ClientConnShared {
+    pub fn new() -> ClientConnShared {
+        ClientConnShared {
+            alive: AtomicBool::new(true),
+            initial_peer_settings: Mutex::new(None),
+            initial_peer_unknown_settings: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+fn client_handshake_settings(
+    enable_push: bool,
+    max_response_header_list_size: Option<u32>,
+) -> Vec<HttpSetting> {
+    let mut settings = vec![HttpSetting::EnablePush(enable_push)];
+    if let Some(max_header_list_size) = max_response_header_list_size {
+        settings.push(HttpSetting::MaxHeaderListSize(max_header_list_size));
+    }
+    settings
+}
+
 pub struct ClientConnData {
-    _callbacks: Box<dyn ClientConnCallbacks>,
+    callbacks: Box<dyn ClientConnCallbacks>,
+    shared: Arc<ClientConnShared>,
+    enable_push: bool,
+    max_response_header_list_size: Option<u32>,
+    max_response_header_count: Option<usize>,
+    handshake_settings_override: Option<SettingsFrame>,
+    require_alpn_h2: bool,
+    allow_uppercase_headers: bool,
+    /// Request sent over HTTP/1.1 as h2c upgrade request,
+    /// its response is expected on stream 1
+    h2c_upgrade_request: Option<oneshot::Receiver<ClientStartRequestMessage>>,
+    /// Requests waiting for peer `MAX_CONCURRENT_STREAMS` limit, in FIFO order
+    pending_starts: VecDeque<ClientStartRequestMessage>,
 }
 
-impl SideSpecific for ClientConnData {}
+impl SideSpecific for ClientConnData {
+    fn goaway(&self) {
+        self.shared.alive.store(false, Ordering::SeqCst);
+    }
+
+    fn goaway_received(&self, frame: &GoawayFrame) {
+        self.callbacks.goaway(
+            frame.last_stream_id(),
+            frame.raw_error_code(),
+            frame.debug_data().clone(),
+        );
+    }
+
+    fn peer_settings(&self, settings: &HttpSettings, unknown: &BTreeMap<u16, u32>) {
+        let mut initial_peer_settings = self.shared.initial_peer_settings.lock().unwrap();
+        if initial_peer_settings.is_none() {
+            *initial_peer_settings = Some(*settings);
+            *self.shared.initial_peer_unknown_settings.lock().unwrap() =
+                unknown.iter().map(|(&id, &val)| (id, val)).collect();
+        }
+    }
+
+    fn handshake_settings_override(&self) -> Option<SettingsFrame> {
+        self.handshake_settings_override.clone()
+    }
+
+    fn require_alpn_h2(&self) -> bool {
+        self.require_alpn_h2
+    }
+
+    fn allow_uppercase_headers(&self) -> bool {
+        self.allow_uppercase_headers
+    }
+
+    fn max_header_count(&self) -> Option<usize> {
+        self.max_response_header_count
+    }
+
+    fn handshake_settings(&self) -> Vec<HttpSetting> {
+        client_handshake_settings(self.enable_push, self.max_response_header_list_size)
+    }
+
+    fn conn_died(&mut self, error: Arc<Error>) {
+        for start in self.pending_starts.drain(..) {
+            start
+                .start
+                .stream_handler
+                .error(Error::ClientDied(error.clone()));
+        }
+    }
+}
 
 pub struct ClientConn {
     write_tx: DeathAwareSender<ClientToWriteMessage>,
+    /// The first request is sent as h2c upgrade request
+    h2c_first_request_tx: Mutex<Option<oneshot::Sender<ClientStartRequestMessage>>>,
+    shared: Arc<ClientConnShared>,
+    conn_died_error_holder: SomethingDiedErrorHolder<ConnDiedType>,
 }
 
 unsafe impl Sync for ClientConn {}
@@ -83,6 +213,8 @@ pub(crate) struct StartRequestMessage {
     pub body: Option<Bytes>,
     pub trailers: Option<Headers>,
     pub end_stream: bool,
+    /// Reset the stream if response is not received within this time
+    pub timeout: Option<Duration>,
     pub stream_handler: Box<dyn ClientStreamCreatedHandler>,
 }
 
@@ -94,6 +226,7 @@ pub struct ClientStartRequestMessage {
 pub(crate) enum ClientToWriteMessage {
     Start(ClientStartRequestMessage),
     WaitForHandshake(oneshot::Sender<result::Result<()>>),
+    RequestTimeout(StreamId),
     Common(CommonToWriteMessage),
 }
 
@@ -108,6 +241,7 @@ impl ErrorAwareDrop for ClientToWriteMessage {
             ClientToWriteMessage::WaitForHandshake(_) => {
                 // TODO: error
             }
+            ClientToWriteMessage::RequestTimeout(_) => {}
             ClientToWriteMessage::Common(_) => {
                 // TODO: error
             }
@@ -136,15 +270,70 @@ where
                 drop(tx.send(Ok(())));
                 Ok(())
             }
+            ClientToWriteMessage::RequestTimeout(stream_id) => {
+                self.process_request_timeout(stream_id)
+            }
         }
     }
+
+    fn process_pending(&mut self) -> result::Result<()> {
+        // called on every loop iteration
+        if self.specific.pending_starts.is_empty() {
+            return Ok(());
+        }
+        while self.can_start_stream() {
+            match self.specific.pending_starts.pop_front() {
+                Some(start) => self.start_stream(start)?,
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn process_handshake_done(&mut self) -> result::Result<()> {
+        if let Some(mut rx) = self.specific.h2c_upgrade_request.take() {
+            // Sent before connect future completed
+            let start = rx.try_recv().ok().flatten().expect("h2c upgrade request");
+            self.start_stream_impl(start, true)?;
+        }
+        Ok(())
+    }
 }
 
 impl<I> Conn<ClientTypes, I>
 where
     I: SocketStream,
 {
+    /// Peer `MAX_CONCURRENT_STREAMS` allows opening one more stream.
+    fn can_start_stream(&self) -> bool {
+        self.goaway_received.is_none()
+            && self.streams.local_streams_count()
+                < self.peer_settings.max_concurrent_streams as usize
+    }
+
     fn process_start(&mut self, start: ClientStartRequestMessage) -> result::Result<()> {
+        if !self.specific.pending_starts.is_empty() || !self.can_start_stream() {
+            debug!(
+                "peer stream limit {} reached, queueing request",
+                self.peer_settings.max_concurrent_streams
+            );
+            self.specific.pending_starts.push_back(start);
+            return Ok(());
+        }
+        self.start_stream(start)
+    }
+
+    fn start_stream(&mut self, start: ClientStartRequestMessage) -> result::Result<()> {
+        self.start_stream_impl(start, false)
+    }
+
+    /// Open a stream for the request. After h2c upgrade stream 1
+    /// is half-closed (local), because the request was sent over HTTP/1.1.
+    fn start_stream_impl(
+        &mut self,
+        start: ClientStartRequestMessage,
+        sent_over_http1: bool,
+    ) -> result::Result<()> {
         let ClientStartRequestMessage {
             start:
                 StartRequestMessage {
@@ -152,6 +341,7 @@ where
                     body,
                     trailers,
                     end_stream,
+                    timeout,
                     stream_handler,
                 },
             write_tx,
@@ -164,7 +354,10 @@ where
                 stream_id,
                 None,
                 InMessageStage::Initial,
-                ClientStreamData {},
+                ClientStreamData {
+                    request_timer: None,
+                    head_request: headers.get_opt(":method") == Some("HEAD"),
+                },
             );
 
             let in_window_size = self
@@ -176,7 +369,7 @@ where
                 .size() as u32;
 
             let req = ClientRequest {
-                common: if end_stream {
+                common: if end_stream || sent_over_http1 {
                     CommonSender::new_done(stream_id)
                 } else {
                     CommonSender::new(stream_id, write_tx, out_window, true)
@@ -188,6 +381,7 @@ where
             let resp = ClientResponse {
                 stream_handler: &mut handler,
                 in_window_size,
+                window_tuner: self.new_stream_window_tuner(),
                 stream_id,
                 to_write_tx: &self.to_write_tx,
             };
@@ -205,15 +399,32 @@ where
                     let mut stream = self.streams.get_mut(stream_id).unwrap();
                     stream.stream().peer_tx = handler;
 
-                    stream.push_back(DataOrHeaders::Headers(headers));
-                    if let Some(body) = body {
-                        stream.push_back(DataOrHeaders::Data(body));
-                    }
-                    if let Some(trailers) = trailers {
-                        stream.push_back(DataOrHeaders::Headers(trailers));
+                    if sent_over_http1 {
+                        stream.close_local();
+                    } else {
+                        stream.push_back(DataOrHeaders::Headers(headers));
+                        if let Some(body) = body {
+                            stream.push_back(DataOrHeaders::Data(body));
+                        }
+                        if let Some(trailers) = trailers {
+                            stream.push_back(DataOrHeaders::Headers(trailers));
+                        }
+                        if end_stream {
+                            stream.close_outgoing(ErrorCode::NoError);
+                        }
                     }
-                    if end_stream {
-                        stream.close_outgoing(ErrorCode::NoError);
+
+                    if let Some(timeout) = timeout {
+                        let to_write_tx = self.to_write_tx.clone();
+                        let timer =
+                            self.loop_handle.spawn(async move {
+                                tokio::time::sleep(timeout).await;
+                                // ignore error, connection may be already dead
+                                drop(to_write_tx.unbounded_send(
+                                    ClientToWriteMessage::RequestTimeout(stream_id),
+                                ));
+                            });
+                        stream.stream().specific.request_timer = Some(RequestTimer(timer));
                     }
                 }
             };
@@ -223,11 +434,31 @@ where
         self.buffer_outg_conn()?;
         Ok(())
     }
+
+    fn process_request_timeout(&mut self, stream_id: StreamId) -> result::Result<()> {
+        let mut stream = match self.streams.get_mut(stream_id) {
+            Some(stream) => stream,
+            None => return Ok(()),
+        };
+
+        // Response is complete, timer is no longer needed
+        if stream.stream().state.is_closed_remote() {
+            stream.stream().specific.request_timer = None;
+            return Ok(());
+        }
+
+        warn!("request timeout, resetting stream {}", stream_id);
+        if let Some(response_handler) = stream.stream().peer_tx.take() {
+            // it is OK to ignore error: handler may be already dead
+            drop(response_handler.error(Error::RequestTimeout));
+        }
+        self.send_rst_stream(stream_id, ErrorCode::Cancel)
+    }
 }
 
 pub trait ClientConnCallbacks: Send + 'static {
     // called at most once
-    fn goaway(&self, stream_id: StreamId, raw_error_code: u32);
+    fn goaway(&self, stream_id: StreamId, raw_error_code: u32, debug_data: Bytes);
 }
 
 impl ClientConn {
@@ -236,25 +467,66 @@ impl ClientConn {
         connect: impl Future<Output = crate::Result<I>> + Send + 'static,
         peer_addr: AnySocketAddr,
         conf: ClientConf,
+        h2c_first_request_tx: Option<oneshot::Sender<ClientStartRequestMessage>>,
+        h2c_upgrade_request: Option<oneshot::Receiver<ClientStartRequestMessage>>,
         callbacks: C,
     ) -> Self
     where
         I: SocketStream,
         C: ClientConnCallbacks,
     {
+        let shared = Arc::new(ClientConnShared::new());
+        let conn_died_error_holder = SomethingDiedErrorHolder::new();
+
+        // Push is allowed according to what is actually advertised
+        let enable_push = match conf.handshake_settings_override {
+            Some(ref frame) => {
+                let mut settings = DEFAULT_SETTINGS;
+                settings.apply_from_frame(frame);
+                settings.enable_push
+            }
+            None => conf.enable_push,
+        };
+
         let (future, write_tx) = Conn::<ClientTypes, _>::new(
             lh.clone(),
             ClientConnData {
-                _callbacks: Box::new(callbacks),
+                callbacks: Box::new(callbacks),
+                shared: shared.clone(),
+                enable_push,
+                max_response_header_list_size: conf.max_response_header_list_size,
+                max_response_header_count: conf.max_response_header_count,
+                handshake_settings_override: conf.handshake_settings_override,
+                require_alpn_h2: conf.require_alpn_h2,
+                allow_uppercase_headers: conf.allow_uppercase_headers,
+                h2c_upgrade_request,
+                pending_starts: VecDeque::new(),
             },
             conf.common,
             connect,
             peer_addr,
+            conn_died_error_holder.clone(),
         );
 
-        lh.spawn(future);
+        let shared_copy = shared.clone();
+        let conn_died_error_holder_copy = conn_died_error_holder.clone();
+        lh.spawn(async move {
+            future.await;
+            shared_copy.alive.store(false, Ordering::SeqCst);
+            // Wake `join` even if connection did not record a reason
+            conn_died_error_holder_copy.set_once(Error::DeathReasonUnknown);
+        });
+
+        ClientConn {
+            write_tx,
+            h2c_first_request_tx: Mutex::new(h2c_first_request_tx),
+            shared,
+            conn_died_error_holder,
+        }
+    }
 
-        ClientConn { write_tx }
+    pub(crate) fn shared(&self) -> Arc<ClientConnShared> {
+        self.shared.clone()
     }
 
     pub fn spawn<H, C>(
@@ -290,9 +562,29 @@ impl ClientConn {
         let no_delay = conf.no_delay.unwrap_or(true);
         let connect = addr.connect_with_timeout(&lh, conf.connect_timeout);
 
+        // The first request goes from `ClientConn` to connect future,
+        // and after upgrade from connect future to the connection
+        let (h2c_first_request_tx, h2c_upgrade, h2c_upgraded_rx) = if conf.enable_h2c_upgrade {
+            let (first_request_tx, first_request_rx) = oneshot::channel();
+            let (upgraded_tx, upgraded_rx) = oneshot::channel();
+            // Upgrade request advertises the same settings as HTTP/2 preface
+            let settings = handshake_settings_frame(
+                conf.handshake_settings_override.clone(),
+                client_handshake_settings(conf.enable_push, conf.max_response_header_list_size),
+                &conf.common,
+            );
+            (
+                Some(first_request_tx),
+                Some((settings, first_request_rx, upgraded_tx)),
+                Some(upgraded_rx),
+            )
+        } else {
+            (None, None, None)
+        };
+
         let addr_copy = addr_struct.clone();
         let connect = async move {
-            let socket = connect.await?;
+            let mut socket = connect.await?;
 
             info!("connected to {}", addr_copy);
 
@@ -300,10 +592,40 @@ impl ClientConn {
                 socket.set_tcp_nodelay(no_delay)?;
             }
 
+            if let Some((settings, first_request_rx, upgraded_tx)) = h2c_upgrade {
+                let start: ClientStartRequestMessage =
+                    first_request_rx.await.map_err(|_| Error::CallerDied)?;
+                let upgrade = match h2c_upgrade_request(&start.start, settings) {
+                    Ok(request) => client_h2c_upgrade(&mut socket, request).await,
+                    Err(e) => Err(e),
+                };
+                match upgrade {
+                    Ok(()) => {
+                        // Connection is alive while connect future is polled
+                        drop(upgraded_tx.send(start));
+                    }
+                    Err(e) => {
+                        // Request fails with connection death reason
+                        // when the connection drops its queue
+                        let write_tx = start.write_tx.clone();
+                        drop(write_tx.unbounded_send(ClientToWriteMessage::Start(start)));
+                        return Err(e);
+                    }
+                }
+            }
+
             Ok(socket)
         };
 
-        ClientConn::spawn_connected(lh, connect, addr_struct, conf, callbacks)
+        ClientConn::spawn_connected(
+            lh,
+            connect,
+            addr_struct,
+            conf,
+            h2c_first_request_tx,
+            h2c_upgraded_rx,
+            callbacks,
+        )
     }
 
     pub fn spawn_tls<H, C>(
@@ -340,18 +662,26 @@ impl ClientConn {
                 })
         };
 
-        ClientConn::spawn_connected(lh, tls_conn, addr_struct, conf, callbacks)
+        ClientConn::spawn_connected(lh, tls_conn, addr_struct, conf, None, None, callbacks)
     }
 
     pub(crate) fn start_request_with_resp_sender(
         &self,
         start: StartRequestMessage,
     ) -> Result<(), (StartRequestMessage, error::Error)> {
-        let client_start = ClientStartRequestMessage {
+        let mut client_start = ClientStartRequestMessage {
             start,
             write_tx: self.write_tx.clone(),
         };
 
+        if let Some(tx) = self.h2c_first_request_tx.lock().unwrap().take() {
+            match tx.send(client_start) {
+                Ok(()) => return Ok(()),
+                // Connection failed before the first request
+                Err(start) => client_start = start,
+            }
+        }
+
         self.write_tx
             .unbounded_send_recover(ClientToWriteMessage::Start(client_start))
             .map_err(|(sent_message, e)| match sent_message {
@@ -360,6 +690,40 @@ impl ClientConn {
             })
     }
 
+    pub fn flush_with_resp_sender(&self, tx: oneshot::Sender<()>) {
+        let message = ClientToWriteMessage::Common(CommonToWriteMessage::Flush(tx));
+        // ignore error
+        drop(self.write_tx.unbounded_send(message));
+    }
+
+    pub fn send_goaway_with_debug(&self, error_code: ErrorCode, debug_data: Bytes) {
+        let message =
+            ClientToWriteMessage::Common(CommonToWriteMessage::Goaway(error_code, debug_data));
+        // ignore error
+        drop(self.write_tx.unbounded_send(message));
+    }
+
+    pub fn ping_with_resp_sender(
+        &self,
+        opaque_data: u64,
+        tx: oneshot::Sender<result::Result<Duration>>,
+    ) {
+        let message = ClientToWriteMessage::Common(CommonToWriteMessage::Ping(opaque_data, tx));
+        // ignore error
+        drop(self.write_tx.unbounded_send(message));
+    }
+
+    pub fn set_tcp_nodelay_with_resp_sender(
+        &self,
+        no_delay: bool,
+        tx: oneshot::Sender<result::Result<()>>,
+    ) {
+        let message =
+            ClientToWriteMessage::Common(CommonToWriteMessage::SetTcpNodelay(no_delay, tx));
+        // ignore error
+        drop(self.write_tx.unbounded_send(message));
+    }
+
     pub fn dump_state_with_resp_sender(&self, tx: oneshot::Sender<ConnStateSnapshot>) {
         let message = ClientToWriteMessage::Common(CommonToWriteMessage::DumpState(tx));
         // ignore error
@@ -379,6 +743,17 @@ impl ClientConn {
         Box::pin(rx)
     }
 
+    /// Create a future which resolves when the connection is closed.
+    ///
+    /// Resolves with `Ok(())` if the connection completed without error
+    /// (e. g. after `GOAWAY`), or with `Error::ConnDied` holding the death reason.
+    pub fn join(&self) -> HttpFutureSend<()> {
+        Box::pin(self.conn_died_error_holder.died().map(|e| match *e {
+            Error::ClientCompletedWithoutError => Ok(()),
+            _ => Err(Error::ConnDied(e)),
+        }))
+    }
+
     pub fn wait_for_connect_with_resp_sender(
         &self,
         tx: oneshot::Sender<result::Result<()>>,
@@ -406,6 +781,7 @@ impl ClientInterface for ClientConn {
             body,
             trailers,
             end_stream,
+            timeout: None,
             stream_handler,
         };
 
@@ -459,6 +835,18 @@ where
             return Ok(None);
         }
 
+        if let Some(max_header_list_size) = self.specific.max_response_header_list_size {
+            let header_list_size = headers.header_list_size();
+            if header_list_size > max_header_list_size as usize {
+                warn!(
+                    "response header list size {} exceeds limit {}",
+                    header_list_size, max_header_list_size
+                );
+                self.send_rst_stream(stream_id, ErrorCode::ProtocolError)?;
+                return Ok(None);
+            }
+        }
+
         let status_1xx = match headers_place {
             HeadersPlace::Initial => {
                 let status = headers.status();
@@ -481,8 +869,35 @@ where
             }
         };
 
+        let head_request = self
+            .streams
+            .get_mut(stream_id)
+            .unwrap()
+            .stream()
+            .specific
+            .head_request;
+        // Responses to `HEAD` and 204 or 304 responses may have
+        // non-zero `content-length` without body (RFC 7540 8.1.2.6)
+        let body_expected = !head_request
+            && headers_place == HeadersPlace::Initial
+            && !status_1xx
+            && headers.status() != 204
+            && headers.status() != 304;
+        let in_rem_content_length = match body_expected {
+            true => headers.content_length(),
+            false => None,
+        };
+        if end_stream == EndStream::Yes && in_rem_content_length.unwrap_or(0) != 0 {
+            warn!(
+                "response without body has non-zero content-length: {}",
+                stream_id
+            );
+            self.send_rst_stream(stream_id, ErrorCode::ProtocolError)?;
+            return Ok(None);
+        }
+
         let mut stream = self.streams.get_mut(stream_id).unwrap();
-        if let Some(in_rem_content_length) = headers.content_length() {
+        if let Some(in_rem_content_length) = in_rem_content_length {
             stream.stream().in_rem_content_length = Some(in_rem_content_length);
         }
 
@@ -492,24 +907,114 @@ where
             (HeadersPlace::Trailing, _) => InMessageStage::AfterTrailingHeaders,
         };
 
-        // Ignore 1xx headers
-        if !status_1xx {
-            if let Some(ref mut response_handler) = stream.stream().peer_tx {
-                // TODO: reset stream on error
-                drop(match headers_place {
-                    HeadersPlace::Initial => response_handler
-                        .0
-                        .headers(headers, end_stream == EndStream::Yes),
-                    HeadersPlace::Trailing => {
-                        assert_eq!(EndStream::Yes, end_stream);
-                        response_handler.trailers(headers)
-                    }
-                });
-            } else {
-                // TODO: reset stream
-            }
+        if let Some(ref mut response_handler) = stream.stream().peer_tx {
+            // TODO: reset stream on error
+            drop(match headers_place {
+                HeadersPlace::Initial if status_1xx => {
+                    response_handler.0.informational_headers(headers)
+                }
+                HeadersPlace::Initial => response_handler
+                    .0
+                    .headers(headers, end_stream == EndStream::Yes),
+                HeadersPlace::Trailing => {
+                    assert_eq!(EndStream::Yes, end_stream);
+                    response_handler.trailers(headers)
+                }
+            });
+        } else {
+            // TODO: reset stream
         }
 
         Ok(Some(stream))
     }
+
+    fn process_push_promise(&mut self, frame: PushPromiseDecodedFrame) -> result::Result<()> {
+        let PushPromiseDecodedFrame {
+            stream_id,
+            promised_stream_id,
+            headers,
+            ..
+        } = frame;
+
+        // A client cannot receive PUSH_PROMISE when push is disabled (6.6)
+        if !self.specific.enable_push {
+            warn!("PUSH_PROMISE received while push is disabled");
+            return self.send_goaway(ErrorCode::ProtocolError);
+        }
+
+        if ClientTypes::init_where(promised_stream_id) == InitWhere::Locally
+            || promised_stream_id <= self.last_peer_stream_id
+        {
+            warn!("PUSH_PROMISE with wrong stream id {}", promised_stream_id);
+            return self.send_goaway(ErrorCode::ProtocolError);
+        }
+
+        self.last_peer_stream_id = promised_stream_id;
+
+        if let Err(e) = headers.validate(RequestOrResponse::Request, HeadersPlace::Initial) {
+            warn!("invalid push promise headers: {:?}: {:?}", e, headers);
+            return self.send_rst_stream(promised_stream_id, ErrorCode::ProtocolError);
+        }
+
+        let response_handler = match self.streams.get_mut(stream_id) {
+            Some(mut stream) => stream.stream().peer_tx.take(),
+            None => {
+                // Stream could be already reset by us
+                debug!("PUSH_PROMISE on closed stream {}", stream_id);
+                return self.send_rst_stream(promised_stream_id, ErrorCode::Cancel);
+            }
+        };
+
+        let mut response_handler = match response_handler {
+            Some(response_handler) => response_handler,
+            None => return self.send_rst_stream(promised_stream_id, ErrorCode::Cancel),
+        };
+
+        let (mut stream, _out_window) = self.new_stream_data(
+            promised_stream_id,
+            None,
+            InMessageStage::Initial,
+            ClientStreamData {
+                request_timer: None,
+                head_request: headers.get_opt(":method") == Some("HEAD"),
+            },
+        );
+
+        // We never send anything on pushed stream
+        stream.stream().close_local();
+
+        let in_window_size = stream.stream().in_window_size.size() as u32;
+
+        let mut handler = None;
+        let resp = ClientResponse {
+            stream_handler: &mut handler,
+            in_window_size,
+            window_tuner: self.new_stream_window_tuner(),
+            stream_id: promised_stream_id,
+            to_write_tx: &self.to_write_tx,
+        };
+
+        let push_result = response_handler
+            .0
+            .push_promise(promised_stream_id, headers, resp);
+
+        if let Some(mut stream) = self.streams.get_mut(stream_id) {
+            stream.stream().peer_tx = Some(response_handler);
+        }
+
+        match push_result {
+            Ok(()) => {
+                self.streams
+                    .get_mut(promised_stream_id)
+                    .unwrap()
+                    .stream()
+                    .peer_tx = handler;
+                Ok(())
+            }
+            Err(e) => {
+                debug!("push promise {} refused: {:?}", promised_stream_id, e);
+                self.send_rst_stream(promised_stream_id, ErrorCode::Cancel)
+            }
+        }
+    }
 }
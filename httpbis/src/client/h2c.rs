@@ -0,0 +1,186 @@
+//! HTTP/2 over cleartext TCP started with HTTP/1.1 `Upgrade` (RFC 7540 section 3.2)
+
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+
+use crate::client::conn::StartRequestMessage;
+use crate::codec::write_buffer::WriteBuffer;
+use crate::common::limits::FRAME_HEADER_LEN;
+use crate::error;
+use crate::net::socket::SocketStream;
+use crate::result;
+use crate::solicit::frame::SettingsFrame;
+use crate::solicit::header::Headers;
+use bytes::Bytes;
+
+/// Limit of HTTP/1 response head to upgrade request
+const MAX_RESPONSE_HEAD_LEN: usize = 16 * 1024;
+
+/// Send upgrade request built with `h2c_upgrade_request`
+/// and wait for `101 Switching Protocols`.
+///
+/// HTTP/2 preface is sent after this function returns as usual.
+pub(crate) async fn client_h2c_upgrade<I: SocketStream>(
+    conn: &mut I,
+    request: Vec<u8>,
+) -> result::Result<()> {
+    debug!("send h2c upgrade request");
+
+    conn.write_all(&request).await?;
+
+    // Read byte by byte, because server sends HTTP/2 frames right after the head
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() == MAX_RESPONSE_HEAD_LEN {
+            return Err(error::Error::H2cUpgradeFailed(format!(
+                "response head is longer than {} bytes",
+                MAX_RESPONSE_HEAD_LEN
+            )));
+        }
+        head.push(conn.read_u8().await?);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let status_line = head.lines().next().unwrap_or_default();
+    match status_line.split(' ').nth(1) {
+        Some("101") => {
+            debug!("h2c upgrade done: {}", status_line);
+            Ok(())
+        }
+        _ => {
+            warn!("h2c upgrade rejected: {}", status_line);
+            Err(error::Error::H2cUpgradeFailed(status_line.to_owned()))
+        }
+    }
+}
+
+/// HTTP/1.1 request head and body of the first request with `Upgrade: h2c`.
+///
+/// The request must be complete: its response is received on stream 1,
+/// and nothing more can be sent on it (RFC 7540 section 3.2).
+pub(crate) fn h2c_upgrade_request(
+    start: &StartRequestMessage,
+    settings: SettingsFrame,
+) -> result::Result<Vec<u8>> {
+    if !start.end_stream || start.trailers.is_some() {
+        return Err(error::Error::H2cUpgradeFailed(
+            "upgrade request must not stream body or send trailers".to_owned(),
+        ));
+    }
+    h2c_upgrade_request_complete(&start.headers, start.body.as_ref(), settings)
+}
+
+fn h2c_upgrade_request_complete(
+    headers: &Headers,
+    body: Option<&Bytes>,
+    settings: SettingsFrame,
+) -> result::Result<Vec<u8>> {
+    let pseudo_header = |name| {
+        headers.get_opt(name).ok_or_else(|| {
+            error::Error::H2cUpgradeFailed(format!("upgrade request has no {}", name))
+        })
+    };
+    let method = pseudo_header(":method")?;
+    let path = pseudo_header(":path")?;
+    let authority = pseudo_header(":authority")?;
+
+    let mut buf = WriteBuffer::new();
+    buf.write_frame(settings);
+    let settings: Vec<u8> = buf.into();
+
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Connection: Upgrade, HTTP2-Settings\r\n\
+         Upgrade: h2c\r\n\
+         HTTP2-Settings: {}\r\n",
+        method,
+        path,
+        authority,
+        base64url_encode(&settings[FRAME_HEADER_LEN..]),
+    )
+    .into_bytes();
+    for header in headers.iter() {
+        if header.is_preudo_header() || header.name() == "content-length" {
+            continue;
+        }
+        request.extend_from_slice(header.name().as_bytes());
+        request.extend_from_slice(b": ");
+        request.extend_from_slice(header.value());
+        request.extend_from_slice(b"\r\n");
+    }
+    let body = body.map(|b| &b[..]).unwrap_or_default();
+    if !body.is_empty() {
+        request.extend_from_slice(format!("Content-Length: {}\r\n", body.len()).as_bytes());
+    }
+    request.extend_from_slice(b"\r\n");
+    request.extend_from_slice(body);
+    Ok(request)
+}
+
+/// Base64 with URL and filename safe alphabet and without padding,
+/// as required for `HTTP2-Settings` header.
+fn base64url_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+    let mut r = String::with_capacity((data.len() * 4).div_ceil(3));
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).cloned().unwrap_or(0),
+            chunk.get(2).cloned().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..chunk.len() + 1 {
+            r.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    r
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_base64url_encode() {
+        assert_eq!("", base64url_encode(b""));
+        assert_eq!("Zg", base64url_encode(b"f"));
+        assert_eq!("Zm8", base64url_encode(b"fo"));
+        assert_eq!("Zm9v", base64url_encode(b"foo"));
+        assert_eq!("Zm9vYmFy", base64url_encode(b"foobar"));
+        assert_eq!("-_8", base64url_encode(&[0xfb, 0xff]));
+    }
+
+    #[test]
+    fn test_h2c_upgrade_request() {
+        let mut headers = Headers::new_post("/upload");
+        headers.add(":authority", "example.com");
+        headers.add(":scheme", "http");
+        headers.add("content-type", "text/plain");
+        let request = h2c_upgrade_request_complete(
+            &headers,
+            Some(&Bytes::from_static(b"hello")),
+            SettingsFrame::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            &b"POST /upload HTTP/1.1\r\n\
+               Host: example.com\r\n\
+               Connection: Upgrade, HTTP2-Settings\r\n\
+               Upgrade: h2c\r\n\
+               HTTP2-Settings: \r\n\
+               content-type: text/plain\r\n\
+               Content-Length: 5\r\n\
+               \r\n\
+               hello"[..],
+            &request[..]
+        );
+
+        let headers = Headers::new_get("/");
+        assert!(matches!(
+            h2c_upgrade_request_complete(&headers, None, SettingsFrame::new()),
+            Err(error::Error::H2cUpgradeFailed(_))
+        ));
+    }
+}
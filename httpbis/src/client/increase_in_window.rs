@@ -2,17 +2,27 @@ use crate::client::types::ClientTypes;
 use crate::common::increase_in_window::IncreaseInWindow;
 use crate::result;
 
+/// Handle to open response stream receive window.
+///
+/// Passed to `ClientResponse::register_stream_handler` callback.
+/// Peer can send only as much data as the window allows, so
+/// a handler which defers `increase_window` until the application
+/// consumed the data applies backpressure to the peer.
 pub struct ClientIncreaseInWindow(pub(crate) IncreaseInWindow<ClientTypes>);
 
 impl ClientIncreaseInWindow {
+    /// Window size known to this handle, valid only if
+    /// `data_frame_processed` is called for each received `DATA` frame.
     pub fn in_window_size(&self) -> u32 {
         self.0.in_window_size()
     }
 
+    /// Account received `DATA` frame payload in the window size.
     pub fn data_frame_processed(&mut self, size: u32) {
         self.0.data_frame_processed(size)
     }
 
+    /// Send `WINDOW_UPDATE` allowing peer to send `inc` more bytes.
     pub fn increase_window(&mut self, inc: u32) -> result::Result<()> {
         self.0.increase_window(inc)
     }
@@ -1,5 +1,6 @@
 pub(crate) mod conf;
 pub(crate) mod conn;
+pub(crate) mod h2c;
 pub(crate) mod increase_in_window;
 pub(crate) mod req;
 pub(crate) mod resp;
@@ -9,8 +10,12 @@ pub(crate) mod types;
 
 use std::net::SocketAddr;
 use std::net::ToSocketAddrs;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::thread;
+use std::time::Duration;
+use std::time::Instant;
 
 use bytes::Bytes;
 
@@ -30,6 +35,8 @@ use crate::error;
 use crate::error::Error;
 use crate::result::Result;
 
+use crate::solicit::frame::GoawayFrame;
+use crate::solicit::frame::HttpSettings;
 use crate::solicit::header::*;
 use crate::solicit::HttpScheme;
 
@@ -37,10 +44,14 @@ use crate::solicit_async::*;
 
 use crate::net::addr::AnySocketAddr;
 use crate::net::connect::ToClientStream;
+use crate::net::happy_eyeballs::HappyEyeballsConnect;
+use crate::net::provided::ProvidedConnect;
+use crate::net::provided::ProvidedIo;
 
 use crate::client::conf::ClientConf;
 use crate::client::conn::ClientConn;
 use crate::client::conn::ClientConnCallbacks;
+use crate::client::conn::ClientConnShared;
 use crate::client::conn::StartRequestMessage;
 
 use crate::client::req::ClientRequest;
@@ -60,8 +71,12 @@ use crate::common::death_aware_channel::ErrorAwareDrop;
 use crate::net::unix::SocketAddrUnix;
 use crate::result;
 use crate::solicit::stream_id::StreamId;
+use crate::DataOrHeaders;
+use crate::ErrorCode;
 use crate::Response;
 use std::fmt;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
 use tokio::runtime::Handle;
 use tokio::runtime::Runtime;
 
@@ -131,13 +146,24 @@ impl<C: TlsConnector> ClientBuilder<C> {
         Ok(())
     }
 
-    pub fn build(self) -> Result<Client> {
-        let client_died_error_holder = SomethingDiedErrorHolder::new();
+    pub fn build(mut self) -> Result<Client> {
+        let addr = self.addr.take().expect("addr is not specified");
+        let http_scheme = self.tls.http_scheme();
+        self.build_with_stream(addr, http_scheme)
+    }
 
-        let addr = self.addr.expect("addr is not specified");
-        let addr_copy = addr.clone();
+    fn build_with_stream<T>(self, connect: T, http_scheme: HttpScheme) -> Result<Client>
+    where
+        T: ToClientStream + Send + Clone + 'static,
+    {
+        self.conf.common.validate()?;
+        if let Some(ref frame) = self.conf.handshake_settings_override {
+            frame.validate()?;
+        }
 
-        let http_scheme = self.tls.http_scheme();
+        let client_died_error_holder = SomethingDiedErrorHolder::new();
+
+        let addr = connect.socket_addr();
 
         // Create a channel to receive shutdown signal.
         let (shutdown_signal, shutdown_future) = shutdown_signal();
@@ -148,6 +174,11 @@ impl<C: TlsConnector> ClientBuilder<C> {
 
         let client_died_error_holder_copy = client_died_error_holder.clone();
 
+        let conn_shared = Arc::new(Mutex::new(Arc::new(ClientConnShared::new())));
+        let conn_shared_copy = conn_shared.clone();
+
+        let retry_conf = self.conf.clone();
+
         let join = if let Some(remote) = self.event_loop {
             let tls = self.tls;
             let conf = self.conf;
@@ -157,13 +188,14 @@ impl<C: TlsConnector> ClientBuilder<C> {
                 spawn_client_event_loop(
                     handle,
                     shutdown_future,
-                    addr_copy,
+                    connect,
                     tls,
                     conf,
                     done_tx,
                     controller_tx,
                     controller_rx,
                     client_died_error_holder_copy,
+                    conn_shared_copy,
                 )
             }));
             Completion::Rx(done_rx)
@@ -186,13 +218,14 @@ impl<C: TlsConnector> ClientBuilder<C> {
                     spawn_client_event_loop(
                         lp.handle().clone(),
                         shutdown_future,
-                        addr_copy,
+                        connect,
                         tls,
                         conf,
                         done_tx,
                         controller_tx,
                         controller_rx,
                         client_died_error_holder_copy,
+                        conn_shared_copy,
                     );
 
                     lp.block_on(done_rx).expect("run");
@@ -208,6 +241,8 @@ impl<C: TlsConnector> ClientBuilder<C> {
             shutdown: shutdown_signal,
             client_died_error_holder,
             addr,
+            conn_shared,
+            retry_conf,
         })
     }
 }
@@ -230,6 +265,10 @@ pub struct Client {
     shutdown: ShutdownSignal,
     client_died_error_holder: SomethingDiedErrorHolder<ClientDiedType>,
     addr: AnySocketAddr,
+    // shared state of current connection
+    conn_shared: Arc<Mutex<Arc<ClientConnShared>>>,
+    // used to decide if a failed request is replayed
+    retry_conf: ClientConf,
 }
 
 impl fmt::Debug for Client {
@@ -277,6 +316,55 @@ impl Client {
         client.build()
     }
 
+    /// Create a new client connected to the specified host and port,
+    /// using TLS if `tls` is specified.
+    ///
+    /// Host is resolved once, and each connection is established by trying
+    /// all resolved addresses with Happy Eyeballs (RFC 8305): attempts
+    /// alternate IPv6 and IPv4 addresses and are started 250ms apart,
+    /// the first connection established is used.
+    pub fn new_resolved<C: TlsConnector>(
+        host: &str,
+        port: u16,
+        tls: ClientTlsOption<C>,
+        conf: ClientConf,
+    ) -> Result<Client> {
+        // TODO: sync
+        let addrs: Vec<_> = (host, port).to_socket_addrs()?.collect();
+        if addrs.is_empty() {
+            return Err(Error::AddrResolvedToEmptyList);
+        }
+
+        let mut client = ClientBuilder::<C>::new();
+        client.tls = tls;
+        client.conf = conf;
+        let http_scheme = client.tls.http_scheme();
+        client.build_with_stream(HappyEyeballsConnect::new(addrs), http_scheme)
+    }
+
+    /// Create a client over a connection where the user has already done
+    /// TLS handshake and ALPN negotiation.
+    ///
+    /// The crate's TLS handshake is skipped and the HTTP/2 preface is sent
+    /// right away. `negotiated_alpn` must be `h2`. The connection cannot be
+    /// reestablished, so requests fail after it is closed.
+    pub fn over_tls_io<I>(io: I, negotiated_alpn: &str, conf: ClientConf) -> Result<Client>
+    where
+        I: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        if negotiated_alpn != "h2" {
+            return Err(Error::AlpnMismatch(negotiated_alpn.to_owned()));
+        }
+
+        let mut client = ClientBuilder::new_plain();
+        client.conf = conf;
+        let connect = ProvidedConnect::new(Box::pin(ProvidedIo(
+            io,
+            negotiated_alpn.as_bytes().to_vec(),
+        )));
+        client.build_with_stream(connect, HttpScheme::Https)
+    }
+
     /// Connect to server using plain or TLS protocol depending on `tls` parameter.
     pub fn new_expl<C: TlsConnector>(
         addr: &SocketAddr,
@@ -297,56 +385,205 @@ impl Client {
         trailers: Option<Headers>,
         end_stream: bool,
     ) -> HttpFutureSend<(ClientRequest, Response)> {
-        let (tx, rx) = oneshot::channel();
+        start_request(
+            &self.controller_tx,
+            &self.client_died_error_holder,
+            headers,
+            body,
+            trailers,
+            end_stream,
+            None,
+        )
+    }
 
-        struct Impl {
-            tx: oneshot::Sender<crate::Result<(ClientRequest, Response)>>,
-        }
+    /// Start request with body streamed by the caller.
+    ///
+    /// Body is sent with `ClientRequest::send_data` and finished with
+    /// `ClientRequest::send_trailers` or `ClientRequest::close`.
+    /// Data is queued without limit, so await `ClientRequest::ready`
+    /// before sending next chunk to avoid buffering the whole body.
+    pub fn start_request_streaming(
+        &self,
+        headers: Headers,
+    ) -> HttpFutureSend<(ClientRequest, Response)> {
+        self.start_request(headers, None, None, false)
+    }
 
-        impl ClientStreamCreatedHandler for Impl {
-            fn request_created(
-                self: Box<Self>,
-                req: ClientRequest,
-                resp: ClientResponse,
-            ) -> result::Result<()> {
-                if let Err(_) = self.tx.send(Ok((req, resp.make_stream()))) {
-                    return Err(error::Error::CallerDied);
-                }
+    /// Start request which is reset with `CANCEL` if response
+    /// is not completely received within `timeout`.
+    ///
+    /// The deadline covers the whole response: headers, body and trailers.
+    /// On timeout response fails with `Error::RequestTimeout`.
+    ///
+    /// Request is not replayed, use `start_request_end_stream_with_timeout`
+    /// for that.
+    pub fn start_request_with_timeout(
+        &self,
+        headers: Headers,
+        body: Option<Bytes>,
+        trailers: Option<Headers>,
+        end_stream: bool,
+        timeout: Duration,
+    ) -> HttpFutureSend<(ClientRequest, Response)> {
+        start_request(
+            &self.controller_tx,
+            &self.client_died_error_holder,
+            headers,
+            body,
+            trailers,
+            end_stream,
+            Some(timeout),
+        )
+    }
 
-                Ok(())
-            }
+    /// Start request with complete body.
+    ///
+    /// Request is replayed on failure if allowed by `ClientConf::retry_count`.
+    pub fn start_request_end_stream(
+        &self,
+        headers: Headers,
+        body: Option<Bytes>,
+        trailers: Option<Headers>,
+    ) -> Response {
+        self.start_request_end_stream_impl(headers, body, trailers, None)
+    }
 
-            fn error(self: Box<Self>, error: crate::Error) {
-                let _ = self.tx.send(Err(error));
-            }
-        }
+    /// Start request with complete body which fails with `Error::RequestTimeout`
+    /// if response is not completely received within `timeout`.
+    ///
+    /// Request is replayed on failure if allowed by `ClientConf::retry_count`.
+    /// The deadline covers the whole response including all replays,
+    /// each replay gets the time left.
+    pub fn start_request_end_stream_with_timeout(
+        &self,
+        headers: Headers,
+        body: Option<Bytes>,
+        trailers: Option<Headers>,
+        timeout: Duration,
+    ) -> Response {
+        self.start_request_end_stream_impl(headers, body, trailers, Some(timeout))
+    }
 
-        if let Err(e) =
-            self.start_request_low_level(headers, body, trailers, end_stream, Box::new(Impl { tx }))
-        {
-            return Box::pin(future::err(e));
+    fn start_request_end_stream_impl(
+        &self,
+        headers: Headers,
+        body: Option<Bytes>,
+        trailers: Option<Headers>,
+        timeout: Option<Duration>,
+    ) -> Response {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+        let retry_count = match headers.get_opt(":method") {
+            Some(method) => self.retry_conf.retry_count_for_method(method),
+            None => 0,
+        };
+
+        if retry_count == 0 {
+            return Response::new(
+                start_request(
+                    &self.controller_tx,
+                    &self.client_died_error_holder,
+                    headers,
+                    body,
+                    trailers,
+                    true,
+                    timeout,
+                )
+                .and_then(move |(_sender, response)| response),
+            );
         }
 
-        let client_error = self.client_died_error_holder.clone();
-        let resp_rx = rx.then(move |r| match r {
-            Ok(Ok(r)) => future::ok(r),
-            Ok(Err(e)) => future::err(e),
-            Err(oneshot::Canceled) => future::err(client_error.error()),
-        });
+        // first attempt is started immediately like non-replayable requests
+        let first = start_request(
+            &self.controller_tx,
+            &self.client_died_error_holder,
+            headers.clone(),
+            body.clone(),
+            trailers.clone(),
+            true,
+            timeout,
+        )
+        .and_then(move |(_sender, response)| response);
 
-        Box::pin(resp_rx)
+        let controller_tx = self.controller_tx.clone();
+        let client_died_error_holder = self.client_died_error_holder.clone();
+        Response::new(async move {
+            let mut r = first.await;
+            let mut attempt = 0;
+            loop {
+                match r {
+                    Err(e) if attempt < retry_count && is_retryable(&e) => {
+                        attempt += 1;
+                        warn!("replaying request, attempt {}, after error: {}", attempt, e);
+                        let timeout = match deadline {
+                            Some(deadline) => {
+                                let now = Instant::now();
+                                if now >= deadline {
+                                    return Err(Error::RequestTimeout);
+                                }
+                                Some(deadline - now)
+                            }
+                            None => None,
+                        };
+                        r = start_request(
+                            &controller_tx,
+                            &client_died_error_holder,
+                            headers.clone(),
+                            body.clone(),
+                            trailers.clone(),
+                            true,
+                            timeout,
+                        )
+                        .and_then(move |(_sender, response)| response)
+                        .await;
+                    }
+                    r => return r,
+                }
+            }
+        })
     }
 
-    pub fn start_request_end_stream(
+    /// Start request with complete body, return response headers
+    /// and response body separately.
+    ///
+    /// Headers future drives the response, so body stream yields data
+    /// only after the headers future is resolved. Body stream fails
+    /// with `OneshotCancelled` if headers future fails or is dropped.
+    pub fn start_request_split(
         &self,
         headers: Headers,
         body: Option<Bytes>,
         trailers: Option<Headers>,
-    ) -> Response {
-        Response::new(
-            self.start_request(headers, body, trailers, true)
-                .and_then(move |(_sender, response)| response),
-        )
+    ) -> (HttpFutureSend<Headers>, HttpFutureStreamSend<Bytes>) {
+        let (tx, rx) = oneshot::channel();
+
+        let headers = self
+            .start_request_end_stream(headers, body, trailers)
+            .0
+            .map_ok(move |(headers, rem)| {
+                // ignore error, caller might be not interested in body
+                let _ = tx.send(rem);
+                headers
+            });
+
+        let body = rx
+            .map_err(|oneshot::Canceled| error::Error::OneshotCancelled)
+            .map_ok(|rem| rem.filter_data())
+            .try_flatten_stream();
+
+        (Box::pin(headers), Box::pin(body))
+    }
+
+    /// Start request with complete body, return response parts in order:
+    /// initial headers, each `DATA` frame payload, and trailers if any.
+    pub fn start_request_parts(
+        &self,
+        headers: Headers,
+        body: Option<Bytes>,
+        trailers: Option<Headers>,
+    ) -> HttpFutureStreamSend<DataOrHeaders> {
+        self.start_request_end_stream(headers, body, trailers)
+            .into_stream()
     }
 
     /// Start HTTP/2 `GET` request.
@@ -397,6 +634,119 @@ impl Client {
         Box::pin(rx.map_err(|_| crate::Error::ConnDied(Arc::new(crate::Error::DeathReasonUnknown))))
     }
 
+    /// Send `GOAWAY` with given error code and debug data on current
+    /// connection and close it. Next request opens a new connection.
+    ///
+    /// Fails if debug data does not fit into a frame.
+    pub fn send_goaway_with_debug(&self, error_code: ErrorCode, debug_data: Bytes) -> Result<()> {
+        GoawayFrame::check_debug_data_len(&debug_data)?;
+        // ignore error
+        drop(
+            self.controller_tx
+                .unbounded_send(ControllerCommand::SendGoaway(error_code, debug_data)),
+        );
+        Ok(())
+    }
+
+    /// ALPN protocol negotiated in TLS handshake of current connection.
+    ///
+    /// `None` for plain connections or if nothing was negotiated.
+    pub fn negotiated_alpn(&self) -> HttpFutureSend<Option<Vec<u8>>> {
+        Box::pin(self.dump_state().map_ok(|state| state.negotiated_alpn))
+    }
+
+    /// Create a future which resolves when requests started so far
+    /// are written to the socket.
+    ///
+    /// Only the connection write buffer is flushed: data held back by
+    /// flow control is not waited for.
+    pub fn flush(&self) -> HttpFutureSend<()> {
+        let (tx, rx) = oneshot::channel();
+        // ignore error
+        drop(
+            self.controller_tx
+                .unbounded_send(ControllerCommand::Flush(tx)),
+        );
+        Box::pin(rx.map_err(|_| crate::Error::ConnDied(Arc::new(crate::Error::DeathReasonUnknown))))
+    }
+
+    /// Send `PING` with given opaque data over current connection
+    /// and return round-trip time when `ACK` is received.
+    ///
+    /// Fails with `Error::PingInFlight` if `max_outstanding_pings` previous `PING` frames
+    /// are not yet acknowledged.
+    pub fn ping(&self, opaque_data: u64) -> HttpFutureSend<Duration> {
+        let (tx, rx) = oneshot::channel();
+        // ignore error
+        drop(
+            self.controller_tx
+                .unbounded_send(ControllerCommand::Ping(opaque_data, tx)),
+        );
+        Box::pin(
+            rx.map_err(|_| crate::Error::ConnDied(Arc::new(crate::Error::DeathReasonUnknown)))
+                .and_then(future::ready),
+        )
+    }
+
+    /// Set `TCP_NODELAY` on the socket of current connection.
+    ///
+    /// Fails if the connection is not over TCP.
+    /// New connections use `ClientConf::no_delay`.
+    pub fn set_nodelay(&self, no_delay: bool) -> HttpFutureSend<()> {
+        let (tx, rx) = oneshot::channel();
+        // ignore error
+        drop(
+            self.controller_tx
+                .unbounded_send(ControllerCommand::SetTcpNodelay(no_delay, tx)),
+        );
+        Box::pin(
+            rx.map_err(|_| crate::Error::ConnDied(Arc::new(crate::Error::DeathReasonUnknown)))
+                .and_then(future::ready),
+        )
+    }
+
+    /// Check if current connection is alive.
+    ///
+    /// Returns `false` after connection died or `GOAWAY` was sent or received.
+    /// Client reconnects on next request, so request can be issued anyway.
+    pub fn is_alive(&self) -> bool {
+        self.conn_shared
+            .lock()
+            .unwrap()
+            .alive
+            .load(Ordering::SeqCst)
+    }
+
+    /// Settings advertised by the server in its first `SETTINGS` frame
+    /// on the current connection.
+    ///
+    /// Settings later updated by the server are not reflected here.
+    /// `None` if the server `SETTINGS` frame was not received yet.
+    pub fn initial_peer_settings(&self) -> Option<HttpSettings> {
+        *self
+            .conn_shared
+            .lock()
+            .unwrap()
+            .initial_peer_settings
+            .lock()
+            .unwrap()
+    }
+
+    /// Settings with identifiers unknown to this implementation
+    /// from the server first `SETTINGS` frame, as `(id, value)` pairs
+    /// ordered by id.
+    ///
+    /// At most 16 identifiers are remembered.
+    pub fn initial_peer_unknown_settings(&self) -> Vec<(u16, u32)> {
+        self.conn_shared
+            .lock()
+            .unwrap()
+            .initial_peer_unknown_settings
+            .lock()
+            .unwrap()
+            .clone()
+    }
+
     /// Create a future which waits for successful connection.
     pub fn wait_for_connect(&self) -> HttpFutureSend<()> {
         let (tx, rx) = oneshot::channel();
@@ -409,7 +759,26 @@ impl Client {
         let client_died_error_holder = self.client_died_error_holder.clone();
         Box::pin(
             rx.map_err(move |_| client_died_error_holder.error())
-                .and_then(|r| future::ready(r)),
+                .and_then(future::ready),
+        )
+    }
+
+    /// Create a future which resolves when the current connection is closed.
+    ///
+    /// Resolves with `Ok(())` if the connection completed without error
+    /// (e. g. after `GOAWAY`), or with `Error::ConnDied` holding the death reason.
+    /// New connection is made on next request or `wait_for_connect` call.
+    pub fn wait_for_conn_close(&self) -> HttpFutureSend<()> {
+        let (tx, rx) = oneshot::channel();
+        // ignore error
+        drop(
+            self.controller_tx
+                .unbounded_send(ControllerCommand::WaitForConnClose(tx)),
+        );
+        let client_died_error_holder = self.client_died_error_holder.clone();
+        Box::pin(
+            rx.map_err(move |_| client_died_error_holder.error())
+                .and_then(future::ready),
         )
     }
 }
@@ -435,31 +804,115 @@ impl ClientInterface for Client {
         end_stream: bool,
         stream_handler: Box<dyn ClientStreamCreatedHandler>,
     ) -> result::Result<()> {
-        let start = StartRequestMessage {
+        start_request_low_level(
+            &self.controller_tx,
             headers,
             body,
             trailers,
             end_stream,
+            None,
             stream_handler,
-        };
+        )
+    }
+}
+
+/// Errors after which request can be safely replayed on a new connection.
+fn is_retryable(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::RstStreamReceived(ErrorCode::RefusedStream)
+            | Error::GoawayReceived(_)
+            | Error::ConnectionError(..)
+            | Error::ConnDied(_)
+    )
+}
+
+fn start_request(
+    controller_tx: &DeathAwareSender<ControllerCommand>,
+    client_died_error_holder: &SomethingDiedErrorHolder<ClientDiedType>,
+    headers: Headers,
+    body: Option<Bytes>,
+    trailers: Option<Headers>,
+    end_stream: bool,
+    timeout: Option<Duration>,
+) -> HttpFutureSend<(ClientRequest, Response)> {
+    let (tx, rx) = oneshot::channel();
+
+    struct Impl {
+        tx: oneshot::Sender<crate::Result<(ClientRequest, Response)>>,
+    }
 
-        if let Err(_) = self
-            .controller_tx
-            .unbounded_send(ControllerCommand::StartRequest(start))
-        {
-            // TODO: cause
-            return Err(error::Error::ClientControllerDied);
+    impl ClientStreamCreatedHandler for Impl {
+        fn request_created(
+            self: Box<Self>,
+            req: ClientRequest,
+            resp: ClientResponse,
+        ) -> result::Result<()> {
+            if let Err(_) = self.tx.send(Ok((req, resp.make_stream()))) {
+                return Err(error::Error::CallerDied);
+            }
+
+            Ok(())
         }
 
-        Ok(())
+        fn error(self: Box<Self>, error: crate::Error) {
+            let _ = self.tx.send(Err(error));
+        }
+    }
+
+    if let Err(e) = start_request_low_level(
+        controller_tx,
+        headers,
+        body,
+        trailers,
+        end_stream,
+        timeout,
+        Box::new(Impl { tx }),
+    ) {
+        return Box::pin(future::err(e));
     }
+
+    let client_error = client_died_error_holder.clone();
+    let resp_rx = rx.then(move |r| match r {
+        Ok(Ok(r)) => future::ok(r),
+        Ok(Err(e)) => future::err(e),
+        Err(oneshot::Canceled) => future::err(client_error.error()),
+    });
+
+    Box::pin(resp_rx)
+}
+
+fn start_request_low_level(
+    controller_tx: &DeathAwareSender<ControllerCommand>,
+    headers: Headers,
+    body: Option<Bytes>,
+    trailers: Option<Headers>,
+    end_stream: bool,
+    timeout: Option<Duration>,
+    stream_handler: Box<dyn ClientStreamCreatedHandler>,
+) -> result::Result<()> {
+    let start = StartRequestMessage {
+        headers,
+        body,
+        trailers,
+        end_stream,
+        timeout,
+        stream_handler,
+    };
+
+    controller_tx.unbounded_send(ControllerCommand::StartRequest(start))
 }
 
 enum ControllerCommand {
     GoAway,
     StartRequest(StartRequestMessage),
     WaitForConnect(oneshot::Sender<Result<()>>),
+    WaitForConnClose(oneshot::Sender<Result<()>>),
     DumpState(oneshot::Sender<ConnStateSnapshot>),
+    Flush(oneshot::Sender<()>),
+    SetTcpNodelay(bool, oneshot::Sender<Result<()>>),
+    Ping(u64, oneshot::Sender<Result<Duration>>),
+    SendGoaway(ErrorCode, Bytes),
 }
 
 impl ErrorAwareDrop for ControllerCommand {
@@ -472,9 +925,22 @@ impl ErrorAwareDrop for ControllerCommand {
             ControllerCommand::WaitForConnect(_) => {
                 // TODO
             }
+            ControllerCommand::WaitForConnClose(_) => {
+                // TODO
+            }
             ControllerCommand::DumpState(_) => {
                 // TODO
             }
+            ControllerCommand::Flush(_) => {
+                // TODO
+            }
+            ControllerCommand::SetTcpNodelay(_, _) => {
+                // TODO
+            }
+            ControllerCommand::Ping(_, _) => {
+                // TODO
+            }
+            ControllerCommand::SendGoaway(_, _) => {}
         }
     }
 }
@@ -486,6 +952,7 @@ struct ControllerState<T: ToClientStream, C: TlsConnector> {
     conf: ClientConf,
     // current connection
     conn: Arc<ClientConn>,
+    conn_shared: Arc<Mutex<Arc<ClientConnShared>>>,
     tx: DeathAwareSender<ControllerCommand>,
 }
 
@@ -501,6 +968,7 @@ impl<T: ToClientStream + 'static + Clone, C: TlsConnector> ControllerState<T, C>
             },
         );
 
+        *self.conn_shared.lock().unwrap() = conn.shared();
         self.conn = Arc::new(conn);
     }
 
@@ -529,9 +997,28 @@ impl<T: ToClientStream + 'static + Clone, C: TlsConnector> ControllerState<T, C>
                     }
                 }
             }
+            ControllerCommand::WaitForConnClose(tx) => {
+                let join = self.conn.join();
+                self.handle.spawn(async move {
+                    // ignore error
+                    drop(tx.send(join.await));
+                });
+            }
             ControllerCommand::DumpState(tx) => {
                 self.conn.dump_state_with_resp_sender(tx);
             }
+            ControllerCommand::Flush(tx) => {
+                self.conn.flush_with_resp_sender(tx);
+            }
+            ControllerCommand::SetTcpNodelay(no_delay, tx) => {
+                self.conn.set_tcp_nodelay_with_resp_sender(no_delay, tx);
+            }
+            ControllerCommand::Ping(opaque_data, tx) => {
+                self.conn.ping_with_resp_sender(opaque_data, tx);
+            }
+            ControllerCommand::SendGoaway(error_code, debug_data) => {
+                self.conn.send_goaway_with_debug(error_code, debug_data);
+            }
         }
     }
 
@@ -550,7 +1037,11 @@ struct CallbacksImpl {
 }
 
 impl ClientConnCallbacks for CallbacksImpl {
-    fn goaway(&self, _stream_id: StreamId, _error_code: u32) {
+    fn goaway(&self, stream_id: StreamId, error_code: u32, debug_data: Bytes) {
+        info!(
+            "GOAWAY received: last stream id: {}, error code: {}, debug data: {:?}",
+            stream_id, error_code, debug_data
+        );
         drop(self.tx.unbounded_send(ControllerCommand::GoAway));
     }
 }
@@ -566,6 +1057,7 @@ fn spawn_client_event_loop<T: ToClientStream + Send + Clone + 'static, C: TlsCon
     controller_tx: DeathAwareSender<ControllerCommand>,
     controller_rx: DeathAwareReceiver<ControllerCommand>,
     client_died_error_holder: SomethingDiedErrorHolder<ClientDiedType>,
+    conn_shared: Arc<Mutex<Arc<ClientConnShared>>>,
 ) {
     let http_conn = ClientConn::spawn(
         handle.clone(),
@@ -577,12 +1069,15 @@ fn spawn_client_event_loop<T: ToClientStream + Send + Clone + 'static, C: TlsCon
         },
     );
 
+    *conn_shared.lock().unwrap() = http_conn.shared();
+
     let init = ControllerState {
         handle: handle.clone(),
         socket_addr: socket_addr.clone(),
         tls,
         conf,
         conn: Arc::new(http_conn),
+        conn_shared,
         tx: controller_tx,
     };
 
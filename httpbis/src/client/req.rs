@@ -10,6 +10,7 @@ use crate::Headers;
 use crate::HttpStreamAfterHeaders;
 use crate::SenderState;
 use bytes::Bytes;
+use futures::future;
 use futures::stream::Stream;
 use futures::task::Context;
 use std::mem;
@@ -73,16 +74,38 @@ impl ClientRequest {
         self.common.poll(cx)
     }
 
+    /// Resolve when stream window allows sending more data.
+    pub async fn ready(&mut self) -> Result<(), StreamDead> {
+        future::poll_fn(|cx| self.poll(cx)).await
+    }
+
     /// Enqueue data to outgoing stream
     ///
     /// This operation fails if stream is in incorrect state.
     ///
     /// The operation does not fail if stream or connection windows is not available,
     /// in that case message will be queued until peer increases window.
+    /// `CommonConf::out_buffer_limit` is ignored too, use `send_data_wait`
+    /// to bound memory used by queued data.
     pub fn send_data(&mut self, data: Bytes) -> Result<(), SendError> {
         self.common.send_data(data)
     }
 
+    /// Wait until stream window allows sending and enqueue data.
+    ///
+    /// Unlike `send_data`, this also waits while outgoing buffers
+    /// are full (see `CommonConf::out_buffer_limit`).
+    pub async fn send_data_wait(&mut self, data: Bytes) -> Result<(), SendError> {
+        self.common.send_data_wait(data).await
+    }
+
+    /// Enqueue data and flush the socket right after the data is written.
+    ///
+    /// Data enqueued after this call is written by a separate socket write.
+    pub fn send_data_flush(&mut self, data: Bytes) -> Result<(), SendError> {
+        self.common.send_data_flush(data)
+    }
+
     /// Send last `DATA` frame
     pub fn send_data_end_of_stream(&mut self, data: Bytes) -> Result<(), SendError> {
         self.common.send_data_end_of_stream(data)
@@ -6,12 +6,14 @@ use crate::common::death_aware_channel::DeathAwareSender;
 use crate::common::increase_in_window::IncreaseInWindow;
 use crate::common::stream_from_network::StreamFromNetwork;
 use crate::common::stream_queue_sync::stream_queue_sync;
+use crate::common::window_tuner::WindowTuner;
 use crate::Response;
 use crate::StreamId;
 
 pub struct ClientResponse<'a> {
     pub(crate) stream_handler: &'a mut Option<ClientResponseStreamHandlerHolder>,
     pub(crate) in_window_size: u32,
+    pub(crate) window_tuner: WindowTuner,
     pub(crate) stream_id: StreamId,
     pub(crate) to_write_tx: &'a DeathAwareSender<ClientToWriteMessage>,
 }
@@ -31,7 +33,8 @@ impl<'a> ClientResponse<'a> {
 
     /// Register synchnous stream handler (callback will be called immediately
     /// when new data arrives). Note that increasing in window size is the handler
-    /// responsibility.
+    /// responsibility: the window is not opened until `ClientIncreaseInWindow`
+    /// passed to `f` is used.
     pub fn register_stream_handler<F, H, R>(self, f: F) -> R
     where
         F: FnOnce(ClientIncreaseInWindow) -> (H, R),
@@ -42,6 +45,7 @@ impl<'a> ClientResponse<'a> {
             stream_id: self.stream_id,
             in_window_size: self.in_window_size,
             to_write_tx: self.to_write_tx.clone(),
+            tuner: self.window_tuner,
         });
         let (h, r) = f(increase_window);
         *self.stream_handler = Some(ClientResponseStreamHandlerHolder(Box::new(h)));
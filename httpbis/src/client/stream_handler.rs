@@ -5,6 +5,7 @@ use crate::result;
 use crate::ClientRequest;
 use crate::ErrorCode;
 use crate::Headers;
+use crate::StreamId;
 use bytes::Bytes;
 
 /// Called once when stream is created
@@ -20,17 +21,50 @@ pub trait ClientStreamCreatedHandler: Send + 'static {
 }
 
 /// Synchrnous callback of incoming data
+///
+/// Stream receive window is opened only with `ClientIncreaseInWindow`
+/// passed to `ClientResponse::register_stream_handler`. Handler may keep
+/// that handle and increase the window when the application consumes data;
+/// not calling `increase_window` naturally stops the peer once the window
+/// is exhausted.
 pub trait ClientResponseStreamHandler: Send + 'static {
     /// Response HEADERS frame received
     fn headers(&mut self, headers: Headers, end_stream: bool) -> result::Result<()>;
+    /// Informational (1xx) response HEADERS frame received,
+    /// e. g. `103 Early Hints` or `100 Continue`.
+    ///
+    /// May be called several times before `headers`. Ignored by default.
+    fn informational_headers(&mut self, _headers: Headers) -> result::Result<()> {
+        Ok(())
+    }
     /// DATA frame received
     fn data_frame(&mut self, data: Bytes, end_stream: bool) -> result::Result<()>;
     /// Trailers HEADERS received
     fn trailers(&mut self, trailers: Headers) -> result::Result<()>;
     /// RST_STREAM frame received
     fn rst(&mut self, error_code: ErrorCode) -> result::Result<()>;
+    /// RST_STREAM frame received, with error code as sent by the peer.
+    ///
+    /// Unknown error codes are passed to `rst` as `InternalError` by default.
+    fn rst_raw(&mut self, raw_error_code: u32) -> result::Result<()> {
+        self.rst(ErrorCode::from(raw_error_code))
+    }
     /// Any other error
     fn error(&mut self, error: error::Error) -> result::Result<()>;
+    /// PUSH_PROMISE frame received.
+    ///
+    /// `request_headers` are headers of the promised request,
+    /// pushed response is delivered to `response`.
+    /// Push is refused with `RST_STREAM` if error is returned.
+    /// Pushes are refused by default.
+    fn push_promise(
+        &mut self,
+        _promised_stream_id: StreamId,
+        _request_headers: Headers,
+        _response: ClientResponse,
+    ) -> result::Result<()> {
+        Err(error::Error::NotImplemented("push_promise"))
+    }
 }
 
 pub(crate) struct ClientResponseStreamHandlerHolder(
@@ -46,8 +80,8 @@ impl StreamHandlerInternal for ClientResponseStreamHandlerHolder {
         self.0.trailers(trailers)
     }
 
-    fn rst(mut self, error_code: ErrorCode) -> result::Result<()> {
-        self.0.rst(error_code)
+    fn rst(mut self, raw_error_code: u32) -> result::Result<()> {
+        self.0.rst_raw(raw_error_code)
     }
 
     fn error(mut self, error: error::Error) -> result::Result<()> {
@@ -2,9 +2,14 @@ use std::marker;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+use futures::channel::oneshot;
 use futures::future;
+use futures::future::Either;
 
 use std::future::Future;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
 
 use crate::error;
 use crate::misc::any_to_string;
@@ -42,9 +47,52 @@ impl DiedType for ConnDiedType {
     }
 }
 
+/// Sets `Error::ReactorGone` if the future is dropped before completion.
+///
+/// Client and connection futures are never dropped by this crate
+/// before they complete, so such drop means the task was cancelled
+/// from outside: usually the event loop it was spawned on is shut down,
+/// but also the executor aborted the task, or the user dropped the
+/// connection future (e. g. returned by `ServerConn::new_plain_single_thread`).
+/// The error is set before the future is dropped, so that it is reported
+/// to streams.
+struct DroppedBeforeCompletion<D: DiedType, F> {
+    holder: SomethingDiedErrorHolder<D>,
+    future: Pin<Box<F>>,
+    done: bool,
+}
+
+impl<D: DiedType, F: Future> Future for DroppedBeforeCompletion<D, F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let r = self.future.as_mut().poll(cx);
+        if r.is_ready() {
+            self.done = true;
+        }
+        r
+    }
+}
+
+impl<D: DiedType, F> Drop for DroppedBeforeCompletion<D, F> {
+    fn drop(&mut self) {
+        if !self.done {
+            warn!("{} dropped before completion", D::what());
+            self.holder.set_once(error::Error::ReactorGone);
+        }
+    }
+}
+
+#[derive(Default)]
+struct DiedState {
+    error: Option<Arc<error::Error>>,
+    /// Notified when error is set
+    waiters: Vec<oneshot::Sender<Arc<error::Error>>>,
+}
+
 #[derive(Default, Clone)]
 pub(crate) struct SomethingDiedErrorHolder<D: DiedType> {
-    error: Arc<Mutex<Option<Arc<error::Error>>>>,
+    state: Arc<Mutex<DiedState>>,
     _marker: marker::PhantomData<D>,
 }
 
@@ -53,9 +101,9 @@ impl<D: DiedType> SomethingDiedErrorHolder<D> {
         Default::default()
     }
 
-    fn raw_error(&self) -> Arc<error::Error> {
-        let lock = self.error.lock().unwrap();
-        (*lock)
+    pub fn raw_error(&self) -> Arc<error::Error> {
+        let lock = self.state.lock().unwrap();
+        lock.error
             .clone()
             .unwrap_or_else(|| Arc::new(crate::Error::DeathReasonUnknown))
     }
@@ -65,9 +113,29 @@ impl<D: DiedType> SomethingDiedErrorHolder<D> {
     }
 
     pub fn set_once(&self, error: error::Error) {
-        let mut lock = self.error.lock().unwrap();
-        if (*lock).is_none() {
-            *lock = Some(Arc::new(error));
+        let mut lock = self.state.lock().unwrap();
+        if lock.error.is_none() {
+            let error = Arc::new(error);
+            lock.error = Some(error.clone());
+            for tx in lock.waiters.drain(..) {
+                // ignore error, waiter might be gone
+                drop(tx.send(error.clone()));
+            }
+        }
+    }
+
+    /// Future which resolves with the death reason once it is known.
+    pub fn died(&self) -> impl Future<Output = Arc<error::Error>> + Send {
+        let mut lock = self.state.lock().unwrap();
+        match &lock.error {
+            Some(error) => Either::Left(future::ready(error.clone())),
+            None => {
+                let (tx, rx) = oneshot::channel();
+                lock.waiters.push(tx);
+                Either::Right(
+                    rx.map(|r| r.unwrap_or_else(|_| Arc::new(crate::Error::DeathReasonUnknown))),
+                )
+            }
         }
     }
 
@@ -105,7 +173,11 @@ impl<D: DiedType> SomethingDiedErrorHolder<D> {
                 Ok(r) => future::ready(r),
             });
 
-        future
+        DroppedBeforeCompletion {
+            holder: self.clone(),
+            future: Box::pin(future),
+            done: false,
+        }
     }
 
     pub fn wrap_future<F>(&self, future: F) -> impl Future<Output = ()> + Send
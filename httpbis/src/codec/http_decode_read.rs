@@ -1,13 +1,19 @@
 use crate::codec::http_framed_read::HttpFramedJoinContinuationRead;
+use crate::error;
 use crate::hpack;
 use crate::result;
 use crate::solicit::frame::HeadersDecodedFrame;
 use crate::solicit::frame::HttpFrame;
 use crate::solicit::frame::HttpFrameDecoded;
+use crate::solicit::frame::ParseFrameError;
+use crate::solicit::frame::PushPromiseDecodedFrame;
+use crate::solicit::header::name::make_ascii_lowercase;
+use crate::solicit::header::HeaderError;
 use crate::solicit::stream_id::StreamId;
 use crate::ErrorCode;
 use crate::Header;
 use crate::Headers;
+use bytes::Bytes;
 use futures::task::Context;
 use std::task::Poll;
 use tokio::io::AsyncRead;
@@ -16,19 +22,104 @@ pub struct HttpDecodeRead<R: AsyncRead + Unpin> {
     framed_read: HttpFramedJoinContinuationRead<R>,
     /// HPACK decoder used to decode incoming headers before passing them on to the session.
     decoder: hpack::Decoder,
+    /// Convert header names to lower case instead of rejecting them.
+    allow_uppercase_headers: bool,
 }
 
 pub enum HttpFrameDecodedOrGoaway {
     Frame(HttpFrameDecoded),
     SendGoaway(ErrorCode),
-    _SendRst(StreamId, ErrorCode),
+    SendRst(StreamId, ErrorCode),
 }
 
 impl<R: AsyncRead + Unpin> HttpDecodeRead<R> {
-    pub fn new(read: R) -> Self {
+    pub fn new(
+        read: R,
+        max_header_block_size: u32,
+        max_continuation_frames: u32,
+        max_header_list_size: u32,
+        max_header_count: Option<usize>,
+        allow_uppercase_headers: bool,
+    ) -> Self {
+        let mut decoder = hpack::Decoder::new();
+        decoder.set_max_header_list_size(Some(max_header_list_size));
+        decoder.set_max_header_count(max_header_count);
         HttpDecodeRead {
-            framed_read: HttpFramedJoinContinuationRead::new(read),
-            decoder: hpack::Decoder::new(),
+            framed_read: HttpFramedJoinContinuationRead::new(
+                read,
+                max_header_block_size,
+                max_continuation_frames,
+            ),
+            decoder,
+            allow_uppercase_headers,
+        }
+    }
+
+    /// HPACK decoder state, mirrors the peer encoder's dynamic table.
+    pub fn decoder(&self) -> &hpack::Decoder {
+        &self.decoder
+    }
+
+    fn decode_headers(
+        &mut self,
+        stream_id: StreamId,
+        header_fragment: Bytes,
+    ) -> Result<Result<Headers, HeaderError>, ErrorCode> {
+        let headers = match self.decoder.decode(header_fragment) {
+            Err(e @ hpack::decoder::DecoderError::HeaderListTooLarge(_)) => {
+                // Decoder state is not usable after decoding is aborted,
+                // so this is a connection error
+                warn!("failed to decode headers in stream {}: {:?}", stream_id, e);
+                return Err(ErrorCode::EnhanceYourCalm);
+            }
+            Err(e @ hpack::decoder::DecoderError::TooManyHeaders(_)) => {
+                // Block is decoded completely, so only the stream is reset
+                warn!("failed to decode headers in stream {}: {:?}", stream_id, e);
+                return Ok(Err(HeaderError::TooManyHeaders));
+            }
+            Err(e) => {
+                warn!("failed to decode headers: {:?}", e);
+                return Err(ErrorCode::CompressionError);
+            }
+            Ok(headers) => headers,
+        };
+
+        let allow_uppercase_headers = self.allow_uppercase_headers;
+        match headers
+            .into_iter()
+            .map(|(mut name, value)| {
+                if allow_uppercase_headers {
+                    make_ascii_lowercase(&mut name);
+                }
+                Header::new_validate(name, value)
+            })
+            .collect::<Result<Vec<_>, _>>()
+        {
+            // Pseudo-headers order is checked by `Headers::validate`,
+            // so malformed block only resets the stream
+            Ok(headers) => Ok(Ok(Headers::from_vec_received(headers))),
+            Err(e @ HeaderError::UppercaseHeaderName) => {
+                // Typical for peers ported from HTTP/1, and decoder state
+                // is consistent, so only the stream is reset
+                warn!("received upper case header name in stream {}", stream_id);
+                Ok(Err(e))
+            }
+            Err(e @ HeaderError::IncorrectCharInValue) => {
+                // Malformed message is a stream error (RFC 7540, section 8.1.2.6)
+                warn!(
+                    "received NUL, CR or LF in header value in stream {}",
+                    stream_id
+                );
+                Ok(Err(e))
+            }
+            Err(e) => {
+                warn!(
+                    "received incorrect headers in stream {}: {:?}",
+                    stream_id, e
+                );
+                // TODO: close connection, because decoder may be in incorrect state
+                Err(ErrorCode::ProtocolError)
+            }
         }
     }
 
@@ -37,43 +128,74 @@ impl<R: AsyncRead + Unpin> HttpDecodeRead<R> {
         cx: &mut Context<'_>,
         max_frame_size: u32,
     ) -> Poll<result::Result<HttpFrameDecodedOrGoaway>> {
-        let frame = match self.framed_read.poll_http_frame(cx, max_frame_size)? {
-            Poll::Ready(frame) => frame,
+        let frame = match self.framed_read.poll_http_frame(cx, max_frame_size) {
+            Poll::Ready(Ok(frame)) => frame,
+            Poll::Ready(Err(error::Error::ParseFrameError(
+                e @ ParseFrameError::StreamIdMustBeZero(_),
+            )))
+            | Poll::Ready(Err(error::Error::ParseFrameError(
+                e @ ParseFrameError::StreamIdMustBeNonZero,
+            ))) => {
+                // Frame sent on wrong stream id is a connection error
+                // of type PROTOCOL_ERROR (Section 6).
+                warn!("received frame with incorrect stream id: {:?}", e);
+                return Poll::Ready(Ok(HttpFrameDecodedOrGoaway::SendGoaway(
+                    ErrorCode::ProtocolError,
+                )));
+            }
+            Poll::Ready(Err(e @ error::Error::ExpectingContinuationGot(..)))
+            | Poll::Ready(Err(e @ error::Error::ExpectingContinuationGotDifferentStreamId(..)))
+            | Poll::Ready(Err(e @ error::Error::ContinuationFrameWithoutHeaders)) => {
+                // A receiver MUST treat the receipt of any other type of frame
+                // or a frame on a different stream as a connection error
+                // of type PROTOCOL_ERROR (6.10).
+                warn!("incorrect header block: {}", e);
+                return Poll::Ready(Ok(HttpFrameDecodedOrGoaway::SendGoaway(
+                    ErrorCode::ProtocolError,
+                )));
+            }
+            Poll::Ready(Err(error::Error::ParseFrameError(
+                e @ ParseFrameError::InvalidSettingsAck(_),
+            )))
+            | Poll::Ready(Err(error::Error::ParseFrameError(
+                e @ ParseFrameError::IncorrectSettingsPayloadLen(_),
+            ))) => {
+                // Malformed `SETTINGS` length is a connection error
+                // of type FRAME_SIZE_ERROR (6.5).
+                warn!("received SETTINGS with incorrect length: {:?}", e);
+                return Poll::Ready(Ok(HttpFrameDecodedOrGoaway::SendGoaway(
+                    ErrorCode::FrameSizeError,
+                )));
+            }
+            Poll::Ready(Err(error::Error::CodeError(ErrorCode::FrameSizeError))) => {
+                // An endpoint MUST send an error code of FRAME_SIZE_ERROR
+                // if a frame exceeds the size defined in SETTINGS_MAX_FRAME_SIZE (4.2).
+                return Poll::Ready(Ok(HttpFrameDecodedOrGoaway::SendGoaway(
+                    ErrorCode::FrameSizeError,
+                )));
+            }
+            Poll::Ready(Err(error::Error::HeaderBlockTooLarge(_)))
+            | Poll::Ready(Err(error::Error::TooManyContinuationFrames(_))) => {
+                return Poll::Ready(Ok(HttpFrameDecodedOrGoaway::SendGoaway(
+                    ErrorCode::EnhanceYourCalm,
+                )));
+            }
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
             Poll::Pending => return Poll::Pending,
         };
         Poll::Ready(Ok(HttpFrameDecodedOrGoaway::Frame(match frame {
             HttpFrame::Data(frame) => HttpFrameDecoded::Data(frame),
             HttpFrame::Headers(frame) => {
-                let headers = match self.decoder.decode(frame.header_fragment) {
-                    Err(e) => {
-                        warn!("failed to decode headers: {:?}", e);
-                        return Poll::Ready(Ok(HttpFrameDecodedOrGoaway::SendGoaway(
-                            ErrorCode::CompressionError,
-                        )));
-                    }
-                    Ok(headers) => headers,
-                };
-
-                let headers = match headers
-                    .into_iter()
-                    .map(|h| Header::new_validate(h.0, h.1))
-                    .collect::<Result<Vec<_>, _>>()
-                    .and_then(Headers::from_vec_pseudo_first)
-                {
-                    Ok(headers) => headers,
-                    Err(e) => {
-                        // All pseudo-header fields MUST appear in the header block before
-                        // regular header fields. Any request or response that contains
-                        // a pseudo-header field that appears in a header block after
-                        // a regular header field MUST be treated as malformed (Section 8.1.2.6).
-                        warn!(
-                            "received incorrect headers in stream {}: {:?}",
-                            frame.stream_id, e
-                        );
-                        // TODO: close connection, because decoder may be in incorrect state
-                        return Poll::Ready(Ok(HttpFrameDecodedOrGoaway::SendGoaway(
+                let headers = match self.decode_headers(frame.stream_id, frame.header_fragment) {
+                    Ok(Ok(headers)) => headers,
+                    Ok(Err(_)) => {
+                        return Poll::Ready(Ok(HttpFrameDecodedOrGoaway::SendRst(
+                            frame.stream_id,
                             ErrorCode::ProtocolError,
-                        )));
+                        )))
+                    }
+                    Err(error_code) => {
+                        return Poll::Ready(Ok(HttpFrameDecodedOrGoaway::SendGoaway(error_code)))
                     }
                 };
 
@@ -88,7 +210,29 @@ impl<R: AsyncRead + Unpin> HttpDecodeRead<R> {
             HttpFrame::Priority(frame) => HttpFrameDecoded::Priority(frame),
             HttpFrame::RstStream(frame) => HttpFrameDecoded::RstStream(frame),
             HttpFrame::Settings(frame) => HttpFrameDecoded::Settings(frame),
-            HttpFrame::PushPromise(frame) => HttpFrameDecoded::PushPromise(frame),
+            HttpFrame::PushPromise(frame) => {
+                // Header block must be decoded even if push is refused
+                // to keep decoder state in sync
+                let headers = match self.decode_headers(frame.stream_id, frame.header_fragment) {
+                    Ok(Ok(headers)) => headers,
+                    Ok(Err(_)) => {
+                        return Poll::Ready(Ok(HttpFrameDecodedOrGoaway::SendRst(
+                            frame.promised_stream_id,
+                            ErrorCode::ProtocolError,
+                        )))
+                    }
+                    Err(error_code) => {
+                        return Poll::Ready(Ok(HttpFrameDecodedOrGoaway::SendGoaway(error_code)))
+                    }
+                };
+
+                HttpFrameDecoded::PushPromise(PushPromiseDecodedFrame {
+                    flags: frame.flags,
+                    stream_id: frame.stream_id,
+                    promised_stream_id: frame.promised_stream_id,
+                    headers,
+                })
+            }
             HttpFrame::Ping(frame) => HttpFrameDecoded::Ping(frame),
             HttpFrame::Goaway(frame) => HttpFrameDecoded::Goaway(frame),
             HttpFrame::WindowUpdate(frame) => HttpFrameDecoded::WindowUpdate(frame),
@@ -1,6 +1,7 @@
 use bytes::Bytes;
 use bytes::BytesMut;
 
+use crate::common::limits::FRAME_HEADER_LEN;
 use crate::error;
 use crate::result;
 use crate::solicit::frame::unpack_header_from_slice;
@@ -11,7 +12,6 @@ use crate::solicit::frame::PushPromiseFlag;
 use crate::solicit::frame::PushPromiseFrame;
 use crate::solicit::frame::RawFrame;
 use crate::solicit::frame::RawHttpFrameType;
-use crate::solicit::frame::FRAME_HEADER_LEN;
 use crate::solicit::stream_id::StreamId;
 use crate::ErrorCode;
 use futures::task::Context;
@@ -108,6 +108,22 @@ impl<R: AsyncRead + Unpin> HttpFramedRead<R> {
     }
 }
 
+/// Stream id of the header block which must be continued
+/// with `CONTINUATION` frames after this frame.
+fn expects_continuation(frame: &HttpFrame) -> Option<StreamId> {
+    let end_headers = match frame {
+        HttpFrame::Headers(h) => h.is_headers_end(),
+        HttpFrame::PushPromise(p) => p.flags.is_set(PushPromiseFlag::EndHeaders),
+        HttpFrame::Continuation(c) => c.is_headers_end(),
+        _ => return None,
+    };
+    if end_headers {
+        None
+    } else {
+        Some(frame.get_stream_id())
+    }
+}
+
 enum ContinuableFrame {
     Headers(HeadersFrame),
     PushPromise(PushPromiseFrame),
@@ -169,17 +185,29 @@ impl Continuable {
     }
 }
 
+/// Default limit of header block size joined from `CONTINUATION` frames.
+pub const DEFAULT_MAX_HEADER_BLOCK_SIZE: u32 = 1 << 20;
+
+/// Default limit of `CONTINUATION` frames in a header block.
+pub const DEFAULT_MAX_CONTINUATION_FRAMES: u32 = 256;
+
 pub struct HttpFramedJoinContinuationRead<R: AsyncRead + Unpin> {
     framed_read: HttpFramedRead<R>,
-    // TODO: check total size is not exceeded some limit
     header_opt: Option<Continuable>,
+    max_header_block_size: u32,
+    max_continuation_frames: u32,
+    /// `CONTINUATION` frames joined into `header_opt` so far
+    continuation_frames: u32,
 }
 
 impl<R: AsyncRead + Unpin> HttpFramedJoinContinuationRead<R> {
-    pub fn new(read: R) -> Self {
+    pub fn new(read: R, max_header_block_size: u32, max_continuation_frames: u32) -> Self {
         HttpFramedJoinContinuationRead {
             framed_read: HttpFramedRead::new(read),
             header_opt: None,
+            max_header_block_size,
+            max_continuation_frames,
+            continuation_frames: 0,
         }
     }
 
@@ -194,6 +222,8 @@ impl<R: AsyncRead + Unpin> HttpFramedJoinContinuationRead<R> {
                 Poll::Ready(frame) => frame,
             };
 
+            let continuation_stream_id = expects_continuation(&frame);
+
             match frame {
                 HttpFrame::Headers(h) => {
                     if let Some(_) = self.header_opt {
@@ -201,10 +231,11 @@ impl<R: AsyncRead + Unpin> HttpFramedJoinContinuationRead<R> {
                             RawHttpFrameType::HEADERS,
                         )));
                     } else {
-                        if h.flags.is_set(HeadersFlag::EndHeaders) {
+                        if continuation_stream_id.is_none() {
                             return Poll::Ready(Ok(HttpFrame::Headers(h)));
                         } else {
                             self.header_opt = Some(Continuable::headers(h));
+                            self.continuation_frames = 0;
                             continue;
                         }
                     }
@@ -215,10 +246,11 @@ impl<R: AsyncRead + Unpin> HttpFramedJoinContinuationRead<R> {
                             RawHttpFrameType::PUSH_PROMISE,
                         )));
                     } else {
-                        if p.flags.is_set(PushPromiseFlag::EndHeaders) {
+                        if continuation_stream_id.is_none() {
                             return Poll::Ready(Ok(HttpFrame::PushPromise(p)));
                         } else {
                             self.header_opt = Some(Continuable::push_promise(p));
+                            self.continuation_frames = 0;
                             continue;
                         }
                     }
@@ -233,8 +265,24 @@ impl<R: AsyncRead + Unpin> HttpFramedJoinContinuationRead<R> {
                                 ),
                             ));
                         } else {
-                            let header_end = c.is_headers_end();
+                            let header_end = continuation_stream_id.is_none();
+                            self.continuation_frames += 1;
+                            if self.continuation_frames > self.max_continuation_frames {
+                                warn!(
+                                    "header block exceeds {} CONTINUATION frames",
+                                    self.max_continuation_frames
+                                );
+                                return Poll::Ready(Err(error::Error::TooManyContinuationFrames(
+                                    self.max_continuation_frames,
+                                )));
+                            }
                             h.extend_header_fragment(c.header_fragment);
+                            if h.header_fragment.len() > self.max_header_block_size as usize {
+                                warn!("header block exceeds {} bytes", self.max_header_block_size);
+                                return Poll::Ready(Err(error::Error::HeaderBlockTooLarge(
+                                    self.max_header_block_size,
+                                )));
+                            }
                             if header_end {
                                 h.set_end_headers();
                                 return Poll::Ready(Ok(h.into_frame()));
@@ -260,3 +308,68 @@ impl<R: AsyncRead + Unpin> HttpFramedJoinContinuationRead<R> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::solicit::frame::ContinuationFlag;
+    use crate::solicit::frame::ContinuationFrame;
+    use crate::solicit::frame::DataFrame;
+    use crate::solicit::frame::Flags;
+
+    #[test]
+    fn expects_continuation_headers() {
+        let mut headers = HeadersFrame::new(Bytes::new(), 3);
+        assert_eq!(
+            Some(3),
+            expects_continuation(&HttpFrame::Headers(headers.clone()))
+        );
+
+        headers.set_flag(HeadersFlag::EndHeaders);
+        assert_eq!(None, expects_continuation(&HttpFrame::Headers(headers)));
+    }
+
+    #[test]
+    fn expects_continuation_push_promise() {
+        let mut push_promise = PushPromiseFrame {
+            flags: Flags::default(),
+            stream_id: 1,
+            promised_stream_id: 2,
+            header_fragment: Bytes::new(),
+            padding_len: 0,
+        };
+        assert_eq!(
+            Some(1),
+            expects_continuation(&HttpFrame::PushPromise(push_promise.clone()))
+        );
+
+        push_promise.flags.set(PushPromiseFlag::EndHeaders);
+        assert_eq!(
+            None,
+            expects_continuation(&HttpFrame::PushPromise(push_promise))
+        );
+    }
+
+    #[test]
+    fn expects_continuation_continuation() {
+        let mut continuation = ContinuationFrame::new(Bytes::new(), 5);
+        assert_eq!(
+            Some(5),
+            expects_continuation(&HttpFrame::Continuation(continuation.clone()))
+        );
+
+        continuation.set_flag(ContinuationFlag::EndHeaders);
+        assert_eq!(
+            None,
+            expects_continuation(&HttpFrame::Continuation(continuation))
+        );
+    }
+
+    #[test]
+    fn expects_continuation_other() {
+        assert_eq!(
+            None,
+            expects_continuation(&HttpFrame::Data(DataFrame::new(1)))
+        );
+    }
+}
@@ -5,12 +5,19 @@ use crate::codec::write_buffer::WriteBuffer;
 use crate::solicit::frame::FrameIR;
 use bytes::Buf;
 use futures::task::Context;
+use std::io;
+use std::io::IoSlice;
 use std::pin::Pin;
 use std::task::Poll;
 
+/// Max number of buffer segments passed to a single vectored write.
+const MAX_IO_SLICES: usize = 64;
+
 pub struct HttpFramedWrite<W: AsyncWrite + Unpin> {
     write: W,
     buf: WriteBuffer,
+    /// Total number of bytes written to the socket
+    bytes_written: u64,
 }
 
 impl<W: AsyncWrite + Unpin> HttpFramedWrite<W> {
@@ -18,6 +25,7 @@ impl<W: AsyncWrite + Unpin> HttpFramedWrite<W> {
         HttpFramedWrite {
             write,
             buf: WriteBuffer::new(),
+            bytes_written: 0,
         }
     }
 
@@ -25,23 +33,164 @@ impl<W: AsyncWrite + Unpin> HttpFramedWrite<W> {
         self.buf.remaining()
     }
 
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
     pub fn buffer_frame<F: FrameIR>(&mut self, frame: F) {
         debug!("send {:?}", frame);
 
-        frame.serialize_into(&mut self.buf);
+        self.buf.write_frame(frame);
     }
 
-    pub fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<()>> {
+    /// Write buffered frames until the buffer is empty or IO is not ready.
+    ///
+    /// Each write is vectored over buffer segments (frame headers, encoded
+    /// frames, `DATA` payloads), so usually a single syscall writes everything.
+    /// Partial write advances the buffer, which keeps the offset
+    /// inside the first unwritten segment.
+    pub fn poll_flush_frames_vectored(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<()>> {
         loop {
             if !self.buf.has_remaining() {
                 return Poll::Ready(Ok(()));
             }
 
-            if let Poll::Pending =
-                tokio_util::io::poll_write_buf(Pin::new(&mut self.write), cx, &mut self.buf)?
-            {
+            let n = {
+                let mut slices = [IoSlice::new(&[]); MAX_IO_SLICES];
+                let count = self.buf.chunks_vectored(&mut slices);
+                match Pin::new(&mut self.write).poll_write_vectored(cx, &slices[..count])? {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(n) => n,
+                }
+            };
+            if n == 0 {
+                return Poll::Ready(Err(io::Error::from(io::ErrorKind::WriteZero).into()));
+            }
+
+            self.buf.advance(n);
+            self.bytes_written += n as u64;
+            self.buf.maybe_shrink();
+        }
+    }
+
+    /// Write buffered frames, then flush the underlying IO.
+    pub fn poll_flush_io(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<()>> {
+        match self.poll_flush_frames_vectored(cx)? {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(()) => {}
+        }
+        match Pin::new(&mut self.write).poll_flush(cx)? {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(()) => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::solicit::frame::DataFrame;
+    use crate::solicit::frame::PingFrame;
+    use crate::solicit::frame::SettingsFrame;
+    use bytes::Bytes;
+    use futures::task::noop_waker_ref;
+    use std::cmp;
+
+    /// Writer which accepts at most `max_per_call` bytes per write
+    /// and is not ready on every other call.
+    struct PartialWrite {
+        data: Vec<u8>,
+        max_per_call: usize,
+        calls: usize,
+    }
+
+    impl AsyncWrite for PartialWrite {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.poll_write_vectored(cx, &[IoSlice::new(buf)])
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<io::Result<usize>> {
+            self.calls += 1;
+            if self.calls % 2 == 0 {
                 return Poll::Pending;
             }
+            let mut n = 0;
+            for buf in bufs {
+                let len = cmp::min(buf.len(), self.max_per_call - n);
+                self.data.extend_from_slice(&buf[..len]);
+                n += len;
+            }
+            Poll::Ready(Ok(n))
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            true
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn buffer_frames<W: AsyncWrite + Unpin>(write: &mut HttpFramedWrite<W>) -> Vec<u8> {
+        let mut data = DataFrame::new(1);
+        data.data = Bytes::from(vec![17; 1000]);
+
+        let mut expected = Vec::new();
+        expected.extend(SettingsFrame::new().serialize_into_vec());
+        expected.extend(data.clone().serialize_into_vec());
+        expected.extend(PingFrame::with_data(18).serialize_into_vec());
+
+        write.buffer_frame(SettingsFrame::new());
+        write.buffer_frame(data);
+        write.buffer_frame(PingFrame::with_data(18));
+        expected
+    }
+
+    #[test]
+    fn flush_frames_vectored_single_write() {
+        let mut write = HttpFramedWrite::new(PartialWrite {
+            data: Vec::new(),
+            max_per_call: usize::MAX,
+            calls: 0,
+        });
+        let expected = buffer_frames(&mut write);
+
+        let mut cx = Context::from_waker(noop_waker_ref());
+        assert!(write.poll_flush_frames_vectored(&mut cx).is_ready());
+        assert_eq!(1, write.write.calls);
+        assert_eq!(expected, write.write.data);
+    }
+
+    #[test]
+    fn flush_frames_vectored_partial_writes() {
+        for max_per_call in &[1, 5, 9, 100, 1003] {
+            let mut write = HttpFramedWrite::new(PartialWrite {
+                data: Vec::new(),
+                max_per_call: *max_per_call,
+                calls: 0,
+            });
+            let expected = buffer_frames(&mut write);
+
+            let mut cx = Context::from_waker(noop_waker_ref());
+            while write.poll_flush_frames_vectored(&mut cx).is_pending() {
+                assert_eq!(expected.len() - write.data_len(), write.write.data.len());
+            }
+            assert_eq!(0, write.data_len());
+            assert_eq!(expected.len() as u64, write.bytes_written());
+            assert_eq!(expected, write.write.data, "{}", max_per_call);
         }
     }
 }
@@ -24,6 +24,10 @@ impl<W: AsyncWrite + Unpin> QueuedWrite<W> {
         self.framed_write.data_len()
     }
 
+    pub fn bytes_written(&self) -> u64 {
+        self.framed_write.bytes_written()
+    }
+
     pub fn _queued_empty(&self) -> bool {
         self.queued_bytes_len() == 0
     }
@@ -48,10 +52,15 @@ impl<W: AsyncWrite + Unpin> QueuedWrite<W> {
     }
 
     pub fn poll(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<()>> {
-        self.framed_write.poll_flush(cx)
+        self.framed_write.poll_flush_frames_vectored(cx)
+    }
+
+    /// Write queued frames and flush the underlying IO.
+    pub fn poll_flush_io(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<()>> {
+        self.framed_write.poll_flush_io(cx)
     }
 
-    pub fn _goaway_queued(&self) -> bool {
+    pub fn goaway_queued(&self) -> bool {
         self.goaway_queued
     }
 
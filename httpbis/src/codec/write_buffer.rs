@@ -1,13 +1,19 @@
 use crate::bytes_ext::buf_vec_deque::BufVecDeque;
 use crate::codec::zeroes::Zeroes;
 use crate::solicit::frame::FrameHeaderBuffer;
+use crate::solicit::frame::FrameIR;
 use crate::BufGetBytes;
 use bytes::Buf;
 use bytes::Bytes;
+use std::cmp;
 use std::io::Cursor;
 use std::io::IoSlice;
 use std::mem;
 
+/// Tail buffer capacity is released if it exceeds this size
+/// and is mostly unused.
+const SHRINK_MIN_CAPACITY: usize = 64 << 10;
+
 enum Item {
     Vec(Cursor<Vec<u8>>),
     Bytes(Bytes),
@@ -122,6 +128,39 @@ impl WriteBuffer {
         self.deque.push_back(Item::Zeroes(Zeroes(zeroes)));
     }
 
+    /// Serialize a frame into this buffer.
+    pub fn write_frame<F: FrameIR>(&mut self, frame: F) {
+        frame.serialize_into(self);
+    }
+
+    /// Serialize frames one after another into this buffer.
+    pub fn write_frames<F, I>(&mut self, frames: I)
+    where
+        F: FrameIR,
+        I: IntoIterator<Item = F>,
+    {
+        for frame in frames {
+            self.write_frame(frame);
+        }
+    }
+
+    /// Release capacity of the tail buffer, e. g. after a huge header block
+    /// was written, so the connection does not hold that memory forever.
+    pub fn maybe_shrink(&mut self) {
+        match self.deque.pop_back() {
+            Some(Item::Vec(cursor)) => {
+                let mut tail = WriteBufferTailVec {
+                    write_buffer: self,
+                    position: cursor.position() as usize,
+                    data: cursor.into_inner(),
+                };
+                tail.maybe_shrink();
+            }
+            Some(o) => self.deque.push_back(o),
+            None => {}
+        }
+    }
+
     pub fn tail_vec(&mut self) -> WriteBufferTailVec {
         match self.deque.pop_back() {
             Some(Item::Vec(cursor)) => WriteBufferTailVec {
@@ -196,13 +235,27 @@ impl<'a> WriteBufferTailVec<'a> {
     }
 
     pub fn reserve(&mut self, additional: usize) {
-        if self.remaining() >= additional {
+        if self.data.capacity() - self.data.len() >= additional {
             return;
         }
-        self.compact();
+        // Compaction moves remaining data, so do it only when
+        // it frees a significant part of the buffer to keep appends amortized O(1)
+        if self.position > self.data.capacity() / 2 {
+            self.compact();
+        }
         self.data.reserve(additional);
     }
 
+    pub fn maybe_shrink(&mut self) {
+        let capacity = self.data.capacity();
+        if capacity <= SHRINK_MIN_CAPACITY || capacity <= self.remaining() * 4 {
+            return;
+        }
+        self.compact();
+        self.data
+            .shrink_to(cmp::max(self.remaining() * 2, SHRINK_MIN_CAPACITY));
+    }
+
     pub fn compact(&mut self) {
         self.data.drain(..self.position);
         self.position = 0;
@@ -212,6 +265,32 @@ impl<'a> WriteBufferTailVec<'a> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::solicit::frame::DataFrame;
+    use crate::solicit::frame::HttpFrame;
+    use crate::solicit::frame::PingFrame;
+    use crate::solicit::frame::SettingsFrame;
+    use crate::solicit::frame::WindowUpdateFrame;
+
+    fn frames() -> Vec<HttpFrame> {
+        vec![
+            SettingsFrame::new().into(),
+            WindowUpdateFrame::for_connection(100).into(),
+            PingFrame::with_data(17).into(),
+        ]
+    }
+
+    #[test]
+    fn write_frames() {
+        let mut expected = WriteBuffer::new();
+        for frame in frames() {
+            frame.serialize_into(&mut expected);
+        }
+
+        let mut buf = WriteBuffer::new();
+        buf.write_frames(frames());
+
+        assert_eq!(Into::<Vec<u8>>::into(expected), Into::<Vec<u8>>::into(buf));
+    }
 
     #[test]
     fn remaining() {
@@ -230,4 +309,61 @@ mod test {
         assert_eq!(b'f', buf.get_u8());
         assert_eq!(0, buf.remaining());
     }
+
+    fn tail_capacity(buf: &mut WriteBuffer) -> usize {
+        buf.tail_vec().data.capacity()
+    }
+
+    #[test]
+    fn small_append_drain_capacity_bounded() {
+        let mut buf = WriteBuffer::new();
+        buf.extend_from_slice(&[0; 10]);
+
+        // Buffer is never drained completely, so the same `Vec` is reused
+        for i in 1..100_000u32 {
+            buf.extend_from_slice(&[i as u8; 100]);
+            assert_eq!(110, buf.remaining());
+            assert_eq!(&[(i - 1) as u8; 10][..], &buf.copy_to_bytes(10)[..]);
+            assert_eq!(&[i as u8; 90][..], &buf.copy_to_bytes(90)[..]);
+        }
+
+        assert!(
+            tail_capacity(&mut buf) < 1000,
+            "{}",
+            tail_capacity(&mut buf)
+        );
+    }
+
+    #[test]
+    fn maybe_shrink() {
+        let mut buf = WriteBuffer::new();
+        buf.extend_from_slice(&vec![1; 1 << 20]);
+        buf.extend_from_slice(b"abc");
+        buf.advance(1 << 20);
+        assert!(tail_capacity(&mut buf) >= 1 << 20);
+
+        buf.maybe_shrink();
+        assert!(tail_capacity(&mut buf) <= SHRINK_MIN_CAPACITY);
+        assert_eq!(b"abc".to_vec(), Into::<Vec<u8>>::into(buf));
+    }
+
+    #[test]
+    fn data_frame_payload_not_copied() {
+        let data = Bytes::from(vec![17; 1 << 20]);
+
+        let mut frame = DataFrame::new(1);
+        frame.data = data.clone();
+
+        let mut buf = WriteBuffer::new();
+        buf.write_frame(frame);
+        assert_eq!(9 + data.len(), buf.remaining());
+
+        let mut slices = [IoSlice::new(&[]); 4];
+        let n = buf.chunks_vectored(&mut slices);
+        assert_eq!(2, n);
+        assert_eq!(9, slices[0].len());
+        // Payload is referenced, not copied into contiguous buffer
+        assert_eq!(data.as_ptr(), slices[1].as_ptr());
+        assert_eq!(data.len(), slices[1].len());
+    }
 }
@@ -1,8 +1,155 @@
-#[derive(Default, Debug, Clone)]
-pub struct CommonConf {}
+use crate::common::conn::handshake_settings_frame;
+use crate::result;
+use crate::solicit::session::StreamState;
+use crate::solicit::stream_id::StreamId;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Default, Clone)]
+pub struct CommonConf {
+    /// Close the connection if buffered data cannot be written
+    /// to the socket for this long (e. g. peer is not reading).
+    pub write_timeout: Option<Duration>,
+    /// Send `PING` when no frames were read or written for this long.
+    /// Disabled by default.
+    pub keepalive_interval: Option<Duration>,
+    /// Close the connection with `Error::KeepAliveTimeout` if keepalive
+    /// `PING` is not acknowledged within this time.
+    /// 20 seconds by default.
+    pub keepalive_timeout: Option<Duration>,
+    /// Close the connection with `SETTINGS_TIMEOUT` if peer does not
+    /// acknowledge our `SETTINGS` frame within this time.
+    /// Disabled by default.
+    pub settings_ack_timeout: Option<Duration>,
+    /// Max size of encoded header block (`HEADERS` or `PUSH_PROMISE`
+    /// joined with `CONTINUATION` frames) buffered while reading.
+    /// Connection is closed with `ENHANCE_YOUR_CALM` when exceeded.
+    /// 1 MiB by default.
+    pub max_header_block_size: Option<u32>,
+    /// Max number of `CONTINUATION` frames in a header block.
+    /// Connection is closed with `ENHANCE_YOUR_CALM` when exceeded.
+    /// 256 by default.
+    pub max_continuation_frames: Option<u32>,
+    /// Max size of decoded header list (name and value lengths plus 32 octets
+    /// per header), advertised in `SETTINGS_MAX_HEADER_LIST_SIZE`.
+    /// Decoding is aborted and connection is closed with `ENHANCE_YOUR_CALM`
+    /// when exceeded.
+    /// 64 KiB by default.
+    pub max_header_list_size: Option<u32>,
+    /// Max frame payload size advertised in `SETTINGS_MAX_FRAME_SIZE`,
+    /// must be in range `16384..=16777215`.
+    /// Connection is closed with `FRAME_SIZE_ERROR` when peer sends larger frame.
+    /// 16384 by default.
+    pub max_frame_size: Option<u32>,
+    /// Initial stream receive window advertised in `SETTINGS_INITIAL_WINDOW_SIZE`.
+    /// 65535 by default.
+    pub initial_window_size: Option<u32>,
+    /// Receive windows of streams and connection are grown up to this size
+    /// when the peer is limited by flow control rather than by the network.
+    /// By default windows are not grown.
+    pub max_window_size: Option<u32>,
+    /// Max total size of `DATA` buffered in send and receive queues
+    /// of all streams of a connection. When exceeded, connection stops
+    /// pulling data from user streams and replenishing receive window.
+    /// Unlimited by default.
+    pub max_buffered_data_size: Option<u32>,
+    /// Max size of outgoing data held in memory: frames in the write buffer
+    /// plus `DATA` queued in streams.
+    ///
+    /// The limit is opt-in: it is only respected by `send_data_wait`,
+    /// `poll` of senders and streams pulled with `pull_from_stream`,
+    /// which wait until data is written to the socket. Plain `send_data`
+    /// never waits or fails because of it and enqueues anyway, so callers
+    /// of `send_data` must bound their data themselves.
+    /// By default only peer flow control limits queued data.
+    pub out_buffer_limit: Option<u32>,
+    /// Frames of streams are not moved to the write buffer while it holds
+    /// at least this many bytes. Smaller buffer lets the priority of streams
+    /// which become writable later take effect, larger buffer fills socket
+    /// writes better. Data enqueued by a single call is moved to the buffer
+    /// at once, so the buffer may exceed this size by that much.
+    /// 32768 by default.
+    pub write_buffer_high_watermark: Option<u32>,
+    /// Max number of `WINDOW_UPDATE` frames on closed streams tolerated
+    /// within 10 seconds. Such frames are ignored, but when exceeded,
+    /// connection is closed with `ENHANCE_YOUR_CALM`.
+    /// 1000 by default.
+    pub max_closed_stream_window_updates: Option<u32>,
+    /// Max number of `PING` frames sent by us and not yet acknowledged.
+    /// Sending more fails with `Error::PingInFlight`.
+    /// 1 by default.
+    pub max_outstanding_pings: Option<u32>,
+    /// Max number of `PING` frames received from peer within 10 seconds.
+    /// When exceeded, connection is closed with `ENHANCE_YOUR_CALM`
+    /// instead of queueing more `ACK` frames.
+    /// 1000 by default.
+    pub max_received_pings: Option<u32>,
+    /// Max size of HPACK dynamic table used by our encoder.
+    /// Larger `SETTINGS_HEADER_TABLE_SIZE` advertised by peer is clamped
+    /// to this value (RFC 7541 section 4.2).
+    /// 4096 by default.
+    pub max_encoder_table_size: Option<u32>,
+    /// Names of headers (e. g. `authorization` or `cookie`) always sent
+    /// as HPACK literals never indexed, so intermediaries won't index
+    /// them either. Names are compared case-insensitively.
+    /// None by default.
+    pub never_indexed_headers: Option<Vec<String>>,
+    /// Huffman-code HPACK string literals when that makes them shorter.
+    /// When disabled, literals are always sent raw.
+    /// `true` by default.
+    pub hpack_huffman: Option<bool>,
+    /// Debugging callback invoked on each stream state transition
+    /// with stream id, old state and new state.
+    pub on_stream_state_change:
+        Option<Arc<dyn Fn(StreamId, StreamState, StreamState) + Send + Sync>>,
+}
+
+impl fmt::Debug for CommonConf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CommonConf")
+            .field("write_timeout", &self.write_timeout)
+            .field("keepalive_interval", &self.keepalive_interval)
+            .field("keepalive_timeout", &self.keepalive_timeout)
+            .field("settings_ack_timeout", &self.settings_ack_timeout)
+            .field("max_header_block_size", &self.max_header_block_size)
+            .field("max_continuation_frames", &self.max_continuation_frames)
+            .field("max_header_list_size", &self.max_header_list_size)
+            .field("max_frame_size", &self.max_frame_size)
+            .field("initial_window_size", &self.initial_window_size)
+            .field("max_window_size", &self.max_window_size)
+            .field("max_buffered_data_size", &self.max_buffered_data_size)
+            .field("out_buffer_limit", &self.out_buffer_limit)
+            .field(
+                "write_buffer_high_watermark",
+                &self.write_buffer_high_watermark,
+            )
+            .field(
+                "max_closed_stream_window_updates",
+                &self.max_closed_stream_window_updates,
+            )
+            .field("max_outstanding_pings", &self.max_outstanding_pings)
+            .field("max_received_pings", &self.max_received_pings)
+            .field("max_encoder_table_size", &self.max_encoder_table_size)
+            .field("never_indexed_headers", &self.never_indexed_headers)
+            .field("hpack_huffman", &self.hpack_huffman)
+            .field(
+                "on_stream_state_change",
+                &self.on_stream_state_change.as_ref().map(|_| ".."),
+            )
+            .finish()
+    }
+}
 
 impl CommonConf {
     pub fn new() -> CommonConf {
         Default::default()
     }
+
+    /// Check settings advertised in the handshake are within bounds
+    /// allowed by RFC 7540 section 6.5.2, e. g. `max_frame_size`.
+    pub(crate) fn validate(&self) -> result::Result<()> {
+        handshake_settings_frame(None, Vec::new(), self).validate()?;
+        Ok(())
+    }
 }
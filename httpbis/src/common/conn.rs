@@ -1,23 +1,32 @@
+use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::pin::Pin;
 
+use bytes::Bytes;
+
 use crate::error;
 use crate::result;
 use crate::AnySocketAddr;
 
+use crate::common::window_tuner::SharedRtt;
+use crate::common::window_tuner::WindowTuner;
 use crate::solicit::frame::GoawayFrame;
 use crate::solicit::frame::HttpFrameType;
 use crate::solicit::frame::HttpSetting;
 use crate::solicit::frame::HttpSettings;
+use crate::solicit::frame::PingFrame;
 use crate::solicit::frame::RstStreamFrame;
 use crate::solicit::frame::SettingsFrame;
 use crate::solicit::frame::WindowUpdateFrame;
 use crate::solicit::session::StreamState;
 use crate::solicit::session::StreamStateIdleOrClosed;
 use crate::solicit::DEFAULT_SETTINGS;
+use std::cmp;
 
 use super::closed_streams::*;
 use super::conf::*;
+use super::priority_tree::PriorityTree;
 use super::stream::*;
 use super::stream_map::*;
 use super::types::*;
@@ -28,6 +37,8 @@ pub use crate::resp::Response;
 use crate::client_died_error_holder::ConnDiedType;
 use crate::client_died_error_holder::SomethingDiedErrorHolder;
 use crate::codec::http_decode_read::HttpDecodeRead;
+use crate::codec::http_framed_read::DEFAULT_MAX_CONTINUATION_FRAMES;
+use crate::codec::http_framed_read::DEFAULT_MAX_HEADER_BLOCK_SIZE;
 use crate::codec::queued_write::QueuedWrite;
 use crate::common::conn_read::ConnReadSideCustom;
 use crate::common::conn_write::ConnWriteSideCustom;
@@ -35,8 +46,10 @@ use crate::common::death_aware_channel::death_aware_channel;
 use crate::common::death_aware_channel::DeathAwareReceiver;
 use crate::common::death_aware_channel::DeathAwareSender;
 use crate::common::init_where::InitWhere;
+use crate::common::sliding_window_counter::SlidingWindowCounter;
 use crate::hpack;
 use crate::solicit::stream_id::StreamId;
+use crate::solicit::stream_id::MAX_STREAM_ID;
 use crate::solicit::window_size::NonNegativeWindowSize;
 use crate::solicit::window_size::WindowSize;
 use crate::ErrorCode;
@@ -50,17 +63,122 @@ use futures::future::Future;
 use futures::stream::Stream;
 use futures::task::Context;
 
+use crate::net::shared_socket::SharedSocket;
 use crate::net::socket::SocketStream;
 use std::mem;
 use std::sync::Arc;
 use std::task::Poll;
-use tokio::io::split;
-use tokio::io::ReadHalf;
-use tokio::io::WriteHalf;
+use std::time::Duration;
+use std::time::Instant;
 use tokio::runtime::Handle;
+use tokio::time::Sleep;
+
+const DEFAULT_KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(20);
+/// Opaque data of `PING` frames sent for keepalive
+pub(crate) const KEEPALIVE_PING_OPAQUE_DATA: u64 = 0x6b65_6570_616c_6976;
+/// Opaque data of `PING` frame sent after the first graceful shutdown `GOAWAY`
+pub(crate) const GRACEFUL_SHUTDOWN_PING_OPAQUE_DATA: u64 = 0x7368_7574_646f_776e;
+
+const DEFAULT_MAX_CLOSED_STREAM_WINDOW_UPDATES: u32 = 1000;
+const CLOSED_STREAM_WINDOW_UPDATES_WINDOW: Duration = Duration::from_secs(10);
+
+const DEFAULT_MAX_OUTSTANDING_PINGS: u32 = 1;
+const DEFAULT_MAX_RECEIVED_PINGS: u32 = 1000;
+const RECEIVED_PINGS_WINDOW: Duration = Duration::from_secs(10);
+
+const DEFAULT_MAX_HEADER_LIST_SIZE: u32 = 64 << 10;
+
+const DEFAULT_WRITE_BUFFER_HIGH_WATERMARK: u32 = 0x8000;
+
+/// `SETTINGS` frame sent in the handshake: the override if any,
+/// otherwise side specific settings with settings from `CommonConf`.
+pub(crate) fn handshake_settings_frame(
+    settings_override: Option<SettingsFrame>,
+    mut handshake_settings: Vec<HttpSetting>,
+    conf: &CommonConf,
+) -> SettingsFrame {
+    if let Some(frame) = settings_override {
+        return frame;
+    }
+    if let Some(initial_window_size) = conf.initial_window_size {
+        handshake_settings.push(HttpSetting::InitialWindowSize(initial_window_size));
+    }
+    if let Some(max_frame_size) = conf.max_frame_size {
+        handshake_settings.push(HttpSetting::MaxFrameSize(max_frame_size));
+    }
+    let max_header_list_size = conf
+        .max_header_list_size
+        .unwrap_or(DEFAULT_MAX_HEADER_LIST_SIZE);
+    // Side specific settings may already advertise a limit, keep the smaller
+    match handshake_settings.iter_mut().find_map(|s| match s {
+        HttpSetting::MaxHeaderListSize(size) => Some(size),
+        _ => None,
+    }) {
+        Some(size) => *size = cmp::min(*size, max_header_list_size),
+        None => handshake_settings.push(HttpSetting::MaxHeaderListSize(max_header_list_size)),
+    }
+    SettingsFrame::from_settings(handshake_settings)
+}
+
+const DEFAULT_MAX_ENCODER_TABLE_SIZE: u32 = 4096;
 
 /// Client or server fields of connection
-pub trait SideSpecific: Send + 'static {}
+pub trait SideSpecific: Send + 'static {
+    /// Called when `GOAWAY` frame is sent or received.
+    fn goaway(&self) {}
+
+    /// Handshake `SETTINGS` frame replacing the one built from configuration.
+    fn handshake_settings_override(&self) -> Option<SettingsFrame> {
+        None
+    }
+
+    /// Close the connection before HTTP/2 handshake if `h2`
+    /// was not negotiated with ALPN.
+    fn require_alpn_h2(&self) -> bool {
+        false
+    }
+
+    /// Convert received header names to lower case instead of
+    /// resetting the stream.
+    fn allow_uppercase_headers(&self) -> bool {
+        false
+    }
+
+    /// Max number of fields in a received header block,
+    /// streams with more fields are reset.
+    fn max_header_count(&self) -> Option<usize> {
+        None
+    }
+
+    /// Called when `GOAWAY` frame is received, at most once.
+    fn goaway_received(&self, _frame: &GoawayFrame) {}
+
+    /// Called after peer `SETTINGS` frame is applied.
+    fn peer_settings(&self, _settings: &HttpSettings, _unknown: &BTreeMap<u16, u32>) {}
+
+    /// Settings sent in the initial `SETTINGS` frame.
+    fn handshake_settings(&self) -> Vec<HttpSetting> {
+        vec![HttpSetting::EnablePush(false)]
+    }
+
+    /// Called when connection is dropped, after streams are notified.
+    fn conn_died(&mut self, _error: Arc<error::Error>) {}
+
+    /// Called when `RST_STREAM` frame is received for a stream
+    /// before its initial headers were sent.
+    /// Return `true` to close the connection with `ENHANCE_YOUR_CALM`.
+    fn rst_stream_received(&mut self) -> bool {
+        false
+    }
+}
+
+/// `PING` sent by us and not yet acknowledged
+pub struct PingSent {
+    pub opaque_data: u64,
+    pub sent: Instant,
+    /// Resolved with round-trip time when `ACK` is received
+    pub rtt_tx: Option<oneshot::Sender<result::Result<Duration>>>,
+}
 
 /// HTTP/2 connection state with socket and streams
 pub(crate) struct Conn<T: Types, I: SocketStream> {
@@ -78,34 +196,99 @@ pub(crate) struct Conn<T: Types, I: SocketStream> {
     pub streams: StreamMap<T>,
     /// Last streams known to be closed by peer
     pub peer_closed_streams: ClosedStreams,
+    /// Stream priorities declared by peer
+    pub priority_tree: PriorityTree,
 
     pub last_local_stream_id: StreamId,
     pub last_peer_stream_id: StreamId,
     pub goaway_sent: Option<GoawayFrame>,
     pub goaway_received: Option<GoawayFrame>,
-    pub ping_sent: Option<u64>,
+    /// First graceful shutdown `GOAWAY` is sent, peer streams are
+    /// still accepted until `PING` sent with it is acknowledged
+    pub graceful_shutdown_ping_sent: bool,
+    /// Last peer stream accepted before graceful shutdown `PING` was
+    /// acknowledged, peer streams after it are refused
+    pub graceful_shutdown_last_stream_id: Option<StreamId>,
+    /// `PING` frames sent and not yet acknowledged, oldest first
+    pub pings_sent: VecDeque<PingSent>,
+    pub max_outstanding_pings: usize,
+    /// `PING` frames received from peer
+    pub received_pings: SlidingWindowCounter,
 
     /// Tracks the size of the outbound flow control window
     pub out_window_size: WindowSize,
     /// Tracks the size of the inbound flow control window
     pub in_window_size: NonNegativeWindowSize,
+    /// Picks the size of the inbound flow control window
+    pub in_window_tuner: WindowTuner,
+    /// Cap for stream inbound windows
+    pub max_window_size: u32,
+    /// Last round-trip time measured with `PING`
+    pub rtt: SharedRtt,
 
     /// Window size from pumper point of view
     pub pump_out_window_size: window_size::ConnOutWindowSender,
-
-    pub framed_read: HttpDecodeRead<ReadHalf<I>>,
-
-    pub queued_write: QueuedWrite<WriteHalf<I>>,
+    /// Pump window increase held back while buffers are over the limit
+    pub pump_out_window_withheld: usize,
+    /// Limit of data buffered in all streams
+    pub max_buffered_data_size: Option<usize>,
+    /// Limit of outgoing data in write buffer and stream send queues
+    pub out_buffer_limit: Option<usize>,
+    /// Stream frames are buffered only while write buffer is smaller
+    pub write_buffer_high_watermark: usize,
+    /// `WINDOW_UPDATE` frames received on closed streams
+    pub closed_stream_window_updates: SlidingWindowCounter,
+
+    pub framed_read: HttpDecodeRead<SharedSocket<I>>,
+
+    pub queued_write: QueuedWrite<SharedSocket<I>>,
+    /// Total size of `DATA` frames queued for sending, frame headers included
+    pub out_data_frames_bytes: u64,
     /// The HPACK encoder used to encode headers before sending them on this connection.
     pub encoder: hpack::Encoder,
+    /// Upper bound of encoder dynamic table size requested by peer
+    pub max_encoder_table_size: u32,
     pub write_rx: DeathAwareReceiver<T::ToWriteMessage>,
+    /// Messages from connection handles (e. g. `ClientConn`),
+    /// `None` when all handles are dropped
+    pub handle_rx: Option<DeathAwareReceiver<T::ToWriteMessage>>,
 
     /// Last known peer settings
     pub peer_settings: HttpSettings,
+    /// Last known values of peer settings with identifiers unknown
+    /// to this implementation, at most `MAX_PEER_UNKNOWN_SETTINGS` entries
+    pub peer_unknown_settings: BTreeMap<u16, u32>,
     /// Last our settings acknowledged
     pub our_settings_ack: HttpSettings,
     /// Last our settings sent
     pub our_settings_sent: HttpSettings,
+    /// Times our `SETTINGS` frames were sent and not yet acknowledged, oldest first
+    pub settings_sent_unacked: VecDeque<Instant>,
+    /// Wait for our `SETTINGS` ack this long
+    pub settings_ack_timeout: Option<Duration>,
+    /// Fires when the oldest unacknowledged `SETTINGS` times out
+    pub settings_ack_deadline: Option<Pin<Box<Sleep>>>,
+
+    pub write_timeout: Option<Duration>,
+    /// Fires if no data is written to the socket while write buffer is not empty
+    pub write_deadline: Option<Pin<Box<Sleep>>>,
+    /// Send `PING` after this idle interval
+    pub keepalive_interval: Option<Duration>,
+    /// Wait for keepalive `PING` ack this long
+    pub keepalive_timeout: Duration,
+    /// Last time a frame was read or data was written
+    pub last_activity: Instant,
+    /// Fires when idle interval or `PING` ack timeout elapses
+    pub keepalive_deadline: Option<Pin<Box<Sleep>>>,
+    /// Notified when write buffer is fully written
+    pub flush_waiters: Vec<oneshot::Sender<()>>,
+    /// Write buffer ends with data sent with `send_data_flush`,
+    /// no more stream data is buffered until it is flushed
+    pub flush_boundary_queued: bool,
+    /// Socket shared with `framed_read` and `queued_write`, used to change socket options
+    pub socket: SharedSocket<I>,
+    /// Protocol negotiated with ALPN during TLS handshake
+    pub negotiated_alpn: Option<Vec<u8>>,
 }
 
 impl<T, I> Drop for Conn<T, I>
@@ -115,6 +298,8 @@ where
 {
     fn drop(&mut self) {
         mem::take(&mut self.streams).conn_died(|| self.conn_died_error_holder.error());
+        self.specific
+            .conn_died(self.conn_died_error_holder.raw_error());
     }
 }
 
@@ -125,6 +310,22 @@ pub struct ConnStateSnapshot {
     pub out_window_size: i32,
     pub pump_out_window_size: isize,
     pub out_buf_bytes: usize,
+    /// Total size of `DATA` frames queued for sending, frame headers included.
+    pub out_data_frames_bytes: u64,
+    /// Total size of `DATA` buffered in send and receive queues of all streams.
+    pub buffered_data_size: usize,
+    /// HPACK decoder dynamic table (mirrors the peer encoder table), most recent first.
+    pub hpack_decoder_table: Vec<(Bytes, Bytes)>,
+    /// HPACK decoder dynamic table size in octets.
+    pub hpack_decoder_table_size: usize,
+    /// HPACK encoder dynamic table, most recent first.
+    pub hpack_encoder_table: Vec<(Bytes, Bytes)>,
+    /// HPACK encoder dynamic table size in octets.
+    pub hpack_encoder_table_size: usize,
+    /// Protocol negotiated with ALPN during TLS handshake.
+    pub negotiated_alpn: Option<Vec<u8>>,
+    /// Last round-trip time measured with `PING`, including keepalive `PING`.
+    pub rtt: Option<Duration>,
     pub streams: HashMap<StreamId, HttpStreamStateSnapshot>,
 }
 
@@ -148,9 +349,10 @@ where
     async fn init(
         loop_handle: Handle,
         specific: T::SideSpecific,
-        _conf: CommonConf,
+        conf: CommonConf,
         to_write_tx: DeathAwareSender<T::ToWriteMessage>,
         write_rx: DeathAwareReceiver<T::ToWriteMessage>,
+        handle_rx: DeathAwareReceiver<T::ToWriteMessage>,
         socket: impl Future<Output = crate::Result<I>> + Send,
         peer_addr: AnySocketAddr,
         conn_died_error_holder: SomethingDiedErrorHolder<ConnDiedType>,
@@ -165,8 +367,26 @@ where
             }
         };
 
-        let handshake_settings_frame =
-            SettingsFrame::from_settings(vec![HttpSetting::EnablePush(false)]);
+        let negotiated_alpn = match socket.negotiated_alpn() {
+            Ok(negotiated_alpn) => negotiated_alpn,
+            Err(e) => {
+                warn!("failed to get negotiated ALPN protocol: {}", e);
+                None
+            }
+        };
+        if specific.require_alpn_h2() && negotiated_alpn.as_deref() != Some(&b"h2"[..]) {
+            let alpn = String::from_utf8_lossy(negotiated_alpn.as_deref().unwrap_or_default())
+                .into_owned();
+            warn!("negotiated ALPN protocol is not h2: {:?}", alpn);
+            conn_died_error_holder.set_once(error::Error::AlpnMismatch(alpn));
+            return;
+        }
+
+        let handshake_settings_frame = handshake_settings_frame(
+            specific.handshake_settings_override(),
+            specific.handshake_settings(),
+            &conf,
+        );
 
         let mut sent_settings = DEFAULT_SETTINGS;
         sent_settings.apply_from_frame(&handshake_settings_frame);
@@ -179,40 +399,131 @@ where
 
         debug!("HTTP/2 handshake done");
 
+        let settings_sent = Instant::now();
+
         let in_window_size =
             NonNegativeWindowSize::new(DEFAULT_SETTINGS.initial_window_size as i32);
         let out_window_size = WindowSize::new(DEFAULT_SETTINGS.initial_window_size as i32);
 
-        let pump_window_size = window_size::ConnOutWindowSender::new(out_window_size.size() as u32);
-
-        let (read, write) = split(socket);
+        let out_buffer_limit = conf.out_buffer_limit.map(|l| l as usize);
+        // Window above the limit is released as buffered data is written
+        let pump_window_initial = match out_buffer_limit {
+            Some(limit) => cmp::min(out_window_size.size() as usize, limit),
+            None => out_window_size.size() as usize,
+        };
+        let pump_window_size = window_size::ConnOutWindowSender::new(pump_window_initial as u32);
+
+        // Connection window is replenished up to the target
+        // on the first read if streams windows are larger
+        let max_window_size = conf
+            .max_window_size
+            .unwrap_or(sent_settings.initial_window_size);
+        let rtt = SharedRtt::default();
+        let in_window_tuner = WindowTuner::new(
+            cmp::max(
+                DEFAULT_SETTINGS.initial_window_size,
+                sent_settings.initial_window_size,
+            ),
+            max_window_size,
+            rtt.clone(),
+        );
 
-        let framed_read = HttpDecodeRead::new(read);
+        let socket = SharedSocket::new(socket);
+        let (read, write) = (socket.clone(), socket.clone());
+
+        let framed_read = HttpDecodeRead::new(
+            read,
+            conf.max_header_block_size
+                .unwrap_or(DEFAULT_MAX_HEADER_BLOCK_SIZE),
+            conf.max_continuation_frames
+                .unwrap_or(DEFAULT_MAX_CONTINUATION_FRAMES),
+            conf.max_header_list_size
+                .unwrap_or(DEFAULT_MAX_HEADER_LIST_SIZE),
+            specific.max_header_count(),
+            specific.allow_uppercase_headers(),
+        );
         let queued_write = QueuedWrite::new(write);
 
+        let mut encoder = hpack::Encoder::new();
+        if let Some(names) = &conf.never_indexed_headers {
+            let names: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+            encoder.set_never_indexed(&names);
+        }
+        if let Some(huffman) = conf.hpack_huffman {
+            encoder.set_huffman(huffman);
+        }
+
         Conn {
             peer_addr,
             conn_died_error_holder,
             specific,
             to_write_tx,
-            streams: StreamMap::new(),
+            streams: StreamMap::new(conf.on_stream_state_change.clone()),
             last_local_stream_id: 0,
             last_peer_stream_id: 0,
             loop_handle,
             goaway_sent: None,
             goaway_received: None,
-            ping_sent: None,
+            graceful_shutdown_ping_sent: false,
+            graceful_shutdown_last_stream_id: None,
+            pings_sent: VecDeque::new(),
+            max_outstanding_pings: conf
+                .max_outstanding_pings
+                .unwrap_or(DEFAULT_MAX_OUTSTANDING_PINGS)
+                as usize,
+            received_pings: SlidingWindowCounter::new(
+                conf.max_received_pings
+                    .unwrap_or(DEFAULT_MAX_RECEIVED_PINGS),
+                RECEIVED_PINGS_WINDOW,
+            ),
             pump_out_window_size: pump_window_size,
+            pump_out_window_withheld: out_window_size.size() as usize - pump_window_initial,
+            max_buffered_data_size: conf.max_buffered_data_size.map(|s| s as usize),
+            out_buffer_limit,
+            // Zero would never let stream frames into the buffer
+            write_buffer_high_watermark: cmp::max(
+                1,
+                conf.write_buffer_high_watermark
+                    .unwrap_or(DEFAULT_WRITE_BUFFER_HIGH_WATERMARK),
+            ) as usize,
+            closed_stream_window_updates: SlidingWindowCounter::new(
+                conf.max_closed_stream_window_updates
+                    .unwrap_or(DEFAULT_MAX_CLOSED_STREAM_WINDOW_UPDATES),
+                CLOSED_STREAM_WINDOW_UPDATES_WINDOW,
+            ),
             peer_closed_streams: ClosedStreams::new(),
+            priority_tree: PriorityTree::new(),
             framed_read,
             queued_write,
+            out_data_frames_bytes: 0,
             write_rx,
-            encoder: hpack::Encoder::new(),
+            handle_rx: Some(handle_rx),
+            encoder,
+            max_encoder_table_size: conf
+                .max_encoder_table_size
+                .unwrap_or(DEFAULT_MAX_ENCODER_TABLE_SIZE),
             in_window_size,
+            in_window_tuner,
+            max_window_size,
+            rtt,
             out_window_size,
             peer_settings: DEFAULT_SETTINGS,
+            peer_unknown_settings: BTreeMap::new(),
             our_settings_ack: DEFAULT_SETTINGS,
             our_settings_sent: sent_settings,
+            settings_sent_unacked: VecDeque::from(vec![settings_sent]),
+            settings_ack_timeout: conf.settings_ack_timeout,
+            settings_ack_deadline: None,
+            write_timeout: conf.write_timeout,
+            write_deadline: None,
+            keepalive_interval: conf.keepalive_interval,
+            keepalive_timeout: conf.keepalive_timeout.unwrap_or(DEFAULT_KEEPALIVE_TIMEOUT),
+            last_activity: Instant::now(),
+            keepalive_deadline: None,
+            flush_waiters: Vec::new(),
+            flush_boundary_queued: false,
+            socket,
+            negotiated_alpn,
         }
         .run()
         .await
@@ -221,29 +532,40 @@ where
     pub fn new(
         loop_handle: Handle,
         specific: T::SideSpecific,
-        _conf: CommonConf,
+        conf: CommonConf,
         socket: impl Future<Output = crate::Result<I>> + Send,
         peer_addr: AnySocketAddr,
+        conn_died_error_holder: SomethingDiedErrorHolder<ConnDiedType>,
     ) -> (
         impl Future<Output = ()> + Send,
         DeathAwareSender<T::ToWriteMessage>,
     ) {
-        let conn_died_error_holder = SomethingDiedErrorHolder::new();
-
         let (write_tx, write_rx) = death_aware_channel(conn_died_error_holder.clone());
+        // Separate from `write_tx`, because connection holds `write_tx` itself
+        let (handle_tx, handle_rx) = death_aware_channel(conn_died_error_holder.clone());
 
         let future = Self::init(
             loop_handle,
             specific,
-            _conf,
-            write_tx.clone(),
+            conf,
+            write_tx,
             write_rx,
+            handle_rx,
             socket,
             peer_addr.clone(),
             conn_died_error_holder,
         );
         let ndc = Arc::new(format!("{} {}", T::CONN_NDC, peer_addr));
-        (log_ndc_future(ndc, future), write_tx)
+        (log_ndc_future(ndc, future), handle_tx)
+    }
+
+    /// Tuner for inbound window of new stream
+    pub fn new_stream_window_tuner(&self) -> WindowTuner {
+        WindowTuner::new(
+            self.our_settings_sent.initial_window_size,
+            self.max_window_size,
+            self.rtt.clone(),
+        )
     }
 
     /// Allocate stream id for locally initiated stream
@@ -256,6 +578,8 @@ where
         id
     }
 
+    /// Register a new stream. Its out window starts from the latest
+    /// `SETTINGS_INITIAL_WINDOW_SIZE` received from peer.
     pub fn new_stream_data(
         &mut self,
         stream_id: StreamId,
@@ -288,6 +612,14 @@ where
             out_window_size: self.out_window_size.size(),
             pump_out_window_size: self.pump_out_window_size.get(),
             out_buf_bytes: self.queued_write.queued_bytes_len(),
+            out_data_frames_bytes: self.out_data_frames_bytes,
+            buffered_data_size: self.streams.buffered_data_size(),
+            hpack_decoder_table: self.framed_read.decoder().dynamic_table_entries(),
+            hpack_decoder_table_size: self.framed_read.decoder().dynamic_table_size(),
+            hpack_encoder_table: self.encoder.dynamic_table_entries(),
+            hpack_encoder_table_size: self.encoder.dynamic_table_size(),
+            negotiated_alpn: self.negotiated_alpn.clone(),
+            rtt: self.rtt.get(),
             streams: self.streams.snapshot(),
         }
     }
@@ -365,7 +697,7 @@ where
         }
     }
 
-    fn stream_state(&self, stream_id: StreamId) -> StreamState {
+    pub fn stream_state(&self, stream_id: StreamId) -> StreamState {
         match self.streams.get_stream_state(stream_id) {
             Some(state) => state,
             None => self.stream_state_idle_or_closed(stream_id).into(),
@@ -467,8 +799,49 @@ where
         self.get_stream_maybe_send_error(stream_id, HttpFrameType::Headers)
     }
 
+    /// Space left before `max_buffered_data_size` is reached.
+    pub fn buffered_data_space(&self) -> Option<usize> {
+        self.max_buffered_data_size
+            .map(|max| max.saturating_sub(self.streams.buffered_data_size()))
+    }
+
+    /// Let pumps pull `size` more bytes from user streams.
+    pub fn increase_pump_out_window(&mut self, size: usize) {
+        self.pump_out_window_withheld += size;
+        self.release_pump_out_window();
+    }
+
+    /// Space left before `out_buffer_limit` is reached, counting
+    /// pump window already released as if it was queued.
+    fn out_buffer_space(&self) -> Option<usize> {
+        self.out_buffer_limit.map(|limit| {
+            let released = cmp::max(0, self.pump_out_window_size.get()) as usize;
+            let queued =
+                self.queued_write.queued_bytes_len() + self.streams.out_buffered_data_size();
+            limit.saturating_sub(queued + released)
+        })
+    }
+
+    /// Pump window increase is held back while buffers are full.
+    pub fn release_pump_out_window(&mut self) {
+        if self.pump_out_window_withheld == 0 || self.buffered_data_space() == Some(0) {
+            return;
+        }
+        let increase = match self.out_buffer_space() {
+            Some(space) => cmp::min(space, self.pump_out_window_withheld),
+            None => self.pump_out_window_withheld,
+        };
+        if increase == 0 {
+            return;
+        }
+        self.pump_out_window_withheld -= increase;
+        self.pump_out_window_size.increase(increase);
+    }
+
     pub fn increase_in_window(&mut self, stream_id: StreamId, increase: u32) -> result::Result<()> {
         if let Some(mut stream) = self.streams.get_mut(stream_id) {
+            // Data is released by the handler
+            stream.in_data_released(increase as usize);
             if let Err(_) = stream.stream().in_window_size.try_increase(increase) {
                 return Err(error::Error::StreamInWindowOverflow(
                     stream_id,
@@ -490,8 +863,23 @@ where
         // Always flush outgoing queue
         self.poll_flush(cx)?;
 
+        if self.poll_settings_ack_timeout(cx)? {
+            self.poll_flush(cx)?;
+        }
+
         if self.queued_write.goaway_queued_and_flushed() {
-            info!("GOAWAY written and flushed, closing connection");
+            // Graceful `GOAWAY` lets existing streams complete
+            if self.goaway_sent.is_none() || self.streams.is_empty() {
+                info!("GOAWAY written and flushed, closing connection");
+                return Poll::Ready(Ok(LoopEvent::ExitLoop));
+            }
+        }
+
+        if self.goaway_sent.is_some()
+            && self.streams.is_empty()
+            && self.queued_write.queued_bytes_len() == 0
+        {
+            info!("GOAWAY sent and streams is empty, closing connection");
             return Poll::Ready(Ok(LoopEvent::ExitLoop));
         }
 
@@ -508,27 +896,139 @@ where
             }
         };
 
+        if let Some(handle_rx) = &mut self.handle_rx {
+            match Pin::new(handle_rx).poll_next(cx) {
+                Poll::Pending => {}
+                Poll::Ready(Some(m)) => return Poll::Ready(Ok(LoopEvent::ToWriteMessage(m))),
+                Poll::Ready(None) => {
+                    self.handle_rx = None;
+                    return Poll::Ready(Ok(LoopEvent::HandlesDropped));
+                }
+            }
+        }
+
         match self.poll_recv_http_frame(cx)? {
-            Poll::Ready(m) => return Poll::Ready(Ok(LoopEvent::Frame(m))),
+            Poll::Ready(m) => {
+                self.last_activity = Instant::now();
+                return Poll::Ready(Ok(LoopEvent::Frame(m)));
+            }
             Poll::Pending => {}
         }
 
+        // Read buffer is drained, send window update for the frames processed so far
+        if self.replenish_in_window()? {
+            self.poll_flush(cx)?;
+        }
+
+        self.release_pump_out_window();
+
+        if self.poll_keepalive(cx)? {
+            self.poll_flush(cx)?;
+        }
+
         Poll::Pending
     }
 
+    /// Nobody can start new streams on this connection, so close it
+    /// after already started streams complete.
+    fn process_handles_dropped(&mut self) {
+        if self.goaway_sent.is_some()
+            || self.graceful_shutdown_ping_sent
+            || self.graceful_shutdown_last_stream_id.is_some()
+            || self.goaway_received.is_some()
+            || self.queued_write.goaway_queued()
+        {
+            debug!("all connection handles dropped, connection is already closing");
+            return;
+        }
+
+        debug!("all connection handles dropped, sending GOAWAY");
+        // Not a terminating `GOAWAY`: frames of in-flight streams
+        // are still written after it
+        let frame = GoawayFrame::new(self.last_peer_stream_id, ErrorCode::NoError);
+        self.goaway_sent = Some(frame.clone());
+        self.queued_write.queue_not_goaway(frame);
+        self.specific.goaway();
+    }
+
+    /// Two-phase graceful shutdown (RFC 7540, section 6.8).
+    ///
+    /// First `GOAWAY` with maximum stream id warns the peer, and is followed
+    /// by `PING`. Streams the peer started before it received the `GOAWAY`
+    /// are accepted until the `PING` is acknowledged, i. e. for at least
+    /// one round-trip. Then second `GOAWAY` with the last accepted stream id
+    /// is sent, new peer streams are refused with `REFUSED_STREAM`,
+    /// and the connection is closed when existing streams complete.
+    pub fn graceful_shutdown(&mut self) {
+        if self.graceful_shutdown_ping_sent
+            || self.graceful_shutdown_last_stream_id.is_some()
+            || self.goaway_received.is_some()
+            || self.queued_write.goaway_queued()
+        {
+            debug!("connection is already closing");
+            return;
+        }
+
+        debug!("starting graceful shutdown");
+        self.graceful_shutdown_ping_sent = true;
+        self.queued_write
+            .queue_not_goaway(GoawayFrame::new(MAX_STREAM_ID, ErrorCode::NoError));
+        // Not limited by `max_outstanding_pings` like keepalive `PING`
+        self.pings_sent.push_back(PingSent {
+            opaque_data: GRACEFUL_SHUTDOWN_PING_OPAQUE_DATA,
+            sent: Instant::now(),
+            rtt_tx: None,
+        });
+        self.send_frame_and_notify(PingFrame::with_data(GRACEFUL_SHUTDOWN_PING_OPAQUE_DATA));
+        self.specific.goaway();
+    }
+
+    /// Second phase of graceful shutdown: peer has seen the first `GOAWAY`,
+    /// so streams started after now are refused.
+    pub fn graceful_shutdown_ping_acked(&mut self) {
+        if !self.graceful_shutdown_ping_sent {
+            return;
+        }
+        self.graceful_shutdown_ping_sent = false;
+
+        debug!(
+            "graceful shutdown PING acknowledged, last stream {}",
+            self.last_peer_stream_id
+        );
+        self.graceful_shutdown_last_stream_id = Some(self.last_peer_stream_id);
+        // Connection is closed when existing streams complete
+        let frame = GoawayFrame::new(self.last_peer_stream_id, ErrorCode::NoError);
+        self.goaway_sent = Some(frame.clone());
+        self.queued_write.queue_not_goaway(frame);
+    }
+
     /// Each connection is a single future which polls event and processed them
     async fn next_event(&mut self) -> result::Result<LoopEvent<T>> {
         future::poll_fn(|cx| self.poll_next_event(cx)).await
     }
 
     async fn run_loop(mut self) -> result::Result<()> {
+        match self.process_events().await {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                // Remember the error before `Drop` notifies streams
+                self.conn_died_error_holder.set_once(e);
+                Err(self.conn_died_error_holder.error())
+            }
+        }
+    }
+
+    async fn process_events(&mut self) -> result::Result<()> {
+        self.process_handshake_done()?;
         loop {
             let event = self.next_event().await?;
             match event {
                 LoopEvent::ToWriteMessage(m) => self.process_message(m)?,
                 LoopEvent::Frame(f) => self.process_http_frame_of_goaway(f)?,
+                LoopEvent::HandlesDropped => self.process_handles_dropped(),
                 LoopEvent::ExitLoop => return Ok(()),
             }
+            self.process_pending()?;
         }
     }
 
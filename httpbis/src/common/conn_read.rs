@@ -1,11 +1,17 @@
 use crate::codec::http_decode_read::HttpFrameDecodedOrGoaway;
 use crate::common::conn::Conn;
+use crate::common::conn::SideSpecific;
+use crate::common::conn::GRACEFUL_SHUTDOWN_PING_OPAQUE_DATA;
 use crate::common::conn_write::ConnWriteSideCustom;
 use crate::common::init_where::InitWhere;
+use crate::common::limits::IN_WINDOW_REPLENISH_DIVISOR;
+use crate::common::limits::MAX_PEER_UNKNOWN_SETTINGS;
 use crate::common::stream::DroppedData;
 use crate::common::stream::HttpStreamCommon;
 use crate::common::stream::HttpStreamData;
+use crate::common::stream::HttpStreamDataSpecific;
 use crate::common::stream::InMessageStage;
+use crate::common::stream::InputEndReason;
 use crate::common::stream_map::HttpStreamRef;
 use crate::common::types::Types;
 use crate::error;
@@ -20,12 +26,13 @@ use crate::solicit::frame::HttpFrameType;
 use crate::solicit::frame::HttpSetting;
 use crate::solicit::frame::PingFrame;
 use crate::solicit::frame::PriorityFrame;
+use crate::solicit::frame::PushPromiseDecodedFrame;
 use crate::solicit::frame::RstStreamFrame;
 use crate::solicit::frame::SettingsFrame;
 use crate::solicit::frame::WindowUpdateFrame;
+use crate::solicit::session::StreamState;
 use crate::solicit::stream_id::StreamId;
 use crate::solicit::window_size::MAX_WINDOW_SIZE;
-use crate::solicit::DEFAULT_SETTINGS;
 use crate::solicit_misc::HttpFrameClassified;
 use crate::solicit_misc::HttpFrameConn;
 use crate::solicit_misc::HttpFrameStream;
@@ -34,7 +41,9 @@ use crate::Headers;
 
 use crate::net::socket::SocketStream;
 use futures::task::Context;
+use std::cmp;
 use std::task::Poll;
+use std::time::Instant;
 
 pub(crate) trait ConnReadSideCustom {
     type Types: Types;
@@ -45,6 +54,8 @@ pub(crate) trait ConnReadSideCustom {
         end_stream: EndStream,
         headers: Headers,
     ) -> result::Result<Option<HttpStreamRef<Self::Types>>>;
+
+    fn process_push_promise(&mut self, frame: PushPromiseDecodedFrame) -> result::Result<()>;
 }
 
 impl<T, I> Conn<T, I>
@@ -60,31 +71,60 @@ where
         &mut self,
         cx: &mut Context<'_>,
     ) -> Poll<result::Result<HttpFrameDecodedOrGoaway>> {
-        let max_frame_size = self.our_settings_ack.max_frame_size;
+        // Peer may use the larger size we advertised before our `SETTINGS` is acknowledged
+        let max_frame_size = cmp::max(
+            self.our_settings_ack.max_frame_size,
+            self.our_settings_sent.max_frame_size,
+        );
 
         self.framed_read.poll_http_frame(cx, max_frame_size)
     }
 
+    /// Replenish connection in window with a single `WINDOW_UPDATE`
+    /// covering all `DATA` frames processed since the previous update.
+    ///
+    /// Called when there are no more frames to read immediately,
+    /// so a batch of `DATA` frames produces at most one update.
+    /// Returns `true` if a frame was queued.
+    pub fn replenish_in_window(&mut self) -> result::Result<bool> {
+        let target = self.in_window_tuner.target() as i32;
+        let consumed = target - self.in_window_size.size();
+        if consumed < target / IN_WINDOW_REPLENISH_DIVISOR as i32 {
+            return Ok(false);
+        }
+
+        let target = self.in_window_tuner.replenish(Instant::now()) as i32;
+        let mut increment = (target - self.in_window_size.size()) as u32;
+        // Peer may send at most window size more, so keep
+        // buffered data and window within the limit
+        if let Some(space) = self.buffered_data_space() {
+            let allowed = space.saturating_sub(self.in_window_size.size() as usize);
+            increment = cmp::min(increment as usize, allowed) as u32;
+            if increment == 0 {
+                debug!("buffered data limit reached, not replenishing in window");
+                return Ok(false);
+            }
+        }
+        let old_in_window_size = self.in_window_size.size();
+        self.in_window_size
+            .try_increase(increment)
+            .map_err(|()| error::Error::ConnInWindowOverflow(old_in_window_size, increment))?;
+        debug!(
+            "requesting increase in window: {} -> {}",
+            old_in_window_size,
+            self.in_window_size.size()
+        );
+
+        let window_update = WindowUpdateFrame::for_connection(increment);
+        self.send_frame_and_notify(window_update);
+        Ok(true)
+    }
+
     fn process_data_frame(&mut self, frame: DataFrame) -> result::Result<Option<HttpStreamRef<T>>> {
         let stream_id = frame.get_stream_id();
 
         self.decrease_in_window(frame.payload_len())?;
 
-        let increment_conn =
-        // TODO: need something better
-            if self.in_window_size.size() < (DEFAULT_SETTINGS.initial_window_size / 2) as i32 {
-                let increment = DEFAULT_SETTINGS.initial_window_size;
-                let old_in_window_size = self.in_window_size.size();
-                self.in_window_size.try_increase(increment)
-                    .map_err(|()| error::Error::ConnInWindowOverflow(self.in_window_size.size(), increment))?;
-                let new_in_window_size = self.in_window_size.size();
-                debug!("requesting increase in window: {} -> {}", old_in_window_size, new_in_window_size);
-
-                Some(increment)
-            } else {
-                None
-            };
-
         let mut error = None;
 
         loop {
@@ -109,6 +149,12 @@ where
 
                 let in_rem_content_length = in_rem_content_length - frame.data.len() as u64;
                 stream.stream().in_rem_content_length = Some(in_rem_content_length);
+
+                if frame.is_end_of_stream() && in_rem_content_length != 0 {
+                    warn!("stream data shorter than content-length");
+                    error = Some(ErrorCode::ProtocolError);
+                    break;
+                }
             }
 
             assert_eq!(
@@ -131,16 +177,13 @@ where
                 new_in_window_size
             );
 
+            stream.stream().specific.data_recvd(frame.data.len());
+
             let end_of_stream = frame.is_end_of_stream();
-            stream.stream().data_recvd(frame.data, end_of_stream);
+            stream.data_recvd(frame.data, end_of_stream);
             break;
         }
 
-        if let Some(increment_conn) = increment_conn {
-            let window_update = WindowUpdateFrame::for_connection(increment_conn);
-            self.send_frame_and_notify(window_update);
-        }
-
         if let Some(error) = error {
             self.send_rst_stream(stream_id, error)?;
             return Ok(None);
@@ -155,20 +198,38 @@ where
 
     fn process_ping(&mut self, frame: PingFrame) -> result::Result<()> {
         if frame.is_ack() {
-            if let Some(opaque_data) = self.ping_sent.take() {
-                if opaque_data == frame.opaque_data {
+            let pos = self
+                .pings_sent
+                .iter()
+                .position(|p| p.opaque_data == frame.opaque_data);
+            match (pos, self.pings_sent.front()) {
+                (Some(pos), _) => {
+                    let ping_sent = self.pings_sent.remove(pos).unwrap();
+                    let rtt = ping_sent.sent.elapsed();
+                    self.rtt.set(rtt);
+                    if let Some(rtt_tx) = ping_sent.rtt_tx {
+                        // ignore error, caller might be already dead
+                        let _ = rtt_tx.send(Ok(rtt));
+                    }
+                    if ping_sent.opaque_data == GRACEFUL_SHUTDOWN_PING_OPAQUE_DATA {
+                        self.graceful_shutdown_ping_acked();
+                    }
+                    Ok(())
+                }
+                (None, Some(ping_sent)) => Err(error::Error::PingAckOpaqueDataMismatch(
+                    ping_sent.opaque_data,
+                    frame.opaque_data,
+                )),
+                (None, None) => {
+                    warn!("PING ACK without PING");
                     Ok(())
-                } else {
-                    Err(error::Error::PingAckOpaqueDataMismatch(
-                        opaque_data,
-                        frame.opaque_data,
-                    ))
                 }
-            } else {
-                warn!("PING ACK without PING");
-                Ok(())
             }
         } else {
+            if self.received_pings.record() {
+                warn!("too many PING frames received, closing connection");
+                return self.send_goaway(ErrorCode::EnhanceYourCalm);
+            }
             let ping = PingFrame::new_ack(frame.opaque_data());
             self.send_frame_and_notify(ping);
             Ok(())
@@ -182,13 +243,18 @@ where
 
         let last_stream_id = frame.last_stream_id;
         let raw_error_code = frame.error_code.0;
+        let debug_data = frame.debug_data().clone();
 
+        self.specific.goaway_received(&frame);
         self.goaway_received = Some(frame);
+        self.specific.goaway();
 
+        // `last_stream_id` may be greater than any stream we opened,
+        // in which case all our streams are allowed to complete.
         for (stream_id, mut stream) in self.streams.remove_local_streams_with_id_gt(last_stream_id)
         {
             debug!("removed stream {} because of GOAWAY", stream_id);
-            stream.goaway_recvd(raw_error_code);
+            stream.goaway_recvd(raw_error_code, &debug_data);
         }
 
         Ok(())
@@ -204,6 +270,22 @@ where
             EndStream::No
         };
 
+        if let Some(stream_dep) = frame.stream_dep {
+            self.priority_tree.update(frame.stream_id, stream_dep);
+            self.streams.priority_changed();
+        }
+
+        // Trailers end the body, which must match `content-length` (8.1.2.6)
+        if let Some(mut stream) = self.streams.get_mut(frame.stream_id) {
+            let trailers = stream.stream().in_message_stage == InMessageStage::AfterInitialHeaders;
+            let in_rem_content_length = stream.stream().in_rem_content_length;
+            if trailers && in_rem_content_length.unwrap_or(0) != 0 {
+                warn!("stream trailers before content-length data received");
+                self.send_rst_stream(frame.stream_id, ErrorCode::ProtocolError)?;
+                return Ok(None);
+            }
+        }
+
         self.process_headers(frame.stream_id, end_stream, frame.headers)
     }
 
@@ -211,6 +293,8 @@ where
         &mut self,
         frame: PriorityFrame,
     ) -> result::Result<Option<HttpStreamRef<T>>> {
+        self.priority_tree.update(frame.stream_id, frame.stream_dep);
+        self.streams.priority_changed();
         Ok(self.streams.get_mut(frame.get_stream_id()))
     }
 
@@ -218,6 +302,7 @@ where
         assert!(frame.is_ack());
 
         self.our_settings_ack = self.our_settings_sent;
+        self.settings_sent_unacked.pop_front();
         Ok(())
     }
 
@@ -239,17 +324,43 @@ where
                     let old_size = self.peer_settings.initial_window_size;
                     let delta = (new_size as i32) - (old_size as i32);
 
+                    // 6.9.2
+                    // An endpoint MUST treat a change to SETTINGS_INITIAL_WINDOW_SIZE
+                    // that causes any flow-control window to exceed the maximum size
+                    // as a connection error (Section 5.4.1) of type FLOW_CONTROL_ERROR.
                     if delta != 0 {
-                        self.streams.add_out_window(delta);
+                        if let Err(()) = self.streams.add_out_window(delta) {
+                            warn!(
+                                "SETTINGS_INITIAL_WINDOW_SIZE {} overflows stream window",
+                                new_size
+                            );
+                            self.send_flow_control_error()?;
+                            return Ok(());
+                        }
                     }
                 }
-                HttpSetting::HeaderTableSize(_new_size) => {}
+                HttpSetting::HeaderTableSize(new_size) => {
+                    let new_size = cmp::min(new_size, self.max_encoder_table_size);
+                    self.encoder.set_max_table_size(new_size as usize);
+                }
                 _ => {}
             }
 
             self.peer_settings.apply(setting);
         }
 
+        for (id, val) in frame.unknown_settings {
+            debug!("unknown setting from peer: {}={}", id, val);
+            if self.peer_unknown_settings.len() < MAX_PEER_UNKNOWN_SETTINGS
+                || self.peer_unknown_settings.contains_key(&id)
+            {
+                self.peer_unknown_settings.insert(id, val);
+            }
+        }
+
+        self.specific
+            .peer_settings(&self.peer_settings, &self.peer_unknown_settings);
+
         self.send_ack_settings()?;
 
         Ok(())
@@ -267,19 +378,26 @@ where
         &mut self,
         frame: WindowUpdateFrame,
     ) -> result::Result<Option<HttpStreamRef<T>>> {
-        let mut stream =
-            match self.get_stream_maybe_send_error(frame.stream_id, HttpFrameType::WindowUpdate)? {
-                Some(s) => s,
-                None => {
-                    // 6.9
-                    // WINDOW_UPDATE can be sent by a peer that has sent a frame bearing the
-                    // END_STREAM flag.  This means that a receiver could receive a
-                    // WINDOW_UPDATE frame on a "half-closed (remote)" or "closed" stream.
-                    // A receiver MUST NOT treat this as an error (see Section 5.1).
-                    debug!("WINDOW_UPDATE of unknown stream: {}", frame.get_stream_id());
-                    return Ok(None);
+        let mut stream = match self
+            .get_stream_maybe_send_error(frame.stream_id, HttpFrameType::WindowUpdate)?
+        {
+            Some(s) => s,
+            None => {
+                // 6.9
+                // WINDOW_UPDATE can be sent by a peer that has sent a frame bearing the
+                // END_STREAM flag.  This means that a receiver could receive a
+                // WINDOW_UPDATE frame on a "half-closed (remote)" or "closed" stream.
+                // A receiver MUST NOT treat this as an error (see Section 5.1).
+                debug!("WINDOW_UPDATE of unknown stream: {}", frame.get_stream_id());
+                if self.stream_state(frame.stream_id) == StreamState::Closed
+                    && self.closed_stream_window_updates.record()
+                {
+                    warn!("too many WINDOW_UPDATE frames on closed streams, closing connection");
+                    self.send_goaway(ErrorCode::EnhanceYourCalm)?;
                 }
-            };
+                return Ok(None);
+            }
+        };
 
         // 6.9.1
         // A sender MUST NOT allow a flow-control window to exceed 2^31-1
@@ -330,7 +448,7 @@ where
             old_window_size, self.out_window_size
         );
 
-        self.pump_out_window_size.increase(frame.increment as usize);
+        self.increase_pump_out_window(frame.increment as usize);
         Ok(())
     }
 
@@ -339,21 +457,35 @@ where
         frame: RstStreamFrame,
     ) -> result::Result<Option<HttpStreamRef<T>>> {
         let stream_id = frame.get_stream_id();
-        let dropped_data = if let Some(stream) =
+        let (dropped_data, out_headers_sent) = if let Some(mut stream) =
             self.get_stream_maybe_send_error(stream_id, HttpFrameType::RstStream)?
         {
-            stream.rst_received_remove(frame.error_code())
+            let out_headers_sent = stream.stream().out_headers_sent;
+            (
+                stream.rst_received_remove(frame.raw_error_code()),
+                out_headers_sent,
+            )
         } else {
-            DroppedData { size: 0 }
+            (DroppedData { size: 0 }, true)
         };
 
+        // Queued data of the reset stream is never written,
+        // so it no longer holds back pumps of other streams
         {
             let DroppedData { size } = dropped_data;
-            self.pump_out_window_size.increase(size);
+            self.increase_pump_out_window(size);
         }
 
         self.peer_closed_streams.add(stream_id);
 
+        // Only resets of streams we have not started to respond to
+        // are counted: this is the rapid reset signature, while resetting
+        // a stream which is being answered is a legitimate cancellation.
+        if !out_headers_sent && self.specific.rst_stream_received() {
+            warn!("too many RST_STREAM frames received, closing connection");
+            self.send_goaway(ErrorCode::EnhanceYourCalm)?;
+        }
+
         Ok(None)
     }
 
@@ -375,28 +507,45 @@ where
         // if the stream has an identifier higher than the included last stream identifier.
         if let Some(ref f) = self.goaway_sent.as_ref() {
             if T::init_where(stream_id) != InitWhere::Locally {
-                if stream_id > f.last_stream_id {
+                // Except on graceful shutdown new streams are refused explicitly,
+                // so peer knows they are safe to retry
+                let refuse = self.graceful_shutdown_last_stream_id.is_some()
+                    && matches!(frame, HttpFrameStream::Headers(..));
+                if stream_id > f.last_stream_id && !refuse {
                     return Ok(());
                 }
             }
         }
 
         {
+            // Stream does not exist yet if it is started by this frame
+            let in_message_stage = self
+                .streams
+                .get_mut(stream_id)
+                .map(|mut stream| stream.stream().in_message_stage);
+            let input_end_reason = match (&frame, in_message_stage) {
+                (HttpFrameStream::Data(..), _) => InputEndReason::Data,
+                (_, None) | (_, Some(InMessageStage::Initial)) => InputEndReason::Headers,
+                (_, Some(_)) => InputEndReason::Trailers,
+            };
+
             let stream = match frame {
                 HttpFrameStream::Data(data) => self.process_data_frame(data)?,
                 HttpFrameStream::Headers(headers) => self.process_headers_frame(headers)?,
                 HttpFrameStream::Priority(priority) => self.process_priority_frame(priority)?,
                 HttpFrameStream::RstStream(rst) => self.process_rst_stream_frame(rst)?,
-                HttpFrameStream::PushPromise(_f) => {
-                    return Err(error::Error::NotImplemented("PUSH_PROMISE"))
+                HttpFrameStream::PushPromise(push_promise) => {
+                    self.process_push_promise(push_promise)?;
+                    None
                 }
                 HttpFrameStream::WindowUpdate(window_update) => {
                     self.process_stream_window_update_frame(window_update)?
                 }
             };
 
-            if let Some(stream) = stream {
+            if let Some(mut stream) = stream {
                 if end_of_stream {
+                    stream.stream().input_end_reason = Some(input_end_reason);
                     stream.close_remote();
                 }
             }
@@ -432,13 +581,9 @@ where
         stream_id: StreamId,
         error_code: ErrorCode,
     ) -> result::Result<()> {
-        if let Some(mut stream) = self.streams.get_mut(stream_id) {
-            stream.close_outgoing(error_code);
-        } else {
-            self.queued_write
-                .queue_not_goaway(RstStreamFrame::new(stream_id, error_code));
-        }
-        Ok(())
+        // Peer won't send anything else on this stream, so pending
+        // outgoing data is dropped too
+        self.send_rst_stream(stream_id, error_code)
     }
 
     pub fn process_http_frame_of_goaway(
@@ -447,7 +592,7 @@ where
     ) -> result::Result<()> {
         match m {
             HttpFrameDecodedOrGoaway::Frame(frame) => self.process_http_frame(frame),
-            HttpFrameDecodedOrGoaway::_SendRst(stream_id, error_code) => {
+            HttpFrameDecodedOrGoaway::SendRst(stream_id, error_code) => {
                 self.process_stream_error(stream_id, error_code)
             }
             HttpFrameDecodedOrGoaway::SendGoaway(error_code) => self.send_goaway(error_code),
@@ -6,21 +6,27 @@ use crate::common::types::Types;
 use crate::data_or_headers_with_flag::DataOrHeadersWithFlag;
 
 use crate::common::conn::ConnStateSnapshot;
+use crate::common::conn::PingSent;
+use crate::common::conn::SideSpecific;
+use crate::common::conn::KEEPALIVE_PING_OPAQUE_DATA;
 use crate::common::conn_read::ConnReadSideCustom;
 use crate::common::pump_stream_to_write_loop::PumpStreamToWrite;
 use crate::common::stream::HttpStreamCommand;
 use crate::common::window_size::StreamOutWindowReceiver;
 use crate::data_or_headers::DataOrHeaders;
 
+use crate::error;
 use crate::result;
 use crate::solicit::end_stream::EndStream;
 use crate::solicit::frame::DataFlag;
 use crate::solicit::frame::DataFrame;
 use crate::solicit::frame::Flags;
+use crate::solicit::frame::Frame;
 use crate::solicit::frame::GoawayFrame;
 use crate::solicit::frame::HeadersFlag;
 use crate::solicit::frame::HeadersMultiFrame;
 use crate::solicit::frame::HttpFrame;
+use crate::solicit::frame::PingFrame;
 use crate::solicit::frame::RstStreamFrame;
 use crate::solicit::frame::SettingsFrame;
 use crate::solicit::stream_id::StreamId;
@@ -33,7 +39,10 @@ use futures::task::Context;
 use std::cmp;
 
 use crate::net::socket::SocketStream;
+use std::future::Future;
 use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
 
 pub(crate) trait ConnWriteSideCustom {
     type Types: Types;
@@ -42,6 +51,16 @@ pub(crate) trait ConnWriteSideCustom {
         &mut self,
         message: <Self::Types as Types>::ToWriteMessage,
     ) -> result::Result<()>;
+
+    /// Called after each loop event to process work postponed earlier.
+    fn process_pending(&mut self) -> result::Result<()> {
+        Ok(())
+    }
+
+    /// Called once after handshake, before the first loop event.
+    fn process_handshake_done(&mut self) -> result::Result<()> {
+        Ok(())
+    }
 }
 
 impl<T, I> Conn<T, I>
@@ -67,6 +86,7 @@ where
                 debug!("sending frame {:?}", frame.debug_no_data());
             }
 
+            self.out_data_frames_bytes += frame.serialized_len() as u64;
             self.queued_write.queue_not_goaway(frame);
 
             return;
@@ -87,6 +107,7 @@ where
                 frame.set_flag(DataFlag::EndStream);
             }
 
+            self.out_data_frames_bytes += frame.serialized_len() as u64;
             self.queued_write.queue_not_goaway(frame);
 
             pos = end;
@@ -94,6 +115,15 @@ where
     }
 
     fn write_part_headers(&mut self, stream_id: StreamId, headers: Headers, end_stream: EndStream) {
+        // `HeaderName` constructors lower case or reject upper case names,
+        // so this only catches a broken invariant
+        debug_assert!(
+            headers
+                .iter()
+                .all(|h| !h.name().bytes().any(|b| b.is_ascii_uppercase())),
+            "upper case header name in stream {}",
+            stream_id
+        );
         let mut flags = Flags::new(0);
         if end_stream == EndStream::Yes {
             flags.set(HeadersFlag::EndStream);
@@ -130,16 +160,20 @@ where
     }
 
     fn has_write_buffer_capacity(&self) -> bool {
-        self.queued_write.queued_bytes_len() < 0x8000
+        // Frames after flush boundary are written by the next socket write
+        !self.flush_boundary_queued
+            && self.queued_write.queued_bytes_len() < self.write_buffer_high_watermark
     }
 
     fn pop_outg_for_stream(
         &mut self,
         stream_id: StreamId,
-    ) -> Option<(StreamId, HttpStreamCommand, bool)> {
+    ) -> Option<(StreamId, HttpStreamCommand, bool, bool)> {
         let stream = self.streams.get_mut(stream_id).unwrap();
-        if let (Some(command), stream) = stream.pop_outg_maybe_remove(&mut self.out_window_size) {
-            return Some((stream_id, command, stream.is_some()));
+        if let (Some(command), flush, stream) =
+            stream.pop_outg_maybe_remove(&mut self.out_window_size)
+        {
+            return Some((stream_id, command, flush, stream.is_some()));
         }
 
         None
@@ -153,7 +187,7 @@ where
             return Ok(updated);
         }
 
-        let writable_stream_ids = self.streams.writable_stream_ids();
+        let writable_stream_ids = self.streams.writable_stream_ids(&self.priority_tree);
 
         for &stream_id in &writable_stream_ids {
             loop {
@@ -161,10 +195,14 @@ where
                     return Ok(updated);
                 }
 
-                if let Some((stream_id, part, cont)) = self.pop_outg_for_stream(stream_id) {
+                if let Some((stream_id, part, flush, cont)) = self.pop_outg_for_stream(stream_id) {
                     self.write_part(stream_id, part);
                     updated = true;
 
+                    if flush {
+                        self.flush_boundary_queued = true;
+                    }
+
                     // Stream is removed from map, need to continue to the next stream
                     if !cont {
                         break;
@@ -206,13 +244,17 @@ where
         &mut self,
         stream_id: StreamId,
         part: DataOrHeadersWithFlag,
+        flush: bool,
     ) -> result::Result<()> {
         let stream = self.streams.get_mut(stream_id);
         if let Some(mut stream) = stream {
             stream.push_back_part(part);
+            if flush {
+                stream.stream().outgoing.mark_flush();
+            }
         } else {
             if let DataOrHeaders::Data(data) = part.content {
-                self.pump_out_window_size.increase(data.len());
+                self.increase_pump_out_window(data.len());
             }
         }
         Ok(())
@@ -242,8 +284,8 @@ where
             CommonToWriteMessage::StreamEnd(stream_id, error_code) => {
                 self.process_stream_end(stream_id, error_code)
             }
-            CommonToWriteMessage::StreamEnqueue(stream_id, part) => {
-                self.process_stream_enqueue(stream_id, part)
+            CommonToWriteMessage::StreamEnqueue(stream_id, part, flush) => {
+                self.process_stream_enqueue(stream_id, part, flush)
             }
             CommonToWriteMessage::Pull(stream_id, stream, out_window_receiver) => {
                 self.process_stream_pull(stream_id, stream, out_window_receiver)
@@ -252,24 +294,246 @@ where
                 self.increase_in_window(stream_id, increase)
             }
             CommonToWriteMessage::DumpState(sender) => self.process_dump_state(sender),
+            CommonToWriteMessage::Flush(sender) => {
+                self.flush_waiters.push(sender);
+                Ok(())
+            }
+            CommonToWriteMessage::SetTcpNodelay(no_delay, sender) => {
+                self.process_set_tcp_nodelay(no_delay, sender)
+            }
+            CommonToWriteMessage::Ping(opaque_data, sender) => {
+                self.send_ping(opaque_data, sender);
+                Ok(())
+            }
+            CommonToWriteMessage::GracefulShutdown => {
+                self.graceful_shutdown();
+                Ok(())
+            }
+            CommonToWriteMessage::Goaway(error_code, debug_data) => {
+                self.send_goaway_with_debug(error_code, debug_data)
+            }
+        }
+    }
+
+    /// Send `PING` and resolve `rtt_tx` with round-trip time when `ACK` arrives.
+    ///
+    /// At most `max_outstanding_pings` can be outstanding at a time.
+    pub fn send_ping(
+        &mut self,
+        opaque_data: u64,
+        rtt_tx: oneshot::Sender<result::Result<Duration>>,
+    ) {
+        if self.pings_sent.len() >= self.max_outstanding_pings {
+            // ignore error, caller might be already dead
+            let _ = rtt_tx.send(Err(error::Error::PingInFlight));
+            return;
         }
+
+        self.pings_sent.push_back(PingSent {
+            opaque_data,
+            sent: Instant::now(),
+            rtt_tx: Some(rtt_tx),
+        });
+        self.send_frame_and_notify(PingFrame::with_data(opaque_data));
+    }
+
+    fn process_set_tcp_nodelay(
+        &mut self,
+        no_delay: bool,
+        sender: oneshot::Sender<result::Result<()>>,
+    ) -> result::Result<()> {
+        let r = self
+            .socket
+            .lock()
+            .set_tcp_nodelay(no_delay)
+            .map_err(error::Error::from);
+        // ignore error, caller might be already dead
+        drop(sender.send(r));
+        Ok(())
     }
 
     pub fn send_goaway(&mut self, error_code: ErrorCode) -> result::Result<()> {
+        self.send_goaway_with_debug(error_code, Bytes::new())
+    }
+
+    /// Send `GOAWAY` with given debug data and close the connection.
+    pub fn send_goaway_with_debug(
+        &mut self,
+        error_code: ErrorCode,
+        debug_data: Bytes,
+    ) -> result::Result<()> {
         debug!("requesting to send GOAWAY with code {:?}", error_code);
-        let frame = GoawayFrame::new(self.last_peer_stream_id, error_code);
+        let frame = GoawayFrame::with_debug_data(self.last_peer_stream_id, error_code, debug_data);
         self.queued_write.queue_goaway(frame);
+        self.specific.goaway();
         Ok(())
     }
 
     pub fn poll_flush(&mut self, cx: &mut Context<'_>) -> result::Result<()> {
+        let bytes_written = self.queued_write.bytes_written();
+        self.poll_flush_queue(cx)?;
+        if self.queued_write.bytes_written() != bytes_written {
+            self.last_activity = Instant::now();
+        }
+        if self.queued_write.queued_bytes_len() == 0 {
+            for sender in self.flush_waiters.drain(..) {
+                // ignore error, caller might be already dead
+                let _ = sender.send(());
+            }
+        }
+        self.poll_write_timeout(cx, bytes_written)
+    }
+
+    /// Fail if buffered data is not written for `write_timeout`.
+    fn poll_write_timeout(
+        &mut self,
+        cx: &mut Context<'_>,
+        bytes_written_before: u64,
+    ) -> result::Result<()> {
+        let write_timeout = match self.write_timeout {
+            Some(write_timeout) => write_timeout,
+            None => return Ok(()),
+        };
+
+        if self.queued_write.queued_bytes_len() == 0 {
+            self.write_deadline = None;
+            return Ok(());
+        }
+
+        if self.write_deadline.is_none()
+            || self.queued_write.bytes_written() != bytes_written_before
+        {
+            self.write_deadline = Some(Box::pin(tokio::time::sleep(write_timeout)));
+        }
+
+        match self.write_deadline.as_mut().unwrap().as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                warn!(
+                    "no write progress in {:?} with {} bytes buffered, closing conn",
+                    write_timeout,
+                    self.queued_write.queued_bytes_len()
+                );
+                Err(error::Error::WriteTimeout)
+            }
+            Poll::Pending => Ok(()),
+        }
+    }
+
+    /// Send `PING` when connection is idle for `keepalive_interval`,
+    /// fail if it is not acknowledged within `keepalive_timeout`.
+    ///
+    /// Return `true` if `PING` is queued.
+    pub fn poll_keepalive(&mut self, cx: &mut Context<'_>) -> result::Result<bool> {
+        let keepalive_interval = match self.keepalive_interval {
+            Some(keepalive_interval) => keepalive_interval,
+            None => return Ok(false),
+        };
+
+        let mut ping_queued = false;
+        loop {
+            let deadline = match self.pings_sent.front() {
+                Some(ping_sent) => ping_sent.sent + self.keepalive_timeout,
+                None => self.last_activity + keepalive_interval,
+            };
+
+            if deadline <= Instant::now() {
+                if !self.pings_sent.is_empty() {
+                    warn!(
+                        "PING is not acknowledged in {:?}, closing conn",
+                        self.keepalive_timeout
+                    );
+                    return Err(error::Error::KeepAliveTimeout);
+                }
+
+                debug!(
+                    "connection is idle for {:?}, sending PING",
+                    keepalive_interval
+                );
+                self.pings_sent.push_back(PingSent {
+                    opaque_data: KEEPALIVE_PING_OPAQUE_DATA,
+                    sent: Instant::now(),
+                    rtt_tx: None,
+                });
+                self.send_frame_and_notify(PingFrame::with_data(KEEPALIVE_PING_OPAQUE_DATA));
+                ping_queued = true;
+                continue;
+            }
+
+            let deadline = tokio::time::Instant::from_std(deadline);
+            match &mut self.keepalive_deadline {
+                Some(sleep) if sleep.deadline() == deadline => {}
+                Some(sleep) => sleep.as_mut().reset(deadline),
+                None => {
+                    self.keepalive_deadline = Some(Box::pin(tokio::time::sleep_until(deadline)))
+                }
+            }
+
+            match self.keepalive_deadline.as_mut().unwrap().as_mut().poll(cx) {
+                Poll::Ready(()) => {}
+                Poll::Pending => return Ok(ping_queued),
+            }
+        }
+    }
+
+    /// Send `GOAWAY` with `SETTINGS_TIMEOUT` if our `SETTINGS`
+    /// is not acknowledged within `settings_ack_timeout`.
+    ///
+    /// Return `true` if `GOAWAY` is queued.
+    pub fn poll_settings_ack_timeout(&mut self, cx: &mut Context<'_>) -> result::Result<bool> {
+        let settings_ack_timeout = match self.settings_ack_timeout {
+            Some(settings_ack_timeout) => settings_ack_timeout,
+            None => return Ok(false),
+        };
+
+        let deadline = match self.settings_sent_unacked.front() {
+            Some(&sent) => tokio::time::Instant::from_std(sent + settings_ack_timeout),
+            None => {
+                self.settings_ack_deadline = None;
+                return Ok(false);
+            }
+        };
+
+        match &mut self.settings_ack_deadline {
+            Some(sleep) if sleep.deadline() == deadline => {}
+            Some(sleep) => sleep.as_mut().reset(deadline),
+            None => self.settings_ack_deadline = Some(Box::pin(tokio::time::sleep_until(deadline))),
+        }
+
+        match self
+            .settings_ack_deadline
+            .as_mut()
+            .unwrap()
+            .as_mut()
+            .poll(cx)
+        {
+            Poll::Ready(()) => {
+                warn!(
+                    "SETTINGS is not acknowledged in {:?}, closing conn",
+                    settings_ack_timeout
+                );
+                self.settings_sent_unacked.clear();
+                self.settings_ack_deadline = None;
+                self.send_goaway(ErrorCode::SettingsTimeout)?;
+                Ok(true)
+            }
+            Poll::Pending => Ok(false),
+        }
+    }
+
+    fn poll_flush_queue(&mut self, cx: &mut Context<'_>) -> result::Result<()> {
         self.buffer_outg_conn()?;
         loop {
-            match self.queued_write.poll(cx) {
+            let poll = if self.flush_boundary_queued {
+                self.queued_write.poll_flush_io(cx)
+            } else {
+                self.queued_write.poll(cx)
+            };
+            match poll {
                 Poll::Pending => return Ok(()),
                 Poll::Ready(Err(e)) => return Err(e),
                 Poll::Ready(Ok(())) => {}
             }
+            self.flush_boundary_queued = false;
             let updated = self.buffer_outg_conn()?;
             if !updated {
                 return Ok(());
@@ -282,8 +546,15 @@ where
 // Processed while write loop is not handling network I/O.
 pub enum CommonToWriteMessage {
     IncreaseInWindow(StreamId, u32),
-    StreamEnqueue(StreamId, DataOrHeadersWithFlag),
+    // flag is set to flush socket after this part is written
+    StreamEnqueue(StreamId, DataOrHeadersWithFlag, bool),
     StreamEnd(StreamId, ErrorCode), // send when user provided handler completed the stream
     Pull(StreamId, HttpStreamAfterHeaders, StreamOutWindowReceiver),
     DumpState(oneshot::Sender<ConnStateSnapshot>),
+    // notified when write buffer is written to the socket
+    Flush(oneshot::Sender<()>),
+    SetTcpNodelay(bool, oneshot::Sender<result::Result<()>>),
+    Ping(u64, oneshot::Sender<result::Result<Duration>>),
+    GracefulShutdown,
+    Goaway(ErrorCode, Bytes),
 }
@@ -50,7 +50,12 @@ impl<T: Hash + Eq + Clone> HashSetShallowClone<T> {
         }
     }
 
-    pub fn items(&mut self) -> HashSetShallowCloneItems<T> {
+    /// Items ordered by `sort`, which is only called when the set
+    /// changed since last call or after `reorder`.
+    pub fn items_sorted_by<F>(&mut self, sort: F) -> HashSetShallowCloneItems<T>
+    where
+        F: FnOnce(&mut [T]),
+    {
         // TODO: store delta and update shallow map
         if let None = self.items {
             if self.set.is_empty() {
@@ -58,13 +63,20 @@ impl<T: Hash + Eq + Clone> HashSetShallowClone<T> {
                 return HashSetShallowCloneItems { items: None };
             }
 
-            self.items = Some(Arc::new(self.set.iter().cloned().collect()));
+            let mut items: Vec<T> = self.set.iter().cloned().collect();
+            sort(&mut items);
+            self.items = Some(Arc::new(items));
         }
         HashSetShallowCloneItems {
             items: Some(self.items.as_mut().unwrap().clone()),
         }
     }
 
+    /// Forget the order of items, e. g. when sort criteria changed.
+    pub fn reorder(&mut self) {
+        self.items.take();
+    }
+
     pub fn get(&self, value: &T) -> Option<&T> {
         self.set.get(value)
     }
@@ -107,13 +119,11 @@ mod test {
     fn insert_updates() {
         let mut s = HashSetShallowClone::new();
         s.insert(10);
-        let mut items = s.items().to_vec();
-        items.sort();
+        let items = s.items_sorted_by(|v| v.sort()).to_vec();
         assert_eq!(&[10], &items[..]);
 
         s.insert(20);
-        let mut items = s.items().to_vec();
-        items.sort();
+        let items = s.items_sorted_by(|v| v.sort()).to_vec();
         assert_eq!(&[10, 20], &items[..]);
     }
 
@@ -121,18 +131,28 @@ mod test {
     fn remove_updates() {
         let mut s = HashSetShallowClone::new();
         s.insert(10);
-        let mut items = s.items().to_vec();
-        items.sort();
+        let items = s.items_sorted_by(|v| v.sort()).to_vec();
         assert_eq!(&[10], &items[..]);
 
         s.insert(20);
-        let mut items = s.items().to_vec();
-        items.sort();
+        let items = s.items_sorted_by(|v| v.sort()).to_vec();
         assert_eq!(&[10, 20], &items[..]);
 
         s.remove(&10);
-        let mut items = s.items().to_vec();
-        items.sort();
+        let items = s.items_sorted_by(|v| v.sort()).to_vec();
         assert_eq!(&[20], &items[..]);
     }
+
+    #[test]
+    fn sorted_until_reorder() {
+        let mut s: HashSetShallowClone<u32> = vec![3, 1, 2].into_iter().collect();
+        assert_eq!(vec![1, 2, 3], s.items_sorted_by(|v| v.sort()).to_vec());
+        // cached order is kept
+        assert_eq!(vec![1, 2, 3], s.items_sorted_by(|_| {}).to_vec());
+        s.reorder();
+        assert_eq!(
+            vec![3, 2, 1],
+            s.items_sorted_by(|v| v.sort_by(|a, b| b.cmp(a))).to_vec()
+        );
+    }
 }
@@ -1,14 +1,19 @@
 use crate::common::conn_write::CommonToWriteMessage;
 use crate::common::death_aware_channel::DeathAwareSender;
+use crate::common::limits::IN_WINDOW_REPLENISH_DIVISOR;
 use crate::common::types::Types;
+use crate::common::window_tuner::WindowTuner;
+use crate::error;
 use crate::result;
 use crate::solicit::stream_id::StreamId;
-use crate::solicit::DEFAULT_SETTINGS;
+use crate::solicit::window_size::MAX_WINDOW_SIZE;
+use std::time::Instant;
 
 pub(crate) struct IncreaseInWindow<T: Types> {
     pub stream_id: StreamId,
     pub in_window_size: u32,
     pub to_write_tx: DeathAwareSender<T::ToWriteMessage>,
+    pub tuner: WindowTuner,
 }
 
 impl<T: Types> IncreaseInWindow<T> {
@@ -30,8 +35,16 @@ impl<T: Types> IncreaseInWindow<T> {
 
     pub fn increase_window(&mut self, inc: u32) -> result::Result<()> {
         let old_in_window_size = self.in_window_size;
-        // TODO: do not panic
-        self.in_window_size = self.in_window_size.checked_add(inc).unwrap();
+        self.in_window_size = match self.in_window_size.checked_add(inc) {
+            Some(new) if new <= MAX_WINDOW_SIZE => new,
+            _ => {
+                return Err(error::Error::StreamInWindowOverflow(
+                    self.stream_id,
+                    old_in_window_size as i32,
+                    inc,
+                ))
+            }
+        };
         debug!(
             "requesting increase stream window: {} -> {}",
             old_in_window_size, self.in_window_size
@@ -41,9 +54,10 @@ impl<T: Types> IncreaseInWindow<T> {
     }
 
     pub fn increase_window_auto_above(&mut self, above: u32) -> result::Result<()> {
-        // TODO: overflow check
-        if self.in_window_size < above + DEFAULT_SETTINGS.initial_window_size / 2 {
-            self.increase_window(DEFAULT_SETTINGS.initial_window_size)
+        let threshold = above.saturating_add(self.tuner.target() / IN_WINDOW_REPLENISH_DIVISOR);
+        if self.in_window_size < threshold {
+            let target = self.tuner.replenish(Instant::now());
+            self.increase_window(target)
         } else {
             Ok(())
         }
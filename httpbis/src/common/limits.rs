@@ -0,0 +1,27 @@
+//! Connection-level constants which are not (yet) configurable.
+
+/// Length of HTTP/2 frame header, fixed by RFC 7540 section 4.1.
+pub use crate::solicit::frame::FRAME_HEADER_LEN;
+
+/// At most this many distinct unknown settings identifiers received
+/// from peer are remembered, others are ignored.
+pub const MAX_PEER_UNKNOWN_SETTINGS: usize = 16;
+
+/// Inbound window of a connection or a stream is replenished when less than
+/// `1 / IN_WINDOW_REPLENISH_DIVISOR` of the target window is left.
+///
+/// Replenishing on every frame would double the number of frames on the wire,
+/// waiting until the window is exhausted would stall the peer for a round-trip.
+pub const IN_WINDOW_REPLENISH_DIVISOR: u32 = 2;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn defaults() {
+        assert_eq!(9, FRAME_HEADER_LEN);
+        assert_eq!(16, MAX_PEER_UNKNOWN_SETTINGS);
+        assert_eq!(2, IN_WINDOW_REPLENISH_DIVISOR);
+    }
+}
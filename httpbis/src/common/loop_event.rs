@@ -4,5 +4,7 @@ use crate::common::types::Types;
 pub(crate) enum LoopEvent<T: Types> {
     ToWriteMessage(T::ToWriteMessage),
     Frame(HttpFrameDecodedOrGoaway),
+    /// All senders to connection handle channel are dropped
+    HandlesDropped,
     ExitLoop,
 }
@@ -11,15 +11,20 @@ pub(crate) mod death_aware_channel;
 pub(crate) mod hash_set_shallow_clone;
 pub(crate) mod increase_in_window;
 pub(crate) mod init_where;
+pub(crate) mod limits;
 pub(crate) mod loop_event;
+pub(crate) mod priority_tree;
 pub(crate) mod pump_stream_to_write_loop;
 pub(crate) mod sender;
+pub(crate) mod sliding_window_counter;
 pub(crate) mod stream;
 pub(crate) mod stream_from_network;
 pub(crate) mod stream_handler;
 pub(crate) mod stream_map;
 pub(crate) mod stream_queue;
 pub(crate) mod stream_queue_sync;
+pub(crate) mod token_bucket;
 pub(crate) mod types;
 pub(crate) mod waiters;
 pub(crate) mod window_size;
+pub(crate) mod window_tuner;
@@ -0,0 +1,206 @@
+use crate::solicit::frame::StreamDependency;
+use crate::solicit::stream_id::StreamId;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+
+/// Weight of stream without explicit priority (16, encoded as `weight - 1`).
+const DEFAULT_WEIGHT: u8 = 15;
+
+/// Max number of streams to remember priority for.
+const MAX_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Node {
+    parent: StreamId,
+    weight: u8,
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Node {
+            parent: 0,
+            weight: DEFAULT_WEIGHT,
+        }
+    }
+}
+
+/// Stream dependencies declared by peer in `HEADERS` and `PRIORITY` frames
+/// (section 5.3). Used to decide in which order writable streams are served.
+#[derive(Default)]
+pub struct PriorityTree {
+    nodes: HashMap<StreamId, Node>,
+    lru: VecDeque<StreamId>,
+}
+
+impl PriorityTree {
+    pub fn new() -> PriorityTree {
+        Default::default()
+    }
+
+    fn node(&self, stream_id: StreamId) -> Node {
+        self.nodes.get(&stream_id).cloned().unwrap_or_default()
+    }
+
+    fn is_descendant(&self, stream_id: StreamId, ancestor: StreamId) -> bool {
+        let mut id = stream_id;
+        // bound iterations in case the tree is broken
+        for _ in 0..=self.nodes.len() {
+            match self.nodes.get(&id) {
+                Some(node) if node.parent == ancestor => return true,
+                Some(node) => id = node.parent,
+                None => return false,
+            }
+        }
+        false
+    }
+
+    /// Make the stream depend on another stream.
+    pub fn update(&mut self, stream_id: StreamId, dep: StreamDependency) {
+        if dep.stream_id == stream_id {
+            // rejected by frame parser
+            return;
+        }
+
+        // If a stream is made dependent on one of its own dependencies,
+        // the formerly dependent stream is first moved to be dependent
+        // on the reprioritized stream's previous parent (5.3.3).
+        if self.is_descendant(dep.stream_id, stream_id) {
+            let node = Node {
+                parent: self.node(stream_id).parent,
+                ..self.node(dep.stream_id)
+            };
+            self.insert(dep.stream_id, node);
+        }
+
+        if dep.is_exclusive {
+            for node in self.nodes.values_mut() {
+                if node.parent == dep.stream_id {
+                    node.parent = stream_id;
+                }
+            }
+        }
+
+        self.insert(
+            stream_id,
+            Node {
+                parent: dep.stream_id,
+                weight: dep.weight,
+            },
+        );
+    }
+
+    fn insert(&mut self, stream_id: StreamId, node: Node) {
+        if self.nodes.insert(stream_id, node).is_none() {
+            if self.lru.len() == MAX_SIZE {
+                let remove = self.lru.pop_front().unwrap();
+                self.remove(remove);
+            }
+
+            self.lru.push_back(stream_id);
+        }
+    }
+
+    /// Forget the stream, its dependents are moved to its parent (5.3.4).
+    fn remove(&mut self, stream_id: StreamId) {
+        if let Some(removed) = self.nodes.remove(&stream_id) {
+            for node in self.nodes.values_mut() {
+                if node.parent == stream_id {
+                    node.parent = removed.parent;
+                }
+            }
+        }
+    }
+
+    /// Path from the root to the stream, siblings ordered by weight.
+    fn sort_key(&self, stream_id: StreamId) -> Vec<(Reverse<u8>, StreamId)> {
+        let mut key = Vec::new();
+        let mut id = stream_id;
+        while id != 0 && key.len() <= self.nodes.len() {
+            let node = self.node(id);
+            key.push((Reverse(node.weight), id));
+            id = node.parent;
+        }
+        key.reverse();
+        key
+    }
+
+    /// Order streams so parents are served before their dependents
+    /// and heavier siblings are served first.
+    pub fn sort(&self, stream_ids: &mut [StreamId]) {
+        if self.nodes.is_empty() {
+            stream_ids.sort_unstable();
+            return;
+        }
+        stream_ids.sort_by_cached_key(|&stream_id| self.sort_key(stream_id));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sorted(tree: &PriorityTree, mut stream_ids: Vec<StreamId>) -> Vec<StreamId> {
+        tree.sort(&mut stream_ids);
+        stream_ids
+    }
+
+    #[test]
+    fn no_priority() {
+        let tree = PriorityTree::new();
+        assert_eq!(vec![1, 3, 5], sorted(&tree, vec![5, 1, 3]));
+    }
+
+    #[test]
+    fn chain() {
+        let mut tree = PriorityTree::new();
+        // 7 <- 5 <- 3
+        tree.update(5, StreamDependency::new(7, 15, false));
+        tree.update(3, StreamDependency::new(5, 15, false));
+        assert_eq!(vec![7, 5, 3], sorted(&tree, vec![3, 5, 7]));
+    }
+
+    #[test]
+    fn weight() {
+        let mut tree = PriorityTree::new();
+        tree.update(1, StreamDependency::new(0, 10, false));
+        tree.update(3, StreamDependency::new(0, 200, false));
+        tree.update(5, StreamDependency::new(3, 255, false));
+        tree.update(7, StreamDependency::new(1, 255, false));
+        // subtree of 3 is heavier
+        assert_eq!(vec![3, 5, 1, 7], sorted(&tree, vec![1, 3, 5, 7]));
+    }
+
+    #[test]
+    fn exclusive() {
+        let mut tree = PriorityTree::new();
+        tree.update(3, StreamDependency::new(1, 15, false));
+        tree.update(5, StreamDependency::new(1, 15, false));
+        // 3 and 5 become dependents of 7
+        tree.update(7, StreamDependency::new(1, 15, true));
+        assert_eq!(vec![1, 7, 3, 5], sorted(&tree, vec![5, 3, 7, 1]));
+    }
+
+    #[test]
+    fn depend_on_own_dependency() {
+        let mut tree = PriorityTree::new();
+        // 1 <- 3 <- 5
+        tree.update(3, StreamDependency::new(1, 15, false));
+        tree.update(5, StreamDependency::new(3, 15, false));
+        // 5 is moved to 1, then 3 depends on 5
+        tree.update(3, StreamDependency::new(5, 15, false));
+        assert_eq!(vec![1, 5, 3], sorted(&tree, vec![3, 5, 1]));
+    }
+
+    #[test]
+    fn evict() {
+        let mut tree = PriorityTree::new();
+        for i in 1..=(MAX_SIZE as StreamId + 1) {
+            tree.update(i, StreamDependency::new(i - 1, 15, false));
+        }
+        assert_eq!(MAX_SIZE, tree.nodes.len());
+        assert_eq!(MAX_SIZE, tree.lru.len());
+        // dependent of evicted stream is moved to the root
+        assert_eq!(0, tree.node(2).parent);
+    }
+}
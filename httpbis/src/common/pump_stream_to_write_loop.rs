@@ -75,7 +75,8 @@ impl<T: Types> PumpStreamToWrite<T> {
                         self.out_window.decrease(d.len());
                     }
 
-                    let msg = CommonToWriteMessage::StreamEnqueue(self.stream_id, part.into());
+                    let msg =
+                        CommonToWriteMessage::StreamEnqueue(self.stream_id, part.into(), false);
                     if let Err(e) = self.to_write_tx.unbounded_send(msg.into()) {
                         warn!(
                             "failed to write to channel, probably connection is closed: {:?}",
@@ -6,12 +6,14 @@ use crate::data_or_headers::DataOrHeaders;
 use crate::data_or_headers_with_flag::DataOrHeadersWithFlag;
 use crate::error;
 use crate::result;
+use crate::solicit::header::HeaderError;
 use crate::solicit::stream_id::StreamId;
 use crate::ErrorCode;
 use crate::Headers;
 use crate::HttpStreamAfterHeaders;
 use crate::StreamDead;
 use bytes::Bytes;
+use futures::future;
 use futures::stream::Stream;
 
 use futures::task::Context;
@@ -29,6 +31,7 @@ pub enum SenderState {
 pub enum SendError {
     ConnectionDied(Arc<error::Error>),
     IncorrectState(SenderState),
+    IncorrectHeaders(HeaderError),
 }
 
 struct CanSendData<T: Types> {
@@ -96,15 +99,24 @@ impl<T: Types> CommonSender<T> {
         }
     }
 
+    pub fn stream_id(&self) -> StreamId {
+        self.stream_id
+    }
+
     pub fn send_common(&mut self, message: CommonToWriteMessage) -> Result<(), SendError> {
+        self.send_message(message.into())
+    }
+
+    /// Send side-specific message to the write loop.
+    pub fn send_message(&mut self, message: T::ToWriteMessage) -> Result<(), SendError> {
         // TODO: why client died?
         self.get_can_send()?
             .write_tx
-            .unbounded_send(message.into())
+            .unbounded_send(message)
             .map_err(|e| SendError::ConnectionDied(Arc::new(e)))
     }
 
-    fn send_data_impl(&mut self, data: Bytes, last: bool) -> Result<(), SendError> {
+    fn send_data_impl(&mut self, data: Bytes, last: bool, flush: bool) -> Result<(), SendError> {
         if self.state() != SenderState::ExpectingBodyOrTrailers {
             return Err(SendError::IncorrectState(self.state()));
         }
@@ -116,6 +128,7 @@ impl<T: Types> CommonSender<T> {
                 content: DataOrHeaders::Data(data),
                 last,
             },
+            flush,
         ))?;
         if last {
             self.state.take();
@@ -123,12 +136,27 @@ impl<T: Types> CommonSender<T> {
         Ok(())
     }
 
+    /// Enqueue data ignoring stream window and `out_buffer_limit`.
     pub fn send_data(&mut self, data: Bytes) -> Result<(), SendError> {
-        self.send_data_impl(data, false)
+        self.send_data_impl(data, false, false)
+    }
+
+    /// Wait until stream window and `out_buffer_limit` allow sending,
+    /// then enqueue data.
+    pub async fn send_data_wait(&mut self, data: Bytes) -> Result<(), SendError> {
+        // Dead stream or connection is reported by `send_data`
+        drop(future::poll_fn(|cx| self.poll(cx)).await);
+        self.send_data(data)
+    }
+
+    /// Send data and flush the socket as soon as the data is written,
+    /// without coalescing it with data enqueued after.
+    pub fn send_data_flush(&mut self, data: Bytes) -> Result<(), SendError> {
+        self.send_data_impl(data, false, true)
     }
 
     pub fn send_data_end_of_stream(&mut self, data: Bytes) -> Result<(), SendError> {
-        self.send_data_impl(data, true)
+        self.send_data_impl(data, true, false)
     }
 
     pub fn send_headers(&mut self, headers: Headers) -> Result<(), SendError> {
@@ -150,6 +178,7 @@ impl<T: Types> CommonSender<T> {
                 content: DataOrHeaders::Headers(headers),
                 last,
             },
+            false,
         ))?;
         if last {
             self.state.take();
@@ -170,6 +199,7 @@ impl<T: Types> CommonSender<T> {
                 content: DataOrHeaders::Headers(trailers),
                 last: true,
             },
+            false,
         ))?;
         self.state = None;
         Ok(())
@@ -0,0 +1,68 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Count events within a sliding time window.
+#[derive(Debug)]
+pub(crate) struct SlidingWindowCounter {
+    limit: u32,
+    window: Duration,
+    // Times of recent events, at most `limit + 1`
+    events: VecDeque<Instant>,
+}
+
+impl SlidingWindowCounter {
+    pub fn new(limit: u32, window: Duration) -> SlidingWindowCounter {
+        SlidingWindowCounter {
+            limit,
+            window,
+            events: VecDeque::new(),
+        }
+    }
+
+    fn record_at(&mut self, now: Instant) -> bool {
+        while let Some(&first) = self.events.front() {
+            if now.saturating_duration_since(first) >= self.window {
+                self.events.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        self.events.push_back(now);
+        if self.events.len() > self.limit as usize {
+            self.events.pop_front();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Record an event, return `true` if more than `limit` events
+    /// happened within the window.
+    pub fn record(&mut self) -> bool {
+        self.record_at(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exceed_then_expire() {
+        let start = Instant::now();
+        let mut counter = SlidingWindowCounter::new(3, Duration::from_secs(1));
+
+        assert!(!counter.record_at(start));
+        assert!(!counter.record_at(start + Duration::from_millis(100)));
+        assert!(!counter.record_at(start + Duration::from_millis(200)));
+        assert!(counter.record_at(start + Duration::from_millis(300)));
+
+        // Two first events left the window
+        let later = start + Duration::from_millis(1250);
+        assert!(!counter.record_at(later));
+        assert!(!counter.record_at(later));
+        assert!(counter.record_at(later));
+    }
+}
@@ -43,6 +43,19 @@ pub struct DroppedData {
     pub size: usize,
 }
 
+/// How peer finished sending on the stream.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum InputEndReason {
+    /// Initial `HEADERS` frame with `END_STREAM` flag, no body
+    Headers,
+    /// `DATA` frame with `END_STREAM` flag
+    Data,
+    /// Trailing `HEADERS` frame
+    Trailers,
+    /// `RST_STREAM` frame
+    Rst(ErrorCode),
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct HttpStreamStateSnapshot {
     pub state: StreamState,
@@ -51,6 +64,10 @@ pub struct HttpStreamStateSnapshot {
     pub pump_out_window_size: isize,
     pub queued_out_data_size: usize,
     pub out_data_size: usize,
+    /// How many times sending was paused because stream window was exhausted.
+    pub out_window_stalls: u64,
+    /// `None` if peer has not finished sending yet.
+    pub input_end_reason: Option<InputEndReason>,
 }
 
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
@@ -75,6 +92,15 @@ pub(crate) struct HttpStreamCommon<T: Types> {
     // Incoming remaining content-length
     pub in_rem_content_length: Option<u64>,
     pub in_message_stage: InMessageStage,
+    pub input_end_reason: Option<InputEndReason>,
+    // Data passed to handler and not yet released by window increase
+    pub in_buffered_data_size: usize,
+    // Size of `outgoing` data counted in `StreamMap`
+    pub out_data_size_counted: usize,
+    // Value of `in_buffered_data_size` counted in `StreamMap`
+    pub in_data_size_counted: usize,
+    // Initial headers (e. g. response headers on server) were written
+    pub out_headers_sent: bool,
 }
 
 impl<T: Types> HttpStreamCommon<T> {
@@ -96,6 +122,11 @@ impl<T: Types> HttpStreamCommon<T> {
             pump_out_window,
             in_rem_content_length,
             in_message_stage,
+            input_end_reason: None,
+            in_buffered_data_size: 0,
+            out_data_size_counted: 0,
+            in_data_size_counted: 0,
+            out_headers_sent: false,
         }
     }
 
@@ -107,6 +138,8 @@ impl<T: Types> HttpStreamCommon<T> {
             pump_out_window_size: self.pump_out_window.get(),
             queued_out_data_size: self.outgoing.data_size(),
             out_data_size: self.outgoing.data_size(),
+            out_window_stalls: self.pump_out_window.stalls(),
+            input_end_reason: self.input_end_reason,
         }
     }
 
@@ -202,6 +235,7 @@ impl<T: Types> HttpStreamCommon<T> {
             false
         };
         if pop_headers {
+            self.out_headers_sent = true;
             let r = self.outgoing.pop_front().unwrap();
             let last = self.outgoing.end() == Some(ErrorCode::NoError);
             if last {
@@ -253,29 +287,39 @@ impl<T: Types> HttpStreamCommon<T> {
 
     pub fn data_recvd(&mut self, data: Bytes, last: bool) {
         if let Some(ref mut response_handler) = self.peer_tx {
+            self.in_buffered_data_size += data.len();
             // TODO: reset stream if rx is dead
             drop(response_handler.data_frame(data, last));
         }
     }
 
-    pub fn rst_recvd(&mut self, error_code: ErrorCode) -> DroppedData {
+    pub fn rst_recvd(&mut self, raw_error_code: u32) -> DroppedData {
+        self.input_end_reason = Some(InputEndReason::Rst(ErrorCode::from(raw_error_code)));
         if let Some(response_handler) = self.peer_tx.take() {
-            drop(response_handler.rst(error_code));
+            drop(response_handler.rst(raw_error_code));
         }
         DroppedData {
             size: self.outgoing.data_size(),
         }
     }
 
-    pub fn goaway_recvd(&mut self, _raw_error_code: u32) {
+    pub fn goaway_recvd(&mut self, raw_error_code: u32, debug_data: &Bytes) {
         if let Some(response_handler) = self.peer_tx.take() {
+            let error = match ErrorCode::from(raw_error_code) {
+                // graceful shutdown
+                ErrorCode::NoError => error::Error::GoawayReceived(debug_data.clone()),
+                error_code => error::Error::ConnectionError(error_code, debug_data.clone()),
+            };
             // it is OK to ignore error: handler may be already dead
-            drop(response_handler.error(error::Error::GoawayReceived));
+            drop(response_handler.error(error));
         }
     }
 }
 
-pub(crate) trait HttpStreamDataSpecific: Send + 'static {}
+pub(crate) trait HttpStreamDataSpecific: Send + 'static {
+    /// Called when `DATA` frame payload is received for the stream.
+    fn data_recvd(&self, _len: usize) {}
+}
 
 pub(crate) trait HttpStreamData {
     type Types: Types;
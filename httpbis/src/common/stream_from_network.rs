@@ -3,8 +3,6 @@
 use futures::stream::Stream;
 use std::task::Poll;
 
-use crate::solicit::DEFAULT_SETTINGS;
-
 use crate::result;
 
 use super::stream_queue_sync::StreamQueueSyncReceiver;
@@ -43,13 +41,8 @@ impl<T: Types> Stream for StreamFromNetwork<T> {
         {
             self.increase_in_window.data_frame_processed(b.len() as u32);
 
-            // TODO: use different
             // TODO: increment after process of the frame (i. e. on next poll)
-            let edge = DEFAULT_SETTINGS.initial_window_size / 2;
-            if self.increase_in_window.in_window_size() < edge {
-                let inc = DEFAULT_SETTINGS.initial_window_size;
-                self.increase_in_window.increase_window(inc)?;
-            }
+            self.increase_in_window.increase_window_auto()?;
         }
 
         Poll::Ready(Some(Ok(part)))
@@ -1,6 +1,5 @@
 use crate::error;
 use crate::result;
-use crate::ErrorCode;
 use crate::Headers;
 use bytes::Bytes;
 
@@ -10,8 +9,8 @@ pub(crate) trait StreamHandlerInternal: Send + 'static {
     fn data_frame(&mut self, data: Bytes, end_stream: bool) -> result::Result<()>;
     /// Trailers HEADERS received
     fn trailers(&mut self, trailers: Headers) -> result::Result<()>;
-    /// RST_STREAM frame received
-    fn rst(self, error_code: ErrorCode) -> result::Result<()>;
+    /// RST_STREAM frame received, error code is not interpreted
+    fn rst(self, raw_error_code: u32) -> result::Result<()>;
     /// Any other error
     fn error(self, error: error::Error) -> result::Result<()>;
 }
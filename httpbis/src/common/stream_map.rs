@@ -1,6 +1,9 @@
 use std::collections::hash_map::Entry;
 use std::collections::hash_map::OccupiedEntry;
 use std::collections::HashMap;
+use std::sync::Arc;
+
+use bytes::Bytes;
 
 use super::stream::HttpStreamCommand;
 use super::stream::HttpStreamCommon;
@@ -9,6 +12,7 @@ use super::types::Types;
 use crate::common::hash_set_shallow_clone::HashSetShallowClone;
 use crate::common::hash_set_shallow_clone::HashSetShallowCloneItems;
 use crate::common::init_where::InitWhere;
+use crate::common::priority_tree::PriorityTree;
 use crate::common::stream::DroppedData;
 use crate::data_or_headers::DataOrHeaders;
 use crate::data_or_headers_with_flag::DataOrHeadersWithFlag;
@@ -18,24 +22,48 @@ use crate::solicit::stream_id::StreamId;
 use crate::solicit::window_size::WindowSize;
 use crate::ErrorCode;
 
+pub(crate) type StreamStateChangeCallback =
+    Arc<dyn Fn(StreamId, StreamState, StreamState) + Send + Sync>;
+
 #[derive(Default)]
 pub(crate) struct StreamMap<T: Types> {
     map: HashMap<StreamId, HttpStreamCommon<T>>,
     // This field must be kept in sync with stream state.
     writable_streams: HashSetShallowClone<StreamId>,
+    // Total size of `DATA` in send queues of all streams,
+    // kept in sync like `writable_streams`.
+    out_data_size: usize,
+    // Total size of `DATA` passed to handlers and not yet released,
+    // kept in sync like `out_data_size`.
+    in_data_size: usize,
+    // Number of streams in `map` initiated locally and by peer,
+    // updated on insert and remove.
+    local_streams_count: usize,
+    peer_streams_count: usize,
+    on_state_change: Option<StreamStateChangeCallback>,
 }
 
 /// Reference to a stream within `StreamMap`
 pub(crate) struct HttpStreamRef<'m, T: Types + 'm> {
     entry: OccupiedEntry<'m, StreamId, HttpStreamCommon<T>>,
     writable_streams: &'m mut HashSetShallowClone<StreamId>,
+    out_data_size: &'m mut usize,
+    in_data_size: &'m mut usize,
+    // Either `local_streams_count` or `peer_streams_count`
+    streams_count: &'m mut usize,
+    on_state_change: &'m Option<StreamStateChangeCallback>,
 }
 
 impl<T: Types> StreamMap<T> {
-    pub fn new() -> StreamMap<T> {
+    pub fn new(on_state_change: Option<StreamStateChangeCallback>) -> StreamMap<T> {
         StreamMap {
             map: HashMap::new(),
             writable_streams: HashSetShallowClone::new(),
+            out_data_size: 0,
+            in_data_size: 0,
+            local_streams_count: 0,
+            peer_streams_count: 0,
+            on_state_change,
         }
     }
 
@@ -48,15 +76,25 @@ impl<T: Types> StreamMap<T> {
 
         // unfortunately HashMap doesn't have an API to convert vacant entry into occupied
         let mut stream = self.get_mut(id).unwrap();
+        *stream.streams_count += 1;
         stream.sync_writable();
+        stream.state_changed(StreamState::Idle);
         stream
     }
 
     pub fn get_mut(&mut self, id: StreamId) -> Option<HttpStreamRef<T>> {
+        let streams_count = match T::init_where(id) {
+            InitWhere::Locally => &mut self.local_streams_count,
+            InitWhere::Peer => &mut self.peer_streams_count,
+        };
         match self.map.entry(id) {
             Entry::Occupied(e) => Some(HttpStreamRef {
                 entry: e,
                 writable_streams: &mut self.writable_streams,
+                out_data_size: &mut self.out_data_size,
+                in_data_size: &mut self.in_data_size,
+                streams_count,
+                on_state_change: &self.on_state_change,
             }),
             Entry::Vacant(_) => None,
         }
@@ -86,8 +124,15 @@ impl<T: Types> StreamMap<T> {
             .collect()
     }
 
-    /// Increment or decrement each stream out window
-    pub fn add_out_window(&mut self, delta: i32) {
+    /// Adjust out windows of all streams by `delta`.
+    ///
+    /// Fails without changing any window if a window would overflow.
+    pub fn add_out_window(&mut self, delta: i32) -> Result<(), ()> {
+        for (_, s) in &self.map {
+            let mut out_window_size = s.out_window_size;
+            out_window_size.try_add(delta)?;
+        }
+
         for (_, s) in &mut self.map {
             // In addition to changing the flow-control window for streams
             // that are not yet active, a SETTINGS frame can alter the initial
@@ -97,12 +142,12 @@ impl<T: Types> StreamMap<T> {
             // a receiver MUST adjust the size of all stream flow-control windows
             // that it maintains by the difference between the new value
             // and the old value.
-            // TODO: handle overflow
             s.out_window_size.try_add(delta).unwrap();
             s.pump_out_window.increase(delta as isize);
         }
 
         self.sync_is_writable();
+        Ok(())
     }
 
     /// Remove locally initiated streams with id > given.
@@ -119,7 +164,12 @@ impl<T: Types> StreamMap<T> {
 
         let mut r = Vec::new();
         for r_id in stream_ids {
-            r.push((r_id, self.map.remove(&r_id).unwrap()))
+            let stream = self.map.remove(&r_id).unwrap();
+            self.writable_streams.remove(&r_id);
+            self.out_data_size -= stream.out_data_size_counted;
+            self.in_data_size -= stream.in_data_size_counted;
+            self.local_streams_count -= 1;
+            r.push((r_id, stream))
         }
         r
     }
@@ -128,12 +178,43 @@ impl<T: Types> StreamMap<T> {
         self.map.is_empty()
     }
 
+    /// Number of not yet closed streams initiated by peer.
+    pub fn peer_streams_count(&self) -> usize {
+        self.peer_streams_count
+    }
+
+    /// Number of not yet closed streams initiated by this side.
+    pub fn local_streams_count(&self) -> usize {
+        self.local_streams_count
+    }
+
     pub fn _stream_ids(&self) -> Vec<StreamId> {
         self.map.keys().cloned().collect()
     }
 
-    pub fn writable_stream_ids(&mut self) -> HashSetShallowCloneItems<StreamId> {
-        self.writable_streams.items()
+    /// Writable streams in priority order. Order is computed only
+    /// when the writable set or priority tree changes.
+    pub fn writable_stream_ids(
+        &mut self,
+        priority_tree: &PriorityTree,
+    ) -> HashSetShallowCloneItems<StreamId> {
+        self.writable_streams
+            .items_sorted_by(|ids| priority_tree.sort(ids))
+    }
+
+    /// Must be called after priority tree update.
+    pub fn priority_changed(&mut self) {
+        self.writable_streams.reorder();
+    }
+
+    /// Total size of `DATA` in send and receive queues of all streams.
+    pub fn buffered_data_size(&self) -> usize {
+        self.out_data_size + self.in_data_size
+    }
+
+    /// Total size of `DATA` in send queues of all streams.
+    pub fn out_buffered_data_size(&self) -> usize {
+        self.out_data_size
     }
 
     pub fn snapshot(&self) -> HashMap<StreamId, HttpStreamStateSnapshot> {
@@ -144,7 +225,10 @@ impl<T: Types> StreamMap<T> {
     where
         F: Fn() -> error::Error,
     {
-        for (_, s) in self.map.drain() {
+        for (id, s) in self.map.drain() {
+            if let Some(f) = &self.on_state_change {
+                f(id, s.state, StreamState::Closed);
+            }
             s.conn_died(error());
         }
     }
@@ -167,10 +251,30 @@ impl<'m, T: Types + 'm> HttpStreamRef<'m, T> {
         self.entry.into_mut()
     }
 
+    /// Notify the subscriber if stream state differs from `old_state`.
+    fn state_changed(&self, old_state: StreamState) {
+        let new_state = self.stream_ref().state;
+        if new_state != old_state {
+            if let Some(f) = self.on_state_change {
+                f(self.id(), old_state, new_state);
+            }
+        }
+    }
+
     fn remove(self) {
         let stream_id = self.id();
         debug!("removing stream {}", stream_id);
+        // Stream may be removed without reaching `Closed`, e. g. on `RST_STREAM`
+        let state = self.stream_ref().state;
+        if state != StreamState::Closed {
+            if let Some(f) = self.on_state_change {
+                f(stream_id, state, StreamState::Closed);
+            }
+        }
         self.writable_streams.remove(&stream_id);
+        *self.out_data_size -= self.entry.get().out_data_size_counted;
+        *self.in_data_size -= self.entry.get().in_data_size_counted;
+        *self.streams_count -= 1;
         self.entry.remove();
     }
 
@@ -199,6 +303,17 @@ impl<'m, T: Types + 'm> HttpStreamRef<'m, T> {
     fn sync_writable(&mut self) {
         let writable = self.stream().is_writable();
         self.mark_writable(writable);
+        self.sync_data_size();
+    }
+
+    fn sync_data_size(&mut self) {
+        let stream = self.entry.get_mut();
+        let size = stream.outgoing.data_size();
+        *self.out_data_size = *self.out_data_size - stream.out_data_size_counted + size;
+        stream.out_data_size_counted = size;
+        let size = stream.in_buffered_data_size;
+        *self.in_data_size = *self.in_data_size - stream.in_data_size_counted + size;
+        stream.in_data_size_counted = size;
     }
 
     pub fn remove_if_closed(mut self) -> Option<Self> {
@@ -210,27 +325,45 @@ impl<'m, T: Types + 'm> HttpStreamRef<'m, T> {
         }
     }
 
+    /// Pop next command to write, also return `true` if socket
+    /// must be flushed after the command is written.
     pub fn pop_outg_maybe_remove(
         mut self,
         conn_out_window_size: &mut WindowSize,
-    ) -> (Option<HttpStreamCommand>, Option<Self>) {
+    ) -> (Option<HttpStreamCommand>, bool, Option<Self>) {
         self.check_state();
 
+        let old_state = self.stream_ref().state;
         let r = self.stream().pop_outg(conn_out_window_size);
+        let flush = r.is_some() && self.stream().outgoing.take_flush();
+        self.state_changed(old_state);
 
         self.sync_writable();
 
         let stream = self.remove_if_closed();
-        (r, stream)
+        (r, flush, stream)
     }
 
     // Reset stream and remove it
-    pub fn rst_received_remove(mut self, error_code: ErrorCode) -> DroppedData {
-        let r = self.stream().rst_recvd(error_code);
+    pub fn rst_received_remove(mut self, raw_error_code: u32) -> DroppedData {
+        let r = self.stream().rst_recvd(raw_error_code);
         self.remove();
         r
     }
 
+    /// Pass `DATA` frame payload to the handler.
+    pub fn data_recvd(&mut self, data: Bytes, last: bool) {
+        self.stream().data_recvd(data, last);
+        self.sync_data_size();
+    }
+
+    /// Data passed to the handler is released by window increase.
+    pub fn in_data_released(&mut self, size: usize) {
+        let in_buffered_data_size = &mut self.stream().in_buffered_data_size;
+        *in_buffered_data_size = in_buffered_data_size.saturating_sub(size);
+        self.sync_data_size();
+    }
+
     pub fn try_increase_window_size(&mut self, increment: u32) -> Result<(), ()> {
         let old_window_size = self.stream().out_window_size.size();
 
@@ -265,8 +398,19 @@ impl<'m, T: Types + 'm> HttpStreamRef<'m, T> {
         self.sync_writable();
     }
 
+    /// Half-close without sending anything, e. g. when request was sent
+    /// over HTTP/1.1 before h2c upgrade.
+    pub fn close_local(&mut self) {
+        let old_state = self.stream_ref().state;
+        self.stream().close_local();
+        self.state_changed(old_state);
+        self.sync_writable();
+    }
+
     pub fn close_remote(mut self) {
+        let old_state = self.stream_ref().state;
         self.stream().close_remote();
+        self.state_changed(old_state);
         self.remove_if_closed();
     }
 }
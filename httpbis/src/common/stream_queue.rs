@@ -21,6 +21,10 @@ pub struct StreamQueue {
     // Some(NoError) means data is successfully generated
     end: Option<ErrorCode>,
     data_size: usize,
+    // total size of data ever pushed to the back of the queue
+    pushed_data_size: u64,
+    // `pushed_data_size` values after which socket must be flushed
+    flush_marks: VecDeque<u64>,
 }
 
 impl StreamQueue {
@@ -29,6 +33,8 @@ impl StreamQueue {
             queue: VecDeque::new(),
             end: None,
             data_size: 0,
+            pushed_data_size: 0,
+            flush_marks: VecDeque::new(),
         }
     }
 
@@ -45,9 +51,32 @@ impl StreamQueue {
             return;
         }
         self.data_size += data_size(&part);
+        self.pushed_data_size += data_size(&part) as u64;
         self.queue.push_back(part);
     }
 
+    /// Request socket flush after all data currently queued is popped.
+    pub fn mark_flush(&mut self) {
+        if let Some(_) = self.end {
+            return;
+        }
+        self.flush_marks.push_back(self.pushed_data_size);
+    }
+
+    /// Return `true` if data popped so far passed a flush mark.
+    pub fn take_flush(&mut self) -> bool {
+        let popped_data_size = self.pushed_data_size - self.data_size as u64;
+        let mut flush = false;
+        while let Some(&mark) = self.flush_marks.front() {
+            if mark > popped_data_size {
+                break;
+            }
+            self.flush_marks.pop_front();
+            flush = true;
+        }
+        flush
+    }
+
     pub fn push_back_part(&mut self, part: DataOrHeadersWithFlag) {
         self.push_back(part.content);
         if part.last {
@@ -87,3 +116,30 @@ impl StreamQueue {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytes::Bytes;
+
+    #[test]
+    fn flush_mark() {
+        let mut queue = StreamQueue::new();
+        queue.push_back(DataOrHeaders::Data(Bytes::from_static(b"ab")));
+        queue.mark_flush();
+        queue.push_back(DataOrHeaders::Data(Bytes::from_static(b"cd")));
+        assert!(!queue.take_flush());
+
+        // Partially popped
+        queue.pop_front();
+        queue.push_front(DataOrHeaders::Data(Bytes::from_static(b"b")));
+        assert!(!queue.take_flush());
+
+        queue.pop_front();
+        assert!(queue.take_flush());
+        assert!(!queue.take_flush());
+
+        queue.pop_front();
+        assert!(!queue.take_flush());
+    }
+}
@@ -64,6 +64,10 @@ impl ServerRequestStreamHandler for StreamQueueSyncSender<ServerTypes> {
         self.send(Err(error::Error::RstStreamReceived(error_code)))
     }
 
+    fn rst_raw(&mut self, raw_error_code: u32) -> result::Result<()> {
+        self.send(Err(error::Error::rst_stream_received(raw_error_code)))
+    }
+
     fn error(&mut self, error: error::Error) -> result::Result<()> {
         self.send(Err(error))
     }
@@ -95,6 +99,10 @@ impl ClientResponseStreamHandler for StreamQueueSyncSender<ClientTypes> {
         self.send(Err(error::Error::RstStreamReceived(error_code)))
     }
 
+    fn rst_raw(&mut self, raw_error_code: u32) -> result::Result<()> {
+        self.send(Err(error::Error::rst_stream_received(raw_error_code)))
+    }
+
     fn error(&mut self, error: error::Error) -> result::Result<()> {
         self.send(Err(error))
     }
@@ -0,0 +1,76 @@
+use std::time::Duration;
+use std::time::Instant;
+
+/// Simple token bucket rate limiter.
+///
+/// Bucket holds at most `rate` tokens (i. e. allows a burst of one second worth of events)
+/// and is refilled at `rate` tokens per second.
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    rate: u32,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: u32) -> TokenBucket {
+        TokenBucket::new_at(rate, Instant::now())
+    }
+
+    fn new_at(rate: u32, now: Instant) -> TokenBucket {
+        TokenBucket {
+            rate,
+            tokens: rate as f64,
+            last_refill: now,
+        }
+    }
+
+    fn refill_at(&mut self, now: Instant) {
+        let elapsed: Duration = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.rate as f64).min(self.rate as f64);
+        self.last_refill = now;
+    }
+
+    fn try_take_at(&mut self, now: Instant) -> bool {
+        self.refill_at(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Take a token if available.
+    pub fn try_take(&mut self) -> bool {
+        self.try_take_at(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn burst_then_refill() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new_at(3, start);
+
+        assert!(bucket.try_take_at(start));
+        assert!(bucket.try_take_at(start));
+        assert!(bucket.try_take_at(start));
+        assert!(!bucket.try_take_at(start));
+
+        let later = start + Duration::from_millis(400);
+        assert!(bucket.try_take_at(later));
+        assert!(!bucket.try_take_at(later));
+
+        // Never accumulate more than `rate` tokens
+        let much_later = later + Duration::from_secs(100);
+        for _ in 0..3 {
+            assert!(bucket.try_take_at(much_later));
+        }
+        assert!(!bucket.try_take_at(much_later));
+    }
+}
@@ -2,6 +2,7 @@
 
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicIsize;
+use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
@@ -23,6 +24,8 @@ struct StreamWindowShared {
     task: AtomicBoxOption<std::task::Waker>,
     closed: AtomicBool,
     window_size: AtomicIsize,
+    /// How many times receiver waited for stream window
+    stalls: AtomicU64,
 }
 
 pub struct ConnOutWindowSender {
@@ -57,6 +60,8 @@ impl Drop for StreamOutWindowSender {
 pub struct StreamOutWindowReceiver {
     conn_waiter: Waiter,
     shared: Arc<StreamWindowShared>,
+    /// Last poll returned `Pending` because of stream window
+    stalled: AtomicBool,
 }
 
 impl ConnOutWindowSender {
@@ -76,6 +81,7 @@ impl ConnOutWindowSender {
             window_size: AtomicIsize::new(initial as isize),
             task: AtomicBoxOption::new(),
             closed: AtomicBool::new(false),
+            stalls: AtomicU64::new(0),
         });
 
         let sender = StreamOutWindowSender {
@@ -84,6 +90,7 @@ impl ConnOutWindowSender {
         let receiver = StreamOutWindowReceiver {
             conn_waiter: self.waker.new_waiter(),
             shared: shared,
+            stalled: AtomicBool::new(false),
         };
         (sender, receiver)
     }
@@ -125,6 +132,11 @@ impl StreamOutWindowSender {
     pub fn get(&self) -> isize {
         self.shared.window_size.load(Ordering::SeqCst) as isize
     }
+
+    /// Number of times the receiver had to wait for stream window.
+    pub fn stalls(&self) -> u64 {
+        self.shared.stalls.load(Ordering::SeqCst)
+    }
 }
 
 struct ConnDead;
@@ -199,9 +211,14 @@ impl StreamOutWindowReceiver {
             self.check_stream_closed()?;
 
             if self.shared.window_size.load(Ordering::SeqCst) <= 0 {
+                // count a stall once, not on every spurious wakeup
+                if !self.stalled.swap(true, Ordering::SeqCst) {
+                    self.shared.stalls.fetch_add(1, Ordering::SeqCst);
+                }
                 return Poll::Pending;
             }
         }
+        self.stalled.store(false, Ordering::SeqCst);
 
         self.poll_conn(cx).map_err(|e| e.into())
     }
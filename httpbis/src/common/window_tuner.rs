@@ -0,0 +1,135 @@
+//! Inbound flow-control window auto-tuning.
+
+use std::cmp;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::solicit::window_size::MAX_WINDOW_SIZE;
+
+/// RTT assumed when no `PING` round-trip was measured yet.
+const DEFAULT_RTT: Duration = Duration::from_millis(100);
+
+/// Number of consecutive window-limited round-trips before growing the window.
+const WINDOW_LIMITED_STREAK: u32 = 2;
+
+/// Last round-trip time measured with `PING`, shared between connection
+/// and its streams.
+#[derive(Clone, Default)]
+pub(crate) struct SharedRtt(Arc<AtomicU64>);
+
+impl SharedRtt {
+    pub fn set(&self, rtt: Duration) {
+        // zero means unknown
+        let micros = cmp::max(1, rtt.as_micros() as u64);
+        self.0.store(micros, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> Option<Duration> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => None,
+            micros => Some(Duration::from_micros(micros)),
+        }
+    }
+}
+
+/// Estimates bandwidth-delay product to pick inbound window size.
+///
+/// The window is replenished when half of it is consumed. If that happens
+/// faster than a round-trip, the peer is limited by the window rather than
+/// by the link, so the window is doubled, up to `max`.
+#[derive(Clone)]
+pub(crate) struct WindowTuner {
+    target: u32,
+    max: u32,
+    rtt: SharedRtt,
+    last_replenish: Option<Instant>,
+    window_limited: u32,
+}
+
+impl WindowTuner {
+    pub fn new(target: u32, max: u32, rtt: SharedRtt) -> WindowTuner {
+        WindowTuner {
+            target,
+            max: cmp::min(cmp::max(target, max), MAX_WINDOW_SIZE / 2),
+            rtt,
+            last_replenish: None,
+            window_limited: 0,
+        }
+    }
+
+    /// Window size we want the peer to see.
+    pub fn target(&self) -> u32 {
+        self.target
+    }
+
+    /// Window is about to be replenished, returns new target.
+    pub fn replenish(&mut self, now: Instant) -> u32 {
+        if let Some(last_replenish) = self.last_replenish {
+            let rtt = self.rtt.get().unwrap_or(DEFAULT_RTT);
+            if now.duration_since(last_replenish) < rtt {
+                self.window_limited += 1;
+            } else {
+                self.window_limited = 0;
+            }
+        }
+        self.last_replenish = Some(now);
+
+        if self.window_limited >= WINDOW_LIMITED_STREAK && self.target < self.max {
+            let old_target = self.target;
+            self.target = cmp::min(self.target.saturating_mul(2), self.max);
+            self.window_limited = 0;
+            debug!("window target: {} -> {}", old_target, self.target);
+        }
+
+        self.target
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn grows_when_window_limited() {
+        let rtt = SharedRtt::default();
+        rtt.set(Duration::from_millis(10));
+        let mut tuner = WindowTuner::new(1000, 3000, rtt);
+
+        let start = Instant::now();
+        assert_eq!(1000, tuner.replenish(start));
+        assert_eq!(1000, tuner.replenish(start + Duration::from_millis(1)));
+        assert_eq!(2000, tuner.replenish(start + Duration::from_millis(2)));
+        assert_eq!(2000, tuner.replenish(start + Duration::from_millis(3)));
+        // capped
+        assert_eq!(3000, tuner.replenish(start + Duration::from_millis(4)));
+        assert_eq!(3000, tuner.replenish(start + Duration::from_millis(5)));
+        assert_eq!(3000, tuner.replenish(start + Duration::from_millis(6)));
+    }
+
+    #[test]
+    fn does_not_grow_when_slow() {
+        let rtt = SharedRtt::default();
+        rtt.set(Duration::from_millis(10));
+        let mut tuner = WindowTuner::new(1000, 1000000, rtt);
+
+        let start = Instant::now();
+        assert_eq!(1000, tuner.replenish(start));
+        assert_eq!(1000, tuner.replenish(start + Duration::from_millis(1)));
+        // streak interrupted
+        assert_eq!(1000, tuner.replenish(start + Duration::from_millis(20)));
+        assert_eq!(1000, tuner.replenish(start + Duration::from_millis(40)));
+    }
+
+    #[test]
+    fn max_not_above_initial() {
+        let mut tuner = WindowTuner::new(1000, 500, SharedRtt::default());
+
+        let start = Instant::now();
+        for i in 0..10 {
+            assert_eq!(1000, tuner.replenish(start + Duration::from_micros(i)));
+        }
+    }
+}
@@ -16,8 +16,10 @@ use crate::solicit::header::Headers;
 use crate::data_or_headers::DataOrHeaders;
 use crate::data_or_headers_with_flag::DataOrHeadersWithFlag;
 use crate::data_or_headers_with_flag::DataOrHeadersWithFlagStream;
+use crate::message::SimpleHttpMessage;
 use crate::misc::any_to_string;
 use crate::solicit::end_stream::EndStream;
+use crate::solicit_async::HttpFutureSend;
 use futures::stream::StreamExt;
 use futures::task::Context;
 use std::pin::Pin;
@@ -123,6 +125,30 @@ impl HttpStreamAfterHeaders {
         })
     }
 
+    /// Drop `DATA` frames, keep trailers.
+    pub fn filter_trailers(self) -> HttpStreamAfterHeaders {
+        HttpStreamAfterHeaders::new(self.try_filter(|p| {
+            future::ready(match p {
+                DataOrTrailers::Data(..) => false,
+                DataOrTrailers::Trailers(..) => true,
+            })
+        }))
+    }
+
+    /// Read the stream to the end and collect it into a message
+    /// with given initial headers.
+    ///
+    /// Body is concatenated, and trailers are stored in `trailers` field,
+    /// like `Response::collect` does.
+    pub fn into_simple_message(self, headers: Headers) -> HttpFutureSend<SimpleHttpMessage> {
+        let mut message = SimpleHttpMessage::new();
+        message.headers = headers;
+        Box::pin(self.try_fold(message, |mut message, part| {
+            message.add(part.into_part().content);
+            future::ok(message)
+        }))
+    }
+
     pub(crate) fn into_flag_stream(
         self,
     ) -> impl Stream<Item = result::Result<DataOrHeadersWithFlag>> + Send {
@@ -158,3 +184,30 @@ impl Stream for HttpStreamAfterHeaders {
         Pin::new(&mut self.0).poll_next(context)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn into_simple_message() {
+        let mut trailers = Headers::new();
+        trailers.add("grpc-status", "0");
+
+        let stream = HttpStreamAfterHeaders::new(stream::iter(vec![
+            Ok(DataOrTrailers::intermediate_data(Bytes::from_static(b"ab"))),
+            Ok(DataOrTrailers::intermediate_data(Bytes::from_static(b"cd"))),
+            Ok(DataOrTrailers::Trailers(trailers)),
+        ]));
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let message = rt
+            .block_on(stream.into_simple_message(Headers::ok_200()))
+            .unwrap();
+
+        assert_eq!(200, message.headers.status());
+        assert_eq!(None, message.headers.get_opt("grpc-status"));
+        assert_eq!("0", message.trailers.as_ref().unwrap().get("grpc-status"));
+        assert_eq!(&b"abcd"[..], &message.body.get_bytes()[..]);
+    }
+}
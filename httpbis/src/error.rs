@@ -1,3 +1,4 @@
+use bytes::Bytes;
 use std::error::Error as std_Error;
 use std::fmt;
 use std::io;
@@ -35,6 +36,11 @@ pub enum Error {
     CodeError(ErrorCode),
     /// `RST_STREAM` received.
     RstStreamReceived(ErrorCode),
+    /// `RST_STREAM` with error code unknown to `ErrorCode` received.
+    RstStreamReceivedUnknown(u32),
+    /// `GOAWAY` with error and debug data received,
+    /// stream was not processed by peer.
+    ConnectionError(ErrorCode, Bytes),
     /// Address resolved to empty list.
     AddrResolvedToEmptyList,
     /// Address resolved to more than one address.
@@ -82,6 +88,10 @@ pub enum Error {
     ClientDiedAndReconnectFailed,
     /// Client controller died.
     ClientControllerDied,
+    /// Client or connection future was dropped before completion:
+    /// the event loop was shut down while it was running,
+    /// or the task was cancelled by the executor or the user.
+    ReactorGone,
     /// Channel died.
     // TODO: meaningless
     ChannelDied,
@@ -105,6 +115,10 @@ pub enum Error {
     ExpectingContinuationGotDifferentStreamId(StreamId, StreamId),
     /// `CONTINUATION` frame without headers.
     ContinuationFrameWithoutHeaders,
+    /// Header block joined from `CONTINUATION` frames exceeds limit.
+    HeaderBlockTooLarge(u32),
+    /// Header block is split into too many `CONTINUATION` frames.
+    TooManyContinuationFrames(u32),
     /// Wrong stream id.
     InitiatedStreamWithServerIdFromClient(StreamId),
     /// Wrong stream id.
@@ -121,6 +135,8 @@ pub enum Error {
     ConnInWindowOverflow(i32, u32),
     /// Ping response wrong payload.
     PingAckOpaqueDataMismatch(u64, u64),
+    /// `PING` requested while too many previous `PING` frames are not acknowledged.
+    PingInFlight,
     /// Goaway after goaway.
     GoawayAfterGoaway,
     /// Got `SETTINGS` ack without `SETTINGS` sent.
@@ -128,8 +144,9 @@ pub enum Error {
     /// `GOAWAY`
     // TODO: explain
     Goaway,
-    /// Received `GOAWAY`
-    GoawayReceived,
+    /// Received `GOAWAY` without error (graceful shutdown) with debug data,
+    /// stream was not processed by peer.
+    GoawayReceived(Bytes),
     /// Stream died.
     // TODO: explain
     PullStreamDied,
@@ -139,6 +156,38 @@ pub enum Error {
     RequestIsMadeUsingHttp1,
     /// Listen address is not specified.
     ListenAddrNotSpecified,
+    /// No data could be written to the socket within write timeout.
+    WriteTimeout,
+    /// Keepalive `PING` was not acknowledged within keepalive timeout.
+    KeepAliveTimeout,
+    /// Peer disabled server push with `SETTINGS_ENABLE_PUSH`.
+    PushDisabled,
+    /// `PUSH_PROMISE` can only be sent on a stream initiated by peer.
+    PushOnPushedStream(StreamId),
+    /// Body ended in the middle of gRPC message, number of unread bytes.
+    GrpcMessageTruncated(usize),
+    /// gRPC message length exceeds the limit: length and limit.
+    GrpcMessageTooLarge(u32, u32),
+    /// Response was not received within request timeout.
+    RequestTimeout,
+    /// Negotiated ALPN protocol is not `h2` (empty if none was negotiated).
+    AlpnMismatch(String),
+    /// Body is longer than the limit.
+    BodyTooLarge(usize),
+    /// Server did not switch to h2c, HTTP/1 response status line.
+    H2cUpgradeFailed(String),
+    /// Request `content-type` is not accepted (`None` if header is missing).
+    UnsupportedMediaType(Option<String>),
+}
+
+impl Error {
+    /// Error delivered to stream when `RST_STREAM` is received.
+    pub(crate) fn rst_stream_received(raw_error_code: u32) -> Error {
+        match ErrorCode::try_from(raw_error_code) {
+            Ok(error_code) => Error::RstStreamReceived(error_code),
+            Err(raw_error_code) => Error::RstStreamReceivedUnknown(raw_error_code),
+        }
+    }
 }
 
 fn _assert_error_sync_send() {
@@ -197,6 +246,21 @@ impl fmt::Display for Error {
             Error::TlsError(e) => write!(f, "Encountered TLS error: {}", e),
             Error::CodeError(e) => write!(f, "Encountered HTTP named error: {}", e),
             Error::RstStreamReceived(e) => write!(f, "Received RST_STREAM from peer: {}", e),
+            Error::RstStreamReceivedUnknown(e) => {
+                write!(
+                    f,
+                    "Received RST_STREAM from peer: unknown error code {:#x}",
+                    e
+                )
+            }
+            Error::ConnectionError(e, debug_data) if debug_data.is_empty() => {
+                write!(f, "Received GOAWAY from peer: {}", e)
+            }
+            Error::ConnectionError(e, debug_data) => write!(
+                f,
+                "Received GOAWAY from peer: {}, debug data: {:?}",
+                e, debug_data
+            ),
             Error::InvalidFrame(..) => {
                 write!(f, "Encountered an invalid or unexpected HTTP/2 frame")
             }
@@ -236,6 +300,9 @@ impl fmt::Display for Error {
             ),
             Error::ClientDiedAndReconnectFailed => write!(f, "Client died and reconnect failed"),
             Error::ClientControllerDied => write!(f, "Client controller died"),
+            Error::ReactorGone => {
+                write!(f, "Event loop is shut down or task was cancelled")
+            }
             Error::ChannelDied => write!(f, "Channel died"),
             Error::ConnDied(e) => write!(f, "Conn died: {}", e),
             Error::EofFromStream => write!(f, "EOF from stream"),
@@ -253,6 +320,15 @@ impl fmt::Display for Error {
                 HttpFrameType::Continuation,
                 HttpFrameType::Headers
             ),
+            Error::HeaderBlockTooLarge(limit) => {
+                write!(f, "Header block exceeds limit of {} bytes", limit)
+            }
+            Error::TooManyContinuationFrames(limit) => write!(
+                f,
+                "Header block exceeds limit of {} {} frames",
+                limit,
+                HttpFrameType::Continuation
+            ),
             Error::InitiatedStreamWithServerIdFromClient(stream_id) => write!(
                 f,
                 "Initiated stream with server id from client: {}",
@@ -268,6 +344,7 @@ impl fmt::Display for Error {
             Error::PingAckOpaqueDataMismatch(_, _) => {
                 write!(f, "{} ack opaque data mismatch", HttpFrameType::Ping)
             }
+            Error::PingInFlight => write!(f, "{} already in flight", HttpFrameType::Ping),
             Error::GoawayAfterGoaway => write!(
                 f,
                 "{} after {}",
@@ -281,11 +358,49 @@ impl fmt::Display for Error {
                 HttpFrameType::Settings
             ),
             Error::Goaway => write!(f, "{}", HttpFrameType::Goaway),
-            Error::GoawayReceived => write!(f, "{} received", HttpFrameType::Goaway),
+            Error::GoawayReceived(debug_data) if debug_data.is_empty() => {
+                write!(f, "{} received", HttpFrameType::Goaway)
+            }
+            Error::GoawayReceived(debug_data) => write!(
+                f,
+                "{} received, debug data: {:?}",
+                HttpFrameType::Goaway,
+                debug_data
+            ),
             Error::PullStreamDied => write!(f, "Pull stream died"),
             Error::PayloadTooLarge(_, _) => write!(f, "Payload too large"),
             Error::RequestIsMadeUsingHttp1 => write!(f, "Request is made using HTTP/1"),
             Error::ListenAddrNotSpecified => write!(f, "Listen addr not specified"),
+            Error::WriteTimeout => write!(f, "Write timeout"),
+            Error::KeepAliveTimeout => {
+                write!(f, "Keepalive {} ack timeout", HttpFrameType::Ping)
+            }
+            Error::PushDisabled => write!(f, "Push disabled by peer"),
+            Error::PushOnPushedStream(stream_id) => write!(
+                f,
+                "Cannot send {} on pushed stream {}",
+                HttpFrameType::PushPromise,
+                stream_id
+            ),
+            Error::GrpcMessageTruncated(len) => {
+                write!(f, "Body ended inside gRPC message, {} bytes left", len)
+            }
+            Error::GrpcMessageTooLarge(len, max) => write!(
+                f,
+                "gRPC message length {} exceeds limit of {} bytes",
+                len, max
+            ),
+            Error::RequestTimeout => write!(f, "Request timeout"),
+            Error::AlpnMismatch(alpn) => {
+                write!(f, "ALPN mismatch: negotiated {:?}, expected \"h2\"", alpn)
+            }
+            Error::BodyTooLarge(limit) => write!(f, "Body exceeds limit of {} bytes", limit),
+            Error::H2cUpgradeFailed(status_line) => {
+                write!(f, "Server did not switch to h2c: {:?}", status_line)
+            }
+            Error::UnsupportedMediaType(content_type) => {
+                write!(f, "Unsupported media type: {:?}", content_type)
+            }
         }
     }
 }
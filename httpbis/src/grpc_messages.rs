@@ -0,0 +1,178 @@
+//! Length-delimited messages in the body as used by gRPC.
+
+use std::pin::Pin;
+use std::task::Poll;
+
+use bytes::Buf;
+use bytes::Bytes;
+use bytes::BytesMut;
+use futures::stream::Stream;
+use futures::task::Context;
+
+use crate::error;
+use crate::result;
+
+/// Compression flag (1 byte) and message length (4 bytes, big endian).
+const GRPC_HEADER_LEN: usize = 5;
+
+/// Default limit of a single message length, same as in gRPC implementations.
+pub(crate) const DEFAULT_GRPC_MAX_MESSAGE_LEN: u32 = 4 << 20;
+
+/// Reassemble messages prefixed with gRPC header from a stream of body chunks.
+///
+/// Chunk boundaries (`DATA` frames) are not related to message boundaries:
+/// a message may span several chunks, and a chunk may contain several messages.
+///
+/// Message length is checked as soon as the header is received,
+/// so no more than one chunk past the header is buffered for a message
+/// longer than `max_message_len`.
+pub(crate) struct GrpcMessages<S> {
+    data: S,
+    buf: BytesMut,
+    max_message_len: u32,
+    done: bool,
+}
+
+impl<S> GrpcMessages<S>
+where
+    S: Stream<Item = result::Result<Bytes>> + Unpin,
+{
+    pub fn new(data: S, max_message_len: u32) -> GrpcMessages<S> {
+        GrpcMessages {
+            data,
+            buf: BytesMut::new(),
+            max_message_len,
+            done: false,
+        }
+    }
+
+    fn next_message(&mut self) -> result::Result<Option<Bytes>> {
+        if self.buf.len() < GRPC_HEADER_LEN {
+            return Ok(None);
+        }
+
+        if self.buf[0] != 0 {
+            return Err(error::Error::NotImplemented("compressed gRPC message"));
+        }
+
+        let len = u32::from_be_bytes([self.buf[1], self.buf[2], self.buf[3], self.buf[4]]);
+        if len > self.max_message_len {
+            return Err(error::Error::GrpcMessageTooLarge(len, self.max_message_len));
+        }
+        if self.buf.len() < GRPC_HEADER_LEN + len as usize {
+            return Ok(None);
+        }
+
+        self.buf.advance(GRPC_HEADER_LEN);
+        Ok(Some(self.buf.split_to(len as usize).freeze()))
+    }
+}
+
+impl<S> Stream for GrpcMessages<S>
+where
+    S: Stream<Item = result::Result<Bytes>> + Unpin,
+{
+    type Item = result::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if self.done {
+                return Poll::Ready(None);
+            }
+
+            match self.next_message() {
+                Ok(Some(message)) => return Poll::Ready(Some(Ok(message))),
+                Ok(None) => {}
+                Err(e) => {
+                    self.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+
+            match Pin::new(&mut self.data).poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Ok(data))) => self.buf.extend_from_slice(&data),
+                Poll::Ready(Some(Err(e))) => {
+                    self.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Ready(None) => {
+                    self.done = true;
+                    if !self.buf.is_empty() {
+                        let len = self.buf.len();
+                        return Poll::Ready(Some(Err(error::Error::GrpcMessageTruncated(len))));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::stream;
+    use futures::stream::StreamExt;
+
+    fn messages_max_len(
+        chunks: Vec<&'static [u8]>,
+        max_message_len: u32,
+    ) -> Vec<result::Result<Bytes>> {
+        let data = stream::iter(chunks.into_iter().map(|c| Ok(Bytes::from_static(c))));
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(GrpcMessages::new(data, max_message_len).collect())
+    }
+
+    fn messages(chunks: Vec<&'static [u8]>) -> Vec<result::Result<Bytes>> {
+        messages_max_len(chunks, DEFAULT_GRPC_MAX_MESSAGE_LEN)
+    }
+
+    #[test]
+    fn split_and_joined() {
+        let messages = messages(vec![b"\0\0\0", b"\0\x02a", b"b\0\0\0\0\x01c\0\0\0\0\0"]);
+        let messages: Vec<Bytes> = messages.into_iter().map(|m| m.unwrap()).collect();
+        assert_eq!(
+            vec![
+                Bytes::from_static(b"ab"),
+                Bytes::from_static(b"c"),
+                Bytes::new()
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let messages = messages(vec![b"\0\0\0\0\x01c", b"\0\0\0\0\x02d"]);
+        assert_eq!(2, messages.len());
+        assert_eq!(&b"c"[..], &messages[0].as_ref().unwrap()[..]);
+        match messages[1] {
+            Err(error::Error::GrpcMessageTruncated(6)) => {}
+            ref r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn too_large() {
+        let messages = messages_max_len(vec![b"\0\0\0\0\x02ab", b"\0\0\0\0\x03", b"abc"], 2);
+        assert_eq!(2, messages.len());
+        assert_eq!(&b"ab"[..], &messages[0].as_ref().unwrap()[..]);
+        match messages[1] {
+            Err(error::Error::GrpcMessageTooLarge(3, 2)) => {}
+            ref r => panic!("{:?}", r),
+        }
+
+        // Length prefix declaring 4 GiB
+        match messages_max_len(vec![b"\0\xff\xff\xff\xff"], DEFAULT_GRPC_MAX_MESSAGE_LEN)[..] {
+            [Err(error::Error::GrpcMessageTooLarge(0xffffffff, DEFAULT_GRPC_MAX_MESSAGE_LEN))] => {}
+            ref r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn compressed() {
+        let messages = messages(vec![b"\x01\0\0\0\x01c"]);
+        assert_eq!(1, messages.len());
+        assert!(messages[0].is_err());
+    }
+}
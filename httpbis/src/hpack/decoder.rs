@@ -14,6 +14,7 @@ use super::huffman::HuffmanDecoderError;
 
 use super::static_table::StaticTable;
 use super::HeaderTable;
+use super::Stats;
 
 /// Decodes an integer encoded with a given prefix size (in bits).
 /// Assumes that the buffer `buf` contains the integer to be decoded,
@@ -193,6 +194,12 @@ pub enum DecoderError {
     /// made by SizeUpdate blocks).
     InvalidMaxDynamicSize(u32, u32),
     SizeUpdateMustBeFirstField,
+    /// Decoded header list size (as defined for `SETTINGS_MAX_HEADER_LIST_SIZE`)
+    /// exceeds the configured limit.
+    HeaderListTooLarge(u32),
+    /// Header block contains more fields than the configured limit.
+    /// Block is decoded completely, so decoder state is consistent.
+    TooManyHeaders(usize),
 }
 
 /// The result returned by the `decode` method of the `Decoder`.
@@ -208,6 +215,12 @@ pub struct Decoder {
     header_table: HeaderTable,
     // Max configured size
     max_size: u32,
+    // Max size of decoded header list
+    max_header_list_size: Option<u32>,
+    // Max number of fields in a header block
+    max_header_count: Option<usize>,
+    // Representations seen so far
+    stats: Stats,
 }
 
 /// Represents a decoder of HPACK encoded headers. Maintains the state
@@ -231,6 +244,9 @@ impl Decoder {
         Decoder {
             header_table: HeaderTable::with_static_table(static_table),
             max_size: 4096,
+            max_header_list_size: None,
+            max_header_count: None,
+            stats: Stats::default(),
         }
     }
 
@@ -242,6 +258,38 @@ impl Decoder {
             .set_max_table_size(new_max_size);
     }
 
+    /// Abort decoding of a header block with `DecoderError::HeaderListTooLarge`
+    /// once the total size of decoded headers (name and value lengths
+    /// plus 32 octets per header) exceeds this limit.
+    pub fn set_max_header_list_size(&mut self, max_header_list_size: Option<u32>) {
+        self.max_header_list_size = max_header_list_size;
+    }
+
+    /// Fail decoding of a header block with `DecoderError::TooManyHeaders`
+    /// if it contains more fields than this limit. Fields past the limit
+    /// are not emitted, but the block is still decoded to keep
+    /// the dynamic table in sync.
+    pub fn set_max_header_count(&mut self, max_header_count: Option<usize>) {
+        self.max_header_count = max_header_count;
+    }
+
+    /// Headers currently stored in the dynamic table, most recently added first.
+    ///
+    /// This is a copy of the table, intended for debugging.
+    pub fn dynamic_table_entries(&self) -> Vec<(Bytes, Bytes)> {
+        self.header_table.dynamic_table.to_vec_of_bytes()
+    }
+
+    /// Current size of the dynamic table in octets, as defined by HPACK.
+    pub fn dynamic_table_size(&self) -> usize {
+        self.header_table.dynamic_table.get_size()
+    }
+
+    /// Number of header field representations seen by this decoder.
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
     /// Decodes the headers found in the given buffer `buf`. Invokes the callback `cb` for each
     /// decoded header in turn, by providing it the header name and value as `Cow` byte array
     /// slices.
@@ -263,6 +311,27 @@ impl Decoder {
     {
         let mut current_size_update = true;
 
+        let max_header_list_size = self.max_header_list_size;
+        let mut header_list_size = 0usize;
+        let max_header_count = self.max_header_count;
+        let mut header_count = 0usize;
+        let mut emit = |name: Bytes, value: Bytes| {
+            header_list_size += name.len() + value.len() + 32;
+            if let Some(max_header_list_size) = max_header_list_size {
+                if header_list_size > max_header_list_size as usize {
+                    return Err(DecoderError::HeaderListTooLarge(max_header_list_size));
+                }
+            }
+            header_count += 1;
+            if let Some(max_header_count) = max_header_count {
+                if header_count > max_header_count {
+                    return Ok(());
+                }
+            }
+            cb(name, value);
+            Ok(())
+        };
+
         while buf.has_remaining() {
             // At this point we are always at the beginning of the next block
             // within the HPACK data.
@@ -272,14 +341,15 @@ impl Decoder {
             let consumed = match FieldRepresentation::new(initial_octet) {
                 FieldRepresentation::Indexed => {
                     let ((name, value), consumed) = self.decode_indexed(&buf[..])?;
-                    cb(name, value);
+                    self.stats.indexed += 1;
+                    emit(name, value)?;
 
                     consumed
                 }
                 FieldRepresentation::LiteralWithIncrementalIndexing => {
                     let ((name, value), consumed) = {
                         let ((name, value), consumed) = self.decode_literal(&buf, true)?;
-                        cb(name.clone(), value.clone());
+                        emit(name.clone(), value.clone())?;
 
                         ((name, value), consumed)
                     };
@@ -289,12 +359,14 @@ impl Decoder {
                     // a header to the table requires a `&mut self`, it fails to compile.
                     // Manually separating it out here works around it...
                     self.header_table.add_header(name, value);
+                    self.stats.literal_with_indexing += 1;
 
                     consumed
                 }
                 FieldRepresentation::LiteralWithoutIndexing => {
                     let ((name, value), consumed) = self.decode_literal(&buf, false)?;
-                    cb(name, value);
+                    self.stats.literal_without_indexing += 1;
+                    emit(name, value)?;
 
                     consumed
                 }
@@ -304,7 +376,8 @@ impl Decoder {
                     // representation received here. We don't care about this
                     // for now.
                     let ((name, value), consumed) = self.decode_literal(&buf, false)?;
-                    cb(name, value);
+                    self.stats.never_indexed += 1;
+                    emit(name, value)?;
 
                     consumed
                 }
@@ -326,6 +399,12 @@ impl Decoder {
             }
         }
 
+        if let Some(max_header_count) = max_header_count {
+            if header_count > max_header_count {
+                return Err(DecoderError::TooManyHeaders(max_header_count));
+            }
+        }
+
         Ok(())
     }
 
@@ -443,6 +522,7 @@ mod tests {
 
     use super::super::encoder::encode_integer;
     use super::super::huffman::HuffmanDecoderError;
+    use super::super::Encoder;
     use super::Decoder;
     use super::DecoderError;
     use super::DecoderResult;
@@ -755,6 +835,77 @@ mod tests {
         assert_eq!(actual, expected_table);
     }
 
+    /// Tests that the dynamic table introspection reflects indexed headers.
+    #[test]
+    fn test_dynamic_table_entries() {
+        let mut encoder = Encoder::new();
+        let mut decoder = Decoder::new();
+
+        let first: Vec<(&[u8], &[u8])> = vec![(b"x-aa", b"11"), (b"x-bb", b"222")];
+        let second: Vec<(&[u8], &[u8])> = vec![(b"x-aa", b"11"), (b"x-cc", b"3")];
+
+        decoder
+            .decode_for_test(&encoder.encode_for_test(first))
+            .unwrap();
+        let header_list = decoder
+            .decode_for_test(&encoder.encode_for_test(second))
+            .unwrap();
+        assert_eq!(
+            header_list,
+            [
+                (Bytes::from_static(b"x-aa"), Bytes::from_static(b"11")),
+                (Bytes::from_static(b"x-cc"), Bytes::from_static(b"3")),
+            ]
+        );
+
+        let expected_table = vec![
+            (Bytes::from_static(b"x-cc"), Bytes::from_static(b"3")),
+            (Bytes::from_static(b"x-bb"), Bytes::from_static(b"222")),
+            (Bytes::from_static(b"x-aa"), Bytes::from_static(b"11")),
+        ];
+        assert_eq!(expected_table, decoder.dynamic_table_entries());
+        assert_eq!(3 * 32 + 4 + 1 + 4 + 3 + 4 + 2, decoder.dynamic_table_size());
+        assert_eq!(expected_table, encoder.dynamic_table_entries());
+        assert_eq!(decoder.dynamic_table_size(), encoder.dynamic_table_size());
+    }
+
+    /// Tests that decoding is aborted when the header list size limit is exceeded.
+    #[test]
+    fn test_max_header_list_size() {
+        let mut decoder = Decoder::new();
+        // `:method: GET` is 32 + 7 + 3 octets
+        decoder.set_max_header_list_size(Some(84));
+
+        let header_list = decoder.decode_for_test(&[0x82, 0x82]).unwrap();
+        assert_eq!(2, header_list.len());
+
+        assert_eq!(
+            Err(DecoderError::HeaderListTooLarge(84)),
+            decoder.decode_for_test(&[0x82, 0x82, 0x82])
+        );
+    }
+
+    /// Tests that a header block with too many fields is rejected,
+    /// but the dynamic table is still updated.
+    #[test]
+    fn test_max_header_count() {
+        let mut decoder = Decoder::new();
+        decoder.set_max_header_count(Some(2));
+
+        let header_list = decoder.decode_for_test(&[0x82, 0x82]).unwrap();
+        assert_eq!(2, header_list.len());
+
+        // :method GET, :method GET, literal `x-a: b` with incremental indexing
+        assert_eq!(
+            Err(DecoderError::TooManyHeaders(2)),
+            decoder.decode_for_test(&[0x82, 0x82, 0x40, 3, b'x', b'-', b'a', 1, b'b'])
+        );
+        assert_eq!(
+            vec![(Bytes::from_static(b"x-a"), Bytes::from_static(b"b"))],
+            decoder.dynamic_table_entries()
+        );
+    }
+
     /// Tests that a header with a name indexed from the dynamic table and a
     /// literal value is correctly decoded.
     #[test]
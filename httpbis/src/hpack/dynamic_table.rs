@@ -73,11 +73,15 @@ impl DynamicTable {
     }
 
     /// Returns the maximum size of the table in octets.
-    #[cfg(test)]
     pub fn get_max_table_size(&self) -> usize {
         self.max_size
     }
 
+    /// Returns the current size of the table in octets, as defined by HPACK.
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
     /// Add a new header to the dynamic table.
     ///
     /// The table automatically gets resized, if necessary.
@@ -124,8 +128,7 @@ impl DynamicTable {
     }
 
     /// Converts the current state of the table to a `Vec`
-    #[cfg(test)]
-    fn to_vec_of_bytes(&self) -> Vec<(Bytes, Bytes)> {
+    pub fn to_vec_of_bytes(&self) -> Vec<(Bytes, Bytes)> {
         let mut ret = Vec::new();
         for elem in &self.table {
             ret.push(elem.clone());
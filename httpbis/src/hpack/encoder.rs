@@ -3,13 +3,17 @@
 //!
 //! Clients should use the `Encoder` struct as the API for performing HPACK
 //! encoding.
+use std::cmp;
 use std::num::Wrapping;
 
 use bytes::Bytes;
 
 use super::HeaderTable;
+use crate::hpack::huffman::huffman_encode;
+use crate::hpack::huffman::huffman_encoded_len;
 use crate::hpack::static_table::StaticTable;
 use crate::hpack::HeaderValueFound;
+use crate::hpack::Stats;
 use bytes::BytesMut;
 
 pub trait EncodeBuf {
@@ -103,6 +107,15 @@ pub fn encode_integer(value: usize, prefix_size: u8) -> Vec<u8> {
 pub struct Encoder {
     /// The header table represents the encoder's context
     header_table: HeaderTable,
+    /// Whether string literals are Huffman-coded
+    huffman: bool,
+    /// Smallest and last table size set since the last encoded block,
+    /// to be signalled to the decoder with dynamic table size updates
+    pending_size_update: Option<(usize, usize)>,
+    /// Names of headers always encoded with never-indexed literal
+    never_indexed: Vec<Vec<u8>>,
+    /// Representations emitted so far
+    stats: Stats,
 }
 
 impl Encoder {
@@ -111,9 +124,75 @@ impl Encoder {
     pub fn new() -> Encoder {
         Encoder {
             header_table: HeaderTable::with_static_table(StaticTable::new()),
+            huffman: true,
+            pending_size_update: None,
+            never_indexed: Vec::new(),
+            stats: Stats::default(),
         }
     }
 
+    /// Change the maximum size of the dynamic table, e. g. when peer
+    /// changes `SETTINGS_HEADER_TABLE_SIZE`.
+    ///
+    /// The change is signalled at the beginning of the next encoded block
+    /// (HPACK spec, section 4.2). If the size was reduced and then increased
+    /// again, both the smallest and the final sizes are signalled.
+    pub fn set_max_table_size(&mut self, new_max_size: usize) {
+        let current = self.header_table.dynamic_table.get_max_table_size();
+        self.pending_size_update = match self.pending_size_update {
+            Some((min, _)) => Some((cmp::min(min, new_max_size), new_max_size)),
+            None if new_max_size != current => Some((new_max_size, new_max_size)),
+            None => None,
+        };
+        self.header_table
+            .dynamic_table
+            .set_max_table_size(new_max_size);
+    }
+
+    /// Headers currently stored in the dynamic table, most recently added first.
+    ///
+    /// This is a copy of the table, intended for debugging.
+    pub fn dynamic_table_entries(&self) -> Vec<(Bytes, Bytes)> {
+        self.header_table.dynamic_table.to_vec_of_bytes()
+    }
+
+    /// Current size of the dynamic table in octets, as defined by HPACK.
+    pub fn dynamic_table_size(&self) -> usize {
+        self.header_table.dynamic_table.get_size()
+    }
+
+    /// Enable or disable Huffman coding of header names and values.
+    ///
+    /// When enabled (the default), a string literal is Huffman-coded
+    /// if that makes it shorter. When disabled, literals are always
+    /// emitted raw with the H bit clear.
+    pub fn set_huffman(&mut self, huffman: bool) {
+        self.huffman = huffman;
+    }
+
+    /// Headers with given names (e. g. `authorization` or `cookie`)
+    /// are always encoded as literals never indexed (HPACK spec,
+    /// section 6.2.3), so intermediaries won't index them either.
+    ///
+    /// Names are compared case-insensitively. Replaces previously set names.
+    pub fn set_never_indexed(&mut self, names: &[&str]) {
+        self.never_indexed = names
+            .iter()
+            .map(|n| n.as_bytes().to_ascii_lowercase())
+            .collect();
+    }
+
+    /// Number of header field representations emitted by this encoder.
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    fn is_never_indexed(&self, name: &[u8]) -> bool {
+        self.never_indexed
+            .iter()
+            .any(|n| n.eq_ignore_ascii_case(name))
+    }
+
     /// Encodes the given headers using the HPACK rules and returns a newly
     /// allocated `Vec` containing the bytes representing the encoded header
     /// set.
@@ -123,8 +202,8 @@ impl Encoder {
     /// already found in the header table and a literal otherwise. When a
     /// header isn't found in the table, it is added if the header name wasn't
     /// found either (i.e. there are never two header names with different
-    /// values in the produced header table). Strings are always encoded as
-    /// literals (Huffman encoding is not used).
+    /// values in the produced header table). Strings are Huffman-coded
+    /// according to `set_huffman`.
     pub fn encode_for_test<'b, I>(&mut self, headers: I) -> Vec<u8>
     where
         I: IntoIterator<Item = (&'b [u8], &'b [u8])>,
@@ -152,6 +231,13 @@ impl Encoder {
         I: IntoIterator<Item = (&'b [u8], &'b [u8])>,
         W: EncodeBuf,
     {
+        if let Some((min, size)) = self.pending_size_update.take() {
+            if min < size {
+                encode_integer_into(min, 5, 0x20, writer);
+            }
+            encode_integer_into(size, 5, 0x20, writer);
+        }
+
         for header in headers {
             self.encode_header_into(header, writer);
         }
@@ -162,11 +248,18 @@ impl Encoder {
     /// Any errors are propagated, similarly to the `encode_into` method, and it is the callers
     /// responsiblity to make sure that the paired encoder sees them too.
     fn encode_header_into<W: EncodeBuf>(&mut self, header: (&[u8], &[u8]), writer: &mut W) {
+        if self.is_never_indexed(header.0) {
+            self.encode_never_indexed(header, writer);
+            self.stats.never_indexed += 1;
+            return;
+        }
+
         match self.header_table.find_header(header) {
             None => {
                 // The name of the header is in no tables: need to encode
                 // it with both a literal name and value.
                 self.encode_literal(&header, true, writer);
+                self.stats.literal_with_indexing += 1;
                 self.header_table.add_header(
                     Bytes::copy_from_slice(header.0),
                     Bytes::copy_from_slice(header.1),
@@ -177,11 +270,13 @@ impl Encoder {
                 // value does not match the current one: need to encode
                 // only the value as a literal.
                 self.encode_indexed_name((index, header.1), false, writer);
+                self.stats.literal_without_indexing += 1;
             }
             Some((index, HeaderValueFound::Found)) => {
                 // The full header was found in one of the tables, so we
                 // just encode the index.
                 self.encode_indexed(index, writer);
+                self.stats.indexed += 1;
             }
         };
     }
@@ -210,13 +305,35 @@ impl Encoder {
         self.encode_string_literal(&header.1, buf);
     }
 
+    /// Encodes a header as a literal never indexed (`0001xxxx` pattern,
+    /// HPACK spec section 6.2.3). The name is taken from the header table
+    /// when possible, the value is always a literal, and the header table
+    /// is not modified.
+    fn encode_never_indexed<W: EncodeBuf>(&mut self, header: (&[u8], &[u8]), buf: &mut W) {
+        match self.header_table.find_header(header) {
+            Some((index, _)) => encode_integer_into(index, 4, 0x10, buf),
+            None => {
+                buf.write_u8(0x10);
+                self.encode_string_literal(header.0, buf);
+            }
+        }
+        self.encode_string_literal(header.1, buf);
+    }
+
     /// Encodes a string literal and places the result in the given buffer
     /// `buf`.
     ///
-    /// The function does not consider Huffman encoding for now, but always
-    /// produces a string literal representations, according to the HPACK spec
-    /// section 5.2.
+    /// The string is Huffman-coded if enabled and shorter than the raw
+    /// representation, according to the HPACK spec section 5.2.
     fn encode_string_literal<W: EncodeBuf>(&mut self, octet_str: &[u8], buf: &mut W) {
+        if self.huffman && huffman_encoded_len(octet_str) < octet_str.len() {
+            let encoded = huffman_encode(octet_str);
+            buf.reserve(encoded.len() + 1);
+            encode_integer_into(encoded.len(), 7, 0x80, buf);
+            buf.write_all(&encoded);
+            return;
+        }
+
         buf.reserve(octet_str.len() + 1);
         encode_integer_into(octet_str.len(), 7, 0, buf);
         buf.write_all(octet_str);
@@ -256,6 +373,7 @@ mod tests {
     use super::Encoder;
 
     use super::super::Decoder;
+    use super::super::Stats;
 
     #[test]
     fn test_encode_integer() {
@@ -322,6 +440,48 @@ mod tests {
         debug!("{:?}", result);
     }
 
+    /// Tests that encoder and decoder count representations of a repeated header.
+    #[test]
+    fn test_stats() {
+        let mut encoder = Encoder::new();
+        let mut decoder = Decoder::new();
+        let headers = vec![(b"custom-key".to_vec(), b"custom-value".to_vec())];
+
+        let first = encoder.encode_for_test(headers.iter().map(|h| (&h.0[..], &h.1[..])));
+        decoder.decode_for_test(&first[..]).unwrap();
+        let stats = Stats {
+            literal_with_indexing: 1,
+            ..Stats::default()
+        };
+        assert_eq!(stats, encoder.stats());
+        assert_eq!(stats, decoder.stats());
+
+        let second = encoder.encode_for_test(headers.iter().map(|h| (&h.0[..], &h.1[..])));
+        decoder.decode_for_test(&second[..]).unwrap();
+        let stats = Stats {
+            indexed: 1,
+            literal_with_indexing: 1,
+            ..Stats::default()
+        };
+        assert_eq!(stats, encoder.stats());
+        assert_eq!(stats, decoder.stats());
+
+        encoder.set_never_indexed(&["authorization"]);
+        let third = encoder.encode_for_test(vec![
+            (&b"custom-key"[..], &b"other-value"[..]),
+            (&b"authorization"[..], &b"secret"[..]),
+        ]);
+        decoder.decode_for_test(&third[..]).unwrap();
+        let stats = Stats {
+            indexed: 1,
+            literal_with_indexing: 1,
+            literal_without_indexing: 1,
+            never_indexed: 1,
+        };
+        assert_eq!(stats, encoder.stats());
+        assert_eq!(stats, decoder.stats());
+    }
+
     /// Tests that when a header gets added to the dynamic table, the encoder
     /// will use the index, instead of the literal representation on the next
     /// encoding of the same header.
@@ -359,6 +519,7 @@ mod tests {
     fn test_name_indexed_value_not() {
         {
             let mut encoder: Encoder = Encoder::new();
+            encoder.set_huffman(false);
             // `:method` is in the static table, but only for GET and POST
             let headers = vec![(b":method", b"PUT")];
 
@@ -372,6 +533,7 @@ mod tests {
         }
         {
             let mut encoder: Encoder = Encoder::new();
+            encoder.set_huffman(false);
             // `:method` is in the static table, but only for GET and POST
             let headers = vec![(b":authority".to_vec(), b"example.com".to_vec())];
 
@@ -401,4 +563,90 @@ mod tests {
 
         assert!(is_decodable(&result, &headers));
     }
+
+    /// Tests that a dynamic table size update is emitted exactly once,
+    /// at the head of the block following the size change.
+    #[test]
+    fn test_size_update() {
+        let mut encoder = Encoder::new();
+        let headers = vec![(b"custom-key".to_vec(), b"custom-value".to_vec())];
+
+        encoder.set_max_table_size(1024);
+
+        let first = encoder.encode_for_test(headers.iter().map(|h| (&h.0[..], &h.1[..])));
+        // `001xxxxx` with 5-bit prefix integer 1024
+        assert_eq!(&first[..3], &[0x3f, 0xe1, 0x07]);
+        assert_eq!(0x40, first[3] & 0xc0);
+        assert!(is_decodable(&first, &headers));
+
+        let second = encoder.encode_for_test(headers.iter().map(|h| (&h.0[..], &h.1[..])));
+        // No size update, only the indexed header
+        assert_eq!(&second[..], &[0x80 | 62]);
+    }
+
+    /// Tests that when the table is shrunk and then grown, both the minimum
+    /// and the final sizes are signalled.
+    #[test]
+    fn test_size_update_shrink_then_grow() {
+        let mut encoder = Encoder::new();
+        let headers = vec![(b"custom-key".to_vec(), b"custom-value".to_vec())];
+
+        let first = encoder.encode_for_test(headers.iter().map(|h| (&h.0[..], &h.1[..])));
+        assert_eq!(0x40, first[0]);
+
+        encoder.set_max_table_size(0);
+        encoder.set_max_table_size(2048);
+
+        let second = encoder.encode_for_test(headers.iter().map(|h| (&h.0[..], &h.1[..])));
+        assert_eq!(&second[..4], &[0x20, 0x3f, 0xe1, 0x0f]);
+        // The header was evicted by shrinking, so it is sent as a literal again
+        assert_eq!(0x40, second[4]);
+        assert_eq!(encoder.header_table.dynamic_table.to_vec_of_vec(), headers);
+
+        let mut decoder = Decoder::new();
+        decoder.decode_for_test(&first).unwrap();
+        let decoded = decoder.decode_for_test(&second).unwrap();
+        assert_eq!(
+            vec![(
+                Bytes::from_static(b"custom-key"),
+                Bytes::from_static(b"custom-value")
+            )],
+            decoded
+        );
+    }
+
+    /// Tests that setting the current size does not produce an update.
+    #[test]
+    fn test_size_update_same_size() {
+        let mut encoder = Encoder::new();
+        encoder.set_max_table_size(4096);
+
+        let result = encoder.encode_for_test(vec![(&b":method"[..], &b"GET"[..])]);
+        assert_eq!(&result[..], &[0x82]);
+    }
+
+    #[test]
+    fn test_never_indexed() {
+        let mut encoder = Encoder::new();
+        encoder.set_never_indexed(&["authorization", "X-Secret"]);
+        let headers = vec![
+            (b"authorization".to_vec(), b"Bearer token".to_vec()),
+            (b"x-secret".to_vec(), b"s".to_vec()),
+        ];
+
+        for _ in 0..2 {
+            let result = encoder.encode_for_test(headers.iter().map(|h| (&h.0[..], &h.1[..])));
+
+            // `authorization` is static table entry 23: 4-bit prefix
+            // saturated, then 23 - 15
+            assert_eq!(&[0x1f, 0x08], &result[..2]);
+            assert!(is_decodable(&result, &headers));
+            // neither header gets into the dynamic table
+            assert!(encoder
+                .header_table
+                .dynamic_table
+                .to_vec_of_vec()
+                .is_empty());
+        }
+    }
 }
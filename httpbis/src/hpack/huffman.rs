@@ -223,6 +223,42 @@ where
     }
 }
 
+/// Returns the length in octets of the Huffman encoding of `buf`.
+pub fn huffman_encoded_len(buf: &[u8]) -> usize {
+    let bits: usize = buf
+        .iter()
+        .map(|&b| HUFFMAN_CODE_TABLE[b as usize].1 as usize)
+        .sum();
+    bits.div_ceil(8)
+}
+
+/// Encodes the buffer `buf` with the Huffman code into a newly allocated `Vec`.
+///
+/// The last octet is padded with the most significant bits of EOS
+/// (HPACK spec, section 5.2).
+pub fn huffman_encode(buf: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(huffman_encoded_len(buf));
+    // Only the lowest `current_len` bits are meaningful
+    let mut current: u64 = 0;
+    let mut current_len: u32 = 0;
+
+    for &b in buf {
+        let (code, code_len) = HUFFMAN_CODE_TABLE[b as usize];
+        current = (current << code_len) | code as u64;
+        current_len += code_len as u32;
+        while current_len >= 8 {
+            current_len -= 8;
+            result.push((current >> current_len) as u8);
+        }
+    }
+
+    if current_len > 0 {
+        result.push(((current << (8 - current_len)) as u8) | (0xff >> current_len));
+    }
+
+    result
+}
+
 static HUFFMAN_CODE_TABLE: &'static [(u32, u8)] = &[
     (0x1ff8, 13),
     (0x7fffd8, 23),
@@ -485,6 +521,8 @@ static HUFFMAN_CODE_TABLE: &'static [(u32, u8)] = &[
 
 #[cfg(test)]
 mod tests {
+    use super::huffman_encode;
+    use super::huffman_encoded_len;
     use super::BitIterator;
     use super::HuffmanDecoder;
     use super::HuffmanDecoderError;
@@ -708,4 +746,27 @@ mod tests {
             );
         }
     }
+
+    /// Tests the encoding of the string from HPACK spec, Appendix C.4.1.
+    #[test]
+    fn test_huffman_encode() {
+        let encoded = huffman_encode(b"www.example.com");
+        assert_eq!(
+            encoded,
+            vec![0xf1, 0xe3, 0xc2, 0xe5, 0xf2, 0x3a, 0x6b, 0xa0, 0xab, 0x90, 0xf4, 0xff]
+        );
+        assert_eq!(encoded.len(), huffman_encoded_len(b"www.example.com"));
+    }
+
+    /// Tests that any octet string survives encoding and decoding.
+    #[test]
+    fn test_huffman_encode_decode() {
+        let all: Vec<u8> = (0..=255).collect();
+        for buf in &[&b""[..], &b"a"[..], &b"no-cache"[..], &all[..]] {
+            let encoded = huffman_encode(buf);
+            assert_eq!(encoded.len(), huffman_encoded_len(buf));
+            let mut decoder = HuffmanDecoder::new();
+            assert_eq!(&decoder.decode(&encoded).unwrap()[..], &buf[..]);
+        }
+    }
 }
@@ -6,8 +6,11 @@
 pub use self::decoder::Decoder;
 pub use self::encoder::Encoder;
 use crate::hpack::dynamic_table::DynamicTable;
+use crate::hpack::encoder::EncodeBuf;
 use crate::hpack::static_table::StaticTable;
+use crate::Headers;
 use bytes::Bytes;
+use bytes::BytesMut;
 
 pub mod decoder;
 mod dynamic_table;
@@ -15,6 +18,39 @@ pub mod encoder;
 pub mod huffman;
 mod static_table;
 
+/// Encode headers into a standalone HPACK block.
+///
+/// The result is a header block fragment without any frame framing,
+/// as it would be placed into a `HEADERS` frame and following `CONTINUATION` frames.
+pub fn encode_headers(encoder: &mut Encoder, headers: &Headers) -> Bytes {
+    let mut buf = BytesMut::new();
+    encode_headers_into(encoder, headers, &mut buf);
+    buf.freeze()
+}
+
+pub(crate) fn encode_headers_into<W: EncodeBuf>(
+    encoder: &mut Encoder,
+    headers: &Headers,
+    buf: &mut W,
+) {
+    let headers = headers.iter().map(|h| (h.name().as_bytes(), h.value()));
+    encoder.encode_into(headers, buf);
+}
+
+/// Number of header field representations encoded or decoded
+/// (HPACK spec, section 6), e. g. to assert encoding strategy in tests.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// Indexed header fields (section 6.1)
+    pub indexed: u64,
+    /// Literals with incremental indexing (section 6.2.1)
+    pub literal_with_indexing: u64,
+    /// Literals without indexing (section 6.2.2)
+    pub literal_without_indexing: u64,
+    /// Literals never indexed (section 6.2.3)
+    pub never_indexed: u64,
+}
+
 /// The struct represents the header table obtained by merging the static and
 /// dynamic tables into a single index address space, as described in section
 /// `2.3.3.` of the HPACK spec.
@@ -149,8 +185,11 @@ mod tests {
 
     use super::static_table::STATIC_TABLE;
     use super::HeaderTable;
+    use crate::hpack;
     use crate::hpack::static_table::StaticTable;
     use crate::hpack::HeaderValueFound;
+    use crate::Header;
+    use crate::Headers;
 
     /// Tests that indexing the header table with indices that correspond to
     /// entries found in the static table works.
@@ -333,4 +372,62 @@ mod tests {
             panic!("The header should have matched only partially");
         }
     }
+
+    #[test]
+    fn encode_headers_round_trip() {
+        let mut headers = Headers::new_get("/foo");
+        headers.add(":scheme", "https");
+        headers.add("x-custom", "some value");
+        headers.add("x-custom", "other value");
+
+        let mut encoder = hpack::Encoder::new();
+        let mut decoder = hpack::Decoder::new();
+
+        // Encode twice to exercise the dynamic table
+        for _ in 0..2 {
+            let block = hpack::encode_headers(&mut encoder, &headers);
+            let decoded = decoder.decode(block).expect("decode");
+            let decoded = Headers::from_vec(
+                decoded
+                    .into_iter()
+                    .map(|(n, v)| Header::new(n, v))
+                    .collect(),
+            );
+            assert_eq!(headers, decoded);
+        }
+    }
+
+    #[test]
+    fn encode_headers_huffman_on_off() {
+        let mut headers = Headers::new_get("/some/long/path");
+        headers.add(":scheme", "https");
+        headers.add(":authority", "www.example.com");
+        headers.add("x-custom", "some custom value");
+
+        let encode_decode = |huffman: bool| {
+            let mut encoder = hpack::Encoder::new();
+            encoder.set_huffman(huffman);
+            let block = hpack::encode_headers(&mut encoder, &headers);
+            let decoded = hpack::Decoder::new().decode(block.clone()).expect("decode");
+            let decoded = Headers::from_vec(
+                decoded
+                    .into_iter()
+                    .map(|(n, v)| Header::new(n, v))
+                    .collect(),
+            );
+            (block, decoded)
+        };
+
+        let (huffman_block, huffman_decoded) = encode_decode(true);
+        let (raw_block, raw_decoded) = encode_decode(false);
+
+        assert_eq!(headers, huffman_decoded);
+        assert_eq!(headers, raw_decoded);
+        assert!(
+            huffman_block.len() < raw_block.len(),
+            "{} {}",
+            huffman_block.len(),
+            raw_block.len()
+        );
+    }
 }
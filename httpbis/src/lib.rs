@@ -29,6 +29,7 @@ mod common;
 mod data_or_headers;
 mod data_or_headers_with_flag;
 mod data_or_trailers;
+mod grpc_messages;
 mod message;
 
 mod futures_misc;
@@ -58,16 +59,25 @@ pub(crate) mod bytes_ext;
 pub use crate::net::addr::AnySocketAddr;
 
 pub use crate::solicit::error_code::ErrorCode;
+pub use crate::solicit::frame::HttpSetting;
+pub use crate::solicit::frame::HttpSettings;
+pub use crate::solicit::frame::SettingsFrame;
 pub use crate::solicit::header::name::HeaderName;
 pub use crate::solicit::header::name::PseudoHeaderName;
 pub use crate::solicit::header::value::HeaderValue;
 pub use crate::solicit::header::Header;
+pub use crate::solicit::header::HeaderError;
 pub use crate::solicit::header::Headers;
+pub use crate::solicit::session::StreamState;
 pub use crate::solicit::stream_id::StreamId;
 pub use crate::solicit::HttpScheme;
 
 pub use crate::client::conf::ClientConf;
+pub use crate::client::increase_in_window::ClientIncreaseInWindow;
 pub use crate::client::req::ClientRequest;
+pub use crate::client::resp::ClientResponse;
+pub use crate::client::stream_handler::ClientResponseStreamHandler;
+pub use crate::client::stream_handler::ClientStreamCreatedHandler;
 pub use crate::client::tls::ClientTlsOption;
 pub use crate::client::Client;
 pub use crate::client::ClientBuilder;
@@ -78,6 +88,7 @@ pub use crate::common::window_size::StreamDead;
 
 pub use crate::server::conf::ServerAlpn;
 pub use crate::server::conf::ServerConf;
+pub use crate::server::event_stream::ServerEventStream;
 pub use crate::server::handler::ServerHandler;
 pub use crate::server::handler::ServerHandlerContext;
 pub use crate::server::handler_paths::ServerHandlerPaths;
@@ -86,9 +97,12 @@ pub use crate::server::req::ServerRequest;
 pub use crate::server::resp::ServerResponse;
 pub use crate::server::stream_handler::ServerRequestStreamHandler;
 pub use crate::server::tls::ServerTlsOption;
+pub use crate::server::Clock;
 pub use crate::server::Server;
 pub use crate::server::ServerBuilder;
+pub use crate::server::SystemClock;
 
+pub use crate::data_or_headers::DataOrHeaders;
 pub use crate::data_or_trailers::DataOrTrailers;
 pub use crate::data_or_trailers::HttpStreamAfterHeaders;
 pub use crate::resp::Response;
@@ -106,6 +120,7 @@ pub use bytes_ext::bytes_deque::BytesDeque;
 pub mod for_test {
     pub use crate::common::conn::ConnStateSnapshot;
     pub use crate::common::stream::HttpStreamStateSnapshot;
+    pub use crate::common::stream::InputEndReason;
     pub use crate::server::conn::ServerConn;
     pub use crate::solicit_async::recv_raw_frame_sync;
 
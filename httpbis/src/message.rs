@@ -8,6 +8,8 @@ use crate::data_or_headers_with_flag::DataOrHeadersWithFlag;
 pub struct SimpleHttpMessage {
     pub headers: Headers,
     pub body: BytesDeque,
+    /// Trailing `HEADERS` frame, if message has one
+    pub trailers: Option<Headers>,
 }
 
 impl SimpleHttpMessage {
@@ -17,11 +19,15 @@ impl SimpleHttpMessage {
 
     /// Multiline string
     pub fn dump(&self) -> String {
-        format!(
+        let mut r = format!(
             "{}\n{}",
             self.headers.dump(),
             String::from_utf8_lossy(&self.body.get_bytes())
-        )
+        );
+        if let Some(ref trailers) = self.trailers {
+            r.push_str(&format!("\n{}", trailers.dump()));
+        }
+        r
     }
 
     pub fn from_parts<I>(iter: I) -> SimpleHttpMessage
@@ -46,6 +52,7 @@ impl SimpleHttpMessage {
         SimpleHttpMessage {
             headers: Headers::not_found_404(),
             body: BytesDeque::copy_from_slice(message.as_bytes()),
+            trailers: None,
         }
     }
 
@@ -53,6 +60,7 @@ impl SimpleHttpMessage {
         SimpleHttpMessage {
             headers: Headers::internal_error_500(),
             body: BytesDeque::copy_from_slice(message.as_bytes()),
+            trailers: None,
         }
     }
 
@@ -60,6 +68,7 @@ impl SimpleHttpMessage {
         SimpleHttpMessage {
             headers: Headers::ok_200(),
             body: BytesDeque::copy_from_slice(body.as_bytes()),
+            trailers: None,
         }
     }
 
@@ -67,13 +76,22 @@ impl SimpleHttpMessage {
         SimpleHttpMessage {
             headers: Headers::redirect_302(location),
             body: BytesDeque::new(),
+            trailers: None,
         }
     }
 
+    /// Append a message part.
+    ///
+    /// First `HEADERS` are initial headers, subsequent `HEADERS` are trailers.
     pub fn add(&mut self, part: DataOrHeaders) {
         match part {
+            DataOrHeaders::Headers(headers) if self.headers.is_empty() => {
+                self.headers = headers;
+            }
             DataOrHeaders::Headers(headers) => {
-                self.headers.extend(headers);
+                self.trailers
+                    .get_or_insert_with(Headers::new)
+                    .extend(headers);
             }
             DataOrHeaders::Data(data) => {
                 self.body.extend(data);
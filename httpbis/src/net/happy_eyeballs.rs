@@ -0,0 +1,191 @@
+//! Connect to one of several addresses with Happy Eyeballs (RFC 8305)
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::Duration;
+
+use futures::future;
+use futures::future::Either;
+use futures::stream::FuturesUnordered;
+use futures::stream::StreamExt;
+use tokio::net::TcpStream;
+use tokio::runtime::Handle;
+use tokio::time;
+
+use crate::net::connect::ToClientStream;
+use crate::net::socket::SocketStream;
+use crate::AnySocketAddr;
+
+/// Recommended delay before starting next connection attempt (RFC 8305 section 8).
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Addresses of a host, tried concurrently with staggered start.
+#[derive(Clone, Debug)]
+pub(crate) struct HappyEyeballsConnect {
+    // in order of connection attempts
+    addrs: Vec<SocketAddr>,
+}
+
+impl HappyEyeballsConnect {
+    /// Addresses must be non-empty and sorted by preference, e. g. as returned by resolver.
+    pub fn new(addrs: Vec<SocketAddr>) -> HappyEyeballsConnect {
+        assert!(!addrs.is_empty());
+        HappyEyeballsConnect {
+            addrs: interleave_families(addrs),
+        }
+    }
+}
+
+impl fmt::Display for HappyEyeballsConnect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.addrs[0])?;
+        if self.addrs.len() > 1 {
+            write!(f, " (+{} more)", self.addrs.len() - 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl ToClientStream for HappyEyeballsConnect {
+    fn connect(
+        &self,
+        _handle: &Handle,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Pin<Box<dyn SocketStream>>>> + Send>> {
+        let addrs = self.addrs.clone();
+        Box::pin(async move {
+            let stream = connect_happy_eyeballs(addrs, CONNECTION_ATTEMPT_DELAY).await?;
+            Ok(Box::pin(stream) as Pin<Box<dyn SocketStream>>)
+        })
+    }
+
+    fn socket_addr(&self) -> AnySocketAddr {
+        AnySocketAddr::Inet(self.addrs[0])
+    }
+}
+
+/// Alternate address families starting with the family of the first address
+/// (RFC 8305 section 4).
+fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_is_ipv6 = addrs[0].is_ipv6();
+    let (first, second): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|a| a.is_ipv6() == first_is_ipv6);
+
+    let mut r = Vec::with_capacity(first.len() + second.len());
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => return r,
+            (a, b) => {
+                r.extend(a);
+                r.extend(b);
+            }
+        }
+    }
+}
+
+/// Start connection to next address when previous attempt fails or
+/// does not complete within `delay`, return first established connection.
+async fn connect_happy_eyeballs(addrs: Vec<SocketAddr>, delay: Duration) -> io::Result<TcpStream> {
+    let mut addrs = addrs.into_iter();
+    let mut attempts = FuturesUnordered::new();
+    let mut last_error = None;
+
+    loop {
+        if attempts.is_empty() {
+            match addrs.next() {
+                Some(addr) => attempts.push(TcpStream::connect(addr)),
+                None => {
+                    return Err(last_error.unwrap_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidInput, "no addresses to connect")
+                    }))
+                }
+            }
+        }
+
+        let timer = Box::pin(time::sleep(delay));
+        let completed = match future::select(attempts.next(), timer).await {
+            Either::Left((completed, _)) => completed,
+            Either::Right(((), _)) => None,
+        };
+
+        match completed {
+            Some(Ok(stream)) => return Ok(stream),
+            Some(Err(e)) => {
+                debug!("connection attempt failed: {}", e);
+                last_error = Some(e);
+            }
+            None => {}
+        }
+
+        if let Some(addr) = addrs.next() {
+            debug!("starting connection attempt to {}", addr);
+            attempts.push(TcpStream::connect(addr));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn interleave() {
+        assert_eq!(
+            vec![
+                addr("[::1]:1"),
+                addr("1.1.1.1:1"),
+                addr("[::2]:1"),
+                addr("2.2.2.2:1"),
+                addr("3.3.3.3:1"),
+            ],
+            interleave_families(vec![
+                addr("[::1]:1"),
+                addr("[::2]:1"),
+                addr("1.1.1.1:1"),
+                addr("2.2.2.2:1"),
+                addr("3.3.3.3:1"),
+            ])
+        );
+        assert_eq!(
+            vec![addr("1.1.1.1:1"), addr("[::1]:1"), addr("2.2.2.2:1")],
+            interleave_families(vec![addr("1.1.1.1:1"), addr("2.2.2.2:1"), addr("[::1]:1")])
+        );
+    }
+
+    #[test]
+    fn fallback_after_refused() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let refused = {
+            let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            closed.local_addr().unwrap()
+        };
+
+        let stream = rt
+            .block_on(connect_happy_eyeballs(
+                vec![refused, listener.local_addr().unwrap()],
+                Duration::from_secs(100),
+            ))
+            .expect("connect");
+        assert_eq!(listener.local_addr().unwrap(), stream.peer_addr().unwrap());
+
+        let err = rt
+            .block_on(connect_happy_eyeballs(
+                vec![refused],
+                Duration::from_secs(100),
+            ))
+            .err()
+            .unwrap();
+        assert_eq!(io::ErrorKind::ConnectionRefused, err.kind());
+    }
+}
@@ -1,7 +1,10 @@
 /// Network utilities
 pub(crate) mod addr;
 pub(crate) mod connect;
+pub(crate) mod happy_eyeballs;
 pub(crate) mod listen;
+pub(crate) mod provided;
+pub(crate) mod shared_socket;
 pub(crate) mod socket;
 pub(crate) mod tcp;
 pub(crate) mod tls;
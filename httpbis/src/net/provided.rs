@@ -0,0 +1,126 @@
+//! Connection established by the user, e. g. with their own TLS and ALPN.
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::Ipv4Addr;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+
+use futures::future;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::ReadBuf;
+use tokio::runtime::Handle;
+
+use crate::net::connect::ToClientStream;
+use crate::net::socket::SocketStream;
+use crate::AnySocketAddr;
+
+/// Arbitrary IO used as a socket, with ALPN protocol negotiated by the user.
+pub(crate) struct ProvidedIo<I>(pub I, pub Vec<u8>);
+
+impl<I> fmt::Debug for ProvidedIo<I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProvidedIo").finish()
+    }
+}
+
+impl<I: AsyncRead + Unpin> AsyncRead for ProvidedIo<I> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl<I: AsyncWrite + Unpin> AsyncWrite for ProvidedIo<I> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+impl<I> SocketStream for ProvidedIo<I>
+where
+    I: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    fn is_tcp(&self) -> bool {
+        false
+    }
+
+    fn set_tcp_nodelay(&self, _no_delay: bool) -> io::Result<()> {
+        Err(io::Error::other(
+            "Cannot set nodelay on provided connection",
+        ))
+    }
+
+    fn peer_addr(&self) -> io::Result<AnySocketAddr> {
+        Ok(ProvidedConnect::unknown_addr())
+    }
+
+    fn negotiated_alpn(&self) -> io::Result<Option<Vec<u8>>> {
+        Ok(Some(self.1.clone()))
+    }
+}
+
+type BoxSocketStream = Pin<Box<dyn SocketStream>>;
+
+/// "Connects" to the provided IO once. The connection cannot be reestablished.
+#[derive(Clone)]
+pub(crate) struct ProvidedConnect {
+    io: Arc<Mutex<Option<BoxSocketStream>>>,
+}
+
+impl ProvidedConnect {
+    pub fn new(io: BoxSocketStream) -> ProvidedConnect {
+        ProvidedConnect {
+            io: Arc::new(Mutex::new(Some(io))),
+        }
+    }
+
+    /// Peer address of the provided IO is not known.
+    fn unknown_addr() -> AnySocketAddr {
+        AnySocketAddr::Inet(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)))
+    }
+}
+
+impl fmt::Display for ProvidedConnect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "provided connection")
+    }
+}
+
+impl ToClientStream for ProvidedConnect {
+    fn connect(
+        &self,
+        _handle: &Handle,
+    ) -> Pin<Box<dyn Future<Output = io::Result<BoxSocketStream>> + Send>> {
+        let r = match self.io.lock().unwrap().take() {
+            Some(io) => Ok(io),
+            None => Err(io::Error::other("provided connection is already used")),
+        };
+        Box::pin(future::ready(r))
+    }
+
+    fn socket_addr(&self) -> AnySocketAddr {
+        ProvidedConnect::unknown_addr()
+    }
+}
@@ -0,0 +1,85 @@
+use std::io;
+use std::io::IoSlice;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::task::Context;
+use std::task::Poll;
+
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::ReadBuf;
+
+/// Socket shared by read and write sides of a connection.
+///
+/// Like halves returned by `tokio::io::split`, but the socket itself
+/// is still accessible, e. g. to change socket options.
+#[derive(Debug)]
+pub(crate) struct SharedSocket<I> {
+    socket: Arc<Mutex<I>>,
+}
+
+impl<I> Clone for SharedSocket<I> {
+    fn clone(&self) -> Self {
+        SharedSocket {
+            socket: self.socket.clone(),
+        }
+    }
+}
+
+impl<I> SharedSocket<I> {
+    pub fn new(socket: I) -> SharedSocket<I> {
+        SharedSocket {
+            socket: Arc::new(Mutex::new(socket)),
+        }
+    }
+
+    /// Access the socket, e. g. to change socket options.
+    ///
+    /// Read and write sides are polled by the connection task,
+    /// so the lock is never contended.
+    pub fn lock(&self) -> MutexGuard<'_, I> {
+        self.socket.lock().unwrap()
+    }
+}
+
+impl<I: AsyncRead + Unpin> AsyncRead for SharedSocket<I> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.lock()).poll_read(cx, buf)
+    }
+}
+
+impl<I: AsyncWrite + Unpin> AsyncWrite for SharedSocket<I> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.lock()).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.lock()).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.lock().is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.lock()).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.lock()).poll_shutdown(cx)
+    }
+}
@@ -15,6 +15,12 @@ pub trait SocketStream: AsyncRead + AsyncWrite + fmt::Debug + Send + Unpin + 'st
     fn set_tcp_nodelay(&self, no_delay: bool) -> io::Result<()>;
 
     fn peer_addr(&self) -> io::Result<AnySocketAddr>;
+
+    /// Protocol negotiated with ALPN during TLS handshake.
+    /// `None` for non-TLS sockets or if nothing was negotiated.
+    fn negotiated_alpn(&self) -> io::Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
 }
 
 impl<S: SocketStream + ?Sized> SocketStream for Pin<Box<S>> {
@@ -29,4 +35,8 @@ impl<S: SocketStream + ?Sized> SocketStream for Pin<Box<S>> {
     fn peer_addr(&self) -> io::Result<AnySocketAddr> {
         (**self).peer_addr()
     }
+
+    fn negotiated_alpn(&self) -> io::Result<Option<Vec<u8>>> {
+        (**self).negotiated_alpn()
+    }
 }
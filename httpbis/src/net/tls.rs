@@ -15,4 +15,8 @@ impl<S: SocketStream> SocketStream for TlsStreamWithSocket<S> {
     fn peer_addr(&self) -> io::Result<AnySocketAddr> {
         self.get_socket_ref().peer_addr()
     }
+
+    fn negotiated_alpn(&self) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.get_alpn_protocol()?)
+    }
 }
@@ -58,7 +58,16 @@ impl Response {
     }
 
     pub fn message(message: SimpleHttpMessage) -> Response {
-        Response::headers_and_bytes(message.headers, message.body)
+        match message.trailers {
+            None => Response::headers_and_bytes(message.headers, message.body),
+            Some(trailers) => Response::headers_and_stream(
+                message.headers,
+                HttpStreamAfterHeaders::new(stream::iter(vec![
+                    Ok(DataOrTrailers::intermediate_data(message.body.into_bytes())),
+                    Ok(DataOrTrailers::Trailers(trailers)),
+                ])),
+            ),
+        }
     }
 
     pub fn found_200_plain_text(body: &str) -> Response {
@@ -129,6 +138,11 @@ impl Response {
         DataOrHeadersWithFlagStream::new(self.into_stream_flag())
     }
 
+    /// Read the whole response.
+    ///
+    /// Trailers, if any, are stored in `trailers` field of the message.
+    /// To receive trailers without buffering the body, await `Response`
+    /// and read `DataOrTrailers` from the `HttpStreamAfterHeaders`.
     pub fn collect(self) -> HttpFutureSend<SimpleHttpMessage> {
         Box::pin(
             self.into_stream()
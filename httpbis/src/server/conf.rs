@@ -1,10 +1,13 @@
 use crate::common::conf::CommonConf;
+use crate::server::date::Clock;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ServerAlpn {
     // Ignore negotiated ALPN
     Ignore,
-    // Return error is ALPN is not "h2"
+    // Close connection with `Error::AlpnMismatch` if ALPN is not "h2"
     Require,
 }
 
@@ -14,6 +17,8 @@ pub struct ServerConf {
     pub no_delay: Option<bool>,
     pub thread_name: Option<String>,
 
+    /// What to do with ALPN protocol negotiated in TLS handshake,
+    /// `ServerAlpn::Ignore` by default.
     pub alpn: Option<ServerAlpn>,
 
     // Bind on both IPv4 and IPv6 addresses when addr is IPv6
@@ -23,6 +28,32 @@ pub struct ServerConf {
     pub reuse_port: Option<bool>,
     pub backlog: Option<i32>,
 
+    /// Limit the rate of new streams initiated by client per connection.
+    /// Excess streams are refused with `REFUSED_STREAM`.
+    pub new_streams_per_second: Option<u32>,
+
+    /// Advertised as `SETTINGS_MAX_CONCURRENT_STREAMS`.
+    /// Streams opened by client above the limit are refused with `REFUSED_STREAM`.
+    pub max_concurrent_streams: Option<u32>,
+
+    /// Close the connection with `ENHANCE_YOUR_CALM` when client sends more than
+    /// this number of `RST_STREAM` frames within `rst_stream_flood_window`
+    /// (rapid reset attack, CVE-2023-44487).
+    /// Only resets of streams which response headers were not sent yet
+    /// are counted.
+    /// Default is 1000.
+    pub max_rst_streams_per_window: Option<u32>,
+    /// Window for `max_rst_streams_per_window`.
+    /// Default is 10 seconds.
+    pub rst_stream_flood_window: Option<Duration>,
+
+    /// Add `date` header to responses which do not have it.
+    /// Default is `true`.
+    pub auto_date: Option<bool>,
+    /// Time source for `date` header.
+    /// Default is `SystemClock`.
+    pub clock: Option<Arc<dyn Clock>>,
+
     pub common: CommonConf,
 }
 
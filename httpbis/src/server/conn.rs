@@ -1,4 +1,7 @@
+use bytes::Bytes;
 use std::panic;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use crate::error;
@@ -26,6 +29,7 @@ use crate::net::socket::SocketStream;
 use crate::common::init_where::InitWhere;
 
 use crate::client_died_error_holder::ConnDiedType;
+use crate::client_died_error_holder::SomethingDiedErrorHolder;
 use crate::common::conn::Conn;
 use crate::common::conn::ConnStateSnapshot;
 use crate::common::conn::SideSpecific;
@@ -35,32 +39,52 @@ use crate::common::conn_write::ConnWriteSideCustom;
 use crate::common::death_aware_channel::DeathAwareSender;
 use crate::common::death_aware_channel::ErrorAwareDrop;
 use crate::common::sender::CommonSender;
+use crate::common::sliding_window_counter::SlidingWindowCounter;
 use crate::common::stream::HttpStreamCommon;
 use crate::common::stream::HttpStreamData;
 use crate::common::stream::HttpStreamDataSpecific;
 use crate::common::stream::InMessageStage;
 use crate::common::stream_handler::StreamHandlerInternal;
 use crate::common::stream_map::HttpStreamRef;
+use crate::common::token_bucket::TokenBucket;
 use crate::headers_place::HeadersPlace;
 use crate::misc::any_to_string;
 use crate::req_resp::RequestOrResponse;
+use crate::server::date::DateCache;
+use crate::server::date::SystemClock;
 use crate::server::handler::ServerHandler;
 use crate::server::handler::ServerHandlerContext;
 use crate::server::req::ServerRequest;
 use crate::server::types::ServerTypes;
+use crate::solicit::frame::GoawayFrame;
+use crate::solicit::frame::HttpSetting;
+use crate::solicit::frame::PushPromiseDecodedFrame;
+use crate::solicit::frame::PushPromiseMultiFrame;
+use crate::solicit::session::StreamState;
 use crate::solicit::stream_id::StreamId;
 use crate::ErrorCode;
+use crate::ServerAlpn;
 use crate::ServerConf;
 use crate::ServerResponse;
 use crate::ServerTlsOption;
 use std::future::Future;
 use std::net::SocketAddr;
 use std::pin::Pin;
+use std::time::Duration;
 use tokio::runtime::Handle;
 
-pub struct ServerStreamData {}
+const DEFAULT_MAX_RST_STREAMS_PER_WINDOW: u32 = 1000;
+const DEFAULT_RST_STREAM_FLOOD_WINDOW: Duration = Duration::from_secs(10);
 
-impl HttpStreamDataSpecific for ServerStreamData {}
+pub struct ServerStreamData {
+    bytes_received: Arc<AtomicU64>,
+}
+
+impl HttpStreamDataSpecific for ServerStreamData {
+    fn data_recvd(&self, len: usize) {
+        self.bytes_received.fetch_add(len as u64, Ordering::Relaxed);
+    }
+}
 
 pub(crate) type ServerStream = HttpStreamCommon<ServerTypes>;
 
@@ -79,9 +103,30 @@ impl HttpStreamData for ServerStream {
 
 pub(crate) struct ServerConnData {
     factory: Arc<dyn ServerHandler>,
+    new_stream_rate_limit: Option<TokenBucket>,
+    max_concurrent_streams: Option<u32>,
+    rst_stream_flood: SlidingWindowCounter,
+    date: Option<Arc<DateCache>>,
+    require_alpn_h2: bool,
 }
 
-impl SideSpecific for ServerConnData {}
+impl SideSpecific for ServerConnData {
+    fn handshake_settings(&self) -> Vec<HttpSetting> {
+        let mut settings = vec![HttpSetting::EnablePush(false)];
+        if let Some(max_concurrent_streams) = self.max_concurrent_streams {
+            settings.push(HttpSetting::MaxConcurrentStreams(max_concurrent_streams));
+        }
+        settings
+    }
+
+    fn require_alpn_h2(&self) -> bool {
+        self.require_alpn_h2
+    }
+
+    fn rst_stream_received(&mut self) -> bool {
+        self.rst_stream_flood.record()
+    }
+}
 
 #[allow(dead_code)] // https://github.com/rust-lang/rust/issues/42303
 type ServerInner<I> = Conn<ServerTypes, I>;
@@ -95,7 +140,7 @@ where
         stream_id: StreamId,
         headers: Headers,
         end_stream: EndStream,
-    ) -> result::Result<HttpStreamRef<ServerTypes>> {
+    ) -> result::Result<Option<HttpStreamRef<ServerTypes>>> {
         if ServerTypes::init_where(stream_id) == InitWhere::Locally {
             return Err(error::Error::InitiatedStreamWithServerIdFromClient(
                 stream_id,
@@ -111,13 +156,63 @@ where
 
         self.last_peer_stream_id = stream_id;
 
+        // Request without body must not declare non-zero `content-length` (8.1.2.6)
+        if end_stream == EndStream::Yes && headers.content_length().unwrap_or(0) != 0 {
+            warn!(
+                "request without body has non-zero content-length: {}",
+                stream_id
+            );
+            self.send_rst_stream(stream_id, ErrorCode::ProtocolError)?;
+            return Ok(None);
+        }
+
+        if self.queued_write.goaway_queued() {
+            debug!("GOAWAY sent, refusing stream {}", stream_id);
+            self.send_rst_stream(stream_id, ErrorCode::RefusedStream)?;
+            return Ok(None);
+        }
+
+        if let Some(last_stream_id) = self.graceful_shutdown_last_stream_id {
+            if stream_id > last_stream_id {
+                warn!("shutting down, refusing stream {}", stream_id);
+                self.send_rst_stream(stream_id, ErrorCode::RefusedStream)?;
+                return Ok(None);
+            }
+        }
+
+        if let Some(rate_limit) = &mut self.specific.new_stream_rate_limit {
+            if !rate_limit.try_take() {
+                warn!(
+                    "new stream rate limit exceeded, refusing stream {}",
+                    stream_id
+                );
+                self.send_rst_stream(stream_id, ErrorCode::RefusedStream)?;
+                return Ok(None);
+            }
+        }
+
+        if let Some(max_concurrent_streams) = self.specific.max_concurrent_streams {
+            if self.streams.peer_streams_count() >= max_concurrent_streams as usize {
+                warn!(
+                    "max concurrent streams {} exceeded, refusing stream {}",
+                    max_concurrent_streams, stream_id
+                );
+                self.send_rst_stream(stream_id, ErrorCode::RefusedStream)?;
+                return Ok(None);
+            }
+        }
+
         debug!("new stream: {}", stream_id);
 
+        let bytes_received = Arc::new(AtomicU64::new(0));
+
         let (_, out_window) = self.new_stream_data(
             stream_id,
             headers.content_length(),
             InMessageStage::AfterInitialHeaders,
-            ServerStreamData {},
+            ServerStreamData {
+                bytes_received: bytes_received.clone(),
+            },
         );
 
         let in_window_size = self
@@ -133,10 +228,16 @@ where
         let sender = ServerResponse {
             common: CommonSender::new(stream_id, self.to_write_tx.clone(), out_window, false),
             drop_callback: None,
+            date: self.specific.date.clone(),
         };
 
         let context = ServerHandlerContext {
             loop_handle: self.loop_handle.clone(),
+            peer_unknown_settings: self
+                .peer_unknown_settings
+                .iter()
+                .map(|(&id, &val)| (id, val))
+                .collect(),
         };
 
         let mut stream_handler = None;
@@ -146,8 +247,10 @@ where
                 end_stream: end_stream == EndStream::Yes,
                 stream_id,
                 in_window_size,
+                window_tuner: self.new_stream_window_tuner(),
                 stream_handler: &mut stream_handler,
                 to_write_tx: &self.to_write_tx,
+                bytes_received,
             };
 
             panic::catch_unwind(panic::AssertUnwindSafe(|| {
@@ -172,12 +275,79 @@ where
 
         stream.stream().peer_tx = stream_handler;
 
-        Ok(stream)
+        Ok(Some(stream))
+    }
+
+    fn push_promise(
+        &mut self,
+        stream_id: StreamId,
+        headers: Headers,
+    ) -> result::Result<ServerResponse> {
+        if !self.peer_settings.enable_push {
+            return Err(error::Error::PushDisabled);
+        }
+
+        if ServerTypes::init_where(stream_id) == InitWhere::Locally {
+            return Err(error::Error::PushOnPushedStream(stream_id));
+        }
+
+        // PUSH_PROMISE frames MUST only be sent on a peer-initiated stream
+        // that is in either the "open" or "half-closed (remote)" state (8.2.1).
+        match self.streams.get_mut(stream_id) {
+            Some(mut stream) => match stream.stream().state {
+                StreamState::Open | StreamState::HalfClosedRemote => {}
+                _ => return Err(error::Error::UnknownStreamId),
+            },
+            None => return Err(error::Error::UnknownStreamId),
+        }
+
+        let promised_stream_id = self.next_local_stream_id();
+
+        debug!(
+            "push promise: {} on stream {}",
+            promised_stream_id, stream_id
+        );
+
+        self.queued_write.queue_not_goaway(PushPromiseMultiFrame {
+            stream_id,
+            promised_stream_id,
+            headers,
+            encoder: &mut self.encoder,
+            max_frame_size: self.peer_settings.max_frame_size,
+        });
+
+        let (mut stream, out_window) = self.new_stream_data(
+            promised_stream_id,
+            None,
+            InMessageStage::Initial,
+            ServerStreamData {
+                bytes_received: Arc::new(AtomicU64::new(0)),
+            },
+        );
+
+        // Client cannot send anything on pushed stream
+        stream.stream().close_remote();
+
+        Ok(ServerResponse {
+            common: CommonSender::new(
+                promised_stream_id,
+                self.to_write_tx.clone(),
+                out_window,
+                false,
+            ),
+            drop_callback: None,
+            date: self.specific.date.clone(),
+        })
     }
 }
 
 pub enum ServerToWriteMessage {
     Common(CommonToWriteMessage),
+    PushPromise(
+        StreamId,
+        Headers,
+        oneshot::Sender<result::Result<ServerResponse>>,
+    ),
 }
 
 impl ErrorAwareDrop for ServerToWriteMessage {
@@ -203,6 +373,11 @@ where
     fn process_message(&mut self, message: ServerToWriteMessage) -> result::Result<()> {
         match message {
             ServerToWriteMessage::Common(common) => self.process_common_message(common),
+            ServerToWriteMessage::PushPromise(stream_id, headers, tx) => {
+                // ignore error, response is reset when dropped
+                let _ = tx.send(self.push_promise(stream_id, headers));
+                Ok(())
+            }
         }
     }
 }
@@ -235,9 +410,7 @@ where
         }
 
         if !existing_stream {
-            return self
-                .new_stream_from_client(stream_id, headers, end_stream)
-                .map(Some);
+            return self.new_stream_from_client(stream_id, headers, end_stream);
         }
 
         if end_stream == EndStream::No {
@@ -250,6 +423,16 @@ where
         stream.stream().trailers_recvd(headers);
         Ok(Some(stream))
     }
+
+    fn process_push_promise(&mut self, frame: PushPromiseDecodedFrame) -> result::Result<()> {
+        // A client cannot push. Thus, servers MUST treat the receipt of a
+        // PUSH_PROMISE frame as a connection error of type PROTOCOL_ERROR (8.2).
+        warn!(
+            "PUSH_PROMISE received from client on stream {}",
+            frame.stream_id
+        );
+        self.send_goaway(ErrorCode::ProtocolError)
+    }
 }
 
 pub struct ServerConn {
@@ -270,10 +453,28 @@ impl ServerConn {
     {
         let (future, write_tx) = Conn::<ServerTypes, I>::new(
             lh.clone(),
-            ServerConnData { factory: service },
+            ServerConnData {
+                factory: service,
+                new_stream_rate_limit: conf.new_streams_per_second.map(TokenBucket::new),
+                max_concurrent_streams: conf.max_concurrent_streams,
+                rst_stream_flood: SlidingWindowCounter::new(
+                    conf.max_rst_streams_per_window
+                        .unwrap_or(DEFAULT_MAX_RST_STREAMS_PER_WINDOW),
+                    conf.rst_stream_flood_window
+                        .unwrap_or(DEFAULT_RST_STREAM_FLOOD_WINDOW),
+                ),
+                date: if conf.auto_date.unwrap_or(true) {
+                    let clock = conf.clock.clone().unwrap_or_else(|| Arc::new(SystemClock));
+                    Some(Arc::new(DateCache::new(clock)))
+                } else {
+                    None
+                },
+                require_alpn_h2: conf.alpn == Some(ServerAlpn::Require),
+            },
             conf.common,
             socket,
             peer_addr,
+            SomethingDiedErrorHolder::new(),
         );
 
         (ServerConn { write_tx }, future)
@@ -364,6 +565,30 @@ impl ServerConn {
         ServerConn::new_plain_single_thread(lh, socket, peer_addr, conf, Arc::new(HttpServiceFn(f)))
     }
 
+    /// Start graceful shutdown of the connection: already started
+    /// requests are processed, new requests are refused.
+    pub fn graceful_shutdown(&self) {
+        let message = ServerToWriteMessage::Common(CommonToWriteMessage::GracefulShutdown);
+        // ignore error
+        drop(self.write_tx.unbounded_send(message));
+    }
+
+    /// Send `GOAWAY` with given error code and debug data, and close the connection.
+    ///
+    /// Fails if debug data does not fit into a frame.
+    pub fn send_goaway_with_debug(
+        &self,
+        error_code: ErrorCode,
+        debug_data: Bytes,
+    ) -> result::Result<()> {
+        GoawayFrame::check_debug_data_len(&debug_data)?;
+        let message =
+            ServerToWriteMessage::Common(CommonToWriteMessage::Goaway(error_code, debug_data));
+        // ignore error
+        drop(self.write_tx.unbounded_send(message));
+        Ok(())
+    }
+
     /// For tests
     pub fn dump_state(&self) -> HttpFutureSend<ConnStateSnapshot> {
         let (tx, rx) = oneshot::channel();
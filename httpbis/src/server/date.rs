@@ -0,0 +1,143 @@
+//! `date` response header value (RFC 7231 section 7.1.1.1).
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use crate::solicit::header::value::HeaderValue;
+
+/// Source of current time for `date` response header.
+pub trait Clock: fmt::Debug + Send + Sync + 'static {
+    /// Current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// Clock which returns `SystemTime::now()`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Format time as IMF-fixdate, e. g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub(crate) fn format_http_date(time: SystemTime) -> String {
+    let secs = unix_secs(time);
+
+    let days = secs / 86400;
+    let secs_of_day = secs % 86400;
+
+    // Convert days since epoch to civil date,
+    // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+    )
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// `date` header value, formatted at most once per second.
+pub(crate) struct DateCache {
+    clock: Arc<dyn Clock>,
+    /// Last formatted value and its time in seconds since epoch
+    cached: Mutex<Option<(u64, HeaderValue)>>,
+}
+
+impl DateCache {
+    pub fn new(clock: Arc<dyn Clock>) -> DateCache {
+        DateCache {
+            clock,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Current time formatted for `date` header.
+    pub fn now(&self) -> HeaderValue {
+        let now = self.clock.now();
+        let secs = unix_secs(now);
+        let mut cached = self.cached.lock().unwrap();
+        match &*cached {
+            Some((cached_secs, value)) if *cached_secs == secs => value.clone(),
+            _ => {
+                let value = HeaderValue::from(format_http_date(now));
+                *cached = Some((secs, value.clone()));
+                value
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn format_secs(secs: u64) -> String {
+        format_http_date(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!("Thu, 01 Jan 1970 00:00:00 GMT", format_secs(0));
+        assert_eq!("Sun, 06 Nov 1994 08:49:37 GMT", format_secs(784111777));
+        assert_eq!("Tue, 29 Feb 2000 23:59:59 GMT", format_secs(951868799));
+        assert_eq!("Fri, 01 Mar 2024 12:00:00 GMT", format_secs(1709294400));
+    }
+
+    #[derive(Debug)]
+    struct TestClock(Mutex<SystemTime>);
+
+    impl Clock for TestClock {
+        fn now(&self) -> SystemTime {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn cache() {
+        let clock = Arc::new(TestClock(Mutex::new(
+            UNIX_EPOCH + Duration::from_secs(784111777),
+        )));
+        let cache = DateCache::new(clock.clone());
+        let first = cache.now();
+        assert_eq!(b"Sun, 06 Nov 1994 08:49:37 GMT", first.as_slice());
+
+        // same second: cached value is returned
+        *clock.0.lock().unwrap() += Duration::from_millis(500);
+        let second = cache.now();
+        assert_eq!(first.as_slice().as_ptr(), second.as_slice().as_ptr());
+
+        *clock.0.lock().unwrap() += Duration::from_millis(500);
+        assert_eq!(b"Sun, 06 Nov 1994 08:49:38 GMT", cache.now().as_slice());
+    }
+}
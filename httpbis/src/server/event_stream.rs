@@ -0,0 +1,72 @@
+use crate::common::sender::SendError;
+use crate::server::resp::ServerResponse;
+use bytes::Bytes;
+
+/// Response body sent as a stream of server-sent events (`text/event-stream`).
+///
+/// Created with `ServerResponse::send_event_stream`.
+pub struct ServerEventStream {
+    pub(crate) resp: ServerResponse,
+}
+
+/// Format event data as `data:` lines terminated by an empty line.
+///
+/// Data is split on `\r\n`, `\r` and `\n`, which are all line
+/// terminators in event stream format.
+fn format_event(data: &str) -> String {
+    let mut event = String::with_capacity(data.len() + 8);
+    let mut rest = data;
+    loop {
+        let (line, next) = match rest.find(['\r', '\n']) {
+            Some(pos) if rest[pos..].starts_with("\r\n") => (&rest[..pos], Some(pos + 2)),
+            Some(pos) => (&rest[..pos], Some(pos + 1)),
+            None => (rest, None),
+        };
+        event.push_str("data: ");
+        event.push_str(line);
+        event.push('\n');
+        match next {
+            Some(next) => rest = &rest[next..],
+            None => break,
+        }
+    }
+    event.push('\n');
+    event
+}
+
+impl ServerEventStream {
+    /// Send an event in its own `DATA` frame.
+    ///
+    /// Multiline data is sent as several `data:` lines of the same event.
+    pub fn send_event(&mut self, data: &str) -> Result<(), SendError> {
+        self.resp.send_data(Bytes::from(format_event(data)))
+    }
+
+    /// Finish the response stream.
+    pub fn finish(mut self) -> Result<(), SendError> {
+        self.resp.send_data_end_of_stream(Bytes::new())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_event_lines() {
+        assert_eq!("data: hello\n\n", format_event("hello"));
+        assert_eq!("data: a\ndata: b\n\n", format_event("a\nb"));
+        assert_eq!("data: \n\n", format_event(""));
+    }
+
+    #[test]
+    fn format_event_line_terminators() {
+        assert_eq!("data: a\ndata: b\n\n", format_event("a\r\nb"));
+        assert_eq!("data: a\ndata: b\n\n", format_event("a\rb"));
+        assert_eq!(
+            "data: a\ndata: \ndata: b\ndata: \n\n",
+            format_event("a\n\rb\r")
+        );
+        assert_eq!("data: a\ndata: \ndata: b\n\n", format_event("a\r\n\r\nb"));
+    }
+}
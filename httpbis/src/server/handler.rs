@@ -5,6 +5,7 @@ use tokio::runtime::Handle;
 
 pub struct ServerHandlerContext {
     pub(crate) loop_handle: Handle,
+    pub(crate) peer_unknown_settings: Vec<(u16, u32)>,
 }
 
 impl ServerHandlerContext {
@@ -12,6 +13,15 @@ impl ServerHandlerContext {
     pub fn loop_remote(&self) -> Handle {
         self.loop_handle.clone()
     }
+
+    /// Settings with identifiers unknown to this implementation
+    /// last advertised by the client on this connection,
+    /// as `(id, value)` pairs ordered by id.
+    ///
+    /// At most 16 identifiers are remembered.
+    pub fn peer_unknown_settings(&self) -> &[(u16, u32)] {
+        &self.peer_unknown_settings
+    }
 }
 
 /// Central HTTP/2 service interface.
@@ -96,6 +96,7 @@ fn test_split_path() {
 #[derive(Default)]
 pub struct ServerHandlerPaths {
     root: Node,
+    default_service: Option<Arc<dyn ServerHandler>>,
 }
 
 impl ServerHandlerPaths {
@@ -165,6 +166,22 @@ impl ServerHandlerPaths {
         self.set_service(path, Arc::new(service))
     }
 
+    /// Register several services at once.
+    pub fn set_services<I>(&mut self, services: I)
+    where
+        I: IntoIterator<Item = (String, Arc<dyn ServerHandler>)>,
+    {
+        for (path, service) in services {
+            self.set_service(&path, service);
+        }
+    }
+
+    /// Service invoked when no registered path matches the request,
+    /// instead of responding with `404`.
+    pub fn set_default_service(&mut self, service: Arc<dyn ServerHandler>) {
+        self.default_service = Some(service);
+    }
+
     pub fn remove_service(&mut self, path: &str) -> Option<Arc<dyn ServerHandler>> {
         assert!(path.starts_with("/"));
         self.root.remove_service(path)
@@ -185,6 +202,9 @@ impl ServerHandler for ServerHandlerPaths {
         if let Some(service) = self.find_service(req.headers.path()) {
             info!("invoking user callback for path {}", req.headers.path());
             service.start_request(context, req, resp)
+        } else if let Some(service) = &self.default_service {
+            info!("invoking default callback for path {}", req.headers.path());
+            service.start_request(context, req, resp)
         } else {
             info!("serving 404 for path {}", req.headers.path());
             drop(resp.send_headers(Headers::not_found_404()));
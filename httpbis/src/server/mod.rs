@@ -1,5 +1,7 @@
 pub mod conf;
 pub mod conn;
+mod date;
+pub mod event_stream;
 pub mod handler;
 pub mod handler_paths;
 pub(crate) mod increase_in_window;
@@ -47,8 +49,13 @@ use crate::net::unix::SocketAddrUnix;
 use crate::result;
 pub use crate::server::conf::ServerConf;
 pub use crate::server::conn::ServerConn;
+pub use crate::server::date::Clock;
+pub use crate::server::date::SystemClock;
 use crate::server::handler::ServerHandler;
 use crate::server::handler_paths::ServerHandlerPaths;
+use crate::solicit::frame::GoawayFrame;
+use crate::ErrorCode;
+use bytes::Bytes;
 use rand::thread_rng;
 use rand::Rng;
 use std::fmt;
@@ -135,11 +142,26 @@ impl<A: tls_api::TlsAcceptor> ServerBuilder<A> {
         }
     }
 
+    /// Register several services at once, see `ServerHandlerPaths::set_service`.
+    pub fn set_services<I>(&mut self, services: I)
+    where
+        I: IntoIterator<Item = (String, Arc<dyn ServerHandler>)>,
+    {
+        self.service.set_services(services);
+    }
+
+    /// Set service for requests not matching any registered path.
+    pub fn set_default_service(&mut self, service: Arc<dyn ServerHandler>) {
+        self.service.set_default_service(service);
+    }
+
     pub fn set_tls(&mut self, acceptor: A) {
         self.tls = ServerTlsOption::Tls(Arc::new(acceptor));
     }
 
     pub fn build(self) -> Result<Server> {
+        self.conf.common.validate()?;
+
         let (alive_tx, alive_rx) = mpsc::channel();
 
         let state: Arc<Mutex<ServerState>> = Default::default();
@@ -388,6 +410,31 @@ impl Server {
         self.alive_rx.try_recv() != Err(mpsc::TryRecvError::Disconnected)
     }
 
+    /// Gracefully shut down current connections.
+    ///
+    /// Each connection sends `GOAWAY`, completes requests already started,
+    /// refuses new requests with `REFUSED_STREAM`, then sends final `GOAWAY`
+    /// and closes. The server keeps accepting new connections until dropped.
+    pub fn graceful_shutdown(&self) {
+        let g = self.state.lock().expect("lock");
+        for conn in g.conns.values() {
+            conn.graceful_shutdown();
+        }
+    }
+
+    /// Send `GOAWAY` with given error code and debug data on current
+    /// connections and close them.
+    ///
+    /// Fails if debug data does not fit into a frame.
+    pub fn send_goaway_with_debug(&self, error_code: ErrorCode, debug_data: Bytes) -> Result<()> {
+        GoawayFrame::check_debug_data_len(&debug_data)?;
+        let g = self.state.lock().expect("lock");
+        for conn in g.conns.values() {
+            conn.send_goaway_with_debug(error_code, debug_data.clone())?;
+        }
+        Ok(())
+    }
+
     // for tests
     pub fn dump_state(&self) -> HttpFutureSend<ServerStateSnapshot> {
         let g = self.state.lock().expect("lock");
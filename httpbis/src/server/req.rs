@@ -1,14 +1,27 @@
+use crate::bytes_ext::bytes_deque::BytesDeque;
 use crate::common::death_aware_channel::DeathAwareSender;
 use crate::common::increase_in_window::IncreaseInWindow;
 use crate::common::stream_from_network::StreamFromNetwork;
 use crate::common::stream_queue_sync::stream_queue_sync;
+use crate::common::window_tuner::WindowTuner;
+use crate::error;
+use crate::grpc_messages::GrpcMessages;
+use crate::grpc_messages::DEFAULT_GRPC_MAX_MESSAGE_LEN;
+use crate::result;
 use crate::server::conn::ServerToWriteMessage;
 use crate::server::increase_in_window::ServerIncreaseInWindow;
 use crate::server::stream_handler::ServerRequestStreamHandler;
 use crate::server::stream_handler::ServerRequestStreamHandlerHolder;
+use crate::solicit_async::HttpFutureSend;
 use crate::Headers;
 use crate::HttpStreamAfterHeaders;
 use crate::StreamId;
+use bytes::Bytes;
+use futures::stream::Stream;
+use futures::stream::TryStreamExt;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 pub struct ServerRequest<'a> {
     /// Request headers
@@ -18,16 +31,52 @@ pub struct ServerRequest<'a> {
     pub(crate) stream_id: StreamId,
     /// Stream in window size at the moment of request start
     pub(crate) in_window_size: u32,
+    pub(crate) window_tuner: WindowTuner,
     pub(crate) stream_handler: &'a mut Option<ServerRequestStreamHandlerHolder>,
     pub(crate) to_write_tx: &'a DeathAwareSender<ServerToWriteMessage>,
+    pub(crate) bytes_received: Arc<AtomicU64>,
 }
 
 impl<'a> ServerRequest<'a> {
-    pub fn make_stream(self) -> HttpStreamAfterHeaders {
+    /// Request body length as declared by `content-length` header.
+    ///
+    /// This is only a hint: the peer may send a body of different size.
+    pub fn content_length_hint(&self) -> Option<u64> {
+        self.headers.content_length()
+    }
+
+    /// Check that request `content-type` media type (ignoring parameters
+    /// like `charset`) is one of `types`, compared case-insensitively.
+    ///
+    /// Fails with `Error::UnsupportedMediaType`, which is usually
+    /// answered with `ServerResponse::send_unsupported_media_type`.
+    pub fn expect_content_type(&self, types: &[&str]) -> result::Result<()> {
+        let content_type = self.headers.get_opt("content-type");
+        let media_type = content_type.map(|c| c.split(';').next().unwrap().trim());
+        match media_type {
+            Some(m) if types.iter().any(|t| t.eq_ignore_ascii_case(m)) => Ok(()),
+            _ => Err(error::Error::UnsupportedMediaType(
+                content_type.map(|c| c.to_owned()),
+            )),
+        }
+    }
+
+    /// Number of request body bytes received from network so far.
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    pub fn make_stream(mut self) -> HttpStreamAfterHeaders {
+        self.take_stream()
+    }
+
+    /// Like `make_stream`, but does not consume the request.
+    /// Panics if the body stream was already taken.
+    pub(crate) fn take_stream(&mut self) -> HttpStreamAfterHeaders {
         if self.end_stream {
             HttpStreamAfterHeaders::empty()
         } else {
-            self.register_stream_handler(|increase_in_window| {
+            self.register_stream_handler_mut(|increase_in_window| {
                 let (inc_tx, inc_rx) = stream_queue_sync();
                 let stream_from_network = StreamFromNetwork {
                     rx: inc_rx,
@@ -42,10 +91,54 @@ impl<'a> ServerRequest<'a> {
         }
     }
 
+    /// Read the whole request body into memory.
+    ///
+    /// Fails with `Error::BodyTooLarge` if the body is longer than `max` bytes.
+    /// Receive window is replenished as the body is read.
+    pub fn collect_body(self, max: usize) -> HttpFutureSend<Bytes> {
+        let mut data = self.make_stream().filter_data();
+        Box::pin(async move {
+            let mut body = BytesDeque::new();
+            while let Some(bytes) = data.try_next().await? {
+                if body.len() + bytes.len() > max {
+                    return Err(error::Error::BodyTooLarge(max));
+                }
+                body.extend(bytes);
+            }
+            Ok(body.into_bytes())
+        })
+    }
+
+    /// Stream of request body messages in gRPC framing
+    /// (compression flag and 4-byte length followed by message).
+    ///
+    /// Messages are reassembled regardless of `DATA` frame boundaries.
+    /// Compressed messages are not supported. Messages longer than 4 MiB
+    /// fail the stream with `Error::GrpcMessageTooLarge`.
+    pub fn grpc_messages(self) -> impl Stream<Item = result::Result<Bytes>> + Send {
+        self.grpc_messages_max_len(DEFAULT_GRPC_MAX_MESSAGE_LEN)
+    }
+
+    /// Like `grpc_messages` with the limit of a single message length.
+    pub fn grpc_messages_max_len(
+        self,
+        max_message_len: u32,
+    ) -> impl Stream<Item = result::Result<Bytes>> + Send {
+        GrpcMessages::new(Box::pin(self.make_stream().filter_data()), max_message_len)
+    }
+
     /// Register synchnous stream handler (callback will be called immediately
     /// when new data arrives). Note that increasing in window size is the handler
     /// responsibility.
-    pub fn register_stream_handler<F, H, R>(self, f: F) -> R
+    pub fn register_stream_handler<F, H, R>(mut self, f: F) -> R
+    where
+        F: FnOnce(ServerIncreaseInWindow) -> (H, R),
+        H: ServerRequestStreamHandler,
+    {
+        self.register_stream_handler_mut(f)
+    }
+
+    fn register_stream_handler_mut<F, H, R>(&mut self, f: F) -> R
     where
         F: FnOnce(ServerIncreaseInWindow) -> (H, R),
         H: ServerRequestStreamHandler,
@@ -55,6 +148,7 @@ impl<'a> ServerRequest<'a> {
             stream_id: self.stream_id,
             in_window_size: self.in_window_size,
             to_write_tx: self.to_write_tx.clone(),
+            tuner: self.window_tuner.clone(),
         });
         let (h, r) = f(increase_window);
         *self.stream_handler = Some(ServerRequestStreamHandlerHolder(Box::new(h)));
@@ -2,8 +2,16 @@ use crate::assert_types::assert_send;
 use crate::common::sender::CommonSender;
 use crate::common::sender::SendError;
 
+use crate::error;
+use crate::headers_place::HeadersPlace;
+use crate::req_resp::RequestOrResponse;
 use crate::result;
+use crate::server::conn::ServerToWriteMessage;
+use crate::server::date::DateCache;
+use crate::server::event_stream::ServerEventStream;
+use crate::server::req::ServerRequest;
 use crate::server::types::ServerTypes;
+use crate::solicit_async::HttpFutureSend;
 use crate::ErrorCode;
 use crate::Headers;
 use crate::HttpStreamAfterHeaders;
@@ -11,9 +19,13 @@ use crate::SenderState;
 use crate::SimpleHttpMessage;
 use crate::StreamDead;
 use bytes::Bytes;
+use futures::channel::oneshot;
+use futures::future;
 use futures::stream::Stream;
 use futures::task::Context;
+use futures::TryFutureExt;
 use std::mem;
+use std::sync::Arc;
 use std::task::Poll;
 
 // NOTE: Keep in sync with ClientRequest
@@ -22,6 +34,8 @@ pub struct ServerResponse {
     // need to replace with FnOnce when rust allows it
     pub(crate) drop_callback:
         Option<Box<dyn FnMut(&mut ServerResponse) -> result::Result<()> + Send>>,
+    /// Add `date` header if response headers do not have it.
+    pub(crate) date: Option<Arc<DateCache>>,
 }
 
 impl Drop for ServerResponse {
@@ -44,6 +58,16 @@ fn _assert_types() {
     assert_send::<ServerResponse>();
 }
 
+/// Reject response headers which cannot be sent, e. g. `:status` with reason phrase.
+fn validate_response_headers(
+    headers: &Headers,
+    headers_place: HeadersPlace,
+) -> Result<(), SendError> {
+    headers
+        .validate(RequestOrResponse::Response, headers_place)
+        .map_err(SendError::IncorrectHeaders)
+}
+
 impl ServerResponse {
     pub fn state(&self) -> SenderState {
         self.common.state()
@@ -64,23 +88,71 @@ impl ServerResponse {
         self.common.poll(cx)
     }
 
-    pub fn send_headers(&mut self, headers: Headers) -> Result<(), SendError> {
+    /// Resolve when stream window allows sending more data.
+    pub async fn ready(&mut self) -> Result<(), StreamDead> {
+        future::poll_fn(|cx| self.poll(cx)).await
+    }
+
+    fn add_date(&self, headers: &mut Headers) {
+        if let Some(date) = &self.date {
+            if headers.get_opt("date").is_none() {
+                headers.add("date", date.now());
+            }
+        }
+    }
+
+    pub fn send_headers(&mut self, mut headers: Headers) -> Result<(), SendError> {
+        validate_response_headers(&headers, HeadersPlace::Initial)?;
+        self.add_date(&mut headers);
         self.common.send_headers(headers)
     }
 
-    pub fn send_headers_end_of_stream(&mut self, headers: Headers) -> Result<(), SendError> {
+    pub fn send_headers_end_of_stream(&mut self, mut headers: Headers) -> Result<(), SendError> {
+        validate_response_headers(&headers, HeadersPlace::Initial)?;
+        self.add_date(&mut headers);
         self.common.send_headers_end_of_stream(headers)
     }
 
+    /// Send a response consisting of single `HEADERS` frame with `END_STREAM` flag.
+    ///
+    /// Headers contain both `:status` and trailers
+    /// (used for example in gRPC "trailers-only" responses).
+    pub fn send_trailers_only(&mut self, headers: Headers) -> Result<(), SendError> {
+        self.send_headers_end_of_stream(headers)
+    }
+
+    /// Enqueue data without waiting for stream window or
+    /// `CommonConf::out_buffer_limit`, use `send_data_wait`
+    /// to bound memory used by queued data.
     pub fn send_data(&mut self, data: Bytes) -> Result<(), SendError> {
         self.common.send_data(data)
     }
 
+    /// Wait until stream window allows sending and enqueue data.
+    ///
+    /// Unlike `send_data`, this also waits while outgoing buffers
+    /// are full (see `CommonConf::out_buffer_limit`).
+    pub async fn send_data_wait(&mut self, data: Bytes) -> Result<(), SendError> {
+        self.common.send_data_wait(data).await
+    }
+
     pub fn send_data_end_of_stream(&mut self, data: Bytes) -> Result<(), SendError> {
         self.common.send_data_end_of_stream(data)
     }
 
+    /// Send data and flush the socket right after the data is written.
+    ///
+    /// Data sent after this call is written by a separate socket write.
+    pub fn send_data_flush(&mut self, data: Bytes) -> Result<(), SendError> {
+        self.common.send_data_flush(data)
+    }
+
+    /// Send trailing `HEADERS` with `END_STREAM` flag after the body.
+    ///
+    /// Fails if headers were not sent, stream is already ended,
+    /// or trailers contain pseudo-headers.
     pub fn send_trailers(&mut self, trailers: Headers) -> Result<(), SendError> {
+        validate_response_headers(&trailers, HeadersPlace::Trailing)?;
         self.common.send_trailers(trailers)
     }
 
@@ -88,6 +160,22 @@ impl ServerResponse {
         self.common.pull_from_stream(stream)
     }
 
+    /// Send trailers of request `req` as trailers of this response,
+    /// ending the response when the request ends (for proxies).
+    ///
+    /// Response headers must be sent before. Request body is read
+    /// and discarded, so `req` is consumed:
+    ///
+    /// ```compile_fail
+    /// fn proxy(mut resp: httpbis::ServerResponse, req: httpbis::ServerRequest) {
+    ///     resp.forward_trailers_from(req).unwrap();
+    ///     req.make_stream();
+    /// }
+    /// ```
+    pub fn forward_trailers_from(&mut self, req: ServerRequest) -> Result<(), SendError> {
+        self.pull_from_stream(req.make_stream().filter_trailers())
+    }
+
     pub fn pull_bytes_from_stream<S>(&mut self, stream: S) -> Result<(), SendError>
     where
         S: Stream<Item = result::Result<Bytes>> + Send + 'static,
@@ -97,7 +185,13 @@ impl ServerResponse {
 
     pub fn send_message(&mut self, message: SimpleHttpMessage) -> Result<(), SendError> {
         self.send_headers(message.headers)?;
-        self.send_data_end_of_stream(message.body.into_bytes())?;
+        match message.trailers {
+            None => self.send_data_end_of_stream(message.body.into_bytes())?,
+            Some(trailers) => {
+                self.send_data(message.body.into_bytes())?;
+                self.send_trailers(trailers)?;
+            }
+        }
         Ok(())
     }
 
@@ -117,6 +211,51 @@ impl ServerResponse {
         self.send_message(SimpleHttpMessage::internal_error_500(message))
     }
 
+    /// Send `415` headers without body, e. g. when
+    /// `ServerRequest::expect_content_type` fails.
+    pub fn send_unsupported_media_type(&mut self) -> Result<(), SendError> {
+        self.send_headers_end_of_stream(Headers::unsupported_media_type_415())
+    }
+
+    /// Send `200` headers with `content-type: text/event-stream`
+    /// and return a handle to send server-sent events.
+    ///
+    /// Each event is sent in a separate `DATA` frame as soon as possible.
+    pub fn send_event_stream(mut self) -> Result<ServerEventStream, SendError> {
+        let mut headers = Headers::ok_200();
+        headers.add("content-type", "text/event-stream");
+        self.send_headers(headers)?;
+        Ok(ServerEventStream { resp: self })
+    }
+
+    /// Push a response to the request the client did not send yet (section 8.2).
+    ///
+    /// `headers` are the request headers of the promised request.
+    /// Push must be initiated before this response is finished.
+    /// The future resolves to the sender of the pushed response
+    /// or fails with `Error::PushDisabled` if the client disabled push.
+    pub fn push_promise(&mut self, headers: Headers) -> HttpFutureSend<ServerResponse> {
+        if let Err(e) = headers.validate(RequestOrResponse::Request, HeadersPlace::Initial) {
+            return Box::pin(future::err(error::Error::SendError(
+                SendError::IncorrectHeaders(e),
+            )));
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let stream_id = self.common.stream_id();
+        if let Err(e) = self
+            .common
+            .send_message(ServerToWriteMessage::PushPromise(stream_id, headers, tx))
+        {
+            return Box::pin(future::err(error::Error::SendError(e)));
+        }
+
+        Box::pin(
+            rx.map_err(|_| error::Error::ConnDied(Arc::new(error::Error::DeathReasonUnknown)))
+                .and_then(future::ready),
+        )
+    }
+
     pub fn reset(&mut self, error_code: ErrorCode) -> Result<(), SendError> {
         self.common.reset(error_code)
     }
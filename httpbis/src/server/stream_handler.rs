@@ -15,6 +15,12 @@ pub trait ServerRequestStreamHandler: Send + 'static {
     fn rst(&mut self, error_code: ErrorCode) -> result::Result<()> {
         self.error(error::Error::CodeError(error_code))
     }
+    /// RST_STREAM frame received, with error code as sent by the peer.
+    ///
+    /// Unknown error codes are passed to `rst` as `InternalError` by default.
+    fn rst_raw(&mut self, raw_error_code: u32) -> result::Result<()> {
+        self.rst(ErrorCode::from(raw_error_code))
+    }
     /// Any other error
     fn error(&mut self, error: error::Error) -> result::Result<()>;
 }
@@ -30,8 +36,8 @@ impl StreamHandlerInternal for ServerRequestStreamHandlerHolder {
         self.0.trailers(trailers)
     }
 
-    fn rst(mut self, error_code: ErrorCode) -> result::Result<()> {
-        self.0.rst(error_code)
+    fn rst(mut self, raw_error_code: u32) -> result::Result<()> {
+        self.0.rst_raw(raw_error_code)
     }
 
     fn error(mut self, error: error::Error) -> result::Result<()> {
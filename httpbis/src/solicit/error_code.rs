@@ -49,7 +49,7 @@ impl fmt::Display for ErrorCode {
 }
 
 impl ErrorCode {
-    fn try_from(error_code: u32) -> Result<ErrorCode, u32> {
+    pub(crate) fn try_from(error_code: u32) -> Result<ErrorCode, u32> {
         Ok(match error_code {
             0x0 => ErrorCode::NoError,
             0x1 => ErrorCode::ProtocolError,
@@ -9,11 +9,15 @@ use crate::solicit::frame::FrameIR;
 use crate::solicit::frame::ParseFrameError;
 use crate::solicit::frame::ParseFrameResult;
 use crate::solicit::frame::RawFrame;
+use crate::solicit::frame::MAX_FRAME_PAYLOAD_LEN;
 
 use crate::codec::write_buffer::WriteBuffer;
+use crate::error;
 use crate::misc::BsDebug;
+use crate::result;
 use crate::solicit::stream_id::StreamId;
 use bytes::Bytes;
+use std::cmp;
 use std::fmt;
 
 pub const DATA_FRAME_TYPE: u8 = 0x0;
@@ -54,6 +58,9 @@ pub struct DataFrame {
     pub stream_id: StreamId,
     /// The data found in the frame as an opaque byte sequence. It never
     /// includes padding bytes.
+    ///
+    /// When the frame is parsed, this is a slice of the `RawFrame` buffer,
+    /// so payload is not copied.
     pub data: Bytes,
     /// The length of the padding applied to the data. Since the spec defines
     /// that the padding length is at most an unsigned integer value, we also
@@ -108,27 +115,46 @@ impl DataFrame {
 
     /// Returns the total length of the payload, taking into account possible
     /// padding.
+    ///
+    /// Saturates at `u32::MAX`, use `checked_payload_len` to check
+    /// that the payload fits into a single frame.
     pub fn payload_len(&self) -> u32 {
-        if self.is_padded() {
-            1 + (self.data.len() as u32) + (self.padding_len as u32)
+        let len = if self.is_padded() {
+            self.data.len() as u64 + 1 + self.padding_len as u64
+        } else {
+            self.data.len() as u64
+        };
+        cmp::min(len, u32::MAX as u64) as u32
+    }
+
+    /// Returns the total length of the payload, or an error if it does not fit
+    /// into 24-bit frame length field.
+    pub fn checked_payload_len(&self) -> result::Result<u32> {
+        let len = if self.is_padded() {
+            self.data.len().checked_add(1 + self.padding_len as usize)
         } else {
-            // Downcasting here is all right, because the HTTP/2 frames cannot
-            // have a length larger than a 32 bit unsigned integer.
-            self.data.len() as u32
+            Some(self.data.len())
+        };
+        match len {
+            Some(len) if len <= MAX_FRAME_PAYLOAD_LEN as usize => Ok(len as u32),
+            len => Err(error::Error::PayloadTooLarge(
+                len.map_or(u32::MAX, |len| len.min(u32::MAX as usize) as u32),
+                MAX_FRAME_PAYLOAD_LEN,
+            )),
         }
     }
 
-    /// Parses the given slice as a DATA frame's payload. Depending on the
+    /// Parses the given bytes as a DATA frame's payload. Depending on the
     /// `padded` flag, it will treat the given bytes as a data frame with
     /// padding or without.
     ///
     /// # Returns
     ///
-    /// A tuple wrapped in the `Some` variant, representing the true data and
-    /// the original padding length.
-    /// If there was no padding, returns `None` for the second tuple member.
+    /// A tuple representing the true data and the original padding length.
+    /// The data is a slice of `payload`, not a copy.
+    /// If there was no padding, the padding length is 0.
     ///
-    /// If the payload was invalid for a DATA frame, returns `None`
+    /// If the payload was invalid for a DATA frame, returns an error.
     fn parse_payload(payload: Bytes, padded: bool) -> ParseFrameResult<(Bytes, u8)> {
         parse_padded_payload(payload, padded)
     }
@@ -208,7 +234,14 @@ impl Frame for DataFrame {
 }
 
 impl FrameIR for DataFrame {
-    fn serialize_into(self, b: &mut WriteBuffer) {
+    /// Data which does not fit into a single frame is split into several
+    /// frames, only the last one carries flags and padding.
+    fn serialize_into(mut self, b: &mut WriteBuffer) {
+        while self.checked_payload_len().is_err() {
+            let len = cmp::min(MAX_FRAME_PAYLOAD_LEN as usize, self.data.len());
+            DataFrame::with_data(self.stream_id, self.data.split_to(len)).serialize_into(b);
+        }
+
         b.write_header(self.get_header());
         if self.is_padded() {
             let pad_len: u8 = self.padding_len;
@@ -250,8 +283,10 @@ impl<'a> fmt::Debug for DataFrameDebugNoData<'a> {
 mod tests {
     use super::DataFlag;
     use super::DataFrame;
+    use crate::error;
     use crate::solicit::frame::pack_header;
     use crate::solicit::frame::tests::build_padded_frame_payload;
+    use crate::solicit::frame::unpack_header_from_slice;
     use crate::solicit::frame::Frame;
     use crate::solicit::frame::FrameHeader;
     use crate::solicit::frame::FrameIR;
@@ -304,6 +339,21 @@ mod tests {
         assert_eq!(frame.get_header(), header);
     }
 
+    /// Tests that the parsed data shares the buffer of the raw frame.
+    #[test]
+    fn test_data_frame_parse_zero_copy() {
+        let data = b"asdf";
+        let payload = build_padded_frame_payload(data, 5);
+        let header = FrameHeader::new(payload.len() as u32, 0u8, 8u8, 1u32);
+
+        let raw = raw_frame_from_parts(header, payload);
+        let frame: DataFrame = Frame::from_raw(&raw).unwrap();
+
+        assert_eq!(&frame.data[..], &data[..]);
+        // 9 bytes of frame header and 1 byte of padding length
+        assert_eq!(raw.raw_content[10..].as_ptr(), frame.data.as_ptr());
+    }
+
     /// Tests that a DATA frame with a zero-length payload is still considered
     /// valid.
     ///
@@ -517,4 +567,43 @@ mod tests {
 
         assert_eq!(serialized, expected);
     }
+
+    #[test]
+    fn test_data_frame_payload_len_max() {
+        let mut frame = DataFrame::with_data(1, Bytes::from(vec![0; (1 << 24) - 3]));
+        frame.set_padding(1);
+
+        assert_eq!((1 << 24) - 1, frame.checked_payload_len().unwrap());
+    }
+
+    #[test]
+    fn test_data_frame_serialize_split() {
+        let mut frame = DataFrame::with_data(1, Bytes::from(vec![0; (1 << 24) - 2]));
+        frame.set_padding(1);
+        frame.set_flag(DataFlag::EndStream);
+        assert_eq!(1 << 24, frame.payload_len());
+
+        let serialized = frame.serialize_into_vec();
+
+        let first = unpack_header_from_slice(&serialized[..9]);
+        assert_eq!(FrameHeader::new((1 << 24) - 2, 0, 0, 1), first);
+        let second = unpack_header_from_slice(&serialized[9 + (1 << 24) - 2..][..9]);
+        // END_STREAM | PADDED, empty data
+        assert_eq!(FrameHeader::new(1 + 1, 0, 0x1 | 0x8, 1), second);
+        assert_eq!(9 + (1 << 24) - 2 + 9 + 2, serialized.len());
+    }
+
+    #[test]
+    fn test_data_frame_payload_len_overflow() {
+        let mut frame = DataFrame::with_data(1, Bytes::from(vec![0; (1 << 24) - 2]));
+        frame.set_padding(1);
+
+        match frame.checked_payload_len() {
+            Err(error::Error::PayloadTooLarge(len, max)) => {
+                assert_eq!(1 << 24, len);
+                assert_eq!((1 << 24) - 1, max);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
 }
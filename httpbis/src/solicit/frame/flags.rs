@@ -71,6 +71,15 @@ impl<F: Flag> Flags<F> {
         flags.clear(flag);
         flags
     }
+
+    /// Known flags which are set, unknown bits are skipped.
+    pub fn iter_set(&self) -> impl Iterator<Item = F> {
+        let flags = *self;
+        F::flags()
+            .iter()
+            .cloned()
+            .filter(move |&flag| flags.is_set(flag))
+    }
 }
 
 impl<F: Flag> Default for Flags<F> {
@@ -116,6 +125,7 @@ impl<F: Flag> fmt::Debug for Flags<F> {
 mod test {
 
     use super::*;
+    use crate::solicit::frame::HeadersFlag;
 
     #[derive(Copy, Clone, Debug, PartialEq, Eq)]
     enum FakeFlag {
@@ -145,4 +155,17 @@ mod test {
             format!("{:?}", Flags::<FakeFlag>::new(0x81 | 0x62))
         );
     }
+
+    #[test]
+    fn iter_set() {
+        let flags = Flags::<HeadersFlag>::default()
+            .with(HeadersFlag::EndStream)
+            .with(HeadersFlag::EndHeaders);
+        assert_eq!(
+            vec![HeadersFlag::EndStream, HeadersFlag::EndHeaders],
+            flags.iter_set().collect::<Vec<_>>()
+        );
+
+        assert_eq!(0, Flags::<FakeFlag>::new(0x62).iter_set().count());
+    }
 }
@@ -3,6 +3,8 @@
 use bytes::Bytes;
 
 use crate::codec::write_buffer::WriteBuffer;
+use crate::error;
+use crate::result;
 use crate::solicit::error_code::ErrorCodeOrUnknown;
 use crate::solicit::frame::flags::*;
 use crate::solicit::frame::parse_stream_id;
@@ -21,6 +23,9 @@ use crate::ErrorCode;
 pub const GOAWAY_MIN_FRAME_LEN: u32 = 8;
 /// The frame type of the `GOAWAY` frame.
 pub const GOAWAY_FRAME_TYPE: u8 = 0x7;
+/// Max length of debug data in `GOAWAY` frame which any peer accepts
+/// (frame payload must fit into initial `SETTINGS_MAX_FRAME_SIZE` of 16384).
+pub const GOAWAY_MAX_DEBUG_DATA_LEN: u32 = 16384 - GOAWAY_MIN_FRAME_LEN;
 
 /// The struct represents the `GOAWAY` HTTP/2 frame.
 #[derive(Clone, Debug, PartialEq)]
@@ -41,6 +46,17 @@ impl GoawayFrame {
         GoawayFrame::with_debug_data(last_stream_id, error_code, Bytes::new())
     }
 
+    /// Check that debug data fits into a frame any peer accepts.
+    pub fn check_debug_data_len(debug_data: &Bytes) -> result::Result<()> {
+        if debug_data.len() > GOAWAY_MAX_DEBUG_DATA_LEN as usize {
+            return Err(error::Error::PayloadTooLarge(
+                debug_data.len().min(u32::MAX as usize) as u32,
+                GOAWAY_MAX_DEBUG_DATA_LEN,
+            ));
+        }
+        Ok(())
+    }
+
     /// Create a new `GOAWAY` frame with the given parts.
     pub fn with_debug_data(
         last_stream_id: StreamId,
@@ -253,4 +269,22 @@ mod tests {
 
         assert_eq!(expected, raw);
     }
+
+    #[test]
+    fn test_round_trip() {
+        for debug_data in &[Bytes::new(), Bytes::from_static(b"too many streams")] {
+            let frame =
+                GoawayFrame::with_debug_data(17, ErrorCode::EnhanceYourCalm, debug_data.clone());
+            let raw = raw_frame_from_parts(
+                frame.get_header(),
+                frame.clone().serialize_into_vec()[9..].to_vec(),
+            );
+            let parsed = GoawayFrame::from_raw(&raw).expect("Expected successful parse");
+
+            assert_eq!(frame, parsed);
+            assert_eq!(17, parsed.last_stream_id());
+            assert_eq!(ErrorCode::EnhanceYourCalm, parsed.error_code());
+            assert_eq!(debug_data, parsed.debug_data());
+        }
+    }
 }
@@ -10,6 +10,7 @@ use crate::solicit::frame::continuation::ContinuationFlag;
 use crate::solicit::frame::flags::*;
 use crate::solicit::frame::pack_header;
 use crate::solicit::frame::parse_padded_payload;
+use crate::solicit::frame::push_promise::PushPromiseFlag;
 use crate::solicit::frame::Frame;
 use crate::solicit::frame::FrameBuilder;
 use crate::solicit::frame::FrameHeader;
@@ -62,7 +63,7 @@ impl Flag for HeadersFlag {
 
 /// The struct represents the dependency information that can be attached to
 /// a stream and sent within a HEADERS frame (one with the Priority flag set).
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub struct StreamDependency {
     /// The ID of the stream that a particular stream depends on
     pub stream_id: StreamId,
@@ -410,8 +411,9 @@ impl<'a> fmt::Debug for HeadersMultiFrame<'a> {
     }
 }
 
-enum HeadersFrameType {
+pub(super) enum HeadersFrameType {
     Headers,
+    PushPromise,
     Continuation,
 }
 
@@ -419,6 +421,7 @@ impl HeadersFrameType {
     fn frame_type(&self) -> HttpFrameType {
         match self {
             HeadersFrameType::Headers => HttpFrameType::Headers,
+            HeadersFrameType::PushPromise => HttpFrameType::PushPromise,
             HeadersFrameType::Continuation => HttpFrameType::Continuation,
         }
     }
@@ -434,6 +437,10 @@ impl HeadersFrameType {
                 }
                 .0
             }
+            HeadersFrameType::PushPromise => match last {
+                true => PushPromiseFlag::EndHeaders.bitmask(),
+                false => 0,
+            },
             HeadersFrameType::Continuation => match last {
                 true => ContinuationFlag::EndHeaders.bitmask(),
                 false => 0,
@@ -442,17 +449,17 @@ impl HeadersFrameType {
     }
 }
 
-struct EncodeBufForHeadersMultiFrame<'a> {
-    current_frame_type: HeadersFrameType,
-    current_frame_offset: usize,
-    stream_id: StreamId,
-    flags: Flags<HeadersFlag>,
-    builder: WriteBufferTailVec<'a>,
-    max_frame_size: u32,
+pub(super) struct EncodeBufForHeadersMultiFrame<'a> {
+    pub(super) current_frame_type: HeadersFrameType,
+    pub(super) current_frame_offset: usize,
+    pub(super) stream_id: StreamId,
+    pub(super) flags: Flags<HeadersFlag>,
+    pub(super) builder: WriteBufferTailVec<'a>,
+    pub(super) max_frame_size: u32,
 }
 
 impl<'a> EncodeBufForHeadersMultiFrame<'a> {
-    fn open_frame(&mut self) {
+    pub(super) fn open_frame(&mut self) {
         self.current_frame_offset = self.builder.remaining();
         // Length is not known at the moment so write an empty head
         // It will be patched later in `finish_frame`.
@@ -465,7 +472,7 @@ impl<'a> EncodeBufForHeadersMultiFrame<'a> {
         }));
     }
 
-    fn finish_frame(&mut self, last: bool) {
+    pub(super) fn finish_frame(&mut self, last: bool) {
         let frame_length = (self.builder.remaining() - self.current_frame_offset) as u32;
         debug_assert!(frame_length >= FRAME_HEADER_LEN as u32);
         let length = frame_length - FRAME_HEADER_LEN as u32;
@@ -530,12 +537,7 @@ impl<'a> FrameIR for HeadersMultiFrame<'a> {
 
         buf.open_frame();
 
-        let headers = self
-            .headers
-            .iter()
-            .map(|h| (h.name().as_bytes(), h.value()));
-
-        self.encoder.encode_into(headers, &mut buf);
+        hpack::encode_headers_into(self.encoder, &self.headers, &mut buf);
 
         buf.finish_frame(true);
     }
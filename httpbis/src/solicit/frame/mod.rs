@@ -64,10 +64,13 @@ pub use self::headers::HeadersDecodedFrame;
 pub use self::headers::HeadersFlag;
 pub use self::headers::HeadersFrame;
 pub use self::headers::HeadersMultiFrame;
+pub use self::headers::StreamDependency;
 pub use self::ping::PingFrame;
 pub use self::priority::PriorityFrame;
+pub use self::push_promise::PushPromiseDecodedFrame;
 pub use self::push_promise::PushPromiseFlag;
 pub use self::push_promise::PushPromiseFrame;
+pub use self::push_promise::PushPromiseMultiFrame;
 pub use self::rst_stream::RstStreamFrame;
 pub use self::settings::HttpSetting;
 pub use self::settings::HttpSettings;
@@ -83,6 +86,9 @@ use std::fmt;
 /// HTTP/2 header length is 9 bytes.
 pub const FRAME_HEADER_LEN: usize = 9;
 
+/// Frame payload length is a 24-bit integer.
+pub const MAX_FRAME_PAYLOAD_LEN: u32 = 0xFFFFFF;
+
 /// An alias for the 9-byte buffer that each HTTP/2 frame header must be stored
 /// in.
 pub type FrameHeaderBuffer = [u8; FRAME_HEADER_LEN];
@@ -111,6 +117,19 @@ impl FrameHeader {
             stream_id,
         }
     }
+
+    /// Create a header checking that payload length fits into 24 bits.
+    pub fn try_new(
+        payload_len: u32,
+        frame_type: u8,
+        flags: u8,
+        stream_id: u32,
+    ) -> ParseFrameResult<FrameHeader> {
+        if payload_len > MAX_FRAME_PAYLOAD_LEN {
+            return Err(ParseFrameError::IncorrectFrameLength(payload_len));
+        }
+        Ok(FrameHeader::new(payload_len, frame_type, flags, stream_id))
+    }
 }
 
 /// Unpack HTTP/2 header.
@@ -161,6 +180,8 @@ pub fn pack_header(header: &FrameHeader) -> FrameHeaderBuffer {
         stream_id,
     } = header;
 
+    debug_assert!(payload_len <= MAX_FRAME_PAYLOAD_LEN);
+
     [
         (((payload_len >> 16) & 0x000000FF) as u8),
         (((payload_len >> 8) & 0x000000FF) as u8),
@@ -249,6 +270,10 @@ pub enum ParseFrameError {
     IncorrectSettingsPushValue(u32),
     /// Incorrect settings max frame size.
     IncorrectSettingsMaxFrameSize(u32),
+    /// `SETTINGS` frame with `ACK` flag has non-empty payload of given length.
+    InvalidSettingsAck(u32),
+    /// `SETTINGS` payload length is not a multiple of 6.
+    IncorrectSettingsPayloadLen(u32),
     /// Window size is too large.
     WindowSizeTooLarge(u32),
     /// Window update increment is invalid.
@@ -285,6 +310,12 @@ pub trait Frame: Sized {
     fn get_stream_id(&self) -> StreamId;
     /// Returns a `FrameHeader` based on the current state of the `Frame`.
     fn get_header(&self) -> FrameHeader;
+
+    /// Exact number of bytes the frame occupies on the wire, header included,
+    /// computed without serializing the frame.
+    fn serialized_len(&self) -> usize {
+        FRAME_HEADER_LEN + self.get_header().payload_len as usize
+    }
 }
 
 /// A struct that defines the format of the raw HTTP/2 frame, i.e. the frame
@@ -433,8 +464,28 @@ impl FrameIR for RawFrame {
 mod tests {
     use super::pack_header;
     use super::unpack_header;
+    use super::ContinuationFrame;
+    use super::DataFrame;
+    use super::Flags;
     use super::FrameHeader;
+    use super::FrameIR;
+    use super::GoawayFrame;
+    use super::HeadersFrame;
+    use super::HttpFrame;
+    use super::HttpSetting;
+    use super::ParseFrameError;
+    use super::PingFrame;
+    use super::PriorityFrame;
+    use super::PushPromiseFlag;
+    use super::PushPromiseFrame;
     use super::RawFrame;
+    use super::RstStreamFrame;
+    use super::SettingsFrame;
+    use super::StreamDependency;
+    use super::WindowUpdateFrame;
+    use super::MAX_FRAME_PAYLOAD_LEN;
+    use crate::ErrorCode;
+    use bytes::Bytes;
 
     /// Tests that the `unpack_header` function correctly returns the
     /// components of HTTP/2 frame headers.
@@ -515,6 +566,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_try_new_payload_len() {
+        let header = FrameHeader::try_new(MAX_FRAME_PAYLOAD_LEN, 0, 0, 1).expect("max len");
+        assert_eq!(MAX_FRAME_PAYLOAD_LEN, header.payload_len);
+        assert_eq!([0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0, 1], pack_header(&header));
+
+        match FrameHeader::try_new(MAX_FRAME_PAYLOAD_LEN + 1, 0, 0, 1) {
+            Err(ParseFrameError::IncorrectFrameLength(len)) => {
+                assert_eq!(MAX_FRAME_PAYLOAD_LEN + 1, len)
+            }
+            r => panic!("expecting incorrect frame length, got: {:?}", r),
+        }
+    }
+
     /// Tests that the `pack_header` function correctly returns the buffer
     /// corresponding to components of HTTP/2 frame headers.
     #[test]
@@ -630,6 +695,48 @@ mod tests {
             assert_eq!(buf.len(), frame.len());
         }
     }
+
+    /// Tests that `serialized_len` matches the length of actually serialized frames.
+    #[test]
+    fn test_serialized_len() {
+        let mut data = DataFrame::with_data(1, Bytes::from_static(b"data"));
+        data.set_padding(3);
+        let mut headers =
+            HeadersFrame::with_dependency(b"abc".to_vec(), 1, StreamDependency::new(0, 5, true));
+        headers.set_padding(2);
+        let mut push_promise = PushPromiseFrame {
+            flags: Flags::default(),
+            stream_id: 1,
+            promised_stream_id: 2,
+            header_fragment: Bytes::from_static(b"fragment"),
+            padding_len: 4,
+        };
+        push_promise.flags.set(PushPromiseFlag::Padded);
+        let mut settings = SettingsFrame::from_settings(vec![HttpSetting::EnablePush(false)]);
+        settings.unknown_settings.push((0x99, 1));
+
+        let frames: Vec<HttpFrame> = vec![
+            data.into(),
+            DataFrame::new(3).into(),
+            headers.into(),
+            HeadersFrame::new(Bytes::from_static(b"abc"), 1).into(),
+            PriorityFrame::new(1, StreamDependency::new(3, 1, false)).into(),
+            RstStreamFrame::new(1, ErrorCode::Cancel).into(),
+            settings.into(),
+            SettingsFrame::new_ack().into(),
+            push_promise.into(),
+            PingFrame::with_data(17).into(),
+            GoawayFrame::with_debug_data(1, ErrorCode::NoError, Bytes::from_static(b"bye")).into(),
+            WindowUpdateFrame::for_stream(1, 10).into(),
+            ContinuationFrame::new(Bytes::from_static(b"cont"), 1).into(),
+            HttpFrame::Unknown(RawFrame::from(vec![0, 0, 1, 0xbb, 0, 0, 0, 0, 1, 7])),
+        ];
+
+        for frame in frames {
+            let len = frame.serialized_len();
+            assert_eq!(len, frame.clone().serialize_into_vec().len(), "{:?}", frame);
+        }
+    }
 }
 
 /// An enum representing all frame variants that can be returned by an `HttpConnection` can handle.
@@ -744,6 +851,23 @@ impl HttpFrame {
             &HttpFrame::Unknown(ref f) => f.frame_type(),
         }
     }
+
+    /// Exact number of bytes the frame occupies on the wire.
+    pub fn serialized_len(&self) -> usize {
+        match self {
+            &HttpFrame::Data(ref f) => f.serialized_len(),
+            &HttpFrame::Headers(ref f) => f.serialized_len(),
+            &HttpFrame::Priority(ref f) => f.serialized_len(),
+            &HttpFrame::RstStream(ref f) => f.serialized_len(),
+            &HttpFrame::Settings(ref f) => f.serialized_len(),
+            &HttpFrame::PushPromise(ref f) => f.serialized_len(),
+            &HttpFrame::Ping(ref f) => f.serialized_len(),
+            &HttpFrame::Goaway(ref f) => f.serialized_len(),
+            &HttpFrame::WindowUpdate(ref f) => f.serialized_len(),
+            &HttpFrame::Continuation(ref f) => f.serialized_len(),
+            &HttpFrame::Unknown(ref f) => f.len(),
+        }
+    }
 }
 
 impl FrameIR for HttpFrame {
@@ -838,7 +962,7 @@ pub enum HttpFrameDecoded {
     /// `SETTINGS`
     Settings(SettingsFrame),
     /// `PUSH_PROMISE`
-    PushPromise(PushPromiseFrame),
+    PushPromise(PushPromiseDecodedFrame),
     /// `PING`
     Ping(PingFrame),
     /// `GOAWAY`
@@ -880,7 +1004,7 @@ pub(crate) enum HttpFrameDecodedDebugNoData<'a> {
     /// `SETTINGS`
     Settings(&'a SettingsFrame),
     /// `PUSH_PROMISE`
-    PushPromise(&'a PushPromiseFrame),
+    PushPromise(&'a PushPromiseDecodedFrame),
     /// `PING`
     Ping(&'a PingFrame),
     /// `GOAWAY`
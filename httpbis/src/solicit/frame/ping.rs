@@ -139,6 +139,7 @@ mod tests {
     use crate::solicit::frame::Frame;
     use crate::solicit::frame::FrameHeader;
     use crate::solicit::frame::FrameIR;
+    use crate::solicit::frame::ParseFrameError;
     use crate::solicit::tests::common::raw_frame_from_parts;
 
     #[test]
@@ -168,6 +169,25 @@ mod tests {
         assert_eq!(frame.opaque_data(), 0x0102030405060708);
     }
 
+    #[test]
+    fn test_parse_non_zero_stream_id() {
+        let raw =
+            raw_frame_from_parts(FrameHeader::new(8, 0x6, 0, 1), vec![0, 0, 0, 0, 0, 0, 0, 0]);
+        match PingFrame::from_raw(&raw) {
+            Err(ParseFrameError::StreamIdMustBeZero(1)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_parse_wrong_payload_len() {
+        let raw = raw_frame_from_parts(FrameHeader::new(4, 0x6, 0, 0), vec![0, 0, 0, 0]);
+        match PingFrame::from_raw(&raw) {
+            Err(ParseFrameError::IncorrectPayloadLen) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
     #[test]
     fn test_serialize() {
         let frame = PingFrame::new_ack(0);
@@ -1,14 +1,14 @@
-use bytes::Buf;
-
 use crate::codec::write_buffer::WriteBuffer;
 use crate::solicit::frame::flags::Flags;
 use crate::solicit::frame::flags::NoFlag;
 use crate::solicit::frame::Frame;
+use crate::solicit::frame::FrameBuilder;
 use crate::solicit::frame::FrameHeader;
 use crate::solicit::frame::FrameIR;
 use crate::solicit::frame::ParseFrameError;
 use crate::solicit::frame::ParseFrameResult;
 use crate::solicit::frame::RawFrame;
+use crate::solicit::frame::StreamDependency;
 use crate::solicit::stream_id::StreamId;
 
 /// `PRIORITY` frame.
@@ -19,15 +19,22 @@ pub struct PriorityFrame {
     /// Frame field
     pub stream_id: StreamId,
     /// Frame field
-    pub exclusive: bool,
-    /// Frame field
-    pub stream_dep: StreamId,
-    /// Frame field
-    pub weight: u8,
+    pub stream_dep: StreamDependency,
 }
 
 pub const PRIORITY_FRAME_TYPE: u8 = 0x2;
 
+impl PriorityFrame {
+    /// Create a frame which changes priority of given stream.
+    pub fn new(stream_id: StreamId, stream_dep: StreamDependency) -> PriorityFrame {
+        PriorityFrame {
+            flags: Flags::default(),
+            stream_id,
+            stream_dep,
+        }
+    }
+}
+
 impl Frame for PriorityFrame {
     type FlagType = NoFlag;
 
@@ -51,23 +58,16 @@ impl Frame for PriorityFrame {
             return Err(ParseFrameError::StreamIdMustBeNonZero);
         }
 
-        let mut payload = &raw_frame.payload()[..];
-        let first = payload.get_u32();
-        let exclusive = (first & 0x80000000) != 0;
-        let stream_dep = first & !0x80000000;
-        let weight = payload.get_u8();
-        assert_eq!(0, payload.remaining());
+        let stream_dep = StreamDependency::parse(&raw_frame.payload());
 
-        if stream_dep == stream_id {
+        if stream_dep.stream_id == stream_id {
             return Err(ParseFrameError::StreamDependencyOnItself(stream_id));
         }
 
         Ok(PriorityFrame {
             flags: Flags::new(flags),
             stream_id,
-            exclusive,
             stream_dep,
-            weight,
         })
     }
 
@@ -90,7 +90,76 @@ impl Frame for PriorityFrame {
 }
 
 impl FrameIR for PriorityFrame {
-    fn serialize_into(self, _builder: &mut WriteBuffer) {
-        unimplemented!()
+    fn serialize_into(self, builder: &mut WriteBuffer) {
+        builder.write_header(self.get_header());
+        builder.extend_from_slice(&self.stream_dep.serialize());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PriorityFrame;
+
+    use crate::solicit::frame::Frame;
+    use crate::solicit::frame::FrameHeader;
+    use crate::solicit::frame::FrameIR;
+    use crate::solicit::frame::ParseFrameError;
+    use crate::solicit::frame::StreamDependency;
+    use crate::solicit::tests::common::raw_frame_from_parts;
+
+    #[test]
+    fn test_parse() {
+        let raw = raw_frame_from_parts(FrameHeader::new(5, 0x2, 0, 3), vec![0x80, 0, 0, 1, 15]);
+        let frame = PriorityFrame::from_raw(&raw).expect("expected valid PRIORITY");
+        assert_eq!(3, frame.stream_id);
+        assert_eq!(StreamDependency::new(1, 15, true), frame.stream_dep);
+    }
+
+    #[test]
+    fn test_parse_wrong_payload_len() {
+        let raw = raw_frame_from_parts(FrameHeader::new(4, 0x2, 0, 3), vec![0, 0, 0, 1]);
+        match PriorityFrame::from_raw(&raw) {
+            Err(ParseFrameError::IncorrectFrameLength(4)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_parse_dependency_on_itself() {
+        let raw = raw_frame_from_parts(FrameHeader::new(5, 0x2, 0, 3), vec![0, 0, 0, 3, 15]);
+        match PriorityFrame::from_raw(&raw) {
+            Err(ParseFrameError::StreamDependencyOnItself(3)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_serialize() {
+        let frame = PriorityFrame::new(3, StreamDependency::new(1, 15, true));
+        let expected: Vec<u8> =
+            raw_frame_from_parts(FrameHeader::new(5, 0x2, 0, 3), vec![0x80, 0, 0, 1, 15])
+                .as_ref()
+                .to_owned();
+
+        assert_eq!(expected, frame.serialize_into_vec());
+    }
+
+    #[test]
+    fn test_serialize_parse_chain() {
+        // 1 <- 3 <- 5 (exclusive) <- 7
+        let frames = vec![
+            PriorityFrame::new(3, StreamDependency::new(1, 200, false)),
+            PriorityFrame::new(5, StreamDependency::new(3, 10, true)),
+            PriorityFrame::new(7, StreamDependency::new(5, 0, false)),
+        ];
+
+        for frame in frames {
+            let serialized = frame.clone().serialize_into_vec();
+            let raw = raw_frame_from_parts(
+                FrameHeader::new(5, 0x2, 0, frame.stream_id),
+                serialized[9..].to_vec(),
+            );
+            assert_eq!(frame, PriorityFrame::from_raw(&raw).expect("parse"));
+        }
     }
 }
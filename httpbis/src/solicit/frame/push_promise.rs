@@ -12,8 +12,13 @@ use crate::solicit::frame::RawFrame;
 
 use super::flags::Flag;
 use super::flags::Flags;
+use super::headers::EncodeBufForHeadersMultiFrame;
+use super::headers::HeadersFrameType;
 use crate::codec::write_buffer::WriteBuffer;
+use crate::hpack;
 use crate::solicit::stream_id::StreamId;
+use crate::Headers;
+use std::fmt;
 
 pub const PUSH_PROMISE_FRAME_TYPE: u8 = 0x5;
 
@@ -92,6 +97,11 @@ impl Frame for PushPromiseFrame {
             return Err(ParseFrameError::InternalError);
         }
 
+        // PUSH_PROMISE frames MUST be associated with a peer-initiated stream
+        if stream_id == 0 {
+            return Err(ParseFrameError::StreamIdMustBeNonZero);
+        }
+
         let flags = Flags::new(flags);
 
         // +---------------+
@@ -108,12 +118,14 @@ impl Frame for PushPromiseFrame {
 
         let (payload, padding_len) = parse_padded_payload(raw_frame.payload(), padded)?;
 
-        let mut buf = &payload[..];
+        if payload.len() < 4 {
+            return Err(ParseFrameError::IncorrectPayloadLen);
+        }
 
-        let promised_stream_id = buf.get_u32();
+        // The reserved bit is ignored
+        let promised_stream_id = (&payload[..4]).get_u32() & !0x80000000;
 
-        let header_fragment =
-            payload.slice((payload_len as usize) - buf.remaining()..payload.len());
+        let header_fragment = payload.slice(4..);
 
         Ok(PushPromiseFrame {
             header_fragment,
@@ -149,6 +161,7 @@ impl FrameIR for PushPromiseFrame {
         if padded {
             b.extend_from_slice(&[self.padding_len]);
         }
+        b.extend_from_slice(&self.promised_stream_id.to_be_bytes());
         // Now the actual headers fragment
         b.extend_from_bytes(self.header_fragment);
         // Finally, add the trailing padding, if required
@@ -157,3 +170,155 @@ impl FrameIR for PushPromiseFrame {
         }
     }
 }
+
+/// `PUSH_PROMISE` frame after header decoding.
+#[derive(Debug, Clone)]
+pub struct PushPromiseDecodedFrame {
+    /// The set of flags for the frame, packed into a single byte.
+    pub flags: Flags<PushPromiseFlag>,
+    /// The ID of the stream with which this frame is associated
+    pub stream_id: StreamId,
+    /// Promised Stream ID
+    pub promised_stream_id: StreamId,
+    /// Request headers of the promised stream.
+    pub headers: Headers,
+}
+
+impl PushPromiseDecodedFrame {
+    /// Get stream id
+    pub fn get_stream_id(&self) -> StreamId {
+        self.stream_id
+    }
+}
+
+/// Encode `PUSH_PROMISE` headers into multiple frames (`PUSH_PROMISE` + `CONTINUATION`).
+pub struct PushPromiseMultiFrame<'a> {
+    /// The ID of the stream with which this frame is associated
+    pub stream_id: StreamId,
+    /// Promised Stream ID
+    pub promised_stream_id: StreamId,
+    /// Request headers of the promised stream.
+    pub headers: Headers,
+
+    /// Header encoder state.
+    pub encoder: &'a mut hpack::Encoder,
+    /// Current max frame size for encoding.
+    pub max_frame_size: u32,
+}
+
+impl<'a> fmt::Debug for PushPromiseMultiFrame<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PushPromiseMultiFrame")
+            .field("stream_id", &self.stream_id)
+            .field("promised_stream_id", &self.promised_stream_id)
+            .field("headers", &self.headers)
+            .field("max_frame_size", &self.max_frame_size)
+            .finish()
+    }
+}
+
+impl<'a> FrameIR for PushPromiseMultiFrame<'a> {
+    fn serialize_into(self, builder: &mut WriteBuffer) {
+        let tail_vec = builder.tail_vec();
+
+        let mut buf = EncodeBufForHeadersMultiFrame {
+            flags: Flags::default(),
+            stream_id: self.stream_id,
+            current_frame_type: HeadersFrameType::PushPromise,
+            current_frame_offset: tail_vec.remaining(),
+            builder: tail_vec,
+            max_frame_size: self.max_frame_size,
+        };
+
+        buf.open_frame();
+        buf.builder
+            .extend_from_slice(&self.promised_stream_id.to_be_bytes());
+
+        hpack::encode_headers_into(self.encoder, &self.headers, &mut buf);
+
+        buf.finish_frame(true);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::solicit::frame::unpack_frames_for_test;
+    use crate::solicit::frame::HttpFrame;
+    use crate::solicit::tests::common::raw_frame_from_parts;
+
+    #[test]
+    fn test_serialize_parse() {
+        let frame = PushPromiseFrame {
+            flags: Flags::new(PushPromiseFlag::EndHeaders.bitmask()),
+            stream_id: 3,
+            promised_stream_id: 4,
+            header_fragment: Bytes::from_static(b"abc"),
+            padding_len: 0,
+        };
+        let raw = RawFrame::from(frame.clone().serialize_into_vec());
+        assert_eq!(frame, PushPromiseFrame::from_raw(&raw).unwrap());
+    }
+
+    #[test]
+    fn test_parse_padded() {
+        let mut payload = vec![2, 0x80, 0, 0, 6];
+        payload.extend_from_slice(b"abc");
+        payload.extend_from_slice(&[0, 0]);
+        let header = FrameHeader {
+            payload_len: payload.len() as u32,
+            frame_type: PUSH_PROMISE_FRAME_TYPE,
+            flags: PushPromiseFlag::Padded.bitmask(),
+            stream_id: 1,
+        };
+        let raw = raw_frame_from_parts(header, payload);
+        let frame = PushPromiseFrame::from_raw(&raw).unwrap();
+        assert_eq!(6, frame.promised_stream_id);
+        assert_eq!(&b"abc"[..], &frame.header_fragment[..]);
+        assert_eq!(2, frame.padding_len);
+    }
+
+    #[test]
+    fn test_parse_too_short() {
+        let header = FrameHeader {
+            payload_len: 3,
+            frame_type: PUSH_PROMISE_FRAME_TYPE,
+            flags: 0,
+            stream_id: 1,
+        };
+        let raw = raw_frame_from_parts(header, vec![0, 0, 0]);
+        match PushPromiseFrame::from_raw(&raw) {
+            Err(ParseFrameError::IncorrectPayloadLen) => {}
+            r => panic!("expecting incorrect payload len, got: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_multi_frame_continuation() {
+        let mut headers = Headers::new();
+        headers.add(":method", "GET");
+        headers.add(":path", "/some/long/path/to/push");
+        let mut encoder = hpack::Encoder::new();
+        let frame = PushPromiseMultiFrame {
+            stream_id: 1,
+            promised_stream_id: 2,
+            headers,
+            encoder: &mut encoder,
+            max_frame_size: 10,
+        };
+        let frames = unpack_frames_for_test(&frame.serialize_into_vec());
+        assert!(frames.len() > 1);
+        match &frames[0] {
+            HttpFrame::PushPromise(f) => {
+                assert_eq!(1, f.stream_id);
+                assert_eq!(2, f.promised_stream_id);
+                assert!(!f.flags.is_set(PushPromiseFlag::EndHeaders));
+            }
+            f => panic!("unexpected frame: {:?}", f),
+        }
+        match frames.last().unwrap() {
+            HttpFrame::Continuation(f) => assert_eq!(1, f.stream_id),
+            f => panic!("unexpected frame: {:?}", f),
+        }
+    }
+}
@@ -136,7 +136,7 @@ impl HttpSetting {
 }
 
 /// All known setting values
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct HttpSettings {
     /// Setting
     pub header_table_size: u32,
@@ -219,6 +219,9 @@ pub struct SettingsFrame {
     /// safe to access this field (to read, add, or remove settings), even
     /// though a helper method `add_setting` exists.
     pub settings: Vec<HttpSetting>,
+    /// Settings with identifiers unknown to this implementation
+    /// as `(id, value)` pairs. Peer must ignore them (6.5.2).
+    pub unknown_settings: Vec<(u16, u32)>,
     /// Represents the flags currently set on the `SettingsFrame`, packed into
     /// a single byte.
     flags: Flags<SettingsFlag>,
@@ -229,6 +232,7 @@ impl SettingsFrame {
     pub fn new() -> SettingsFrame {
         SettingsFrame {
             settings: Vec::new(),
+            unknown_settings: Vec::new(),
             // By default, no flags are set
             flags: Flags::default(),
         }
@@ -239,6 +243,7 @@ impl SettingsFrame {
     pub fn new_ack() -> SettingsFrame {
         SettingsFrame {
             settings: Vec::new(),
+            unknown_settings: Vec::new(),
             flags: SettingsFlag::Ack.to_flags(),
         }
     }
@@ -247,6 +252,7 @@ impl SettingsFrame {
     pub fn from_settings(settings: Vec<HttpSetting>) -> SettingsFrame {
         SettingsFrame {
             settings,
+            unknown_settings: Vec::new(),
             flags: Flags::default(),
         }
     }
@@ -270,7 +276,7 @@ impl SettingsFrame {
     /// Returns the total length of the payload in bytes.
     fn payload_len(&self) -> u32 {
         // Each setting is represented with 6 bytes =>
-        6 * self.settings.len() as u32
+        6 * (self.settings.len() + self.unknown_settings.len()) as u32
     }
 
     /// Parses the given buffer, considering it a representation of a settings
@@ -278,32 +284,59 @@ impl SettingsFrame {
     ///
     /// # Returns
     ///
-    /// A `Vec` of settings that are set by the given payload.
+    /// A frame without flags with settings that are set by the given payload.
     ///
-    /// Any unknown setting is ignored, as per the HTTP/2 spec requirement.
+    /// Unknown settings must be ignored as per the HTTP/2 spec requirement,
+    /// but they are preserved in `unknown_settings`.
     ///
     /// If the frame is invalid (i.e. the length of the payload is not a
-    /// multiple of 6) it returns `None`.
-    fn parse_payload(payload: &[u8]) -> ParseFrameResult<Vec<HttpSetting>> {
+    /// multiple of 6) it returns an error.
+    fn parse_payload(payload: &[u8]) -> ParseFrameResult<SettingsFrame> {
         if payload.len() % 6 != 0 {
-            return Err(ParseFrameError::ProtocolError);
+            return Err(ParseFrameError::IncorrectSettingsPayloadLen(
+                payload.len() as u32
+            ));
         }
 
         // Iterates through chunks of the raw payload of size 6 bytes and
         // parses each of them into an `HttpSetting`
-        let mut settings = Vec::new();
+        let mut frame = SettingsFrame::new();
         for chunk in payload.chunks(6) {
-            if let Some(setting) = HttpSetting::parse_setting(chunk)? {
-                settings.push(setting);
+            match HttpSetting::parse_setting(chunk)? {
+                Some(setting) => frame.settings.push(setting),
+                None => {
+                    let id: u16 = ((chunk[0] as u16) << 8) | (chunk[1] as u16);
+                    let val: u32 = unpack_octets_4!(chunk, 2, u32);
+                    frame.unknown_settings.push((id, val));
+                }
             }
         }
-        Ok(settings)
+        Ok(frame)
     }
 
     /// Sets the given flag for the frame.
     pub fn set_flag(&mut self, flag: SettingsFlag) {
         self.flags.set(flag);
     }
+
+    /// Check the frame can be sent: no ACK flag and setting values
+    /// within bounds allowed by 6.5.2.
+    pub fn validate(&self) -> ParseFrameResult<()> {
+        if self.is_ack() {
+            return Err(ParseFrameError::IncorrectFlags(self.flags.0));
+        }
+        for setting in &self.settings {
+            match *setting {
+                HttpSetting::InitialWindowSize(size) if size > MAX_WINDOW_SIZE => {
+                    return Err(ParseFrameError::WindowSizeTooLarge(size));
+                }
+                _ => {
+                    HttpSetting::from_id(setting.get_id(), setting.get_val())?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Frame for SettingsFrame {
@@ -349,19 +382,18 @@ impl Frame for SettingsFrame {
                 // Ack is set and there's no payload => just an Ack frame
                 Ok(SettingsFrame {
                     settings: Vec::new(),
+                    unknown_settings: Vec::new(),
                     flags: Flags::new(flags),
                 })
             } else {
                 // The SETTINGS flag MUST not have a payload if Ack is set
-                Err(ParseFrameError::ProtocolError)
+                Err(ParseFrameError::InvalidSettingsAck(payload_len))
             };
         }
 
-        let settings = SettingsFrame::parse_payload(&raw_frame.payload())?;
-        Ok(SettingsFrame {
-            settings,
-            flags: Flags::new(flags),
-        })
+        let mut frame = SettingsFrame::parse_payload(&raw_frame.payload())?;
+        frame.flags = Flags::new(flags);
+        Ok(frame)
     }
 
     /// Tests if the given flag is set for the frame.
@@ -393,6 +425,10 @@ impl FrameIR for SettingsFrame {
         for setting in &self.settings {
             b.extend_from_slice(&setting.serialize());
         }
+        for &(id, val) in &self.unknown_settings {
+            b.extend_from_slice(&id.to_be_bytes());
+            b.extend_from_slice(&val.to_be_bytes());
+        }
     }
 }
 
@@ -404,7 +440,35 @@ mod tests {
     use crate::solicit::frame::Frame;
     use crate::solicit::frame::FrameHeader;
     use crate::solicit::frame::FrameIR;
+    use crate::solicit::frame::ParseFrameError;
     use crate::solicit::tests::common::raw_frame_from_parts;
+    use crate::solicit::window_size::MAX_WINDOW_SIZE;
+    use crate::solicit::DEFAULT_SETTINGS;
+
+    #[test]
+    fn validate() {
+        let frame = SettingsFrame::from_settings(vec![
+            HttpSetting::EnablePush(true),
+            HttpSetting::InitialWindowSize(MAX_WINDOW_SIZE),
+            HttpSetting::MaxFrameSize(0x4000),
+        ]);
+        assert!(frame.validate().is_ok());
+
+        assert!(SettingsFrame::new_ack().validate().is_err());
+
+        let frame = SettingsFrame::from_settings(vec![HttpSetting::MaxFrameSize(0x3fff)]);
+        assert!(matches!(
+            frame.validate(),
+            Err(ParseFrameError::IncorrectSettingsMaxFrameSize(0x3fff))
+        ));
+
+        let frame =
+            SettingsFrame::from_settings(vec![HttpSetting::InitialWindowSize(MAX_WINDOW_SIZE + 1)]);
+        assert!(matches!(
+            frame.validate(),
+            Err(ParseFrameError::WindowSizeTooLarge(size)) if size == MAX_WINDOW_SIZE + 1
+        ));
+    }
 
     /// Tests that a `SettingsFrame` correctly handles a SETTINGS frame with
     /// no ACK flag and only a single setting.
@@ -509,14 +573,56 @@ mod tests {
         let frame: SettingsFrame = Frame::from_raw(&raw).unwrap();
 
         // All the settings are returned twice, but the unkown isn't found in
-        // the returned Vec. For now, we ignore the unknown setting fully, not
-        // exposing it in any way to any other higher-level clients.
+        // the returned Vec; it is kept separately.
         assert_eq!(frame.settings.len(), 4);
         assert_eq!(&frame.settings[0..2], &settings[..]);
         assert_eq!(&frame.settings[2..], &settings[..]);
+        assert_eq!(frame.unknown_settings, vec![(10, 0)]);
         assert!(!frame.is_ack());
     }
 
+    /// Tests that unknown settings mixed with known ones are preserved
+    /// in order and exposed through `HttpSettings::unknown`.
+    #[test]
+    fn test_settings_frame_parse_known_and_unknown_settings() {
+        let payload = vec![
+            0, 1, 0, 0, 0, 7, // HeaderTableSize(7)
+            0xf0, 0x00, 0, 0, 0x12, 0x34, // unknown 0xf000
+            0, 3, 0, 0, 0, 100, // MaxConcurrentStreams(100)
+            0, 8, 0, 0, 0, 1, // unknown 8
+            0xf0, 0x00, 0, 0, 0, 5, // unknown 0xf000 again
+        ];
+        let header = FrameHeader::new(payload.len() as u32, 4, 0, 0);
+
+        let raw = raw_frame_from_parts(header, payload.clone());
+        let frame: SettingsFrame = Frame::from_raw(&raw).unwrap();
+
+        assert_eq!(
+            frame.settings,
+            vec![
+                HttpSetting::HeaderTableSize(7),
+                HttpSetting::MaxConcurrentStreams(100),
+            ]
+        );
+        assert_eq!(
+            frame.unknown_settings,
+            vec![(0xf000, 0x1234), (8, 1), (0xf000, 5)]
+        );
+
+        let mut settings = DEFAULT_SETTINGS;
+        settings.apply_from_frame(&frame);
+        assert_eq!(7, settings.header_table_size);
+        assert_eq!(100, settings.max_concurrent_streams);
+
+        // unknown settings are serialized after known ones
+        let mut expected = payload[0..6].to_vec();
+        expected.extend_from_slice(&payload[12..18]);
+        expected.extend_from_slice(&payload[6..12]);
+        expected.extend_from_slice(&payload[18..]);
+        let serialized = frame.serialize_into_vec();
+        assert_eq!(&serialized[9..], &expected[..]);
+    }
+
     /// Tests that a `SettingsFrame` correctly handles a SETTINGS frame with an
     /// ACK flag and no settings.
     #[test]
@@ -559,7 +665,10 @@ mod tests {
         let raw = raw_frame_from_parts(header, payload);
         let frame = SettingsFrame::from_raw(&raw);
 
-        assert!(frame.is_err());
+        match frame {
+            Err(ParseFrameError::InvalidSettingsAck(6)) => {}
+            r => panic!("expecting invalid settings ack, got: {:?}", r),
+        }
     }
 
     /// Tests that a `SettingsFrame` correctly handles a SETTINGS frame which
@@ -590,6 +699,23 @@ mod tests {
         assert!(frame.is_err());
     }
 
+    /// Tests that a `SettingsFrame` with a payload one byte short of
+    /// a single setting is rejected with the payload length.
+    #[test]
+    fn test_settings_frame_parse_payload_len_5() {
+        let payload = vec![0, 2, 0, 0, 0];
+
+        let header = FrameHeader::new(payload.len() as u32, 4, 0, 0);
+
+        let raw = raw_frame_from_parts(header, payload);
+        let frame = SettingsFrame::from_raw(&raw);
+
+        match frame {
+            Err(ParseFrameError::IncorrectSettingsPayloadLen(5)) => {}
+            r => panic!("expecting incorrect settings payload length, got: {:?}", r),
+        }
+    }
+
     /// Tests that a `SettingsFrame` gets correctly serialized when it contains
     /// only settings and no ACK.
     #[test]
@@ -118,6 +118,7 @@ mod tests {
     use crate::solicit::frame::Frame;
     use crate::solicit::frame::FrameHeader;
     use crate::solicit::frame::FrameIR;
+    use crate::solicit::frame::ParseFrameError;
     use crate::solicit::tests::common::raw_frame_from_parts;
 
     #[test]
@@ -145,6 +146,33 @@ mod tests {
         assert_eq!(frame.get_stream_id(), 1);
     }
 
+    #[test]
+    fn test_parse_zero_increment() {
+        let raw = raw_frame_from_parts(FrameHeader::new(4, 0x8, 0, 1), vec![0, 0, 0, 0]);
+        match WindowUpdateFrame::from_raw(&raw) {
+            Err(ParseFrameError::WindowUpdateIncrementInvalid(0)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_parse_zero_increment_reserved_bit() {
+        let raw = raw_frame_from_parts(FrameHeader::new(4, 0x8, 0, 0), vec![0x80, 0, 0, 0]);
+        match WindowUpdateFrame::from_raw(&raw) {
+            Err(ParseFrameError::WindowUpdateIncrementInvalid(0)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_parse_wrong_payload_len() {
+        let raw = raw_frame_from_parts(FrameHeader::new(5, 0x8, 0, 0), vec![0, 0, 0, 1, 0]);
+        match WindowUpdateFrame::from_raw(&raw) {
+            Err(ParseFrameError::IncorrectFrameLength(5)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
     #[test]
     fn test_serialize_connection_level() {
         let frame = WindowUpdateFrame::for_connection(10);
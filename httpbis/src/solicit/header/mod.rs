@@ -18,6 +18,7 @@ use crate::solicit::header::method::METHOD_POST;
 use crate::solicit::header::name::HeaderName;
 use crate::solicit::header::name::PseudoHeaderName;
 use crate::solicit::header::name::PseudoHeaderNameSet;
+use crate::solicit::header::status::parse_status_header_value;
 use crate::solicit::header::status::status_to_header_value;
 use crate::HeaderValue;
 
@@ -59,6 +60,8 @@ pub enum HeaderError {
     EmptyValue(PseudoHeaderName),
     /// Incorrect character in header name.
     IncorrectCharInName,
+    /// Upper case character in header name.
+    UppercaseHeaderName,
     /// Incorrect character in header value.
     IncorrectCharInValue,
     /// Header name is not ASCII.
@@ -79,6 +82,10 @@ pub enum HeaderError {
     ConnectionSpecificHeader(&'static str),
     /// RE can only contain trailers.
     TeCanOnlyContainTrailer,
+    /// `:status` is not a three-digit status code.
+    IncorrectStatus,
+    /// Header block contains more fields than allowed.
+    TooManyHeaders,
 }
 
 /// Type alias.
@@ -88,13 +95,12 @@ impl Header {
     /// Create a new `Header` object with exact values of `name` and `value`.
     ///
     /// This function performs header validation, in particular,
-    /// header name must be lower case.
+    /// header name must be lower case, and value must not contain
+    /// NUL, CR or LF. Value is otherwise opaque, as received from the peer.
     pub fn new_validate(name: Bytes, value: Bytes) -> HeaderResult<Header> {
         let name = HeaderName::new_validate(name).map_err(|(e, _)| e)?;
-        Ok(Header {
-            name,
-            value: HeaderValue::from(value),
-        })
+        let value = HeaderValue::from_bytes_received(value).map_err(|(e, _)| e)?;
+        Ok(Header { name, value })
     }
 
     /// Creates a new `Header` with the given name and value.
@@ -160,10 +166,35 @@ impl Header {
 
     /// Validate header as request or response header.
     pub fn validate(&self, req_or_resp: RequestOrResponse) -> HeaderResult<()> {
+        // A field value MUST NOT contain the zero value, line feed
+        // or carriage return characters (RFC 7540, section 10.3).
+        if self
+            .value
+            .as_slice()
+            .iter()
+            .any(|&b| b == 0 || b == b'\r' || b == b'\n')
+        {
+            return Err(HeaderError::IncorrectCharInValue);
+        }
+
+        // Header field names MUST be lower case (Section 8.1.2).
+        if self.name().bytes().any(|b| b.is_ascii_uppercase()) {
+            return Err(HeaderError::UppercaseHeaderName);
+        }
+
         if let Some(h) = self.pseudo_header_name() {
             if h.req_or_resp() != req_or_resp {
                 return Err(HeaderError::UnexpectedPseudoHeader(h));
             }
+
+            // Request pseudo-header values are tokens or URI parts,
+            // which only contain visible ASCII; `:status` is checked
+            // to be a three-digit code in `Headers::validate`.
+            if h != PseudoHeaderName::Status
+                && !self.value.as_slice().iter().all(|b| b.is_ascii_graphic())
+            {
+                return Err(HeaderError::IncorrectCharInValue);
+            }
         }
 
         if req_or_resp == RequestOrResponse::Request && self.name() == "te" {
@@ -186,11 +217,22 @@ impl<N: Into<HeaderName>, V: Into<HeaderValue>> From<(N, V)> for Header {
 }
 
 /// HTTP message headers (or trailers)
-#[derive(Default, Debug, PartialEq, Eq, Clone)]
+#[derive(Default, Debug, Eq, Clone)]
 pub struct Headers {
     // Pseudo-headers stored before regular headers
     headers: Vec<Header>,
     pseudo_count: usize,
+    // Received block had a pseudo-header after a regular header,
+    // reported by `validate`
+    pseudo_after_regular: bool,
+}
+
+/// Headers are compared by their content, `pseudo_after_regular`
+/// is a validation state of a received block.
+impl PartialEq for Headers {
+    fn eq(&self, other: &Headers) -> bool {
+        self.headers == other.headers && self.pseudo_count == other.pseudo_count
+    }
 }
 
 impl Headers {
@@ -206,26 +248,22 @@ impl Headers {
         Headers {
             headers,
             pseudo_count,
+            pseudo_after_regular: false,
         }
     }
 
-    pub(crate) fn from_vec_pseudo_first(headers: Vec<Header>) -> Result<Headers, HeaderError> {
-        let mut saw_regular_header = false;
-        let mut pseudo_count = 0;
-        for header in &headers {
-            if header.is_preudo_header() {
-                if saw_regular_header {
-                    return Err(HeaderError::PseudoHeadersAfterRegularHeaders);
-                }
-                pseudo_count += 1;
-            } else {
-                saw_regular_header = true;
-            }
+    /// Construct headers from a decoded header block, remembering
+    /// if pseudo-headers were not all placed before regular headers.
+    pub(crate) fn from_vec_received(headers: Vec<Header>) -> Headers {
+        let first_regular = headers.iter().position(|h| !h.is_preudo_header());
+        let pseudo_after_regular = match first_regular {
+            Some(i) => headers[i..].iter().any(|h| h.is_preudo_header()),
+            None => false,
+        };
+        Headers {
+            pseudo_after_regular,
+            ..Headers::from_vec(headers)
         }
-        return Ok(Headers {
-            headers,
-            pseudo_count,
-        });
     }
 
     /// Return an iterator over headers.
@@ -235,6 +273,16 @@ impl Headers {
         self.headers.iter()
     }
 
+    /// Number of headers, including pseudo headers.
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// No headers
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
     fn pseudo_headers(&self) -> &[Header] {
         &self.headers[..self.pseudo_count]
     }
@@ -283,6 +331,11 @@ impl Headers {
         Headers::new_status(500)
     }
 
+    /// Construct `:status 415` headers
+    pub fn unsupported_media_type_415() -> Headers {
+        Headers::new_status(415)
+    }
+
     /// Construct `:status 302; location: <location>` headers
     pub fn redirect_302(location: impl Into<HeaderValue>) -> Headers {
         let mut headers = Headers::new_status(302);
@@ -295,6 +348,12 @@ impl Headers {
         req_or_resp: RequestOrResponse,
         headers_place: HeadersPlace,
     ) -> HeaderResult<()> {
+        // All pseudo-header fields MUST appear in the header block before
+        // regular header fields (Section 8.1.2.1).
+        if self.pseudo_after_regular {
+            return Err(HeaderError::PseudoHeadersAfterRegularHeaders);
+        }
+
         let mut pseudo_headers_met = PseudoHeaderNameSet::new();
 
         for header in self.pseudo_headers() {
@@ -317,6 +376,10 @@ impl Headers {
                     return Err(HeaderError::EmptyValue(header_name));
                 }
             }
+
+            if header_name == PseudoHeaderName::Status {
+                parse_status_header_value(header.value.as_slice())?;
+            }
         }
 
         for header in self.regular_headers() {
@@ -385,9 +448,11 @@ impl Headers {
     }
 
     /// Path header.
-    // TODO: return bytes, because headers it not require to be valid UTF-8
+    ///
+    /// Empty if there is no `:path` (e. g. in `CONNECT` request).
+    /// Received `:path` is validated to be visible ASCII.
     pub fn path(&self) -> &str {
-        self.get(":path")
+        self.get_opt(":path").unwrap_or("")
     }
 
     /// Method header.
@@ -403,6 +468,15 @@ impl Headers {
         }
     }
 
+    /// Header list size as defined for `SETTINGS_MAX_HEADER_LIST_SIZE`:
+    /// sum of name and value lengths plus 32 bytes overhead per header.
+    pub fn header_list_size(&self) -> usize {
+        self.headers
+            .iter()
+            .map(|h| h.name().len() + h.value().len() + 32)
+            .sum()
+    }
+
     /// Add a header
     pub fn add(&mut self, name: impl Into<HeaderName>, value: impl Into<HeaderValue>) {
         self.add_header(Header::new(name, value));
@@ -437,7 +511,13 @@ impl FromIterator<Header> for Headers {
 #[cfg(test)]
 mod test {
 
+    use crate::headers_place::HeadersPlace;
+    use crate::req_resp::RequestOrResponse;
     use crate::solicit::header::Header;
+    use crate::solicit::header::HeaderError;
+    use crate::solicit::header::HeaderValue;
+    use crate::solicit::header::Headers;
+    use bytes::Bytes;
 
     #[test]
     fn test_partial_eq_of_headers() {
@@ -461,4 +541,105 @@ mod test {
             format!("{:?}", Header::new(&b":method"[..], &b"\t"[..]))
         );
     }
+
+    #[test]
+    fn value_to_str() {
+        let value = HeaderValue::from_bytes_received(Bytes::from_static(b"caf\xc3\xa9")).unwrap();
+        assert_eq!("caf\u{e9}", value.to_str().unwrap());
+        let value = HeaderValue::from_bytes_received(Bytes::from_static(b"caf\xe9")).unwrap();
+        assert!(value.to_str().is_err());
+    }
+
+    #[test]
+    fn new_validate_value() {
+        assert!(
+            Header::new_validate(Bytes::from_static(b"x-foo"), Bytes::from_static(b"bar baz"))
+                .is_ok()
+        );
+        // obs-text is opaque data
+        let header = Header::new_validate(
+            Bytes::from_static(b"x-foo"),
+            Bytes::from_static(b"caf\xc3\xa9 \xe9"),
+        )
+        .unwrap();
+        assert_eq!(b"caf\xc3\xa9 \xe9", header.value());
+        assert!(matches!(
+            Header::new_validate(Bytes::from_static(b"x-foo"), Bytes::from_static(b"bar\0")),
+            Err(HeaderError::IncorrectCharInValue)
+        ));
+        assert!(matches!(
+            Header::new_validate(
+                Bytes::from_static(b"x-foo"),
+                Bytes::from_static(b"bar\r\nx-injected: 1")
+            ),
+            Err(HeaderError::IncorrectCharInValue)
+        ));
+    }
+
+    #[test]
+    fn validate_value() {
+        let header = Header::new("x-foo", "bar");
+        assert!(header.validate(RequestOrResponse::Request).is_ok());
+
+        let header = Header::new("x-foo", unsafe {
+            HeaderValue::from_bytes_unchecked(Bytes::from_static(b"bar\r\nx-injected: 1"))
+        });
+        assert!(matches!(
+            header.validate(RequestOrResponse::Request),
+            Err(HeaderError::IncorrectCharInValue)
+        ));
+    }
+
+    #[test]
+    fn validate_pseudo_header_value() {
+        let header = Header::new(":path", "/a?b=c");
+        assert!(header.validate(RequestOrResponse::Request).is_ok());
+
+        for value in [&b"/a b"[..], &b"/caf\xe9"[..], &b"/a\t"[..]] {
+            let header = Header::new(":path", unsafe {
+                HeaderValue::from_bytes_unchecked(Bytes::copy_from_slice(value))
+            });
+            assert!(matches!(
+                header.validate(RequestOrResponse::Request),
+                Err(HeaderError::IncorrectCharInValue)
+            ));
+        }
+    }
+
+    #[test]
+    fn validate_pseudo_headers_order() {
+        let headers = Headers::from_vec_received(vec![
+            Header::new(":method", "GET"),
+            Header::new(":scheme", "https"),
+            Header::new(":path", "/"),
+            Header::new("content-type", "text/plain"),
+        ]);
+        assert!(headers
+            .validate(RequestOrResponse::Request, HeadersPlace::Initial)
+            .is_ok());
+
+        let headers = Headers::from_vec_received(vec![
+            Header::new(":method", "GET"),
+            Header::new(":scheme", "https"),
+            Header::new("content-type", "text/plain"),
+            Header::new(":path", "/"),
+        ]);
+        assert!(matches!(
+            headers.validate(RequestOrResponse::Request, HeadersPlace::Initial),
+            Err(HeaderError::PseudoHeadersAfterRegularHeaders)
+        ));
+    }
+
+    #[test]
+    fn eq_ignores_received_order() {
+        let headers = vec![
+            Header::new(":method", "GET"),
+            Header::new("content-type", "text/plain"),
+            Header::new(":path", "/"),
+        ];
+        assert_eq!(
+            Headers::from_vec(headers.clone()),
+            Headers::from_vec_received(headers)
+        );
+    }
 }
@@ -160,7 +160,9 @@ impl RegularHeaderName {
                 return Err((HeaderError::IncorrectCharInName, bs));
             }
             if b.is_ascii_uppercase() {
-                return Err((HeaderError::IncorrectCharInName, bs));
+                // Header field names MUST be converted to lowercase
+                // prior to their encoding in HTTP/2 (Section 8.1.2).
+                return Err((HeaderError::UppercaseHeaderName, bs));
             }
             let bad_chars = b"()<>@,;:\\\"/[]?={} \t";
             if bad_chars.contains(&b) {
@@ -403,7 +405,7 @@ impl fmt::Display for HeaderName {
     }
 }
 
-fn make_ascii_lowercase(bytes: &mut Bytes) {
+pub(crate) fn make_ascii_lowercase(bytes: &mut Bytes) {
     if bytes.as_ref().iter().all(|c| !c.is_ascii_uppercase()) {
         return;
     }
@@ -421,6 +423,15 @@ mod test {
         assert_eq!("content-type", HeaderName::new("Content-Type").name());
     }
 
+    #[test]
+    fn header_name_new_validate_uppercase() {
+        assert!(HeaderName::new_validate(Bytes::from("content-type")).is_ok());
+        assert!(matches!(
+            HeaderName::new_validate(Bytes::from("Content-Type")),
+            Err((HeaderError::UppercaseHeaderName, _))
+        ));
+    }
+
     #[test]
     fn header_name_display() {
         assert_eq!(
@@ -1,5 +1,7 @@
 use bytes::Bytes;
 
+use crate::solicit::header::HeaderError;
+use crate::solicit::header::HeaderResult;
 use crate::HeaderValue;
 
 pub const STATUS_200: HeaderValue =
@@ -19,3 +21,33 @@ pub fn status_to_header_value(code: u32) -> HeaderValue {
             .unwrap(),
     }
 }
+
+/// Parse `:status` header value.
+///
+/// HTTP/2 has no reason phrase, so the value must be exactly three digits.
+pub(crate) fn parse_status_header_value(value: &[u8]) -> HeaderResult<u16> {
+    match value {
+        [a @ b'1'..=b'9', b @ b'0'..=b'9', c @ b'0'..=b'9'] => {
+            Ok((a - b'0') as u16 * 100 + (b - b'0') as u16 * 10 + (c - b'0') as u16)
+        }
+        _ => Err(HeaderError::IncorrectStatus),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_status() {
+        assert_eq!(200, parse_status_header_value(b"200").unwrap());
+        assert_eq!(100, parse_status_header_value(b"100").unwrap());
+        assert_eq!(599, parse_status_header_value(b"599").unwrap());
+        assert!(parse_status_header_value(b"").is_err());
+        assert!(parse_status_header_value(b"20").is_err());
+        assert!(parse_status_header_value(b"2000").is_err());
+        assert!(parse_status_header_value(b"099").is_err());
+        assert!(parse_status_header_value(b"200 OK").is_err());
+        assert!(parse_status_header_value(b"OK").is_err());
+    }
+}
@@ -1,11 +1,14 @@
-use crate::ascii::Ascii;
 use crate::solicit::header::HeaderError;
 use bytes::Bytes;
 use std::fmt;
+use std::str;
 
 /// A convenience struct representing a header value.
+///
+/// Values created locally are visible ASCII, received values
+/// may also contain opaque `obs-text` octets.
 #[derive(Eq, PartialEq, Hash, Clone)]
-pub struct HeaderValue(Ascii);
+pub struct HeaderValue(Bytes);
 
 impl HeaderValue {
     /// Validate and create header value from bytes.
@@ -44,28 +47,46 @@ impl HeaderValue {
             }
         }
 
-        Ok(HeaderValue(unsafe { Ascii::from_bytes_unchecked(bs) }))
+        Ok(HeaderValue(bs))
+    }
+
+    /// Validate header value received from the peer.
+    ///
+    /// A field value MUST NOT contain the zero value, line feed
+    /// or carriage return characters (RFC 7540, section 10.3).
+    /// Other octets, including `obs-text`, are passed through as opaque data.
+    pub(crate) fn from_bytes_received(bs: Bytes) -> Result<HeaderValue, (HeaderError, Bytes)> {
+        if bs.iter().any(|&b| b == 0 || b == b'\r' || b == b'\n') {
+            return Err((HeaderError::IncorrectCharInValue, bs));
+        }
+        Ok(HeaderValue(bs))
     }
 
     /// Into underlying storage object.
     pub fn into_inner(self) -> Bytes {
-        self.0.into_bytes()
+        self.0
     }
 
     /// As bytes.
     pub fn as_slice(&self) -> &[u8] {
-        self.0.as_bytes()
+        &self.0
+    }
+
+    /// As string, fails if received value contains `obs-text`
+    /// which is not valid UTF-8.
+    pub fn to_str(&self) -> Result<&str, str::Utf8Error> {
+        str::from_utf8(&self.0)
     }
 
     /// Unsafe no-validation `const` constructor.
     pub const unsafe fn from_bytes_unchecked(bytes: Bytes) -> HeaderValue {
-        HeaderValue(Ascii::from_bytes_unchecked(bytes))
+        HeaderValue(bytes)
     }
 }
 
 impl fmt::Debug for HeaderValue {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Debug::fmt(&self.0, fmt)
+        fmt::Debug::fmt(&String::from_utf8_lossy(&self.0), fmt)
     }
 }
 
@@ -101,18 +122,24 @@ impl<'a> From<&'a str> for HeaderValue {
 
 impl Into<Bytes> for HeaderValue {
     fn into(self) -> Bytes {
-        self.0.into()
+        self.0
     }
 }
 
 impl AsRef<[u8]> for HeaderValue {
     fn as_ref(&self) -> &[u8] {
-        self.0.as_ref()
+        &self.0
     }
 }
 
+/// Empty string if received value contains `obs-text` which is not
+/// valid UTF-8.
+///
+/// Deprecated: use `to_str` which reports such values as an error,
+/// or `as_slice` to access them as bytes. Trait implementations cannot
+/// be marked with `#[deprecated]`, this one will be removed.
 impl AsRef<str> for HeaderValue {
     fn as_ref(&self) -> &str {
-        self.0.as_ref()
+        str::from_utf8(&self.0).unwrap_or("")
     }
 }
@@ -129,3 +129,33 @@ impl NonNegativeWindowSize {
         self.0.try_increase(delta)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn try_increase_up_to_max() {
+        let mut w = WindowSize::new(65535);
+        assert_eq!(Ok(()), w.try_increase(MAX_WINDOW_SIZE - 65535));
+        assert_eq!(MAX_WINDOW_SIZE as i32, w.size());
+    }
+
+    #[test]
+    fn try_increase_above_max() {
+        let mut w = WindowSize::new(65535);
+        assert_eq!(Err(()), w.try_increase(MAX_WINDOW_SIZE - 65534));
+
+        // Negative window may grow by more than max increment total
+        let mut w = WindowSize::new(-10);
+        assert_eq!(Ok(()), w.try_increase(MAX_WINDOW_SIZE));
+        assert_eq!(Err(()), w.try_increase(11));
+    }
+
+    #[test]
+    fn try_increase_invalid_increment() {
+        let mut w = WindowSize::new(0);
+        assert_eq!(Err(()), w.try_increase(0));
+        assert_eq!(Err(()), w.try_increase(MAX_WINDOW_SIZE_INC + 1));
+    }
+}
@@ -12,6 +12,8 @@ use tokio::io::AsyncRead;
 use tokio::io::AsyncWrite;
 use tokio::io::AsyncWriteExt;
 
+use crate::codec::write_buffer::WriteBuffer;
+use crate::common::limits::FRAME_HEADER_LEN;
 use crate::error;
 use crate::error::Error;
 use crate::result;
@@ -22,7 +24,6 @@ use crate::solicit::frame::FrameIR;
 use crate::solicit::frame::RawFrame;
 use crate::solicit::frame::RawFrameRef;
 use crate::solicit::frame::SettingsFrame;
-use crate::solicit::frame::FRAME_HEADER_LEN;
 
 use crate::misc::BsDebug;
 use crate::net::socket::SocketStream;
@@ -93,10 +94,13 @@ pub async fn client_handshake<I: SocketStream>(
     conn: &mut I,
     settings: SettingsFrame,
 ) -> result::Result<()> {
-    debug!("send PREFACE");
-    conn.write_all(PREFACE).await?;
-
-    send_settings(conn, settings).await?;
+    debug!("send PREFACE and SETTINGS");
+    // Single write for preface and settings
+    let mut buf = WriteBuffer::new();
+    buf.extend_from_slice(PREFACE);
+    buf.write_frame(settings);
+    let buf: Vec<u8> = buf.into();
+    conn.write_all(&buf).await?;
 
     Ok(())
 }
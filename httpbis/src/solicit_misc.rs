@@ -6,7 +6,7 @@ use crate::solicit::frame::HttpFrame;
 use crate::solicit::frame::HttpFrameDecoded;
 use crate::solicit::frame::PingFrame;
 use crate::solicit::frame::PriorityFrame;
-use crate::solicit::frame::PushPromiseFrame;
+use crate::solicit::frame::PushPromiseDecodedFrame;
 use crate::solicit::frame::RawFrame;
 use crate::solicit::frame::RstStreamFrame;
 use crate::solicit::frame::SettingsFrame;
@@ -20,7 +20,7 @@ pub enum HttpFrameStream {
     Headers(HeadersDecodedFrame),
     Priority(PriorityFrame),
     RstStream(RstStreamFrame),
-    PushPromise(PushPromiseFrame),
+    PushPromise(PushPromiseDecodedFrame),
     WindowUpdate(WindowUpdateFrame),
 }
 